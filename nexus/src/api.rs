@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
-use scylla::Session;
 use tokio::sync::Mutex;
 
+use crate::{db::Database, state::connection::ConnectionState};
+
 use self::routes::get_routes;
 
 pub mod filters;
@@ -10,14 +11,14 @@ pub mod handlers;
 pub mod jwt;
 pub mod routes;
 
-pub async fn run_http(session: Arc<Mutex<Session>>) {
-    let routes = get_routes(session);
+pub async fn run_http(db: Arc<Database>, state: Arc<Mutex<ConnectionState>>) {
+    let routes = get_routes(db, state);
 
     tokio::spawn(async move {
         warp::serve(routes)
             .tls()
-            .cert_path(&std::env::var("TLS_CERT_PATH").unwrap())
-            .key_path(&std::env::var("TLS_KEY_PATH").unwrap())
+            .cert_path(std::env::var("TLS_CERT_PATH").unwrap())
+            .key_path(std::env::var("TLS_KEY_PATH").unwrap())
             .run(([127, 0, 0, 1], 8082))
             .await;
     });
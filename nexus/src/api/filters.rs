@@ -1,27 +1,53 @@
 use std::sync::Arc;
 
 use nexuslib::models::user::role::Role;
-use scylla::Session;
 use tokio::sync::Mutex;
-use warp::{header::headers_cloned, http::HeaderValue, hyper::HeaderMap, Filter, Rejection};
+use uuid::Uuid;
+use warp::{
+    header::headers_cloned, http::HeaderValue, hyper::HeaderMap, reject, Filter, Rejection,
+};
+
+use crate::{db::Database, state::connection::ConnectionState};
+
+use super::jwt::jwt_from_header;
 
 use self::auth::authorize;
 
+pub mod admin;
 pub mod auth;
+pub mod calls;
+pub mod me;
+pub mod media;
+pub mod messages;
 pub mod users;
 
-pub fn with_session(
-    session: Arc<Mutex<Session>>,
-) -> impl Filter<Extract = (Arc<Mutex<Session>>,), Error = std::convert::Infallible> + Clone {
-    warp::any().map(move || session.clone())
+pub fn with_db(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (Arc<Database>,), Error = std::convert::Infallible> + Clone {
+    warp::any().map(move || db.clone())
+}
+
+pub fn with_state(
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (Arc<Mutex<ConnectionState>>,), Error = std::convert::Infallible> + Clone
+{
+    warp::any().map(move || state.clone())
 }
 
+/// Authorizes the request and extracts the UUID of the user
 pub fn with_auth(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     role: Role,
-) -> impl Filter<Extract = ((),), Error = Rejection> + Clone {
+) -> impl Filter<Extract = (Uuid,), Error = Rejection> + Clone {
     headers_cloned()
         .map(move |headers: HeaderMap<HeaderValue>| (role, headers))
-        .and(with_session(session))
+        .and(with_db(db))
         .and_then(authorize)
 }
+
+/// Extracts the JWT of the request
+pub fn with_token() -> impl Filter<Extract = (String,), Error = Rejection> + Clone {
+    headers_cloned().and_then(|headers: HeaderMap<HeaderValue>| async move {
+        jwt_from_header(&headers).map_err(reject::custom)
+    })
+}
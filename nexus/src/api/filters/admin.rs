@@ -0,0 +1,139 @@
+use std::sync::Arc;
+
+use nexuslib::{
+    models::user::role::Role,
+    request::admin::{AuditQuery, RestrictionRequest, RoleChangeRequest, UserListQuery},
+};
+use tokio::sync::Mutex;
+use warp::Filter;
+
+use crate::{api::handlers, db::Database, state::connection::ConnectionState};
+
+use super::{with_auth, with_db, with_state};
+
+pub fn admin(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("admin").and(
+        users_list(db.clone())
+            .or(users_suspend(db.clone(), state.clone()))
+            .or(users_ban(db.clone(), state.clone()))
+            .or(users_lift_restriction(db.clone()))
+            .or(users_change_role(db.clone(), state.clone()))
+            .or(users_password_reset(db.clone()))
+            .or(audit_list(db.clone()))
+            .or(call_stats_get(db, state)),
+    )
+}
+
+/// GET /admin/users?role=&username=&restricted=&limit=
+pub fn users_list(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users")
+        .and(warp::get())
+        .and(warp::query::<UserListQuery>())
+        .and(with_auth(db.clone(), Role::Moderator))
+        .and(with_db(db))
+        .and_then(handlers::admin::list_users)
+}
+
+/// POST /admin/users/:uuid/suspend
+pub fn users_suspend(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users" / String / "suspend")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::Moderator))
+        .and(json_body_restriction())
+        .and(with_db(db))
+        .and(with_state(state))
+        .and_then(handlers::admin::suspend_user)
+}
+
+/// POST /admin/users/:uuid/ban
+pub fn users_ban(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users" / String / "ban")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::Admin))
+        .and(json_body_restriction())
+        .and(with_db(db))
+        .and(with_state(state))
+        .and_then(handlers::admin::ban_user)
+}
+
+/// DELETE /admin/users/:uuid/restriction
+pub fn users_lift_restriction(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users" / String / "restriction")
+        .and(warp::delete())
+        .and(with_auth(db.clone(), Role::Moderator))
+        .and(with_db(db))
+        .and_then(handlers::admin::lift_restriction)
+}
+
+/// PUT /admin/users/:uuid/role
+pub fn users_change_role(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users" / String / "role")
+        .and(warp::put())
+        .and(with_auth(db.clone(), Role::Admin))
+        .and(json_body_role())
+        .and(with_db(db))
+        .and(with_state(state))
+        .and_then(handlers::admin::change_role)
+}
+
+/// POST /admin/users/:uuid/password-reset
+pub fn users_password_reset(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("users" / String / "password-reset")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::Admin))
+        .and(with_db(db))
+        .and_then(handlers::admin::issue_password_reset)
+}
+
+/// GET /admin/audit?month=&limit=
+pub fn audit_list(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("audit")
+        .and(warp::get())
+        .and(warp::query::<AuditQuery>())
+        .and(with_auth(db.clone(), Role::Admin))
+        .and(with_db(db))
+        .and_then(handlers::admin::list_audit)
+}
+
+/// GET /admin/calls/:uuid/stats
+pub fn call_stats_get(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("calls" / String / "stats")
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::Admin))
+        .and(with_db(db))
+        .and(with_state(state))
+        .and_then(handlers::admin::get_call_stats)
+}
+
+fn json_body_restriction(
+) -> impl Filter<Extract = (RestrictionRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_role() -> impl Filter<Extract = (RoleChangeRequest,), Error = warp::Rejection> + Clone
+{
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
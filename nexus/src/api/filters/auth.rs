@@ -1,68 +1,92 @@
 use std::{str::FromStr, sync::Arc};
 
 use jsonwebtoken::{decode, Algorithm, DecodingKey, TokenData, Validation};
-use scylla::Session;
-use tokio::sync::Mutex;
+use uuid::Uuid;
 use warp::{http::HeaderValue, hyper::HeaderMap, reject, Filter, Rejection};
 
 use nexuslib::{
     models::user::role::Role,
-    request::auth::{AuthRequest, LogoutRequest},
+    request::auth::{AuthRequest, LogoutRequest, PasswordResetRequest, TwoFactorLoginRequest},
 };
 
 use crate::{
     api::{
-        handlers::{self, auth::validate_session},
+        handlers::{self, admin::active_restriction, auth::validate_session},
         jwt::{jwt_from_header, Claims},
     },
+    db::Database,
     errors::jwt::JWTError,
 };
 
-use super::with_session;
+use super::with_db;
 
 // All auth routes
 pub fn auth(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path("auth").and(
-        login(session.clone())
-            .or(register(session.clone()))
-            .or(logout(session.clone())),
+        login(db.clone())
+            .or(login_two_factor(db.clone()))
+            .or(register(db.clone()))
+            .or(logout(db.clone()))
+            .or(reset_password(db.clone())),
     )
 }
 
 /// POST /auth/login
 pub fn login(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("login")
         .and(warp::post())
-        .and(with_session(session))
+        .and(with_db(db))
         .and(warp::body::json())
         .and_then(handlers::auth::login)
 }
 
+/// POST /auth/login/2fa
+pub fn login_two_factor(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("login" / "2fa")
+        .and(warp::post())
+        .and(with_db(db))
+        .and(json_body_two_factor())
+        .and_then(handlers::two_factor::login)
+}
+
 /// POST /auth/register
 pub fn register(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("register")
         .and(warp::post())
-        .and(with_session(session))
+        .and(with_db(db))
         .and(json_body())
         .and_then(handlers::auth::register)
 }
 
 pub fn logout(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("logout")
         .and(warp::post())
-        .and(with_session(session))
+        .and(with_db(db))
         .and(json_body_logout())
         .and_then(handlers::auth::logout)
 }
 
+/// POST /auth/reset-password
+pub fn reset_password(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("reset-password")
+        .and(warp::post())
+        .and(with_db(db))
+        .and(json_body_reset())
+        .and_then(handlers::auth::reset_password)
+}
+
 fn json_body() -> impl Filter<Extract = (AuthRequest,), Error = warp::Rejection> + Clone {
     // When accepting a body, we want a JSON body
     // (and to reject huge payloads)...
@@ -73,33 +97,41 @@ fn json_body_logout() -> impl Filter<Extract = (LogoutRequest,), Error = warp::R
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
 }
 
+fn json_body_two_factor(
+) -> impl Filter<Extract = (TwoFactorLoginRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_reset(
+) -> impl Filter<Extract = (PasswordResetRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+/// Checks the JWT and the role of the user
+///
+/// Returns the UUID of the authorized user
 pub async fn authorize(
     (role, headers): (Role, HeaderMap<HeaderValue>),
-    session: Arc<Mutex<Session>>,
-) -> Result<(), Rejection> {
+    db: Arc<Database>,
+) -> Result<Uuid, Rejection> {
     match jwt_from_header(&headers) {
         Ok(token) => {
-            let decoded = check_token(session, &token)
-                .await
-                .map_err(|_| reject::custom(JWTError::JWTToken))?;
+            let decoded = check_token(db, &token).await.map_err(reject::custom)?;
 
-            if ![Role::Admin, Role::Moderator]
-                .contains(&Role::from_str(&decoded.claims.role).unwrap())
-                && [Role::Admin, Role::Moderator].contains(&role)
-            {
+            // roles are ordered by privilege: `Admin` < `Moderator` < `User`
+            let user_role = Role::from_str(&decoded.claims.role)
+                .map_err(|_| reject::custom(JWTError::JWTToken))?;
+            if user_role.get_index() > role.get_index() {
                 return Err(reject::custom(JWTError::NoPermission));
             }
 
-            Ok(())
+            Uuid::parse_str(&decoded.claims.sub).map_err(|_| reject::custom(JWTError::JWTToken))
         }
         Err(e) => Err(reject::custom(e)),
     }
 }
 
-pub async fn check_token(
-    session: Arc<Mutex<Session>>,
-    token: &str,
-) -> Result<TokenData<Claims>, JWTError> {
+pub async fn check_token(db: Arc<Database>, token: &str) -> Result<TokenData<Claims>, JWTError> {
     let decoded = decode::<Claims>(
         token,
         &DecodingKey::from_secret(b"123"),
@@ -108,9 +140,16 @@ pub async fn check_token(
     .map_err(|_| JWTError::JWTToken)?;
 
     // Check tokens
-    let validated = validate_session(session, token).await;
+    let validated = validate_session(db.clone(), token).await;
     if validated.is_err() {
         return Err(JWTError::JWTToken);
     }
-    Ok(decoded)
+
+    // suspended and banned users keep their sessions, but cannot use them
+    let user_uuid = Uuid::parse_str(&decoded.claims.sub).map_err(|_| JWTError::JWTToken)?;
+    match active_restriction(db, &user_uuid).await {
+        Ok(None) => Ok(decoded),
+        Ok(Some(_)) => Err(JWTError::Restricted),
+        Err(_) => Err(JWTError::JWTToken),
+    }
 }
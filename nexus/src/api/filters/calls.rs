@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use nexuslib::{models::user::role::Role, request::call::CallLogQuery};
+use tokio::sync::Mutex;
+use warp::Filter;
+
+use crate::{api::handlers, db::Database, state::connection::ConnectionState};
+
+use super::{with_auth, with_db, with_state};
+
+pub fn calls(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("calls").and(calls_list(db.clone()).or(stats_get(db, state)))
+}
+
+/// GET /calls?before=&limit=
+pub fn calls_list(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path::end()
+        .and(warp::get())
+        .and(warp::query::<CallLogQuery>())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::calls::list_calls)
+}
+
+/// GET /calls/:uuid/stats
+pub fn stats_get(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String / "stats")
+        .and(warp::get())
+        .and(with_auth(db, Role::User))
+        .and(with_state(state))
+        .and_then(handlers::calls::get_stats)
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use nexuslib::{
+    models::user::role::Role,
+    request::{
+        auth::{PasswordChangeRequest, TotpCodeRequest},
+        profile::{ProfileUpdateRequest, SettingsUpdateRequest},
+    },
+};
+use warp::Filter;
+
+use crate::{api::handlers, db::Database};
+
+use super::{with_auth, with_db, with_token};
+
+/// Maximum size of an avatar image in bytes
+const AVATAR_MAX_SIZE: u64 = 1024 * 1024 * 5;
+
+pub fn me(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("me").and(
+        profile_get(db.clone())
+            .or(profile_update(db.clone()))
+            .or(avatar_upload(db.clone()))
+            .or(settings_get(db.clone()))
+            .or(settings_update(db.clone()))
+            .or(password_change(db.clone()))
+            .or(two_factor_enroll(db.clone()))
+            .or(two_factor_confirm(db.clone()))
+            .or(two_factor_disable(db)),
+    )
+}
+
+/// GET /me/profile
+pub fn profile_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("profile")
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::me::get_profile)
+}
+
+/// PATCH /me/profile with JSON body
+pub fn profile_update(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("profile")
+        .and(warp::patch())
+        .and(with_auth(db.clone(), Role::User))
+        .and(json_body_profile())
+        .and(with_db(db))
+        .and_then(handlers::me::update_profile)
+}
+
+/// PUT /me/avatar with image body
+pub fn avatar_upload(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("avatar")
+        .and(warp::put())
+        .and(with_auth(db.clone(), Role::User))
+        .and(warp::body::content_length_limit(AVATAR_MAX_SIZE))
+        .and(warp::body::bytes())
+        .and(with_db(db))
+        .and_then(handlers::me::upload_avatar)
+}
+
+/// GET /me/settings
+pub fn settings_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("settings")
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::me::get_settings)
+}
+
+/// PATCH /me/settings with JSON body
+pub fn settings_update(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("settings")
+        .and(warp::patch())
+        .and(with_auth(db.clone(), Role::User))
+        .and(json_body_settings())
+        .and(with_db(db))
+        .and_then(handlers::me::update_settings)
+}
+
+/// POST /me/password with JSON body
+pub fn password_change(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("password")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_token())
+        .and(json_body_password())
+        .and(with_db(db))
+        .and_then(handlers::me::change_password)
+}
+
+/// POST /me/2fa/enroll
+pub fn two_factor_enroll(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("2fa" / "enroll")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::two_factor::enroll)
+}
+
+/// POST /me/2fa/confirm with JSON body
+pub fn two_factor_confirm(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("2fa" / "confirm")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(json_body_code())
+        .and(with_db(db))
+        .and_then(handlers::two_factor::confirm)
+}
+
+/// POST /me/2fa/disable with JSON body
+pub fn two_factor_disable(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("2fa" / "disable")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(json_body_code())
+        .and(with_db(db))
+        .and_then(handlers::two_factor::disable)
+}
+
+fn json_body_profile(
+) -> impl Filter<Extract = (ProfileUpdateRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_settings(
+) -> impl Filter<Extract = (SettingsUpdateRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_password(
+) -> impl Filter<Extract = (PasswordChangeRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
+
+fn json_body_code() -> impl Filter<Extract = (TotpCodeRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
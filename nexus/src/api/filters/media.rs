@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use nexuslib::{models::user::role::Role, request::file::PresignedUploadRequest};
+use warp::Filter;
+
+use crate::{api::handlers, db::Database};
+
+use super::{with_auth, with_db};
+
+pub fn media(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("media").and(
+        avatar_get(db.clone())
+            .or(media_get(db.clone()))
+            .or(thumbnail_get(db.clone()))
+            .or(url_get(db.clone()))
+            .or(upload_presign(db.clone()))
+            .or(upload_commit(db)),
+    )
+}
+
+/// GET /media/avatars/:uuid
+pub fn avatar_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("avatars" / String)
+        .and(warp::get())
+        .and(with_auth(db, Role::User))
+        .and_then(handlers::media::get_avatar)
+}
+
+/// GET /media/:uuid
+pub fn media_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String)
+        .and(warp::get())
+        .and(warp::header::optional::<String>("range"))
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::media::get_media)
+}
+
+/// GET /media/:uuid/thumbnails/:size
+pub fn thumbnail_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String / "thumbnails" / u32)
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::media::get_thumbnail)
+}
+
+/// GET /media/:uuid/url
+pub fn url_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String / "url")
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::media::presign_download)
+}
+
+/// POST /media/uploads
+pub fn upload_presign(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("uploads")
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and(json_body_presign())
+        .and_then(handlers::media::presign_upload)
+}
+
+/// POST /media/uploads/:uuid
+pub fn upload_commit(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("uploads" / String)
+        .and(warp::post())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::media::commit_upload)
+}
+
+fn json_body_presign(
+) -> impl Filter<Extract = (PresignedUploadRequest,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(1024 * 16).and(warp::body::json())
+}
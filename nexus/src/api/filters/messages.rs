@@ -0,0 +1,26 @@
+use std::sync::Arc;
+
+use nexuslib::{models::user::role::Role, request::message::MessageHistoryQuery};
+use warp::Filter;
+
+use crate::{api::handlers, db::Database};
+
+use super::{with_auth, with_db};
+
+pub fn messages(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("messages").and(history_get(db))
+}
+
+/// GET /messages/:uuid?before=&limit=
+pub fn history_get(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!(String)
+        .and(warp::get())
+        .and(warp::query::<MessageHistoryQuery>())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::messages::list_messages)
+}
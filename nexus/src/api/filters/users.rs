@@ -1,84 +1,86 @@
 use std::sync::Arc;
 
-use nexuslib::models::user::{role::Role, User};
-use scylla::Session;
+use nexuslib::{models::user::role::Role, request::user::UsernameUpdateRequest};
 use tokio::sync::Mutex;
 use warp::Filter;
 
-use crate::api::handlers;
+use crate::{api::handlers, db::Database, state::connection::ConnectionState};
 
-use super::{with_auth, with_session};
+use super::{with_auth, with_db, with_state};
 
 pub fn users(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
-    users_list(session.clone())
-        .or(users_get_by_uuid(session.clone()))
-        .or(users_by_username(session.clone()))
-        .or(users_update(session.clone()))
-        .or(users_delete(session.clone()))
-        .or(users_get_key(session))
+    users_list(db.clone())
+        .or(users_get_by_uuid(db.clone()))
+        .or(users_by_username(db.clone()))
+        .or(users_update(db.clone()))
+        .or(users_delete(db.clone(), state))
+        .or(users_get_key(db.clone()))
+        .or(users_get_profile(db))
 }
 
 /// GET /users
 pub fn users_list(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("users")
         .and(warp::get())
-        .and(with_session(session.clone()))
-        .and(with_auth(session, Role::User))
+        .and(with_db(db.clone()))
+        .and(with_auth(db, Role::User))
         .and_then(handlers::users::list)
 }
 
 /// GET /users/:username
 pub fn users_by_username(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("users" / String)
         .and(warp::get())
-        .and(with_auth(session.clone(), Role::User))
-        .and(with_session(session))
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
         .and_then(handlers::users::get_by_username)
 }
 
 /// GET /users/:uuid
 pub fn users_get_by_uuid(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("users" / String)
         .and(warp::get())
-        .and(with_auth(session.clone(), Role::User))
-        .and(with_session(session))
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
         .and_then(handlers::users::get_by_uuid)
 }
 
-/// POST /users with JSON body
+// /// POST /users with JSON body
 // pub fn users_create(
-//     session: Arc<Mutex<Session>>,
+//     db: Arc<Database>,
 // ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
 //     warp::path!("users")
 //         .and(warp::post())
 //         .and(json_body())
-//         .and(with_session(session))
+//         .and(with_db(db))
 //         .and_then(handlers::users::create)
 // }
 
-/// PUT /users/:id with JSON body
+/// PUT /users/:id with JSON body (username change)
 pub fn users_update(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("users" / String)
         .and(warp::put())
-        .and(with_auth(session.clone(), Role::User))
+        .and(with_auth(db.clone(), Role::User))
         .and(json_body())
-        .and(with_session(session))
+        .and(with_db(db))
         .and_then(handlers::users::update)
 }
 
 /// DELETE /users/:id
 pub fn users_delete(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path!("users" / String)
         // It is important to put the auth check _after_ the path filters.
@@ -86,23 +88,37 @@ pub fn users_delete(
         // would try this filter and reject because the authorization header doesn't match,
         // rather because the param is wrong for that other path.
         .and(warp::delete())
-        .and(with_auth(session.clone(), Role::User))
-        .and(with_session(session))
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and(with_state(state))
         .and_then(handlers::users::delete)
 }
 
 pub fn users_get_key(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
 ) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
     warp::path("users")
         .and(warp::path!("key" / String))
         .and(warp::post())
-        .and(with_auth(session.clone(), Role::User))
-        .and(with_session(session))
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
         .and_then(handlers::users::get_key)
 }
 
-pub fn json_body() -> impl Filter<Extract = (User,), Error = warp::Rejection> + Clone {
+/// GET /users/profile/:uuid
+pub fn users_get_profile(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path("users")
+        .and(warp::path!("profile" / String))
+        .and(warp::get())
+        .and(with_auth(db.clone(), Role::User))
+        .and(with_db(db))
+        .and_then(handlers::users::get_profile)
+}
+
+pub fn json_body(
+) -> impl Filter<Extract = (UsernameUpdateRequest,), Error = warp::Rejection> + Clone {
     // When accepting a body, we want a JSON body
     // (and to reject huge payloads)...
     warp::body::content_length_limit(1024 * 16).and(warp::body::json())
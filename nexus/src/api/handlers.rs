@@ -1,2 +1,8 @@
+pub mod admin;
 pub mod auth;
+pub mod calls;
+pub mod me;
+pub mod media;
+pub mod messages;
+pub mod two_factor;
 pub mod users;
@@ -0,0 +1,484 @@
+use std::{convert::Infallible, str::FromStr, sync::Arc};
+
+use chrono::{NaiveDate, Utc};
+use hashbrown::HashMap;
+use rand_core::{OsRng, RngCore};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Reply};
+
+use nexuslib::{
+    crypto::hasher::get_hash,
+    models::{
+        moderation::{AuditEntry, RestrictionKind, UserRestriction},
+        user::role::Role,
+    },
+    request::admin::{AuditQuery, RestrictionRequest, RoleChangeRequest, UserListQuery},
+    response::{admin::UserSummary, auth::PasswordResetResponse},
+};
+
+use crate::{
+    db::{
+        repository::{audit_month, PasswordReset},
+        Database,
+    },
+    errors::db::DbError,
+    state::connection::ConnectionState,
+};
+
+use super::auth::revoke_sessions;
+
+/// Lifetime of a password reset token in seconds
+const RESET_TOKEN_TTL: i64 = 60 * 60;
+const REASON_MAX_LEN: usize = 512;
+/// Default and maximal number of entries in the lists
+const LIST_LIMIT: usize = 100;
+const LIST_MAX_LIMIT: usize = 1000;
+
+/// GET /admin/users
+///
+/// Lists the users with their active restrictions.
+/// Can be filtered by role, username prefix and restriction
+pub async fn list_users(
+    query: UserListQuery,
+    _uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let role = match query.role.as_deref().map(Role::from_str) {
+        Some(Ok(role)) => Some(role),
+        Some(Err(_)) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+        None => None,
+    };
+    let limit = query.limit.unwrap_or(LIST_LIMIT).clamp(1, LIST_MAX_LIMIT);
+
+    let users = match db.users.list().await {
+        Ok(users) => users,
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    let mut restrictions = match active_restrictions(db).await {
+        Ok(restrictions) => restrictions,
+        Err(e) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response())
+        }
+    };
+
+    let mut summaries = users
+        .into_iter()
+        .filter(|user| role.is_none_or(|role| user.role == role))
+        .filter(|user| {
+            query
+                .username
+                .as_deref()
+                .is_none_or(|prefix| user.username.starts_with(prefix))
+        })
+        .map(|user| UserSummary {
+            uuid: user.uuid,
+            restriction: restrictions.remove(&user.uuid),
+            username: user.username,
+            role: user.role,
+            created_at: user.created_at,
+        })
+        .filter(|summary| {
+            query
+                .restricted
+                .is_none_or(|restricted| summary.restriction.is_some() == restricted)
+        })
+        .collect::<Vec<_>>();
+    summaries.sort_by(|a, b| a.username.cmp(&b.username));
+    summaries.truncate(limit);
+
+    Ok(warp::reply::json(&summaries).into_response())
+}
+
+/// POST /admin/users/:uuid/suspend
+///
+/// Temporarily restricts the user, the expiration is required
+pub async fn suspend_user(
+    id: String,
+    actor_uuid: Uuid,
+    body: RestrictionRequest,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    if body.expires_at.is_none() {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+    restrict(RestrictionKind::Suspended, id, actor_uuid, body, db, state).await
+}
+
+/// POST /admin/users/:uuid/ban
+///
+/// Restricts the user until the expiration or permanently
+pub async fn ban_user(
+    id: String,
+    actor_uuid: Uuid,
+    body: RestrictionRequest,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    restrict(RestrictionKind::Banned, id, actor_uuid, body, db, state).await
+}
+
+/// DELETE /admin/users/:uuid/restriction
+///
+/// Lifts the suspension or the ban, only admins can lift bans
+pub async fn lift_restriction(
+    id: String,
+    actor_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let restriction = match active_restriction(db.clone(), &user_uuid).await {
+        Ok(Some(restriction)) => restriction,
+        Ok(None) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    let actor = match db.users.get(&actor_uuid).await {
+        Ok(actor) => actor,
+        Err(_) => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+    };
+    if restriction.kind == RestrictionKind::Banned && actor.role != Role::Admin {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    if db.users.lift_restriction(&user_uuid).await.is_err() {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    audit(
+        db,
+        &actor_uuid,
+        Some(&user_uuid),
+        "lift_restriction",
+        restriction.kind.to_string(),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// PUT /admin/users/:uuid/role
+///
+/// Promotes or demotes the user.
+/// The sessions of the user are revoked, since the role is a part of the JWT
+pub async fn change_role(
+    id: String,
+    actor_uuid: Uuid,
+    body: RoleChangeRequest,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    // an admin cannot lock themselves out
+    if user_uuid == actor_uuid {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    if user.role == body.role {
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    if db.users.set_role(&user, body.role).await.is_err() {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    state.lock().await.disconnect_user(&user.uuid);
+    if revoke_sessions(db.clone(), &user.uuid, None).await.is_err() {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    audit(
+        db,
+        &actor_uuid,
+        Some(&user.uuid),
+        "change_role",
+        format!("{} -> {}", user.role, body.role),
+    )
+    .await;
+
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// GET /admin/audit
+///
+/// Returns the admin actions of the month, the latest first
+pub async fn list_audit(
+    query: AuditQuery,
+    _uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let month = match query.month {
+        Some(month) => {
+            if NaiveDate::parse_from_str(&format!("{month}-01"), "%Y-%m-%d").is_err() {
+                return Ok(StatusCode::BAD_REQUEST.into_response());
+            }
+            month
+        }
+        None => audit_month(Utc::now().timestamp()),
+    };
+    let limit = query.limit.map_or(LIST_LIMIT, |limit| {
+        limit.clamp(1, LIST_MAX_LIMIT as i32) as usize
+    });
+
+    let entries = db.users.audit(&month, limit).await;
+
+    match entries {
+        Ok(entries) => Ok(warp::reply::json(&entries).into_response()),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response()),
+    }
+}
+
+/// GET /admin/calls/:uuid/stats
+///
+/// Returns the stats of the relay of a call or a room, the live ones
+/// while it is in progress, otherwise the summary written when it ended
+pub async fn get_call_stats(
+    id: String,
+    _uid: Uuid,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    let call_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let live = state
+        .lock()
+        .await
+        .relays
+        .get(&call_uuid)
+        .map(|relay| relay.stats());
+    if let Some(stats) = live {
+        return Ok(warp::reply::json(&stats).into_response());
+    }
+
+    match db.calls.stats(&call_uuid).await {
+        Ok(Some(stats)) => Ok(warp::reply::json(&stats).into_response()),
+        Ok(None) => Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response()),
+    }
+}
+
+/// POST /admin/users/:uuid/password-reset
+///
+/// Issues a one-time password reset token for the user.
+/// Only the hash of the token is stored, the token itself
+/// is returned to the admin once
+pub async fn issue_password_reset(
+    id: String,
+    admin_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    if db.users.get(&user_uuid).await.is_err() {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    }
+
+    let mut raw_token = [0u8; 32];
+    OsRng.fill_bytes(&mut raw_token);
+    let token = hex::encode(raw_token);
+
+    let created_at = Utc::now().timestamp();
+    let expires_at = created_at + RESET_TOKEN_TTL;
+
+    let reset = PasswordReset {
+        user: user_uuid,
+        issued_by: admin_uuid,
+        created_at,
+        expires_at,
+    };
+    let result = db
+        .sessions
+        .add_password_reset(&get_hash(&token), &reset)
+        .await;
+
+    match result {
+        Ok(_) => {
+            audit(
+                db,
+                &admin_uuid,
+                Some(&user_uuid),
+                "password_reset",
+                String::new(),
+            )
+            .await;
+
+            let response = PasswordResetResponse::new(user_uuid, token, expires_at);
+            Ok(
+                warp::reply::with_status(warp::reply::json(&response), StatusCode::CREATED)
+                    .into_response(),
+            )
+        }
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// Returns the restriction of the user if it is in force
+pub async fn active_restriction(
+    db: Arc<Database>,
+    user_uuid: &Uuid,
+) -> Result<Option<UserRestriction>, DbError> {
+    let now = Utc::now().timestamp();
+    db.users
+        .restriction(user_uuid)
+        .await
+        .map(|restriction| restriction.filter(|restriction| restriction.is_active(now)))
+}
+
+/// Writes the action to the audit log
+///
+/// A failure is only logged, the action itself is already done
+pub async fn audit(
+    db: Arc<Database>,
+    actor: &Uuid,
+    target: Option<&Uuid>,
+    action: &str,
+    details: String,
+) {
+    let entry = AuditEntry {
+        uuid: Uuid::new_v4(),
+        actor: *actor,
+        target: target.copied(),
+        action: action.to_owned(),
+        details,
+        created_at: Utc::now().timestamp(),
+    };
+
+    if let Err(e) = db.users.add_audit(&entry).await {
+        log::error!("Failed to write the audit entry `{action}` by {actor}: {e:?}");
+    }
+}
+
+/// Suspends or bans the user and drops their live connections
+async fn restrict(
+    kind: RestrictionKind,
+    id: String,
+    actor_uuid: Uuid,
+    body: RestrictionRequest,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+    if user_uuid == actor_uuid {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let reason = body.reason.trim().to_string();
+    if reason.is_empty() || reason.len() > REASON_MAX_LEN {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let created_at = Utc::now().timestamp();
+    if body
+        .expires_at
+        .is_some_and(|expires_at| expires_at <= created_at)
+    {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    let actor = match db.users.get(&actor_uuid).await {
+        Ok(actor) => actor,
+        Err(_) => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+    };
+
+    // only users of a lower rank can be restricted
+    if actor.role.get_index() >= user.role.get_index() {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    // the new restriction replaces the current one, so only an admin may replace a ban
+    match active_restriction(db.clone(), &user_uuid).await {
+        Ok(Some(current))
+            if current.kind == RestrictionKind::Banned && actor.role != Role::Admin =>
+        {
+            return Ok(StatusCode::FORBIDDEN.into_response())
+        }
+        Ok(_) => (),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    let restriction = UserRestriction {
+        user: user_uuid,
+        kind,
+        reason,
+        issued_by: actor_uuid,
+        created_at,
+        expires_at: body.expires_at,
+    };
+
+    // the restriction is removed once it expires
+    if db.users.restrict(&restriction).await.is_err() {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    let disconnected = state.lock().await.disconnect_user(&user_uuid);
+    log::debug!("restrict: {disconnected} live session(s) disconnected");
+
+    audit(
+        db,
+        &actor_uuid,
+        Some(&user_uuid),
+        match kind {
+            RestrictionKind::Suspended => "suspend",
+            RestrictionKind::Banned => "ban",
+        },
+        serde_json::to_string(&body).unwrap_or_default(),
+    )
+    .await;
+
+    Ok(
+        warp::reply::with_status(warp::reply::json(&restriction), StatusCode::CREATED)
+            .into_response(),
+    )
+}
+
+/// Returns all restrictions in force by the user
+async fn active_restrictions(db: Arc<Database>) -> Result<HashMap<Uuid, UserRestriction>, DbError> {
+    let now = Utc::now().timestamp();
+
+    db.users.restrictions().await.map(|restrictions| {
+        restrictions
+            .into_iter()
+            .filter(|restriction| restriction.is_active(now))
+            .map(|restriction| (restriction.user, restriction))
+            .collect()
+    })
+}
@@ -1,30 +1,71 @@
 use std::{convert::Infallible, sync::Arc};
 
-use chrono::{Duration, Utc};
-use scylla::{frame::value::Timestamp, prepared_statement::PreparedStatement, Session};
-use tokio::sync::Mutex;
 use uuid::Uuid;
 use warp::{hyper::StatusCode, reject, Reply};
 
 use crate::{
     api::filters::auth::check_token,
     api::jwt::generate_jwt,
-    db::models_wrapper::UserDB,
+    db::Database,
     errors::{db::DbError, jwt::JWTError},
 };
 use nexuslib::{
+    crypto::hasher::get_hash,
     models::user::User,
-    request::auth::{AuthRequest, AuthRequestMeta, LogoutRequest},
+    request::auth::{AuthRequest, AuthRequestMeta, LogoutRequest, PasswordResetRequest},
     response::auth::AuthResponse,
 };
 
-use super::users::create;
+use super::{admin::active_restriction, two_factor, users::create};
+
+/// Minimal length of a new password
+pub const PASSWORD_MIN_LEN: usize = 8;
 
 pub async fn login(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     body: AuthRequest,
 ) -> Result<warp::reply::Response, Infallible> {
-    let result = validate_user(session, body).await;
+    let user = match check_credentials(db.clone(), &body).await {
+        Ok(user) => user,
+        Err(e) => {
+            let status = match e {
+                DbError::WrongCredentials => StatusCode::UNAUTHORIZED,
+                DbError::Restricted => StatusCode::FORBIDDEN,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            return Ok(warp::reply::with_status(warp::reply::json(&e), status).into_response());
+        }
+    };
+
+    // with two-factor authentication the session is created only after the code is checked
+    match two_factor::is_enabled(db.clone(), &user.uuid).await {
+        Ok(true) => {
+            return match two_factor::create_challenge(db, &user.uuid, body.meta).await {
+                Ok(challenge) => Ok(warp::reply::with_status(
+                    warp::reply::json(&challenge),
+                    StatusCode::ACCEPTED,
+                )
+                .into_response()),
+                Err(e) => Ok(warp::reply::with_status(
+                    warp::reply::json(&e),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+                .into_response()),
+            }
+        }
+        Ok(false) => (),
+        Err(e) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response())
+        }
+    }
+
+    let result = add_jwt_session(db, &user, body.meta)
+        .await
+        .map(|token| (user.uuid, token));
 
     match result {
         Ok((uuid, token)) => {
@@ -40,17 +81,17 @@ pub async fn login(
 }
 
 pub async fn register(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     body: AuthRequest,
 ) -> Result<warp::reply::Response, Infallible> {
     let (user, secret) = User::new(&body.username, &body.password, None);
-    let created = create((user, secret), session.clone()).await.unwrap();
+    let created = create((user, secret), db.clone()).await.unwrap();
 
     if !created.is_success() {
         return Ok(created.into_response());
     }
 
-    let result = validate_user(session, body).await;
+    let result = validate_user(db, body).await;
 
     match result {
         Ok((uuid, token)) => {
@@ -66,20 +107,15 @@ pub async fn register(
 }
 
 pub async fn logout(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     body: LogoutRequest,
 ) -> Result<warp::reply::Response, Infallible> {
-    let _token_decoded = check_token(session.clone(), &body.token)
+    let _token_decoded = check_token(db.clone(), &body.token)
         .await
         .map_err(|_| reject::custom(JWTError::JWTToken))
         .unwrap();
 
-    match session
-        .lock()
-        .await
-        .query("DELETE FROM nexus.sessions WHERE jwt = ?;", (body.token,))
-        .await
-    {
+    match db.sessions.remove(&body.token).await {
         Ok(_) => Ok(StatusCode::UNAUTHORIZED.into_response()),
         Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
     }
@@ -88,106 +124,122 @@ pub async fn logout(
 /// Check if user exists and returns
 /// the UUID of the user and the session jwt
 pub async fn validate_user(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     body: AuthRequest,
 ) -> Result<(Uuid, String), DbError> {
-    let user_row = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.users WHERE username = ? ALLOW FILTERING;",
-            (body.username,),
-        )
-        .await
-        .unwrap()
-        .first_row();
-
-    if user_row.is_err() {
-        return Err(DbError::WrongCredentials);
-    }
-    let user = user_row.unwrap().into_typed::<UserDB>();
+    let user = check_credentials(db.clone(), &body).await?;
 
-    if user.is_err() {
-        return Err(DbError::FailedToConvertRow);
+    match add_jwt_session(db, &user, body.meta).await {
+        Ok(token) => Ok((user.uuid, token)),
+        Err(e) => Err(e),
     }
+}
 
-    let user = user.unwrap().get_user();
+/// Returns the user if the username and the password match
+pub async fn check_credentials(db: Arc<Database>, body: &AuthRequest) -> Result<User, DbError> {
+    let user = match db.users.get_by_username(&body.username).await {
+        Ok(user) => user,
+        Err(DbError::NotFound) => return Err(DbError::WrongCredentials),
+        Err(e) => return Err(e),
+    };
 
     if user.password != body.password {
         return Err(DbError::WrongCredentials);
     }
 
-    match add_jwt_session(session, &user, body.meta).await {
-        Ok(token) => Ok((user.uuid, token)),
-        Err(e) => Err(e),
+    if active_restriction(db, &user.uuid).await?.is_some() {
+        return Err(DbError::Restricted);
     }
+
+    Ok(user)
 }
 
 pub async fn add_jwt_session(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     user: &User,
     meta: AuthRequestMeta,
 ) -> Result<String, DbError> {
-    let user = user.to_owned();
-    let token = generate_jwt(&user.uuid.to_string(), user.role);
+    let token =
+        generate_jwt(&user.uuid.to_string(), user.role).map_err(|_| DbError::FailedToAdd)?;
 
-    if token.is_err() {
-        // return Err(token.err().unwrap());
-    }
+    db.sessions.add(&token, &user.uuid, &meta).await?;
 
-    let token = token.unwrap();
+    Ok(token)
+}
 
-    let prepared_result= session.lock().await
-        .prepare(
-            "INSERT INTO nexus.sessions (jwt, user, location, device_name, device_type, device_os, created_at) VALUES(?, ?, ?, ?, ?, ?, ?);",
-        )
-        .await;
+pub async fn validate_session(db: Arc<Database>, token: &str) -> Result<(), DbError> {
+    db.sessions.user_of(token).await.map(|_| ())
+}
 
-    if prepared_result.is_err() {
-        return Err(DbError::FailedToAdd);
+/// POST /auth/reset-password
+///
+/// Sets a new password using the one-time token issued by an admin
+pub async fn reset_password(
+    db: Arc<Database>,
+    body: PasswordResetRequest,
+) -> Result<warp::reply::Response, Infallible> {
+    if body.new_password.len() < PASSWORD_MIN_LEN {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
     }
 
-    let prepared: PreparedStatement = prepared_result.unwrap();
-    let session_result = session
-        .lock()
-        .await
-        .execute(
-            &prepared,
-            (
-                &token.to_owned(),
-                user.uuid,
-                meta.location,
-                meta.device_name,
-                meta.device_type,
-                meta.device_os,
-                Timestamp(Duration::try_seconds(Utc::now().timestamp()).unwrap()),
-            ),
-        )
-        .await;
+    let user_uuid = match redeem_reset_token(db.clone(), &body.token).await {
+        Ok(user_uuid) => user_uuid,
+        Err(DbError::NotFound) => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
 
-    if session_result.is_err() {
-        return Err(DbError::FailedToAdd);
+    if db
+        .users
+        .set_password(&user, &body.new_password)
+        .await
+        .is_err()
+    {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
     }
 
-    Ok(token)
+    // nobody with the old password may stay logged in
+    match revoke_sessions(db, &user.uuid, None).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
 }
 
-pub async fn validate_session(session: Arc<Mutex<Session>>, token: &str) -> Result<(), DbError> {
-    let result = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.sessions WHERE jwt = ? ALLOW FILTERING;",
-            (token,),
-        )
-        .await
-        .unwrap()
-        .rows()
-        .unwrap();
-
-    if result.is_empty() {
-        return Err(DbError::NotFound);
+/// Deletes all JWT sessions of the user except the given one
+pub async fn revoke_sessions(
+    db: Arc<Database>,
+    user_uuid: &Uuid,
+    except: Option<&str>,
+) -> Result<(), DbError> {
+    for token in db.sessions.tokens_of(user_uuid).await? {
+        if Some(token.as_str()) == except {
+            continue;
+        }
+        db.sessions.remove(&token).await?;
     }
 
     Ok(())
 }
+
+/// Checks the reset token and invalidates it
+///
+/// Returns the UUID of the user the token was issued for
+async fn redeem_reset_token(db: Arc<Database>, token: &str) -> Result<Uuid, DbError> {
+    let token_hash = get_hash(token);
+
+    let reset = db
+        .sessions
+        .password_reset(&token_hash)
+        .await?
+        .ok_or(DbError::NotFound)?;
+
+    // the token is single use: only one of the concurrent requests can claim it
+    match db.sessions.claim_password_reset(&token_hash).await? {
+        true => Ok(reset.user),
+        false => Err(DbError::NotFound),
+    }
+}
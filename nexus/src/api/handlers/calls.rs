@@ -0,0 +1,67 @@
+use std::{convert::Infallible, sync::Arc};
+
+use chrono::Duration;
+use nexuslib::request::call::CallLogQuery;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Reply};
+
+use crate::{db::Database, state::connection::ConnectionState};
+
+/// Default and maximal number of entries in the call history
+const LOG_LIMIT: i32 = 50;
+const LOG_MAX_LIMIT: i32 = 200;
+
+/// GET /calls?before=&limit=
+///
+/// Returns the call history of the user, the latest first.
+/// The next page starts `before` the oldest entry of the previous one
+pub async fn list_calls(
+    query: CallLogQuery,
+    uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let limit = query.limit.unwrap_or(LOG_LIMIT).clamp(1, LOG_MAX_LIMIT);
+
+    if query
+        .before
+        .is_some_and(|before| Duration::try_seconds(before).is_none())
+    {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    match db.calls.log(&uid, query.before, limit as usize).await {
+        Ok(entries) => Ok(warp::reply::json(&entries).into_response()),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response()),
+    }
+}
+
+/// GET /calls/:uuid/stats
+///
+/// Returns the relay counters and the latest RTCP reports of a call in progress.
+/// Only the participants of the call have access to them
+pub async fn get_stats(
+    call_uuid: String,
+    uid: Uuid,
+    state: Arc<Mutex<ConnectionState>>,
+) -> Result<warp::reply::Response, Infallible> {
+    let call_uuid = match Uuid::parse_str(&call_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let state = state.lock().await;
+    let relay = match state.relays.get(&call_uuid) {
+        Some(relay) => relay,
+        None => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+    if !relay.participants.contains_key(&uid) {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    Ok(warp::reply::json(&relay.stats()).into_response())
+}
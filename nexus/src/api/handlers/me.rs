@@ -0,0 +1,298 @@
+use std::{convert::Infallible, io::Cursor, sync::Arc};
+
+use chrono::Utc;
+use image::ImageOutputFormat;
+use uuid::Uuid;
+use warp::{
+    hyper::{body::Bytes, StatusCode},
+    Reply,
+};
+
+use nexuslib::{
+    models::{
+        message::media::MediaType,
+        user::{profile::UserProfile, settings::UserSettings},
+    },
+    request::{
+        auth::PasswordChangeRequest,
+        profile::{ProfileUpdateRequest, SettingsUpdateRequest},
+    },
+};
+
+use crate::{
+    db::{repository::MediaEntry, Database},
+    errors::db::DbError,
+    storage::{bucket_name, put_object, remove_object},
+};
+
+use super::auth::{revoke_sessions, PASSWORD_MIN_LEN};
+
+const DISPLAY_NAME_MAX_LEN: usize = 64;
+const BIO_MAX_LEN: usize = 512;
+const LANGUAGE_MAX_LEN: usize = 16;
+const THEMES: [&str; 3] = ["light", "dark", "system"];
+
+/// Width and height of the avatar thumbnail in pixels
+pub const AVATAR_THUMBNAIL_SIZE: u32 = 128;
+
+/// GET /me/profile
+pub async fn get_profile(
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    match fetch_profile(db, user_uuid).await {
+        Ok(profile) => Ok(warp::reply::json(&profile).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// PATCH /me/profile
+pub async fn update_profile(
+    user_uuid: Uuid,
+    body: ProfileUpdateRequest,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let mut profile = match fetch_profile(db.clone(), user_uuid).await {
+        Ok(profile) => profile,
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    if let Some(display_name) = body.display_name {
+        let display_name = display_name.trim();
+        if display_name.chars().count() > DISPLAY_NAME_MAX_LEN {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        }
+        profile.display_name = non_empty(display_name);
+    }
+
+    if let Some(bio) = body.bio {
+        let bio = bio.trim();
+        if bio.chars().count() > BIO_MAX_LEN {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        }
+        profile.bio = non_empty(bio);
+    }
+
+    match db.users.save_profile(&profile).await {
+        Ok(_) => Ok(warp::reply::json(&profile).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// PUT /me/avatar
+///
+/// Stores the original image and its thumbnail in the `images` bucket
+pub async fn upload_avatar(
+    user_uuid: Uuid,
+    bytes: Bytes,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let format = match image::guess_format(&bytes) {
+        Ok(format) => format,
+        Err(_) => return Ok(StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response()),
+    };
+    let image = match image::load_from_memory_with_format(&bytes, format) {
+        Ok(image) => image,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let mut thumbnail = Vec::new();
+    if image
+        .thumbnail(AVATAR_THUMBNAIL_SIZE, AVATAR_THUMBNAIL_SIZE)
+        .write_to(&mut Cursor::new(&mut thumbnail), ImageOutputFormat::Png)
+        .is_err()
+    {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    let mut profile = match fetch_profile(db.clone(), user_uuid).await {
+        Ok(profile) => profile,
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    let avatar_uuid = Uuid::new_v4();
+    let bucket = bucket_name(MediaType::Image);
+    let object_name = format!("{avatar_uuid}.{}", format.extensions_str()[0]);
+
+    if let Err(err) = put_object(&bucket, &object_name, bytes.to_vec()).await {
+        log::error!("Error uploading the avatar: {err}");
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+    if let Err(err) = put_object(&bucket, &avatar_thumbnail_name(&avatar_uuid), thumbnail).await {
+        log::error!("Error uploading the avatar thumbnail: {err}");
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    if add_avatar(db.clone(), user_uuid, avatar_uuid, &object_name)
+        .await
+        .is_err()
+    {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    // the previous avatar is not referenced anymore
+    if let Some(old_avatar) = profile.avatar.replace(avatar_uuid) {
+        remove_avatar(db.clone(), old_avatar).await;
+    }
+
+    match db.users.save_profile(&profile).await {
+        Ok(_) => Ok(warp::reply::json(&profile).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// GET /me/settings
+pub async fn get_settings(
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    match fetch_settings(db, user_uuid).await {
+        Ok(settings) => Ok(warp::reply::json(&settings).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// PATCH /me/settings
+pub async fn update_settings(
+    user_uuid: Uuid,
+    body: SettingsUpdateRequest,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let mut settings = match fetch_settings(db.clone(), user_uuid).await {
+        Ok(settings) => settings,
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    if let Some(language) = body.language {
+        if language.is_empty()
+            || language.len() > LANGUAGE_MAX_LEN
+            || !language
+                .chars()
+                .all(|c| c.is_ascii_alphabetic() || c == '-')
+        {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        }
+        settings.language = language;
+    }
+
+    if let Some(theme) = body.theme {
+        if !THEMES.contains(&theme.as_str()) {
+            return Ok(StatusCode::BAD_REQUEST.into_response());
+        }
+        settings.theme = theme;
+    }
+
+    match db.users.save_settings(&user_uuid, &settings).await {
+        Ok(_) => Ok(warp::reply::json(&settings).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// POST /me/password
+///
+/// Requires the current password, all other sessions are revoked on success
+pub async fn change_password(
+    user_uuid: Uuid,
+    token: String,
+    body: PasswordChangeRequest,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+
+    if user.password != body.current_password {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+    if body.new_password.len() < PASSWORD_MIN_LEN {
+        return Ok(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    if db
+        .users
+        .set_password(&user, &body.new_password)
+        .await
+        .is_err()
+    {
+        return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+    }
+
+    match revoke_sessions(db, &user_uuid, Some(&token)).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// Returns the profile of the user
+///
+/// If the user has never filled the profile, the empty one is returned
+pub async fn fetch_profile(db: Arc<Database>, user_uuid: Uuid) -> Result<UserProfile, DbError> {
+    db.users
+        .profile(&user_uuid)
+        .await
+        .map(|profile| profile.unwrap_or_else(|| UserProfile::new(user_uuid)))
+}
+
+/// Returns the settings of the user or the default ones
+pub async fn fetch_settings(db: Arc<Database>, user_uuid: Uuid) -> Result<UserSettings, DbError> {
+    db.users
+        .settings(&user_uuid)
+        .await
+        .map(Option::unwrap_or_default)
+}
+
+/// Checks whether the media is the avatar in the profile of its sender
+///
+/// The name of the media is chosen by the client, so it can not tell
+pub async fn is_avatar(db: Arc<Database>, media: &MediaEntry) -> Result<bool, DbError> {
+    let profile = db.users.profile(&media.sender).await?;
+    Ok(profile.and_then(|profile| profile.avatar) == Some(media.uuid))
+}
+
+/// Name of the avatar thumbnail object in the `images` bucket
+pub fn avatar_thumbnail_name(avatar_uuid: &Uuid) -> String {
+    format!("{avatar_uuid}_thumb.png")
+}
+
+/// Records the avatar in the media table
+async fn add_avatar(
+    db: Arc<Database>,
+    user_uuid: Uuid,
+    avatar_uuid: Uuid,
+    object_name: &str,
+) -> Result<(), DbError> {
+    let avatar = MediaEntry {
+        uuid: avatar_uuid,
+        name: "avatar".to_owned(),
+        path: object_name.to_owned(),
+        media_type: MediaType::Image,
+        sender: user_uuid,
+    };
+    db.media.add(&avatar, Utc::now().timestamp()).await
+}
+
+/// Removes the avatar objects and its media entry
+async fn remove_avatar(db: Arc<Database>, avatar_uuid: Uuid) {
+    let bucket = bucket_name(MediaType::Image);
+
+    if let Ok(avatar) = db.media.get(&avatar_uuid).await {
+        if let Err(err) = remove_object(&bucket, &avatar.path).await {
+            log::error!("Error removing the avatar `{}`: {err}", avatar.path);
+        }
+    }
+    if let Err(err) = remove_object(&bucket, &avatar_thumbnail_name(&avatar_uuid)).await {
+        log::error!("Error removing the avatar thumbnail: {err}");
+    }
+
+    if db.media.remove(&avatar_uuid).await.is_err() {
+        log::error!("Error removing the avatar `{avatar_uuid}` from the DB!");
+    }
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    match value.is_empty() {
+        true => None,
+        false => Some(value.to_owned()),
+    }
+}
@@ -0,0 +1,541 @@
+use std::{convert::Infallible, sync::Arc};
+
+use chrono::Utc;
+use futures::TryStreamExt;
+use sha3::{Digest, Sha3_256};
+use uuid::Uuid;
+use warp::{
+    hyper::{Body, StatusCode},
+    Reply,
+};
+
+use nexuslib::{
+    crypto::attachment::ENCRYPTED_CONTENT_TYPE,
+    errors::file::UploadError,
+    models::{
+        file::{format::SNIFF_LEN, media_file::MediaFile, upload::check_file},
+        message::media::MediaType,
+    },
+    request::file::PresignedUploadRequest,
+    response::file::PresignedUrl,
+};
+
+use crate::{
+    db::{
+        repository::{MediaEntry, PendingMedia},
+        Database,
+    },
+    errors::{db::DbError, file::FileError, storage::StorageError},
+    ops::{
+        blob::{discard_object, reuse_blob, share_object},
+        file::{add_file, check_quota, object_name},
+    },
+    processing::spawn_previews,
+    storage::{
+        bucket_name, get_object, get_object_range, object_size, policy::upload_policy,
+        presign_object, remove_object, stat_object, stream_object_range, PresignMethod,
+    },
+};
+
+use super::me::{avatar_thumbnail_name, is_avatar};
+
+/// Time in seconds the presigned upload URL is valid
+const UPLOAD_URL_TTL: u32 = 15 * 60;
+
+/// Time in seconds the presigned download URL is valid
+const DOWNLOAD_URL_TTL: u32 = 5 * 60;
+
+/// Time in seconds the upload can be committed after the URL was issued
+const PENDING_TTL: i64 = 60 * 60;
+
+/// Size of the ranges the object is read in while it is verified
+const VERIFY_RANGE: usize = 1024 * 1024;
+
+/// GET /media/avatars/:uuid
+///
+/// Returns the thumbnail of the avatar as PNG
+pub async fn get_avatar(
+    avatar_uuid: String,
+    _uid: Uuid,
+) -> Result<warp::reply::Response, Infallible> {
+    let avatar_uuid = match Uuid::parse_str(&avatar_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    match get_object(
+        &bucket_name(MediaType::Image),
+        &avatar_thumbnail_name(&avatar_uuid),
+    )
+    .await
+    {
+        Ok(bytes) => {
+            Ok(warp::reply::with_header(bytes, "content-type", "image/png").into_response())
+        }
+        Err(_) => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
+
+/// GET /media/:uuid
+///
+/// Returns the file, a single `Range` of bytes is supported
+/// so the downloads can be resumed and the media can be seeked.
+/// The file is streamed, so it is never held in memory as a whole.
+/// Only the sender and the receivers of the file can download it
+pub async fn get_media(
+    media_uuid: String,
+    range: Option<String>,
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let media_uuid = match Uuid::parse_str(&media_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let MediaEntry {
+        name,
+        path,
+        media_type,
+        ..
+    } = match fetch_accessible(db, media_uuid, user_uuid).await {
+        Ok(media) => media,
+        Err(status) => return Ok(status.into_response()),
+    };
+    let bucket = bucket_name(media_type);
+
+    let size = match object_size(&bucket, &path).await {
+        Ok(size) => size,
+        Err(err) => {
+            log::error!("Error reading the object `{path}`: {err}");
+            return Ok(StatusCode::NOT_FOUND.into_response());
+        }
+    };
+
+    let range = range.map_or(ByteRange::Full, |range| parse_range(&range, size));
+    let (start, length) = match range {
+        ByteRange::Full => (0, size),
+        ByteRange::Partial(start, end) => (start, end - start + 1),
+        ByteRange::Unsatisfiable => {
+            let response = warp::reply::with_header(
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                "content-range",
+                format!("bytes */{size}"),
+            );
+            return Ok(response.into_response());
+        }
+    };
+
+    // the headers are already sent when a later range fails
+    let body = stream_object_range(bucket, path.clone(), start, length)
+        .inspect_err(move |err| log::error!("Error downloading the object `{path}`: {err}"));
+
+    let mut response = warp::reply::Response::new(Body::wrap_stream(body));
+    let headers = response.headers_mut();
+    headers.insert("content-type", "application/octet-stream".parse().unwrap());
+    headers.insert("content-length", length.into());
+    headers.insert("accept-ranges", "bytes".parse().unwrap());
+    if let Ok(disposition) = format!("attachment; filename=\"{}\"", name.replace('"', "")).parse() {
+        headers.insert("content-disposition", disposition);
+    }
+    if let ByteRange::Partial(start, end) = range {
+        headers.insert(
+            "content-range",
+            format!("bytes {start}-{end}/{size}").parse().unwrap(),
+        );
+        *response.status_mut() = StatusCode::PARTIAL_CONTENT;
+    }
+
+    Ok(response)
+}
+
+/// POST /media/uploads
+///
+/// Returns a presigned URL the file is uploaded to directly,
+/// the media can be used only after the upload is committed.
+/// If the same content is already stored, the file is committed at once
+/// and returned instead of the URL
+pub async fn presign_upload(
+    user_uuid: Uuid,
+    db: Arc<Database>,
+    body: PresignedUploadRequest,
+) -> Result<warp::reply::Response, Infallible> {
+    let file = body.file;
+    if file.sender != user_uuid {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+    let policy = upload_policy(file.media_type);
+    let content_type = body.content_type.as_str();
+    let checked = check_file(&file)
+        .and_then(|_| policy.check_size(&file))
+        .and_then(|_| match file.secret {
+            // the ciphertext of an encrypted file has no type of its own
+            true if content_type.trim() == ENCRYPTED_CONTENT_TYPE => Ok(()),
+            false if !file.media_type.accepts(content_type) => Err(UploadError::ContentType),
+            false if !policy.allows_mime(content_type) => Err(UploadError::UnsupportedFormat),
+            false => Ok(()),
+            true => Err(UploadError::ContentType),
+        });
+    if let Err(e) = checked {
+        return Ok(rejection(e));
+    }
+    match check_quota(db.clone(), user_uuid, file.len_bytes).await {
+        Ok(()) => (),
+        Err(FileError::Rejected(e)) => return Ok(rejection(e)),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    // the UUID of the media can not be taken over
+    match db.media.get(&file.uuid).await {
+        Err(DbError::NotFound) => (),
+        Ok(_) => return Ok(StatusCode::CONFLICT.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    // nothing has to be uploaded
+    match reuse_blob(db.clone(), &file, user_uuid).await {
+        Ok(Some(object)) => {
+            spawn_previews(db, &file, &object);
+            return Ok(warp::reply::json(&file).into_response());
+        }
+        Ok(None) => (),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    let object = object_name(&file, &Uuid::new_v4());
+    let url = match presign_object(
+        PresignMethod::Put,
+        &bucket_name(file.media_type),
+        &object,
+        UPLOAD_URL_TTL,
+    )
+    .await
+    {
+        Ok(url) => url,
+        Err(StorageError::Unsupported) => return Ok(StatusCode::NOT_IMPLEMENTED.into_response()),
+        Err(err) => {
+            log::error!("Error presigning the upload: {err}");
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    };
+
+    let pending = PendingMedia {
+        file: file.clone(),
+        object,
+        content_type: body.content_type,
+        expires_at: Utc::now().timestamp() + PENDING_TTL,
+    };
+    match db.media.add_pending(&pending).await {
+        Ok(()) => {
+            let expires_at = Utc::now().timestamp() + UPLOAD_URL_TTL as i64;
+            let presigned = PresignedUrl::new(file.uuid, url, expires_at);
+            Ok(
+                warp::reply::with_status(warp::reply::json(&presigned), StatusCode::CREATED)
+                    .into_response(),
+            )
+        }
+        Err(DbError::AlreadyExists) => Ok(StatusCode::CONFLICT.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// POST /media/uploads/:uuid
+///
+/// Verifies the uploaded object against the file it was presigned for,
+/// the object is removed if it does not match
+pub async fn commit_upload(
+    media_uuid: String,
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let media_uuid = match Uuid::parse_str(&media_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let PendingMedia {
+        file,
+        object,
+        content_type,
+        ..
+    } = match db.media.pending(&media_uuid).await {
+        Ok(pending) => pending,
+        Err(DbError::NotFound) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    if file.sender != user_uuid {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    let bucket = bucket_name(file.media_type);
+    let verified = match verify_object(&bucket, &object, &file, &content_type).await {
+        // the upload has not finished yet
+        Err(StorageError::NotFound) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&UploadError::Incomplete),
+                StatusCode::CONFLICT,
+            )
+            .into_response())
+        }
+        Err(err) => {
+            log::error!("Error verifying the object `{object}`: {err}");
+            return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+        Ok(verified) => verified,
+    };
+
+    // only one of the concurrent commits goes on
+    match db.media.claim_pending(&media_uuid).await {
+        Ok(true) => (),
+        Ok(false) => return Ok(StatusCode::CONFLICT.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+    if let Err(e) = verified {
+        if let Err(err) = remove_object(&bucket, &object).await {
+            log::error!("Error removing the object `{object}`: {err}");
+        }
+        return Ok(rejection(e));
+    }
+
+    // the same content may have been committed meanwhile
+    let object = match share_object(db.clone(), &file, &object).await {
+        Ok(object) => object,
+        Err(e) => {
+            let _ = remove_object(&bucket, &object).await;
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&e),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            )
+            .into_response());
+        }
+    };
+
+    match add_file(db.clone(), &file, &object, user_uuid).await {
+        Ok(_) => {
+            spawn_previews(db, &file, &object);
+            Ok(warp::reply::json(&file).into_response())
+        }
+        Err(e) => {
+            discard_object(db, &file, &object).await;
+            let status = match e {
+                DbError::AlreadyExists => StatusCode::CONFLICT,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            };
+            Ok(warp::reply::with_status(warp::reply::json(&e), status).into_response())
+        }
+    }
+}
+
+/// GET /media/:uuid/thumbnails/:size
+///
+/// Returns the thumbnail of the size as JPEG,
+/// there is none until the previews are generated
+pub async fn get_thumbnail(
+    media_uuid: String,
+    size: u32,
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let media_uuid = match Uuid::parse_str(&media_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let media = match fetch_accessible(db.clone(), media_uuid, user_uuid).await {
+        Ok(media) => media,
+        Err(status) => return Ok(status.into_response()),
+    };
+    let preview = match db.media.preview(&media_uuid).await {
+        Ok(Some(preview)) => preview,
+        Ok(None) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+    let Some(thumbnail) = preview
+        .thumbnails
+        .iter()
+        .find(|thumbnail| thumbnail.size == size)
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    match get_object(&bucket_name(media.media_type), &thumbnail.path).await {
+        Ok(bytes) => {
+            Ok(warp::reply::with_header(bytes, "content-type", "image/jpeg").into_response())
+        }
+        Err(StorageError::NotFound) => Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(err) => {
+            log::error!("Error downloading the object `{}`: {err}", thumbnail.path);
+            Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// GET /media/:uuid/url
+///
+/// Returns a presigned URL the file is downloaded from directly
+pub async fn presign_download(
+    media_uuid: String,
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let media_uuid = match Uuid::parse_str(&media_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    let MediaEntry {
+        path, media_type, ..
+    } = match fetch_accessible(db, media_uuid, user_uuid).await {
+        Ok(media) => media,
+        Err(status) => return Ok(status.into_response()),
+    };
+
+    let bucket = bucket_name(media_type);
+    match presign_object(PresignMethod::Get, &bucket, &path, DOWNLOAD_URL_TTL).await {
+        Ok(url) => {
+            let expires_at = Utc::now().timestamp() + DOWNLOAD_URL_TTL as i64;
+            Ok(warp::reply::json(&PresignedUrl::new(media_uuid, url, expires_at)).into_response())
+        }
+        Err(StorageError::Unsupported) => Ok(StatusCode::NOT_IMPLEMENTED.into_response()),
+        Err(err) => {
+            log::error!("Error presigning the download of `{path}`: {err}");
+            Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response())
+        }
+    }
+}
+
+/// Returns the media if the user can download it
+///
+/// The sender and the receivers of the messages the media is attached to
+/// have access to it, avatars are visible to everyone
+async fn fetch_accessible(
+    db: Arc<Database>,
+    media_uuid: Uuid,
+    user_uuid: Uuid,
+) -> Result<MediaEntry, StatusCode> {
+    let media = match db.media.get(&media_uuid).await {
+        Ok(media) => media,
+        Err(DbError::NotFound) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    };
+    if media.sender == user_uuid {
+        return Ok(media);
+    }
+    match is_avatar(db.clone(), &media).await {
+        Ok(true) => return Ok(media),
+        Ok(false) => (),
+        Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+
+    match db.messages.is_attached_for(&media_uuid, &user_uuid).await {
+        Ok(true) => Ok(media),
+        Ok(false) => Err(StatusCode::FORBIDDEN),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+/// Checks the size, the type, the content and the hash of the uploaded object
+///
+/// The outer error means the object could not be read
+async fn verify_object(
+    bucket: &str,
+    object: &str,
+    file: &MediaFile,
+    content_type: &str,
+) -> Result<Result<(), UploadError>, StorageError> {
+    let stat = stat_object(bucket, object).await?;
+    if stat.size != file.len_bytes {
+        return Ok(Err(UploadError::SizeMismatch));
+    }
+    // the type is known only if the backend keeps it
+    if stat
+        .content_type
+        .is_some_and(|stored| !stored.eq_ignore_ascii_case(content_type))
+    {
+        return Ok(Err(UploadError::ContentType));
+    }
+
+    let mut hasher = Sha3_256::new();
+    let mut offset = 0;
+    while offset < stat.size {
+        let length = VERIFY_RANGE.min(stat.size - offset);
+        let data = get_object_range(bucket, object, offset, length).await?;
+        if data.len() != length {
+            return Ok(Err(UploadError::SizeMismatch));
+        }
+        // the content is known from the first bytes
+        if offset == 0 {
+            let head = &data[..data.len().min(SNIFF_LEN)];
+            if let Err(e) = upload_policy(file.media_type).check_content(file, head) {
+                return Ok(Err(e));
+            }
+        }
+        hasher.update(&data);
+        offset += length;
+    }
+
+    match hex::encode(hasher.finalize()) == file.hash.to_lowercase() {
+        true => Ok(Ok(())),
+        false => Ok(Err(UploadError::Corrupted)),
+    }
+}
+
+/// Range of bytes asked with the `Range` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The header is ignored and the whole file is sent
+    Full,
+    /// The first and the last byte
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Parses the `bytes=` range of the file of the size
+///
+/// Several ranges, other units and invalid ranges are ignored (RFC 9110 14.2)
+pub fn parse_range(range: &str, size: usize) -> ByteRange {
+    let Some(spec) = range.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    let Some((start, end)) = spec.split_once('-').filter(|_| !spec.contains(',')) else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        // the last bytes
+        ("", suffix) => match suffix.parse::<usize>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) if size > 0 => (size - suffix.min(size), size - 1),
+            Ok(_) => return ByteRange::Unsatisfiable,
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => {
+            let Ok(start) = start.parse::<usize>() else {
+                return ByteRange::Full;
+            };
+            let end = match end {
+                "" => usize::MAX,
+                end => match end.parse::<usize>() {
+                    Ok(end) if end >= start => end,
+                    _ => return ByteRange::Full,
+                },
+            };
+            if start >= size {
+                return ByteRange::Unsatisfiable;
+            }
+            (start, end.min(size - 1))
+        }
+    };
+
+    ByteRange::Partial(start, end)
+}
+
+/// Answers with the reason the upload is rejected
+fn rejection(e: UploadError) -> warp::reply::Response {
+    let status = match e {
+        UploadError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        UploadError::QuotaExceeded => StatusCode::INSUFFICIENT_STORAGE,
+        _ => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+    warp::reply::with_status(warp::reply::json(&e), status).into_response()
+}
@@ -0,0 +1,55 @@
+use std::{convert::Infallible, sync::Arc};
+
+use nexuslib::request::message::MessageHistoryQuery;
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Reply};
+
+use crate::db::Database;
+
+/// Default and maximal number of messages in a page of the history
+const HISTORY_LIMIT: i32 = 50;
+const HISTORY_MAX_LIMIT: i32 = 200;
+
+/// GET /messages/:uuid?before=&limit=
+///
+/// Returns the conversation with the user, the latest message first,
+/// the attachments and the voice notes are returned with the messages.
+/// The next page starts `before` the oldest message of the previous one
+pub async fn list_messages(
+    peer: String,
+    query: MessageHistoryQuery,
+    uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let peer = match Uuid::parse_str(&peer) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+    let limit = query
+        .limit
+        .unwrap_or(HISTORY_LIMIT)
+        .clamp(1, HISTORY_MAX_LIMIT) as usize;
+
+    // the messages are partitioned by the time, so both directions are filtered
+    let mut messages = Vec::new();
+    for (sender, receiver) in [(uid, peer), (peer, uid)] {
+        match db.messages.between(&sender, &receiver).await {
+            Ok(mut sent) => messages.append(&mut sent),
+            Err(e) => {
+                return Ok(warp::reply::with_status(
+                    warp::reply::json(&e),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+                .into_response())
+            }
+        }
+    }
+
+    if let Some(before) = query.before {
+        messages.retain(|message| message.get_created_at().timestamp() < before);
+    }
+    messages.sort_by_key(|message| std::cmp::Reverse(message.get_created_at()));
+    messages.truncate(limit);
+
+    Ok(warp::reply::json(&messages).into_response())
+}
@@ -0,0 +1,300 @@
+use std::{convert::Infallible, sync::Arc};
+
+use chrono::Utc;
+use rand_core::{OsRng, RngCore};
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Reply};
+
+use nexuslib::{
+    crypto::{hasher::get_hash, totp},
+    request::auth::{AuthRequestMeta, TotpCodeRequest, TwoFactorLoginRequest},
+    response::auth::{
+        AuthResponse, RecoveryCodesResponse, TotpEnrollResponse, TwoFactorChallengeResponse,
+    },
+};
+
+use crate::{
+    db::{
+        repository::{LoginChallenge, TotpEntry},
+        Database,
+    },
+    errors::db::DbError,
+};
+
+use super::{admin::active_restriction, auth::add_jwt_session};
+
+/// Issuer shown in authenticator apps
+const ISSUER: &str = "Nexus";
+/// Accepted clock drift in TOTP steps
+const TOTP_WINDOW: i64 = 1;
+const RECOVERY_CODES_COUNT: usize = 10;
+/// Lifetime of a login challenge in seconds
+const CHALLENGE_TTL: i64 = 5 * 60;
+/// Wrong codes allowed per login challenge
+const CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+
+/// POST /me/2fa/enroll
+///
+/// Generates a new secret, 2FA is enabled only after the first code is confirmed
+pub async fn enroll(
+    user_uuid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+    };
+
+    match fetch_totp(db.clone(), &user_uuid).await {
+        Ok(Some(entry)) if entry.enabled => return Ok(StatusCode::CONFLICT.into_response()),
+        Ok(_) => (),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    let secret = totp::generate_secret();
+
+    match db.users.enroll_totp(&user_uuid, &secret).await {
+        Ok(_) => Ok(warp::reply::json(&TotpEnrollResponse {
+            secret: totp::encode_secret(&secret),
+            otpauth_uri: totp::otpauth_uri(&secret, &user.username, ISSUER),
+        })
+        .into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// POST /me/2fa/confirm
+///
+/// Enables 2FA and returns the recovery codes
+pub async fn confirm(
+    user_uuid: Uuid,
+    body: TotpCodeRequest,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let entry = match fetch_totp(db.clone(), &user_uuid).await {
+        Ok(Some(entry)) if entry.enabled => return Ok(StatusCode::CONFLICT.into_response()),
+        Ok(Some(entry)) => entry,
+        Ok(None) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    let step = match totp::verify(
+        &entry.secret,
+        &body.code,
+        Utc::now().timestamp(),
+        TOTP_WINDOW,
+    ) {
+        Some(step) => step,
+        None => return Ok(StatusCode::FORBIDDEN.into_response()),
+    };
+
+    let recovery_codes = generate_recovery_codes();
+    let hashes = recovery_codes
+        .iter()
+        .map(|code| get_hash(&normalize_recovery_code(code)))
+        .collect::<Vec<_>>();
+
+    match db.users.enable_totp(&user_uuid, step, &hashes).await {
+        Ok(_) => Ok(warp::reply::json(&RecoveryCodesResponse { recovery_codes }).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// POST /me/2fa/disable
+///
+/// Requires a valid TOTP or recovery code
+pub async fn disable(
+    user_uuid: Uuid,
+    body: TotpCodeRequest,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let entry = match fetch_totp(db.clone(), &user_uuid).await {
+        Ok(Some(entry)) if entry.enabled => entry,
+        Ok(_) => return Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    };
+
+    if check_code(db.clone(), &user_uuid, &entry, &body.code)
+        .await
+        .is_err()
+    {
+        return Ok(StatusCode::FORBIDDEN.into_response());
+    }
+
+    match db.users.remove_totp(&user_uuid).await {
+        Ok(_) => Ok(StatusCode::NO_CONTENT.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
+
+/// POST /auth/login/2fa
+///
+/// Second step of the login: exchanges the challenge and the code for a session
+pub async fn login(
+    db: Arc<Database>,
+    body: TwoFactorLoginRequest,
+) -> Result<warp::reply::Response, Infallible> {
+    let token_hash = get_hash(&body.challenge);
+
+    let challenge = match db.sessions.challenge(&token_hash).await {
+        Ok(Some(challenge)) => challenge,
+        _ => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+    };
+    let user_uuid = challenge.user;
+
+    let entry = match fetch_totp(db.clone(), &user_uuid).await {
+        Ok(Some(entry)) if entry.enabled => entry,
+        _ => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+    };
+
+    if check_code(db.clone(), &user_uuid, &entry, &body.code)
+        .await
+        .is_err()
+    {
+        let attempts = challenge.attempts + 1;
+        let result = match attempts >= CHALLENGE_MAX_ATTEMPTS {
+            // too many attempts => the user has to start the login again
+            true => db.sessions.remove_challenge(&token_hash).await,
+            false => {
+                db.sessions
+                    .set_challenge_attempts(&token_hash, attempts, challenge.expires_at)
+                    .await
+            }
+        };
+        if let Err(e) = result {
+            log::error!("{e:?}");
+        }
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    }
+
+    // the challenge is single use
+    let claimed = db
+        .sessions
+        .claim_challenge(&token_hash)
+        .await
+        .unwrap_or(false);
+    if !claimed {
+        return Ok(StatusCode::UNAUTHORIZED.into_response());
+    }
+
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::UNAUTHORIZED.into_response()),
+    };
+
+    // the user could have been restricted after the first step
+    match active_restriction(db.clone(), &user.uuid).await {
+        Ok(None) => (),
+        Ok(Some(_)) => {
+            return Ok(warp::reply::with_status(
+                warp::reply::json(&DbError::Restricted),
+                StatusCode::FORBIDDEN,
+            )
+            .into_response())
+        }
+        Err(_) => return Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+
+    match add_jwt_session(db, &user, challenge.meta).await {
+        Ok(token) => Ok(warp::reply::json(&AuthResponse::new(user.uuid, token)).into_response()),
+        Err(e) => Ok(warp::reply::with_status(
+            warp::reply::json(&e),
+            StatusCode::INTERNAL_SERVER_ERROR,
+        )
+        .into_response()),
+    }
+}
+
+/// Checks whether the user has confirmed 2FA
+pub async fn is_enabled(db: Arc<Database>, user_uuid: &Uuid) -> Result<bool, DbError> {
+    fetch_totp(db, user_uuid)
+        .await
+        .map(|entry| entry.map(|entry| entry.enabled).unwrap_or(false))
+}
+
+/// Stores a new login challenge, only its hash is kept in the DB
+pub async fn create_challenge(
+    db: Arc<Database>,
+    user_uuid: &Uuid,
+    meta: AuthRequestMeta,
+) -> Result<TwoFactorChallengeResponse, DbError> {
+    let mut raw_token = [0u8; 32];
+    OsRng.fill_bytes(&mut raw_token);
+    let token = hex::encode(raw_token);
+    let expires_at = Utc::now().timestamp() + CHALLENGE_TTL;
+
+    let challenge = LoginChallenge {
+        user: *user_uuid,
+        attempts: 0,
+        meta,
+        expires_at,
+    };
+    db.sessions
+        .add_challenge(&get_hash(&token), &challenge)
+        .await?;
+
+    Ok(TwoFactorChallengeResponse::new(token, expires_at))
+}
+
+/// Returns the TOTP state of the user if 2FA was ever enrolled
+async fn fetch_totp(db: Arc<Database>, user_uuid: &Uuid) -> Result<Option<TotpEntry>, DbError> {
+    db.users.totp(user_uuid).await
+}
+
+/// Checks a TOTP code or consumes a recovery code
+///
+/// Both updates are compare-and-set, so a code can be used only once
+async fn check_code(
+    db: Arc<Database>,
+    user_uuid: &Uuid,
+    entry: &TotpEntry,
+    code: &str,
+) -> Result<(), DbError> {
+    if let Some(step) = totp::verify(&entry.secret, code, Utc::now().timestamp(), TOTP_WINDOW) {
+        // the code (or an older one) was already used
+        if step <= entry.last_step {
+            return Err(DbError::WrongCredentials);
+        }
+
+        let advanced = db
+            .users
+            .advance_totp_step(user_uuid, step, entry.last_step)
+            .await?;
+
+        return match advanced {
+            true => Ok(()),
+            false => Err(DbError::WrongCredentials),
+        };
+    }
+
+    let code_hash = get_hash(&normalize_recovery_code(code));
+    if !entry.recovery_codes.contains(&code_hash) {
+        return Err(DbError::WrongCredentials);
+    }
+
+    let used = db
+        .users
+        .use_recovery_code(user_uuid, &code_hash, &entry.recovery_codes)
+        .await?;
+
+    match used {
+        true => Ok(()),
+        false => Err(DbError::WrongCredentials),
+    }
+}
+
+/// Generates recovery codes in the form of `xxxxx-xxxxx`
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES_COUNT)
+        .map(|_| {
+            let mut raw = [0u8; 5];
+            OsRng.fill_bytes(&mut raw);
+            let code = hex::encode(raw);
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+fn normalize_recovery_code(code: &str) -> String {
+    code.trim().replace('-', "").to_lowercase()
+}
@@ -1,313 +1,331 @@
 use std::{convert::Infallible, sync::Arc};
 
-use chrono::Duration;
-use scylla::{
-    batch::Batch, frame::value::Timestamp, prepared_statement::PreparedStatement, IntoTypedRows,
-    Session,
-};
 use tokio::sync::Mutex;
 use uuid::{self, Uuid};
 use warp::{hyper::StatusCode, Reply};
 
-use nexuslib::models::user::User;
+use nexuslib::{
+    models::user::{role::Role, User},
+    request::user::UsernameUpdateRequest,
+};
+
+use crate::{
+    db::Database,
+    errors::db::DbError,
+    ops::blob::unlink_media,
+    processing::remove_thumbnails,
+    state::connection::ConnectionState,
+    storage::{bucket_name, remove_object},
+};
+
+use super::{
+    auth::revoke_sessions,
+    me::{avatar_thumbnail_name, fetch_profile},
+};
 
-use crate::{db::models_wrapper::UserDB, errors::db::DbError};
+const USERNAME_MAX_LEN: usize = 32;
 
-pub async fn list(session: Arc<Mutex<Session>>, _uid: ()) -> Result<impl warp::Reply, Infallible> {
+pub async fn list(db: Arc<Database>, _uid: Uuid) -> Result<warp::reply::Response, Infallible> {
     // Just return a JSON array of users
-    let users = session
-        .lock()
-        .await
-        .query("SELECT * from nexus.users", &[])
-        .await
-        .unwrap()
-        .rows
-        .unwrap_or_default()
-        .into_typed::<UserDB>()
-        .map(|u| u.unwrap().get_user())
-        .collect::<Vec<User>>();
-
-    Ok(warp::reply::json(&users))
+    match db.users.list().await {
+        Ok(users) => Ok(warp::reply::json(&users).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
 }
 
 pub async fn get_by_uuid(
     id: String,
-    _uid: (),
-    session: Arc<Mutex<Session>>,
-) -> Result<impl warp::Reply, Infallible> {
+    _uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
     // Just return a JSON object of user
-    let user_uuid = Uuid::parse_str(&id).unwrap();
-    let user = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.users WHERE uuid = ? ALLOW FILTERING;",
-            (user_uuid,),
-        )
-        .await
-        .unwrap()
-        .first_row()
-        .unwrap()
-        .into_typed::<UserDB>()
-        .unwrap()
-        .get_user();
-
-    Ok(warp::reply::json(&user))
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    match db.users.get(&user_uuid).await {
+        Ok(user) => Ok(warp::reply::json(&user).into_response()),
+        Err(DbError::NotFound) => Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
 }
 
 pub async fn get_by_username(
     username: String,
-    _uid: (),
-    session: Arc<Mutex<Session>>,
-) -> Result<impl warp::Reply, Infallible> {
+    _uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
     // Just return a JSON object of user
-    let user = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.users WHERE username = ? ALLOW FILTERING;",
-            (username,),
-        )
-        .await
-        .unwrap()
-        .first_row()
-        .unwrap()
-        .into_typed::<UserDB>()
-        .unwrap()
-        .get_user();
-
-    Ok(warp::reply::json(&user))
+    match db.users.get_by_username(&username).await {
+        Ok(user) => Ok(warp::reply::json(&user).into_response()),
+        Err(DbError::NotFound) => Ok(StatusCode::NOT_FOUND.into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
 }
 
-pub async fn create(
-    user: (User, [u8; 32]),
-    session: Arc<Mutex<Session>>,
-) -> Result<StatusCode, Infallible> {
+pub async fn create(user: (User, [u8; 32]), db: Arc<Database>) -> Result<StatusCode, Infallible> {
     let secret = user.1;
     let user = user.0;
 
     log::debug!("create_user: {:?}", user);
 
-    if check_user_by_uuid(session.clone(), &user.uuid)
+    if check_user_by_uuid(db.clone(), &user.uuid).await.is_err() {
+        return Ok(StatusCode::BAD_REQUEST);
+    }
+
+    if !is_valid_username(&user.username) {
+        return Ok(StatusCode::BAD_REQUEST);
+    }
+
+    if check_user_by_username(db.clone(), &user.username)
         .await
         .is_err()
     {
         return Ok(StatusCode::BAD_REQUEST);
     }
 
-    if check_user_by_username(session.clone(), &user.username)
+    // reserve the username, so the concurrent registration can not take it
+    if db
+        .users
+        .claim_username(&user.username, &user.uuid)
         .await
         .is_err()
     {
         return Ok(StatusCode::BAD_REQUEST);
     }
 
-    // create batch
-    let mut batch: Batch = Default::default();
-
-    // prepare statements
-    let prepared_user: PreparedStatement = session.lock().await
-        .prepare(
-            "INSERT INTO nexus.users (uuid, username, password, role, public_key, created_at) VALUES(?, ?, ?, ?, ?, ?);",
-        )
-        .await
-        .unwrap();
-
-    let prepared_secret: PreparedStatement = session
-        .lock()
-        .await
-        .prepare("INSERT INTO nexus.secret_keys (user, private_key) VALUES(?, ?);")
-        .await
-        .unwrap();
-
-    // append all statements to the batch
-    batch.append_statement(prepared_user);
-    batch.append_statement(prepared_secret);
-
-    // define values to insert
-    let user_values = (
-        user.uuid,
-        &user.username.to_owned(),
-        &user.password.to_owned(),
-        (user.role.to_owned().get_index()).to_owned() as i8,
-        &user.public_key_str().to_owned(),
-        Timestamp(Duration::try_seconds(user.created_at).unwrap()),
-    );
-    let secret_values = (user.uuid, secret.to_vec());
-    let batch_values = (user_values, secret_values);
-
-    match session.lock().await.batch(&batch, batch_values).await {
+    match db.users.create(&user, &secret).await {
         Ok(_) => Ok(StatusCode::CREATED),
-        Err(_e) => Ok(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(_e) => {
+            release_username(db, &user.username, &user.uuid).await;
+            Ok(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
 
 pub async fn update(
     id: String,
-    _uid: (),
-    user: User,
-    session: Arc<Mutex<Session>>,
+    uid: Uuid,
+    body: UsernameUpdateRequest,
+    db: Arc<Database>,
 ) -> Result<impl warp::Reply, Infallible> {
-    log::debug!("update_user: id={}, user={:?}", id, user);
+    log::debug!("update_user: id={}, username={}", id, body.username);
 
-    if check_user_by_uuid(session.clone(), &user.uuid)
-        .await
-        .is_ok()
-    {
-        return Ok(StatusCode::NOT_FOUND);
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST),
+    };
+
+    // only the user can change the own username
+    if user_uuid != uid {
+        return Ok(StatusCode::FORBIDDEN);
     }
 
-    if session
-        .lock()
-        .await
-        .query(
-            "UPDATE nexus.users SET username = ? WHERE username = ?;",
-            (user.username.to_owned(),),
-        )
-        .await
-        .is_err()
-    {
-        return Ok(StatusCode::NOT_FOUND);
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND),
+    };
+
+    let username = body.username.trim();
+    if !is_valid_username(username) {
+        return Ok(StatusCode::BAD_REQUEST);
+    }
+    if username == user.username {
+        return Ok(StatusCode::OK);
     }
 
-    Ok(StatusCode::OK)
+    match change_username(db, &user, username).await {
+        Ok(_) => Ok(StatusCode::OK),
+        Err(DbError::AlreadyExists) => Ok(StatusCode::CONFLICT),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR),
+    }
 }
 
 pub async fn delete(
     user_uuid: String,
-    _uid: (),
-    session: Arc<Mutex<Session>>,
+    uid: Uuid,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
 ) -> Result<impl warp::Reply, Infallible> {
     log::debug!("delete_user: user_uuid={}", user_uuid);
-    let user_uuid = Uuid::parse_str(&user_uuid).unwrap();
-
-    if check_user_by_uuid(session.clone(), &user_uuid)
-        .await
-        .is_ok()
-    {
-        return Ok(StatusCode::NOT_FOUND);
+    let user_uuid = match Uuid::parse_str(&user_uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST),
+    };
+
+    // the account can be deleted by its owner or by an admin
+    if user_uuid != uid && !is_admin(db.clone(), &uid).await {
+        return Ok(StatusCode::FORBIDDEN);
     }
 
-    match session
-        .lock()
-        .await
-        .query(
-            "DELETE FROM nexus.users WHERE users.uuid = ?;",
-            (user_uuid,),
-        )
-        .await
-    {
+    let user = match db.users.get(&user_uuid).await {
+        Ok(user) => user,
+        Err(_) => return Ok(StatusCode::NOT_FOUND),
+    };
+
+    match delete_account(db, state, &user).await {
         Ok(_) => Ok(StatusCode::NO_CONTENT),
         Err(_) => Ok(StatusCode::SERVICE_UNAVAILABLE),
     }
 }
 
-pub async fn check_user_by_uuid(
-    session: Arc<Mutex<Session>>,
-    user_uuid: &Uuid,
-) -> Result<(), DbError> {
-    if let Some(rows) = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.users WHERE uuid = ? ALLOW FILTERING;",
-            (user_uuid,),
-        )
+/// Checks whether the user has the `Admin` role
+pub async fn is_admin(db: Arc<Database>, user_uuid: &Uuid) -> bool {
+    db.users
+        .get(user_uuid)
         .await
-        .unwrap()
-        .rows
-    {
-        if !rows.is_empty() {
-            return Err(DbError::AlreadyExists);
-        }
-    }
+        .map(|user| user.role == Role::Admin)
+        .unwrap_or(false)
+}
 
-    Ok(())
+/// Checks that the username is not empty, not too long
+/// and consists of latin letters, digits, `_` and `.`
+pub fn is_valid_username(username: &str) -> bool {
+    !username.is_empty()
+        && username.len() <= USERNAME_MAX_LEN
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
 }
 
-pub async fn check_user_by_username(
-    session: Arc<Mutex<Session>>,
+/// Changes the username of the user
+///
+/// The new username is claimed atomically before the user row is rewritten,
+/// since the username is a part of the primary key
+pub async fn change_username(
+    db: Arc<Database>,
+    user: &User,
     username: &str,
 ) -> Result<(), DbError> {
-    if let Some(rows) = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.users WHERE username = ? ALLOW FILTERING;",
-            (username.to_owned(),),
-        )
-        .await
-        .unwrap()
-        .rows
-    {
-        if !rows.is_empty() {
-            return Err(DbError::AlreadyExists);
-        }
+    // users registered before the usernames index existed are not claimed there
+    check_user_by_username(db.clone(), username).await?;
+    db.users.claim_username(username, &user.uuid).await?;
+
+    if let Err(e) = db.users.rename(user, username).await {
+        release_username(db, username, &user.uuid).await;
+        return Err(e);
     }
 
+    release_username(db, &user.username, &user.uuid).await;
     Ok(())
 }
 
-pub async fn get_uuid_by_token(session: Arc<Mutex<Session>>, token: &str) -> Result<Uuid, DbError> {
-    let user_sessions = session
-        .lock()
-        .await
-        .query(
-            "SELECT * FROM nexus.sessions WHERE jwt = ? ALLOW FILTERING;",
-            (token,),
-        )
-        .await
-        .unwrap()
-        .first_row();
-
-    match user_sessions {
-        Ok(row) => {
-            let user_session = row
-                .into_typed::<(
-                    String,
-                    Uuid,
-                    chrono::Duration,
-                    String,
-                    String,
-                    String,
-                    String,
-                )>()
-                .unwrap();
-
-            Ok(user_session.1)
+/// Frees the username if it is owned by the user
+pub async fn release_username(db: Arc<Database>, username: &str, user_uuid: &Uuid) {
+    if let Err(e) = db.users.release_username(username, user_uuid).await {
+        log::error!("Error releasing the username `{username}`: {e:?}");
+    }
+}
+
+/// Deletes the account and everything that belongs to it
+///
+/// Live sessions are disconnected first, so the user can not
+/// produce new data while the account is being deleted
+pub async fn delete_account(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user: &User,
+) -> Result<(), DbError> {
+    let disconnected = state.lock().await.disconnect_user(&user.uuid);
+    log::debug!("delete_account: {disconnected} live session(s) disconnected");
+
+    // sessions (JWT)
+    revoke_sessions(db.clone(), &user.uuid, None).await?;
+
+    // the profile is removed with the rest of the data below
+    let avatar = fetch_profile(db.clone(), user.uuid).await?.avatar;
+
+    // media objects and their entries
+    for media in db.media.sent_by(&user.uuid).await? {
+        let bucket = bucket_name(media.media_type);
+        // a shared blob is removed once nothing points to it
+        let shared = unlink_media(db.clone(), media.uuid).await?;
+        if !shared {
+            if let Err(err) = remove_object(&bucket, &media.path).await {
+                log::error!("Error removing the object `{}`: {err}", media.path);
+            }
+        }
+        if avatar == Some(media.uuid) {
+            let _ = remove_object(&bucket, &avatar_thumbnail_name(&media.uuid)).await;
         }
-        Err(_) => Err(DbError::NotFound),
+        remove_thumbnails(&bucket, &media.uuid).await;
+
+        db.media.remove(&media.uuid).await?;
+        db.messages.remove_attachments(&media.uuid).await?;
     }
+
+    // access to the media received in messages
+    db.messages.remove_received(&user.uuid).await?;
+
+    // messages metadata of both sides
+    db.messages.remove_for_user(&user.uuid).await?;
+
+    // calls of both sides with their history and stats
+    db.calls.remove_for_user(&user.uuid).await?;
+
+    // keys, profile, settings, restrictions and storage usage
+    db.users.remove_data(&user.uuid).await?;
+    db.media.remove_usage(&user.uuid).await?;
+
+    // the user itself goes last, so a failed deletion can be retried
+    release_username(db.clone(), &user.username, &user.uuid).await;
+    db.users.remove(&user.uuid).await
+}
+
+/// Returns `AlreadyExists` if there is a user with the UUID
+pub async fn check_user_by_uuid(db: Arc<Database>, user_uuid: &Uuid) -> Result<(), DbError> {
+    match db.users.get(user_uuid).await {
+        Ok(_) => Err(DbError::AlreadyExists),
+        Err(DbError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Returns `AlreadyExists` if there is a user with the username
+pub async fn check_user_by_username(db: Arc<Database>, username: &str) -> Result<(), DbError> {
+    match db.users.get_by_username(username).await {
+        Ok(_) => Err(DbError::AlreadyExists),
+        Err(DbError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn get_uuid_by_token(db: Arc<Database>, token: &str) -> Result<Uuid, DbError> {
+    db.sessions.user_of(token).await
 }
 
 pub async fn get_key(
     id: String,
-    _uid: (),
-    session: Arc<Mutex<Session>>,
+    _uid: Uuid,
+    db: Arc<Database>,
 ) -> Result<warp::reply::Response, Infallible> {
     // parsing UUID
-    let user_uuid = Uuid::parse_str(&id).unwrap();
-
-    // preparing the query
-    let prepared = session
-        .lock()
-        .await
-        .prepare("SELECT * FROM nexus.secret_keys WHERE user = ? ALLOW FILTERING;")
-        .await
-        .unwrap();
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
 
-    // executing the query
-    match session.lock().await.execute(&prepared, (user_uuid,)).await {
+    match db.users.secret_key(&user_uuid).await {
         // if Ok => return the key
-        Ok(row) => {
-            let key = row
-                .first_row()
-                .unwrap()
-                .into_typed::<(Uuid, Vec<u8>)>()
-                .unwrap();
-            Ok(warp::reply::json(&key.1).into_response())
-        }
+        Ok(key) => Ok(warp::reply::json(&key).into_response()),
         // if Err => return error
         Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
     }
 }
+
+pub async fn get_profile(
+    id: String,
+    _uid: Uuid,
+    db: Arc<Database>,
+) -> Result<warp::reply::Response, Infallible> {
+    let user_uuid = match Uuid::parse_str(&id) {
+        Ok(uuid) => uuid,
+        Err(_) => return Ok(StatusCode::BAD_REQUEST.into_response()),
+    };
+
+    match fetch_profile(db, user_uuid).await {
+        Ok(profile) => Ok(warp::reply::json(&profile).into_response()),
+        Err(_) => Ok(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+    }
+}
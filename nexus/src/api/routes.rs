@@ -1,32 +1,79 @@
 use std::{convert::Infallible, sync::Arc};
 
-use scylla::Session;
 use tokio::sync::Mutex;
 use warp::{Filter, Reply};
 
-use crate::{errors::handle_rejection, api::filters};
+use crate::{
+    api::filters, db::Database, errors::handle_rejection, state::connection::ConnectionState,
+};
 
 /// Routes
 ///
 /// All server routes have to be registered here
 pub fn get_routes(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Infallible> + Clone {
     /*
 
     ---  AUTH    ---
     POST                     /auth/login
+    POST                     /auth/login/2fa
     POST                     /auth/register
     POST                     /auth/logout
+    POST                     /auth/reset-password
 
     ---  USERS   ---
     GET                      /users
     GET | PUT | DELETE       /users/:uuid
     POST                     /users/key/:uuid
+    GET                      /users/profile/:uuid
+
+    ---  ME      ---
+    GET | PATCH              /me/profile
+    PUT                      /me/avatar
+    GET | PATCH              /me/settings
+    POST                     /me/password
+    POST                     /me/2fa/enroll
+    POST                     /me/2fa/confirm
+    POST                     /me/2fa/disable
+
+    ---  CALLS   ---
+    GET                      /calls
+    GET                      /calls/:uuid/stats
+
+    ---  MESSAGES ---
+    GET                      /messages/:uuid
+
+    ---  MEDIA   ---
+    GET                      /media/avatars/:uuid
+    GET                      /media/:uuid
+    GET                      /media/:uuid/thumbnails/:size
+    GET                      /media/:uuid/url
+    POST                     /media/uploads
+    POST                     /media/uploads/:uuid
+
+    ---  ADMIN   ---
+    GET                      /admin/users
+    POST                     /admin/users/:uuid/suspend
+    POST                     /admin/users/:uuid/ban
+    DELETE                   /admin/users/:uuid/restriction
+    PUT                      /admin/users/:uuid/role
+    POST                     /admin/users/:uuid/password-reset
+    GET                      /admin/audit
+    GET                      /admin/calls/:uuid/stats
 
     */
     warp::path("api")
-        .and(filters::users::users(session.clone()).or(filters::auth::auth(session.clone())))
+        .and(
+            filters::users::users(db.clone(), state.clone())
+                .or(filters::auth::auth(db.clone()))
+                .or(filters::me::me(db.clone()))
+                .or(filters::calls::calls(db.clone(), state.clone()))
+                .or(filters::messages::messages(db.clone()))
+                .or(filters::media::media(db.clone()))
+                .or(filters::admin::admin(db.clone(), state)),
+        )
         .with(warp::cors().allow_any_origin())
         .recover(handle_rejection)
 }
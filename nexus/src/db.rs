@@ -1,5 +1,7 @@
 use std::{
     io::stdout,
+    process,
+    sync::Arc,
     time::{Duration, Instant},
 };
 
@@ -11,9 +13,76 @@ use crossterm::{
 };
 use scylla::Session;
 
+use self::repository::{
+    CallRepository, MediaRepository, MessageRepository, SessionRepository, UserRepository,
+};
+
 pub mod database;
 mod db_queries;
+pub mod memory;
 pub mod models_wrapper;
+pub mod repository;
+pub mod scylladb;
+
+/// Repositories the server keeps its data in
+pub struct Database {
+    pub users: Box<dyn UserRepository>,
+    pub sessions: Box<dyn SessionRepository>,
+    pub messages: Box<dyn MessageRepository>,
+    pub calls: Box<dyn CallRepository>,
+    pub media: Box<dyn MediaRepository>,
+}
+
+impl Database {
+    /// Repositories backed by the ScyllaDB session
+    pub fn scylla(session: Session) -> Self {
+        let session = Arc::new(session);
+        Self {
+            users: Box::new(scylladb::users::ScyllaUsers::new(session.clone())),
+            sessions: Box::new(scylladb::sessions::ScyllaSessions::new(session.clone())),
+            messages: Box::new(scylladb::messages::ScyllaMessages::new(session.clone())),
+            calls: Box::new(scylladb::calls::ScyllaCalls::new(session.clone())),
+            media: Box::new(scylladb::media::ScyllaMedia::new(session)),
+        }
+    }
+
+    /// Repositories kept in memory, everything is lost once the server stops
+    ///
+    /// Meant for development and tests, so the server can run without ScyllaDB
+    pub fn memory() -> Self {
+        Self {
+            users: Box::<memory::users::MemoryUsers>::default(),
+            sessions: Box::<memory::sessions::MemorySessions>::default(),
+            messages: Box::<memory::messages::MemoryMessages>::default(),
+            calls: Box::<memory::calls::MemoryCalls>::default(),
+            media: Box::<memory::media::MemoryMedia>::default(),
+        }
+    }
+}
+
+/// Sets up the backend selected by `DB_BACKEND`: `scylla` (default) or `memory`
+pub async fn db_setup() -> Database {
+    match std::env::var("DB_BACKEND").as_deref().unwrap_or("scylla") {
+        "scylla" => Database::scylla(session_setup().await),
+        "memory" => {
+            execute!(
+                stdout(),
+                SetAttribute(crossterm::style::Attribute::Bold),
+                Print("DB session "),
+                SetAttribute(crossterm::style::Attribute::Reset),
+                SetForegroundColor(Color::Green),
+                Print("\tin memory\n"),
+                ResetColor
+            )
+            .unwrap();
+            Database::memory()
+        }
+        backend => {
+            log::error!("Exiting, due to: unknown DB backend `{backend}`");
+            process::exit(1);
+        }
+    }
+}
 
 pub async fn session_setup() -> Session {
     let uri = std::env::var("SCYLLA_URI").unwrap_or_else(|_| "127.0.0.1:9042".to_string());
@@ -1,6 +1,9 @@
-use scylla::{Session, SessionBuilder};
+use scylla::{
+    transport::errors::{DbError, QueryError},
+    QueryResult, Session, SessionBuilder,
+};
 
-use crate::Result;
+use crate::result::Result;
 
 use super::db_queries::*;
 
@@ -15,6 +18,7 @@ pub async fn create_session(uri: &str) -> Result<Session> {
 pub async fn initialize(session: &Session) -> Result<()> {
     create_keyspace(session).await?;
     create_tables(session).await?;
+    add_columns(session).await?;
     Ok(())
 }
 
@@ -29,11 +33,28 @@ async fn create_keyspace(session: &Session) -> Result<()> {
 async fn create_tables(session: &Session) -> Result<()> {
     let tables = [
         CREATE_USER_TABLE_QUERY,
+        CREATE_USERNAME_TABLE_QUERY,
+        CREATE_PROFILE_TABLE_QUERY,
+        CREATE_SETTINGS_TABLE_QUERY,
         CREATE_SECRET_KEYS_TABLE_QUERY,
         CREATE_SESSION_TABLE_QUERY,
+        CREATE_PASSWORD_RESET_TABLE_QUERY,
+        CREATE_TOTP_TABLE_QUERY,
+        CREATE_LOGIN_CHALLENGE_TABLE_QUERY,
+        CREATE_RESTRICTION_TABLE_QUERY,
+        CREATE_ADMIN_AUDIT_TABLE_QUERY,
         CREATE_MESSAGE_TABLE_QUERY,
         CREATE_CALL_TABLE_QUERY,
+        CREATE_CALL_LOG_TABLE_QUERY,
+        CREATE_MISSED_CALL_TABLE_QUERY,
+        CREATE_CALL_STATS_TABLE_QUERY,
         CREATE_MEDIA_TABLE_QUERY,
+        CREATE_MEDIA_PREVIEW_TABLE_QUERY,
+        CREATE_BLOB_TABLE_QUERY,
+        CREATE_STORAGE_USAGE_TABLE_QUERY,
+        CREATE_MEDIA_BLOB_TABLE_QUERY,
+        CREATE_PENDING_MEDIA_TABLE_QUERY,
+        CREATE_ATTACHMENT_TABLE_QUERY,
     ];
 
     for table in tables {
@@ -43,6 +64,34 @@ async fn create_tables(session: &Session) -> Result<()> {
     Ok(())
 }
 
+async fn add_columns(session: &Session) -> Result<()> {
+    let columns = [
+        ADD_MESSAGE_VOICE_COLUMN_QUERY,
+        ADD_CALL_STATE_COLUMN_QUERY,
+        ADD_PENDING_MEDIA_OBJECT_COLUMN_QUERY,
+    ];
+
+    for column in columns {
+        add_column(session, column).await?;
+    }
+
+    Ok(())
+}
+
+/// Adds a column to an existing table, a column that is already there is left as is
+async fn add_column(session: &Session, query: &str) -> Result<()> {
+    match session.query(query, ()).await {
+        Ok(_) => Ok(()),
+        Err(QueryError::DbError(DbError::Invalid, message))
+            if message.contains("conflicts with an existing column")
+                || message.contains("already exists") =>
+        {
+            Ok(())
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Function to create a single entity
 async fn create_entity(session: &Session, query: &str) -> Result<()> {
     session
@@ -51,3 +100,15 @@ async fn create_entity(session: &Session, query: &str) -> Result<()> {
         .map(|_| ())
         .map_err(From::from)
 }
+
+/// Returns whether a lightweight transaction (`IF ...`) was applied
+///
+/// The first column of the LWT result is always `[applied]`
+pub fn is_applied(result: QueryResult) -> bool {
+    result
+        .first_row()
+        .ok()
+        .and_then(|row| row.columns.into_iter().next().flatten())
+        .and_then(|value| value.as_boolean())
+        .unwrap_or(false)
+}
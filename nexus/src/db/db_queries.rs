@@ -20,6 +20,38 @@ pub static CREATE_USER_TABLE_QUERY: &str = r#"
   );
 "#;
 
+// USERNAMES
+// Index that guarantees the uniqueness of usernames (claimed with LWT)
+pub static CREATE_USERNAME_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.usernames (
+    username text,
+    user UUID,
+    PRIMARY KEY(username)
+  );
+"#;
+
+// PROFILES
+pub static CREATE_PROFILE_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.profiles (
+    user UUID,
+    display_name text,
+    bio text,
+    avatar UUID,
+    updated_at timestamp,
+    PRIMARY KEY(user)
+  );
+"#;
+
+// SETTINGS
+pub static CREATE_SETTINGS_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.settings (
+    user UUID,
+    language text,
+    theme text,
+    PRIMARY KEY(user)
+  );
+"#;
+
 // CHAT KEYS
 pub static CREATE_SECRET_KEYS_TABLE_QUERY: &str = r#"
   CREATE TABLE IF NOT EXISTS nexus.secret_keys (
@@ -43,12 +75,85 @@ pub static CREATE_SESSION_TABLE_QUERY: &str = r#"
   );
 "#;
 
+// PASSWORD RESETS
+// Only the hash of the one-time token is stored
+pub static CREATE_PASSWORD_RESET_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.password_resets (
+    token_hash text,
+    user UUID,
+    issued_by UUID,
+    created_at timestamp,
+    expires_at timestamp,
+    PRIMARY KEY(token_hash)
+  );
+"#;
+
+// TWO-FACTOR AUTHENTICATION
+// Recovery codes are stored hashed
+pub static CREATE_TOTP_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.totp (
+    user UUID,
+    secret text,
+    enabled Boolean,
+    last_step BigInt,
+    recovery_codes set<text>,
+    created_at timestamp,
+    PRIMARY KEY(user)
+  );
+"#;
+
+// LOGIN CHALLENGES
+// Short-lived tokens between the password and the second factor
+pub static CREATE_LOGIN_CHALLENGE_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.login_challenges (
+    token_hash text,
+    user UUID,
+    attempts int,
+    location text,
+    device_name text,
+    device_type text,
+    device_os text,
+    expires_at timestamp,
+    PRIMARY KEY(token_hash)
+  );
+"#;
+
+// RESTRICTIONS
+// Active suspension or ban of a user, `kind` is the index of `RestrictionKind`
+pub static CREATE_RESTRICTION_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.restrictions (
+    user UUID,
+    kind Tinyint,
+    reason text,
+    issued_by UUID,
+    created_at timestamp,
+    expires_at timestamp,
+    PRIMARY KEY(user)
+  );
+"#;
+
+// ADMIN AUDIT
+// Log of the admin actions partitioned by month (`YYYY-MM`)
+pub static CREATE_ADMIN_AUDIT_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.admin_audit (
+    month text,
+    uuid UUID,
+    actor UUID,
+    target UUID,
+    action text,
+    details text,
+    created_at timestamp,
+    PRIMARY KEY(month, created_at, uuid))
+    WITH CLUSTERING ORDER BY (created_at DESC, uuid ASC);
+"#;
+
 // MESSAGES
 pub static CREATE_MESSAGE_TABLE_QUERY: &str = r#"
   CREATE TABLE IF NOT EXISTS nexus.messages (
     uuid UUID,
     text text,
     media text,
+    voice text,
     nonce text,
     sender UUID,
     receiver UUID,
@@ -62,6 +167,12 @@ pub static CREATE_MESSAGE_TABLE_QUERY: &str = r#"
   );
 "#;
 
+// Columns added to the existing tables, the tables created before them lack the columns
+pub static ADD_MESSAGE_VOICE_COLUMN_QUERY: &str = "ALTER TABLE nexus.messages ADD voice text;";
+pub static ADD_CALL_STATE_COLUMN_QUERY: &str = "ALTER TABLE nexus.calls ADD state tinyint;";
+pub static ADD_PENDING_MEDIA_OBJECT_COLUMN_QUERY: &str =
+    "ALTER TABLE nexus.pending_media ADD object text;";
+
 // CALLS
 pub static CREATE_CALL_TABLE_QUERY: &str = r#"
   CREATE TABLE IF NOT EXISTS nexus.calls (
@@ -70,13 +181,56 @@ pub static CREATE_CALL_TABLE_QUERY: &str = r#"
     receiver UUID,
     duration BigInt,
     accepted Boolean,
+    state Tinyint,
     secret Boolean,
     created_at timestamp,
     PRIMARY KEY(uuid, created_at))
     WITH CLUSTERING ORDER BY (created_at DESC);
 "#;
 
-// CALLS
+// CALL LOG
+// History of each user, every call has a row for both sides
+pub static CREATE_CALL_LOG_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.call_log (
+    user UUID,
+    created_at timestamp,
+    call UUID,
+    peer UUID,
+    direction Tinyint,
+    duration BigInt,
+    accepted Boolean,
+    state Tinyint,
+    PRIMARY KEY(user, created_at, call))
+    WITH CLUSTERING ORDER BY (created_at DESC, call ASC);
+"#;
+
+// MISSED CALLS
+// Calls missed while the user was offline, removed once delivered
+pub static CREATE_MISSED_CALL_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.missed_calls (
+    user UUID,
+    created_at timestamp,
+    call UUID,
+    content blob,
+    PRIMARY KEY(user, created_at, call));
+"#;
+
+// CALL STATS
+// Summary of the media relayed for a call or a room, written when it ends.
+// `participants` and `streams` are JSON arrays of their stats
+pub static CREATE_CALL_STATS_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.call_stats (
+    call UUID,
+    packets BigInt,
+    bytes BigInt,
+    dropped BigInt,
+    participants text,
+    streams text,
+    ended_at timestamp,
+    PRIMARY KEY(call));
+"#;
+
+// MEDIA
 pub static CREATE_MEDIA_TABLE_QUERY: &str = r#"
   CREATE TABLE IF NOT EXISTS nexus.media (
     uuid UUID,
@@ -89,3 +243,69 @@ pub static CREATE_MEDIA_TABLE_QUERY: &str = r#"
     WITH CLUSTERING ORDER BY (created_at DESC);
 "#;
 
+// STORAGE USAGE
+// Bytes of the media sent by the user, checked against the quota
+pub static CREATE_STORAGE_USAGE_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.storage_usage (
+    user UUID,
+    used counter,
+    PRIMARY KEY(user));
+"#;
+
+// BLOBS
+// Stored content shared by the media with the same hash,
+// `refs` is changed only with compare-and-set.
+// The blobs without references are removed by the garbage collection
+pub static CREATE_BLOB_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.blobs (
+    bucket text,
+    hash text,
+    object text,
+    size BigInt,
+    refs int,
+    PRIMARY KEY((bucket, hash)));
+"#;
+
+// MEDIA BLOBS
+// The blob every media that is not encrypted end-to-end points to
+pub static CREATE_MEDIA_BLOB_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.media_blobs (
+    media UUID,
+    bucket text,
+    hash text,
+    PRIMARY KEY(media));
+"#;
+
+// MEDIA PREVIEWS
+// Thumbnails and placeholders of the images and the videos,
+// `preview` is the `MediaPreview` as JSON
+pub static CREATE_MEDIA_PREVIEW_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.media_previews (
+    media UUID,
+    preview text,
+    PRIMARY KEY(media));
+"#;
+
+// ATTACHMENTS
+// Receivers of the media sent in messages, they are allowed to download it
+pub static CREATE_ATTACHMENT_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.attachments (
+    media UUID,
+    receiver UUID,
+    message UUID,
+    PRIMARY KEY(media, receiver, message));
+"#;
+
+// PENDING MEDIA
+// Media uploaded with a presigned URL that is not verified yet,
+// `file` is the `MediaFile` as bincode. Rows are inserted with a TTL
+pub static CREATE_PENDING_MEDIA_TABLE_QUERY: &str = r#"
+  CREATE TABLE IF NOT EXISTS nexus.pending_media (
+    uuid UUID,
+    sender UUID,
+    file blob,
+    object text,
+    content_type text,
+    expires_at timestamp,
+    PRIMARY KEY(uuid));
+"#;
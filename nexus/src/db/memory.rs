@@ -0,0 +1,12 @@
+use chrono::Utc;
+
+pub mod calls;
+pub mod media;
+pub mod messages;
+pub mod sessions;
+pub mod users;
+
+/// Checks whether the entry that expires at the timestamp is still there
+fn is_alive(expires_at: i64) -> bool {
+    expires_at > Utc::now().timestamp()
+}
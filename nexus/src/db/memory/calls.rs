@@ -0,0 +1,166 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hashbrown::HashMap;
+use uuid::Uuid;
+
+use nexuslib::models::call::{
+    log::{CallDirection, CallLogEntry},
+    media_call::MediaCall,
+    state::CallState,
+    stats::CallStats,
+};
+
+use crate::{db::repository::CallRepository, errors::db::DbError};
+
+#[derive(Default)]
+struct Calls {
+    calls: HashMap<Uuid, MediaCall>,
+    /// History of each user
+    log: HashMap<Uuid, Vec<CallLogEntry>>,
+    missed: HashMap<Uuid, Vec<MediaCall>>,
+    stats: HashMap<Uuid, CallStats>,
+}
+
+impl Calls {
+    /// Writes the state of the call to the history of both sides
+    fn log_call(&mut self, call: &MediaCall, state: CallState, duration: i64) {
+        let sender = call.sides.get_sender();
+        let receiver = call.sides.get_receiver();
+
+        for (user, peer, direction) in [
+            (sender, receiver, CallDirection::Outgoing),
+            (receiver, sender, CallDirection::Incoming),
+        ] {
+            let log = self.log.entry(user).or_default();
+            log.retain(|entry| entry.call != call.uuid);
+            log.push(CallLogEntry {
+                call: call.uuid,
+                peer,
+                direction,
+                state,
+                duration,
+                accepted: call.accepted,
+                created_at: call.get_created_at().timestamp(),
+            });
+        }
+    }
+}
+
+/// Calls kept in memory
+#[derive(Default)]
+pub struct MemoryCalls {
+    state: Mutex<Calls>,
+}
+
+#[async_trait]
+impl CallRepository for MemoryCalls {
+    async fn add(&self, call: &MediaCall, state: CallState) -> Result<(), DbError> {
+        let mut calls = self.state.lock().unwrap();
+        calls.calls.insert(call.uuid, call.clone());
+        calls.log_call(call, state, 0);
+        Ok(())
+    }
+
+    async fn update(
+        &self,
+        call: &MediaCall,
+        state: CallState,
+        duration: i64,
+    ) -> Result<(), DbError> {
+        let mut calls = self.state.lock().unwrap();
+        if !calls.calls.contains_key(&call.uuid) {
+            return Ok(());
+        }
+        calls.calls.insert(call.uuid, call.clone());
+        calls.log_call(call, state, duration);
+        Ok(())
+    }
+
+    async fn log(
+        &self,
+        user_uuid: &Uuid,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CallLogEntry>, DbError> {
+        let mut entries = self
+            .state
+            .lock()
+            .unwrap()
+            .log
+            .get(user_uuid)
+            .into_iter()
+            .flatten()
+            .filter(|entry| before.is_none_or(|before| entry.created_at < before))
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.created_at));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn add_stats(&self, stats: &CallStats) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .stats
+            .insert(stats.call, stats.clone());
+        Ok(())
+    }
+
+    async fn stats(&self, call_uuid: &Uuid) -> Result<Option<CallStats>, DbError> {
+        Ok(self.state.lock().unwrap().stats.get(call_uuid).cloned())
+    }
+
+    async fn queue_missed(&self, call: &MediaCall) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .missed
+            .entry(call.sides.get_receiver())
+            .or_default()
+            .push(call.clone());
+        Ok(())
+    }
+
+    async fn missed(&self, user_uuid: &Uuid) -> Result<Vec<MediaCall>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .missed
+            .get(user_uuid)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn remove_missed(&self, user_uuid: &Uuid, call: &MediaCall) -> Result<(), DbError> {
+        if let Some(missed) = self.state.lock().unwrap().missed.get_mut(user_uuid) {
+            missed.retain(|missed| missed.uuid != call.uuid);
+        }
+        Ok(())
+    }
+
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        let mut calls = self.state.lock().unwrap();
+        let removed = calls
+            .calls
+            .values()
+            .filter(|call| {
+                call.sides.get_sender() == *user_uuid || call.sides.get_receiver() == *user_uuid
+            })
+            .map(|call| call.uuid)
+            .collect::<Vec<_>>();
+        for call_uuid in removed {
+            calls.calls.remove(&call_uuid);
+            calls.stats.remove(&call_uuid);
+        }
+
+        for log in calls.log.values_mut() {
+            log.retain(|entry| entry.peer != *user_uuid);
+        }
+        calls.log.remove(user_uuid);
+        calls.missed.remove(user_uuid);
+        Ok(())
+    }
+}
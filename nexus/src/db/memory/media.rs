@@ -0,0 +1,247 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hashbrown::HashMap;
+use uuid::Uuid;
+
+use nexuslib::models::message::media::MediaPreview;
+
+use crate::{
+    db::repository::{Blob, MediaEntry, MediaRepository, PendingMedia},
+    errors::db::DbError,
+};
+
+use super::is_alive;
+
+#[derive(Default)]
+struct Media {
+    media: HashMap<Uuid, MediaEntry>,
+    previews: HashMap<Uuid, MediaPreview>,
+    usage: HashMap<Uuid, u64>,
+    /// Blobs by bucket and hash
+    blobs: HashMap<(String, String), Blob>,
+    media_blobs: HashMap<Uuid, (String, String)>,
+    pending: HashMap<Uuid, PendingMedia>,
+}
+
+/// Media kept in memory, the expired uploads are never returned
+#[derive(Default)]
+pub struct MemoryMedia {
+    state: Mutex<Media>,
+}
+
+fn blob_key(bucket: &str, hash: &str) -> (String, String) {
+    (bucket.to_owned(), hash.to_owned())
+}
+
+#[async_trait]
+impl MediaRepository for MemoryMedia {
+    async fn add(&self, media: &MediaEntry, _created_at: i64) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        if state.media.contains_key(&media.uuid) {
+            return Err(DbError::AlreadyExists);
+        }
+        state.media.insert(media.uuid, media.clone());
+        Ok(())
+    }
+
+    async fn get(&self, media_uuid: &Uuid) -> Result<MediaEntry, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .media
+            .get(media_uuid)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn with_path(&self, object: &str) -> Result<Vec<MediaEntry>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .media
+            .values()
+            .filter(|media| media.path == object)
+            .cloned()
+            .collect())
+    }
+
+    async fn sent_by(&self, user_uuid: &Uuid) -> Result<Vec<MediaEntry>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .media
+            .values()
+            .filter(|media| media.sender == *user_uuid)
+            .cloned()
+            .collect())
+    }
+
+    async fn remove(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        state.media.remove(media_uuid);
+        state.previews.remove(media_uuid);
+        Ok(())
+    }
+
+    async fn add_preview(&self, media_uuid: &Uuid, preview: &MediaPreview) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .previews
+            .insert(*media_uuid, preview.clone());
+        Ok(())
+    }
+
+    async fn preview(&self, media_uuid: &Uuid) -> Result<Option<MediaPreview>, DbError> {
+        Ok(self.state.lock().unwrap().previews.get(media_uuid).cloned())
+    }
+
+    async fn usage(&self, user_uuid: &Uuid) -> Result<u64, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .usage
+            .get(user_uuid)
+            .copied()
+            .unwrap_or_default())
+    }
+
+    async fn add_usage(&self, user_uuid: &Uuid, len_bytes: usize) -> Result<(), DbError> {
+        *self
+            .state
+            .lock()
+            .unwrap()
+            .usage
+            .entry(*user_uuid)
+            .or_default() += len_bytes as u64;
+        Ok(())
+    }
+
+    async fn remove_usage(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().usage.remove(user_uuid);
+        Ok(())
+    }
+
+    async fn blob(&self, bucket: &str, hash: &str) -> Result<Blob, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .blobs
+            .get(&blob_key(bucket, hash))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn add_blob(&self, bucket: &str, hash: &str, blob: &Blob) -> Result<bool, DbError> {
+        let mut state = self.state.lock().unwrap();
+        let key = blob_key(bucket, hash);
+        if state.blobs.contains_key(&key) {
+            return Ok(false);
+        }
+        state.blobs.insert(key, blob.clone());
+        Ok(true)
+    }
+
+    async fn set_blob_refs(
+        &self,
+        bucket: &str,
+        hash: &str,
+        refs: i32,
+        expected: i32,
+    ) -> Result<bool, DbError> {
+        let mut state = self.state.lock().unwrap();
+        match state.blobs.get_mut(&blob_key(bucket, hash)) {
+            Some(blob) if blob.refs == expected => {
+                blob.refs = refs;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn unreferenced_blobs(&self) -> Result<Vec<(String, String, String)>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .blobs
+            .iter()
+            .filter(|(_, blob)| blob.refs == 0)
+            .map(|((bucket, hash), blob)| (bucket.clone(), hash.clone(), blob.object.clone()))
+            .collect())
+    }
+
+    async fn remove_unreferenced_blob(&self, bucket: &str, hash: &str) -> Result<bool, DbError> {
+        let mut state = self.state.lock().unwrap();
+        let key = blob_key(bucket, hash);
+        match state.blobs.get(&key) {
+            Some(blob) if blob.refs == 0 => {
+                state.blobs.remove(&key);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn link_blob(&self, media_uuid: &Uuid, bucket: &str, hash: &str) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .media_blobs
+            .insert(*media_uuid, blob_key(bucket, hash));
+        Ok(())
+    }
+
+    async fn blob_of(&self, media_uuid: &Uuid) -> Result<Option<(String, String)>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .media_blobs
+            .get(media_uuid)
+            .cloned())
+    }
+
+    async fn unlink_blob(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().media_blobs.remove(media_uuid);
+        Ok(())
+    }
+
+    async fn add_pending(&self, pending: &PendingMedia) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        if state
+            .pending
+            .get(&pending.file.uuid)
+            .is_some_and(|pending| is_alive(pending.expires_at))
+        {
+            return Err(DbError::AlreadyExists);
+        }
+        state.pending.insert(pending.file.uuid, pending.clone());
+        Ok(())
+    }
+
+    async fn pending(&self, media_uuid: &Uuid) -> Result<PendingMedia, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .pending
+            .get(media_uuid)
+            .filter(|pending| is_alive(pending.expires_at))
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn claim_pending(&self, media_uuid: &Uuid) -> Result<bool, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .pending
+            .remove(media_uuid)
+            .is_some_and(|pending| is_alive(pending.expires_at)))
+    }
+}
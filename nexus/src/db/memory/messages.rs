@@ -0,0 +1,96 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use nexuslib::{models::message::MessageBody, Message};
+
+use crate::{db::repository::MessageRepository, errors::db::DbError};
+
+#[derive(Default)]
+struct Messages {
+    messages: Vec<Message<MessageBody>>,
+    /// Media, receiver and message of each attachment
+    attachments: Vec<(Uuid, Uuid, Uuid)>,
+}
+
+/// Messages kept in memory
+#[derive(Default)]
+pub struct MemoryMessages {
+    state: Mutex<Messages>,
+}
+
+#[async_trait]
+impl MessageRepository for MemoryMessages {
+    async fn add(&self, message: &Message<MessageBody>) -> Result<(), DbError> {
+        self.state.lock().unwrap().messages.push(message.clone());
+        Ok(())
+    }
+
+    async fn between(
+        &self,
+        sender: &Uuid,
+        receiver: &Uuid,
+    ) -> Result<Vec<Message<MessageBody>>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .messages
+            .iter()
+            .filter(|message| {
+                message.sides.get_sender() == *sender && message.sides.get_receiver() == *receiver
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().messages.retain(|message| {
+            message.sides.get_sender() != *user_uuid && message.sides.get_receiver() != *user_uuid
+        });
+        Ok(())
+    }
+
+    async fn add_attachment(
+        &self,
+        media_uuid: &Uuid,
+        receiver: &Uuid,
+        message_uuid: &Uuid,
+    ) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .attachments
+            .push((*media_uuid, *receiver, *message_uuid));
+        Ok(())
+    }
+
+    async fn is_attached_for(&self, media_uuid: &Uuid, user_uuid: &Uuid) -> Result<bool, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .attachments
+            .iter()
+            .any(|(media, receiver, _)| media == media_uuid && receiver == user_uuid))
+    }
+
+    async fn remove_attachments(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .attachments
+            .retain(|(media, _, _)| media != media_uuid);
+        Ok(())
+    }
+
+    async fn remove_received(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .attachments
+            .retain(|(_, receiver, _)| receiver != user_uuid);
+        Ok(())
+    }
+}
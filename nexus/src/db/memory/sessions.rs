@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hashbrown::HashMap;
+use uuid::Uuid;
+
+use nexuslib::request::auth::AuthRequestMeta;
+
+use crate::{
+    db::repository::{LoginChallenge, PasswordReset, SessionRepository},
+    errors::db::DbError,
+};
+
+use super::is_alive;
+
+#[derive(Default)]
+struct Sessions {
+    /// User of each JWT
+    sessions: HashMap<String, Uuid>,
+    challenges: HashMap<String, LoginChallenge>,
+    resets: HashMap<String, PasswordReset>,
+}
+
+/// Sessions kept in memory, the expired entries are never returned
+#[derive(Default)]
+pub struct MemorySessions {
+    state: Mutex<Sessions>,
+}
+
+#[async_trait]
+impl SessionRepository for MemorySessions {
+    async fn add(
+        &self,
+        token: &str,
+        user_uuid: &Uuid,
+        _meta: &AuthRequestMeta,
+    ) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .sessions
+            .insert(token.to_owned(), *user_uuid);
+        Ok(())
+    }
+
+    async fn user_of(&self, token: &str) -> Result<Uuid, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .sessions
+            .get(token)
+            .copied()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn tokens_of(&self, user_uuid: &Uuid) -> Result<Vec<String>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .sessions
+            .iter()
+            .filter(|(_, user)| *user == user_uuid)
+            .map(|(token, _)| token.to_owned())
+            .collect())
+    }
+
+    async fn remove(&self, token: &str) -> Result<(), DbError> {
+        self.state.lock().unwrap().sessions.remove(token);
+        Ok(())
+    }
+
+    async fn add_challenge(
+        &self,
+        token_hash: &str,
+        challenge: &LoginChallenge,
+    ) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .challenges
+            .insert(token_hash.to_owned(), challenge.clone());
+        Ok(())
+    }
+
+    async fn challenge(&self, token_hash: &str) -> Result<Option<LoginChallenge>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .challenges
+            .get(token_hash)
+            .filter(|challenge| is_alive(challenge.expires_at))
+            .cloned())
+    }
+
+    async fn set_challenge_attempts(
+        &self,
+        token_hash: &str,
+        attempts: i32,
+        expires_at: i64,
+    ) -> Result<(), DbError> {
+        if let Some(challenge) = self.state.lock().unwrap().challenges.get_mut(token_hash) {
+            challenge.attempts = attempts;
+            challenge.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn remove_challenge(&self, token_hash: &str) -> Result<(), DbError> {
+        self.state.lock().unwrap().challenges.remove(token_hash);
+        Ok(())
+    }
+
+    async fn claim_challenge(&self, token_hash: &str) -> Result<bool, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .challenges
+            .remove(token_hash)
+            .is_some_and(|challenge| is_alive(challenge.expires_at)))
+    }
+
+    async fn add_password_reset(
+        &self,
+        token_hash: &str,
+        reset: &PasswordReset,
+    ) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .resets
+            .insert(token_hash.to_owned(), reset.clone());
+        Ok(())
+    }
+
+    async fn password_reset(&self, token_hash: &str) -> Result<Option<PasswordReset>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .resets
+            .get(token_hash)
+            .filter(|reset| is_alive(reset.expires_at))
+            .cloned())
+    }
+
+    async fn claim_password_reset(&self, token_hash: &str) -> Result<bool, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .resets
+            .remove(token_hash)
+            .is_some_and(|reset| is_alive(reset.expires_at)))
+    }
+}
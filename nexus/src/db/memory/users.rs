@@ -0,0 +1,294 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use hashbrown::HashMap;
+use uuid::Uuid;
+
+use nexuslib::models::{
+    moderation::{AuditEntry, UserRestriction},
+    user::{profile::UserProfile, role::Role, settings::UserSettings, User},
+};
+
+use crate::{
+    db::repository::{audit_month, TotpEntry, UserRepository},
+    errors::db::DbError,
+};
+
+use super::is_alive;
+
+#[derive(Default)]
+struct Users {
+    users: HashMap<Uuid, User>,
+    usernames: HashMap<String, Uuid>,
+    secret_keys: HashMap<Uuid, Vec<u8>>,
+    profiles: HashMap<Uuid, UserProfile>,
+    settings: HashMap<Uuid, UserSettings>,
+    totp: HashMap<Uuid, TotpEntry>,
+    restrictions: HashMap<Uuid, UserRestriction>,
+    audit: Vec<AuditEntry>,
+}
+
+/// Users kept in memory
+#[derive(Default)]
+pub struct MemoryUsers {
+    state: Mutex<Users>,
+}
+
+impl MemoryUsers {
+    /// Applies the change to the user, `NotFound` if there is none
+    fn update(&self, user_uuid: &Uuid, change: impl FnOnce(&mut User)) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        let user = state.users.get_mut(user_uuid).ok_or(DbError::NotFound)?;
+        change(user);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl UserRepository for MemoryUsers {
+    async fn list(&self) -> Result<Vec<User>, DbError> {
+        Ok(self.state.lock().unwrap().users.values().cloned().collect())
+    }
+
+    async fn get(&self, user_uuid: &Uuid) -> Result<User, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .users
+            .get(user_uuid)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn get_by_username(&self, username: &str) -> Result<User, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .users
+            .values()
+            .find(|user| user.username == username)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn create(&self, user: &User, secret: &[u8]) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        state.users.insert(user.uuid, user.clone());
+        state.secret_keys.insert(user.uuid, secret.to_vec());
+        Ok(())
+    }
+
+    async fn rename(&self, user: &User, username: &str) -> Result<(), DbError> {
+        self.update(&user.uuid, |user| user.username = username.to_owned())
+    }
+
+    async fn set_password(&self, user: &User, password: &str) -> Result<(), DbError> {
+        self.update(&user.uuid, |user| user.password = password.to_owned())
+    }
+
+    async fn set_role(&self, user: &User, role: Role) -> Result<(), DbError> {
+        self.update(&user.uuid, |user| user.role = role)
+    }
+
+    async fn remove(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().users.remove(user_uuid);
+        Ok(())
+    }
+
+    async fn claim_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        if state.usernames.contains_key(username) {
+            return Err(DbError::AlreadyExists);
+        }
+        state.usernames.insert(username.to_owned(), *user_uuid);
+        Ok(())
+    }
+
+    async fn release_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        if state.usernames.get(username) == Some(user_uuid) {
+            state.usernames.remove(username);
+        }
+        Ok(())
+    }
+
+    async fn secret_key(&self, user_uuid: &Uuid) -> Result<Vec<u8>, DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .secret_keys
+            .get(user_uuid)
+            .cloned()
+            .ok_or(DbError::NotFound)
+    }
+
+    async fn profile(&self, user_uuid: &Uuid) -> Result<Option<UserProfile>, DbError> {
+        Ok(self.state.lock().unwrap().profiles.get(user_uuid).cloned())
+    }
+
+    async fn save_profile(&self, profile: &UserProfile) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .profiles
+            .insert(profile.user, profile.clone());
+        Ok(())
+    }
+
+    async fn settings(&self, user_uuid: &Uuid) -> Result<Option<UserSettings>, DbError> {
+        Ok(self.state.lock().unwrap().settings.get(user_uuid).cloned())
+    }
+
+    async fn save_settings(
+        &self,
+        user_uuid: &Uuid,
+        settings: &UserSettings,
+    ) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .settings
+            .insert(*user_uuid, settings.clone());
+        Ok(())
+    }
+
+    async fn totp(&self, user_uuid: &Uuid) -> Result<Option<TotpEntry>, DbError> {
+        Ok(self.state.lock().unwrap().totp.get(user_uuid).cloned())
+    }
+
+    async fn enroll_totp(&self, user_uuid: &Uuid, secret: &[u8]) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        let recovery_codes = state
+            .totp
+            .remove(user_uuid)
+            .map(|entry| entry.recovery_codes)
+            .unwrap_or_default();
+        state.totp.insert(
+            *user_uuid,
+            TotpEntry {
+                secret: secret.to_vec(),
+                enabled: false,
+                last_step: 0,
+                recovery_codes,
+            },
+        );
+        Ok(())
+    }
+
+    async fn enable_totp(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        recovery_codes: &[String],
+    ) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        let entry = state.totp.get_mut(user_uuid).ok_or(DbError::NotFound)?;
+        entry.enabled = true;
+        entry.last_step = step;
+        entry.recovery_codes = recovery_codes.to_vec();
+        Ok(())
+    }
+
+    async fn remove_totp(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().totp.remove(user_uuid);
+        Ok(())
+    }
+
+    async fn advance_totp_step(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        last_step: i64,
+    ) -> Result<bool, DbError> {
+        let mut state = self.state.lock().unwrap();
+        match state.totp.get_mut(user_uuid) {
+            Some(entry) if entry.last_step == last_step => {
+                entry.last_step = step;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_uuid: &Uuid,
+        code_hash: &str,
+        recovery_codes: &[String],
+    ) -> Result<bool, DbError> {
+        let mut state = self.state.lock().unwrap();
+        match state.totp.get_mut(user_uuid) {
+            Some(entry) if entry.recovery_codes == recovery_codes => {
+                entry.recovery_codes.retain(|code| code != code_hash);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn restriction(&self, user_uuid: &Uuid) -> Result<Option<UserRestriction>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .restrictions
+            .get(user_uuid)
+            .filter(|restriction| restriction.expires_at.is_none_or(is_alive))
+            .cloned())
+    }
+
+    async fn restrictions(&self) -> Result<Vec<UserRestriction>, DbError> {
+        Ok(self
+            .state
+            .lock()
+            .unwrap()
+            .restrictions
+            .values()
+            .filter(|restriction| restriction.expires_at.is_none_or(is_alive))
+            .cloned()
+            .collect())
+    }
+
+    async fn restrict(&self, restriction: &UserRestriction) -> Result<(), DbError> {
+        self.state
+            .lock()
+            .unwrap()
+            .restrictions
+            .insert(restriction.user, restriction.clone());
+        Ok(())
+    }
+
+    async fn lift_restriction(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        self.state.lock().unwrap().restrictions.remove(user_uuid);
+        Ok(())
+    }
+
+    async fn add_audit(&self, entry: &AuditEntry) -> Result<(), DbError> {
+        self.state.lock().unwrap().audit.push(entry.clone());
+        Ok(())
+    }
+
+    async fn audit(&self, month: &str, limit: usize) -> Result<Vec<AuditEntry>, DbError> {
+        let mut entries = self
+            .state
+            .lock()
+            .unwrap()
+            .audit
+            .iter()
+            .filter(|entry| audit_month(entry.created_at) == month)
+            .cloned()
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.created_at));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    async fn remove_data(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        let mut state = self.state.lock().unwrap();
+        state.secret_keys.remove(user_uuid);
+        state.profiles.remove(user_uuid);
+        state.settings.remove(user_uuid);
+        state.restrictions.remove(user_uuid);
+        Ok(())
+    }
+}
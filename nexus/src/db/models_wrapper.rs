@@ -1,7 +1,21 @@
-use nexuslib::models::user::{role::Role, User};
+use nexuslib::{
+    models::{
+        message::{
+            media::{Media, MediaType},
+            text::TextMessage,
+            voice::{VoiceMessage, VoiceNote},
+            MessageBody,
+        },
+        user::{role::Role, User},
+    },
+    utils::string_to_vec,
+    Message,
+};
 use scylla::FromRow;
 use uuid::Uuid;
 
+use super::repository::MediaEntry;
+
 pub struct UserDB(User);
 
 impl UserDB {
@@ -35,3 +49,74 @@ impl FromRow for UserDB {
         }))
     }
 }
+
+impl FromRow for MediaEntry {
+    fn from_row(
+        row: scylla::frame::response::result::Row,
+    ) -> Result<Self, scylla::cql_to_rust::FromRowError> {
+        let (uuid, name, path, media_type, sender) =
+            <(Uuid, String, String, i8, Uuid)>::from_row(row)?;
+
+        Ok(Self {
+            uuid,
+            name,
+            path,
+            media_type: MediaType::from_index(media_type as u8).unwrap_or(MediaType::File),
+            sender,
+        })
+    }
+}
+
+pub struct MessageDB(Message<MessageBody>);
+
+impl MessageDB {
+    pub fn get_message(self) -> Message<MessageBody> {
+        self.0
+    }
+}
+
+impl FromRow for MessageDB {
+    fn from_row(
+        row: scylla::frame::response::result::Row,
+    ) -> Result<Self, scylla::cql_to_rust::FromRowError> {
+        let (uuid, text, media, voice, nonce, sender, receiver, sent, read, edited, created_at) =
+            <(
+                Uuid,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                Option<String>,
+                Uuid,
+                Uuid,
+                Option<bool>,
+                Option<bool>,
+                Option<bool>,
+                chrono::Duration,
+            )>::from_row(row)?;
+
+        let text = text.unwrap_or_default();
+        // the messages other than voice notes have an empty string
+        let content = match voice.and_then(|voice| serde_json::from_str::<VoiceNote>(&voice).ok()) {
+            Some(voice) => MessageBody::Voice(VoiceMessage::new(&text, voice)),
+            None => MessageBody::Text(TextMessage::new(&text)),
+        };
+        let nonce = string_to_vec(nonce.unwrap_or_default());
+        let mut message = Message::new(content, nonce, sender, receiver)
+            .with_created_at(created_at.num_seconds());
+        message.uuid = uuid;
+
+        if sent.unwrap_or(false) {
+            message.status.set_sent();
+        }
+        if read.unwrap_or(false) {
+            message.status.set_read();
+        }
+        if edited.unwrap_or(false) {
+            message.status.set_edited();
+        }
+        // messages stored before the attachments have an empty string
+        message.media = media.and_then(|media| serde_json::from_str::<Media>(&media).ok());
+
+        Ok(Self(message))
+    }
+}
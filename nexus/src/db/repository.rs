@@ -0,0 +1,382 @@
+use async_trait::async_trait;
+use uuid::Uuid;
+
+use nexuslib::{
+    models::{
+        call::{log::CallLogEntry, media_call::MediaCall, state::CallState, stats::CallStats},
+        file::media_file::MediaFile,
+        message::{
+            media::{MediaPreview, MediaType},
+            MessageBody,
+        },
+        moderation::{AuditEntry, UserRestriction},
+        user::{profile::UserProfile, role::Role, settings::UserSettings, User},
+    },
+    request::auth::AuthRequestMeta,
+    Message,
+};
+
+use crate::errors::db::DbError;
+
+/// Entry of a stored media
+#[derive(Debug, Clone)]
+pub struct MediaEntry {
+    pub uuid: Uuid,
+    pub name: String,
+    /// Key of the object in the bucket of the type
+    pub path: String,
+    pub media_type: MediaType,
+    pub sender: Uuid,
+}
+
+/// Stored content shared by all the media with the same hash
+///
+/// Files encrypted end-to-end are never shared, their ciphertexts differ anyway
+#[derive(Debug, Clone)]
+pub struct Blob {
+    /// Key of the object in the bucket, the one of the first upload
+    pub object: String,
+    pub size: usize,
+    /// Number of the media pointing to the object
+    pub refs: i32,
+}
+
+/// Media uploaded with a presigned URL that is not verified yet
+#[derive(Debug, Clone)]
+pub struct PendingMedia {
+    pub file: MediaFile,
+    /// Key of the object the URL is presigned for
+    pub object: String,
+    pub content_type: String,
+    pub expires_at: i64,
+}
+
+/// TOTP state of a user
+#[derive(Debug, Clone)]
+pub struct TotpEntry {
+    pub secret: Vec<u8>,
+    pub enabled: bool,
+    /// The last time step a code was accepted for
+    pub last_step: i64,
+    /// Hashes of the unused recovery codes
+    pub recovery_codes: Vec<String>,
+}
+
+/// Step between the password and the second factor of the login
+#[derive(Debug, Clone)]
+pub struct LoginChallenge {
+    pub user: Uuid,
+    pub attempts: i32,
+    pub meta: AuthRequestMeta,
+    pub expires_at: i64,
+}
+
+/// One-time password reset issued by an admin
+#[derive(Debug, Clone)]
+pub struct PasswordReset {
+    pub user: Uuid,
+    pub issued_by: Uuid,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Users with their keys, profiles, settings, 2FA, restrictions and the admin audit
+#[async_trait]
+pub trait UserRepository: Send + Sync {
+    async fn list(&self) -> Result<Vec<User>, DbError>;
+
+    /// Returns the user by UUID, `NotFound` if there is none
+    async fn get(&self, user_uuid: &Uuid) -> Result<User, DbError>;
+
+    /// Returns the user by username, `NotFound` if there is none
+    async fn get_by_username(&self, username: &str) -> Result<User, DbError>;
+
+    /// Adds the user together with the secret key of the chats
+    async fn create(&self, user: &User, secret: &[u8]) -> Result<(), DbError>;
+
+    /// Rewrites the user with the new username, which has to be claimed before
+    async fn rename(&self, user: &User, username: &str) -> Result<(), DbError>;
+
+    async fn set_password(&self, user: &User, password: &str) -> Result<(), DbError>;
+
+    async fn set_role(&self, user: &User, role: Role) -> Result<(), DbError>;
+
+    /// Removes the user itself, see `remove_data` for the rest
+    async fn remove(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Atomically reserves the username, `AlreadyExists` if it is taken
+    async fn claim_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Frees the username if it is owned by the user
+    async fn release_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    async fn secret_key(&self, user_uuid: &Uuid) -> Result<Vec<u8>, DbError>;
+
+    /// Returns the profile, `None` if the user has never filled it
+    async fn profile(&self, user_uuid: &Uuid) -> Result<Option<UserProfile>, DbError>;
+
+    /// Inserts or overwrites the profile
+    async fn save_profile(&self, profile: &UserProfile) -> Result<(), DbError>;
+
+    /// Returns the settings, `None` if the user has never changed them
+    async fn settings(&self, user_uuid: &Uuid) -> Result<Option<UserSettings>, DbError>;
+
+    /// Inserts or overwrites the settings
+    async fn save_settings(&self, user_uuid: &Uuid, settings: &UserSettings)
+        -> Result<(), DbError>;
+
+    /// Returns the TOTP state if 2FA was ever enrolled
+    async fn totp(&self, user_uuid: &Uuid) -> Result<Option<TotpEntry>, DbError>;
+
+    /// Stores a new disabled TOTP secret in place of the previous one
+    async fn enroll_totp(&self, user_uuid: &Uuid, secret: &[u8]) -> Result<(), DbError>;
+
+    /// Enables 2FA with the first accepted step and the hashes of the recovery codes
+    async fn enable_totp(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        recovery_codes: &[String],
+    ) -> Result<(), DbError>;
+
+    async fn remove_totp(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Compare-and-set of the last accepted step, `false` if it was changed meanwhile
+    async fn advance_totp_step(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        last_step: i64,
+    ) -> Result<bool, DbError>;
+
+    /// Removes the recovery code if the codes are still the given ones,
+    /// `false` if they were changed meanwhile
+    async fn use_recovery_code(
+        &self,
+        user_uuid: &Uuid,
+        code_hash: &str,
+        recovery_codes: &[String],
+    ) -> Result<bool, DbError>;
+
+    /// Returns the restriction of the user unless it has expired
+    async fn restriction(&self, user_uuid: &Uuid) -> Result<Option<UserRestriction>, DbError>;
+
+    /// Returns the restrictions that have not expired
+    async fn restrictions(&self) -> Result<Vec<UserRestriction>, DbError>;
+
+    /// Stores the restriction in place of the previous one,
+    /// it is removed once it expires
+    async fn restrict(&self, restriction: &UserRestriction) -> Result<(), DbError>;
+
+    async fn lift_restriction(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    async fn add_audit(&self, entry: &AuditEntry) -> Result<(), DbError>;
+
+    /// Returns the entries of the month (`YYYY-MM`), the latest first
+    async fn audit(&self, month: &str, limit: usize) -> Result<Vec<AuditEntry>, DbError>;
+
+    /// Removes the key, the profile, the settings and the restriction of the user
+    async fn remove_data(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+}
+
+/// JWT sessions, login challenges and password resets
+///
+/// The challenges and the resets are looked up by the hash of their token
+#[async_trait]
+pub trait SessionRepository: Send + Sync {
+    async fn add(
+        &self,
+        token: &str,
+        user_uuid: &Uuid,
+        meta: &AuthRequestMeta,
+    ) -> Result<(), DbError>;
+
+    /// Returns the user of the session, `NotFound` if there is none
+    async fn user_of(&self, token: &str) -> Result<Uuid, DbError>;
+
+    async fn tokens_of(&self, user_uuid: &Uuid) -> Result<Vec<String>, DbError>;
+
+    async fn remove(&self, token: &str) -> Result<(), DbError>;
+
+    /// Stores the challenge until it expires
+    async fn add_challenge(
+        &self,
+        token_hash: &str,
+        challenge: &LoginChallenge,
+    ) -> Result<(), DbError>;
+
+    /// Returns the challenge unless it has expired
+    async fn challenge(&self, token_hash: &str) -> Result<Option<LoginChallenge>, DbError>;
+
+    async fn set_challenge_attempts(
+        &self,
+        token_hash: &str,
+        attempts: i32,
+        expires_at: i64,
+    ) -> Result<(), DbError>;
+
+    async fn remove_challenge(&self, token_hash: &str) -> Result<(), DbError>;
+
+    /// Removes the challenge, `false` if it has already been removed
+    async fn claim_challenge(&self, token_hash: &str) -> Result<bool, DbError>;
+
+    /// Stores the reset until it expires
+    async fn add_password_reset(
+        &self,
+        token_hash: &str,
+        reset: &PasswordReset,
+    ) -> Result<(), DbError>;
+
+    async fn password_reset(&self, token_hash: &str) -> Result<Option<PasswordReset>, DbError>;
+
+    /// Removes the reset, `false` if it has already been removed
+    async fn claim_password_reset(&self, token_hash: &str) -> Result<bool, DbError>;
+}
+
+/// Messages and the attachments they granted access to
+#[async_trait]
+pub trait MessageRepository: Send + Sync {
+    async fn add(&self, message: &Message<MessageBody>) -> Result<(), DbError>;
+
+    /// Returns the messages sent from the sender to the receiver
+    async fn between(
+        &self,
+        sender: &Uuid,
+        receiver: &Uuid,
+    ) -> Result<Vec<Message<MessageBody>>, DbError>;
+
+    /// Removes the messages sent and received by the user
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Records that the media was sent to the receiver in the message
+    async fn add_attachment(
+        &self,
+        media_uuid: &Uuid,
+        receiver: &Uuid,
+        message_uuid: &Uuid,
+    ) -> Result<(), DbError>;
+
+    /// Checks if the media was sent to the user
+    async fn is_attached_for(&self, media_uuid: &Uuid, user_uuid: &Uuid) -> Result<bool, DbError>;
+
+    /// Removes the access of all the receivers to the media
+    async fn remove_attachments(&self, media_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Removes the access of the user to the media received in messages
+    async fn remove_received(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+}
+
+/// Calls with the history of both sides, the missed calls and the relay stats
+#[async_trait]
+pub trait CallRepository: Send + Sync {
+    /// Adds the call with the entries in the history of both sides
+    async fn add(&self, call: &MediaCall, state: CallState) -> Result<(), DbError>;
+
+    /// Updates the call and its entries in the history
+    async fn update(
+        &self,
+        call: &MediaCall,
+        state: CallState,
+        duration: i64,
+    ) -> Result<(), DbError>;
+
+    /// Returns the history of the user, the latest first,
+    /// only the calls started `before` the timestamp if it is given
+    async fn log(
+        &self,
+        user_uuid: &Uuid,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CallLogEntry>, DbError>;
+
+    async fn add_stats(&self, stats: &CallStats) -> Result<(), DbError>;
+
+    async fn stats(&self, call_uuid: &Uuid) -> Result<Option<CallStats>, DbError>;
+
+    /// Queues the call for the callee
+    async fn queue_missed(&self, call: &MediaCall) -> Result<(), DbError>;
+
+    /// Returns the calls queued for the user
+    async fn missed(&self, user_uuid: &Uuid) -> Result<Vec<MediaCall>, DbError>;
+
+    async fn remove_missed(&self, user_uuid: &Uuid, call: &MediaCall) -> Result<(), DbError>;
+
+    /// Removes the calls of the user with their stats and the history of both sides
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+}
+
+/// Media entries, previews, blobs, storage usage and pending uploads
+#[async_trait]
+pub trait MediaRepository: Send + Sync {
+    /// Adds the media, `AlreadyExists` if its UUID is taken
+    async fn add(&self, media: &MediaEntry, created_at: i64) -> Result<(), DbError>;
+
+    /// Returns the media by UUID, `NotFound` if there is none
+    async fn get(&self, media_uuid: &Uuid) -> Result<MediaEntry, DbError>;
+
+    /// Returns the media pointing to the object
+    async fn with_path(&self, object: &str) -> Result<Vec<MediaEntry>, DbError>;
+
+    async fn sent_by(&self, user_uuid: &Uuid) -> Result<Vec<MediaEntry>, DbError>;
+
+    /// Removes the media with its previews
+    async fn remove(&self, media_uuid: &Uuid) -> Result<(), DbError>;
+
+    async fn add_preview(&self, media_uuid: &Uuid, preview: &MediaPreview) -> Result<(), DbError>;
+
+    /// Returns the previews, `None` until they are generated
+    async fn preview(&self, media_uuid: &Uuid) -> Result<Option<MediaPreview>, DbError>;
+
+    /// Returns the bytes of the media sent by the user
+    async fn usage(&self, user_uuid: &Uuid) -> Result<u64, DbError>;
+
+    async fn add_usage(&self, user_uuid: &Uuid, len_bytes: usize) -> Result<(), DbError>;
+
+    async fn remove_usage(&self, user_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Returns the blob of the content, `NotFound` if there is none
+    async fn blob(&self, bucket: &str, hash: &str) -> Result<Blob, DbError>;
+
+    /// Adds the blob unless the content already has one, `false` if it has
+    async fn add_blob(&self, bucket: &str, hash: &str, blob: &Blob) -> Result<bool, DbError>;
+
+    /// Compare-and-set of the references, `false` if they were changed meanwhile
+    async fn set_blob_refs(
+        &self,
+        bucket: &str,
+        hash: &str,
+        refs: i32,
+        expected: i32,
+    ) -> Result<bool, DbError>;
+
+    /// Returns the bucket, the hash and the object of the blobs without references
+    async fn unreferenced_blobs(&self) -> Result<Vec<(String, String, String)>, DbError>;
+
+    /// Removes the blob if it still has no references, `false` if it has
+    async fn remove_unreferenced_blob(&self, bucket: &str, hash: &str) -> Result<bool, DbError>;
+
+    /// Records the blob the media points to
+    async fn link_blob(&self, media_uuid: &Uuid, bucket: &str, hash: &str) -> Result<(), DbError>;
+
+    /// Returns the bucket and the hash of the blob the media points to
+    async fn blob_of(&self, media_uuid: &Uuid) -> Result<Option<(String, String)>, DbError>;
+
+    async fn unlink_blob(&self, media_uuid: &Uuid) -> Result<(), DbError>;
+
+    /// Records the upload until it expires, `AlreadyExists` if the UUID is pending
+    async fn add_pending(&self, pending: &PendingMedia) -> Result<(), DbError>;
+
+    /// Returns the pending upload, `NotFound` if there is none
+    async fn pending(&self, media_uuid: &Uuid) -> Result<PendingMedia, DbError>;
+
+    /// Removes the pending upload, `false` if it has already been removed
+    async fn claim_pending(&self, media_uuid: &Uuid) -> Result<bool, DbError>;
+}
+
+/// Partition of the audit log for the timestamp
+pub fn audit_month(timestamp: i64) -> String {
+    chrono::DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .format("%Y-%m")
+        .to_string()
+}
@@ -0,0 +1,95 @@
+use chrono::{Duration, Utc};
+use scylla::{
+    frame::value::{Timestamp, ValueList},
+    FromRow, Session,
+};
+
+use crate::errors::db::DbError;
+
+use super::database::is_applied;
+
+pub mod calls;
+pub mod media;
+pub mod messages;
+pub mod sessions;
+pub mod users;
+
+/// CQL timestamp of the UNIX time in seconds
+fn timestamp(seconds: i64) -> Timestamp {
+    Timestamp(Duration::try_seconds(seconds).unwrap_or_default())
+}
+
+/// Current CQL timestamp
+fn now() -> Timestamp {
+    timestamp(Utc::now().timestamp())
+}
+
+/// TTL of a row that expires at the timestamp, at least a second
+fn ttl(expires_at: i64) -> i64 {
+    (expires_at - Utc::now().timestamp()).max(1)
+}
+
+/// Selects all rows of the query
+async fn select_all<T: FromRow>(
+    session: &Session,
+    query: impl Into<scylla::query::Query> + Send,
+    values: impl ValueList,
+) -> Result<Vec<T>, DbError> {
+    session
+        .query(query, values)
+        .await
+        .map_err(|e| {
+            log::error!("{e:?}");
+            DbError::NotFound
+        })?
+        .rows_typed_or_empty::<T>()
+        .collect::<Result<Vec<T>, _>>()
+        .map_err(|_| DbError::FailedToConvertRow)
+}
+
+/// Selects the first row of the query if there is one
+async fn select_one<T: FromRow>(
+    session: &Session,
+    query: impl Into<scylla::query::Query> + Send,
+    values: impl ValueList,
+) -> Result<Option<T>, DbError> {
+    session
+        .query(query, values)
+        .await
+        .map_err(|e| {
+            log::error!("{e:?}");
+            DbError::NotFound
+        })?
+        .maybe_first_row_typed::<T>()
+        .map_err(|_| DbError::FailedToConvertRow)
+}
+
+/// Runs the modifying query, `error` is returned if it fails
+async fn run(
+    session: &Session,
+    query: impl Into<scylla::query::Query> + Send,
+    values: impl ValueList,
+    error: DbError,
+) -> Result<(), DbError> {
+    session.query(query, values).await.map(|_| ()).map_err(|e| {
+        log::error!("{e:?}");
+        error
+    })
+}
+
+/// Runs the lightweight transaction, returns whether it was applied
+async fn run_lwt(
+    session: &Session,
+    query: impl Into<scylla::query::Query> + Send,
+    values: impl ValueList,
+    error: DbError,
+) -> Result<bool, DbError> {
+    session
+        .query(query, values)
+        .await
+        .map(is_applied)
+        .map_err(|e| {
+            log::error!("{e:?}");
+            error
+        })
+}
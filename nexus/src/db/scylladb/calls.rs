@@ -0,0 +1,285 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use scylla::{frame::value::Timestamp, Session};
+use uuid::Uuid;
+
+use nexuslib::models::call::{
+    log::{CallDirection, CallLogEntry},
+    media_call::MediaCall,
+    state::CallState,
+    stats::{CallStats, ParticipantStats, StreamStats},
+};
+
+use crate::{db::repository::CallRepository, errors::db::DbError};
+
+use super::{now, run, select_all, select_one, timestamp};
+
+type LogRow = (Uuid, Uuid, i8, i64, bool, i8, Duration);
+
+/// Calls in `nexus.calls`, `nexus.call_log`, `nexus.missed_calls` and `nexus.call_stats`
+pub struct ScyllaCalls {
+    session: Arc<Session>,
+}
+
+impl ScyllaCalls {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self { session }
+    }
+
+    /// Writes the state of the call to the history of both sides
+    async fn log_call(
+        &self,
+        call: &MediaCall,
+        state: CallState,
+        duration: i64,
+    ) -> Result<(), DbError> {
+        let sender = call.sides.get_sender();
+        let receiver = call.sides.get_receiver();
+
+        for (user, peer, direction) in [
+            (sender, receiver, CallDirection::Outgoing),
+            (receiver, sender, CallDirection::Incoming),
+        ] {
+            run(
+                &self.session,
+                "INSERT INTO nexus.call_log (user, created_at, call, peer, direction, duration, accepted, state) VALUES(?, ?, ?, ?, ?, ?, ?, ?);",
+                (
+                    user,
+                    timestamp(call.get_created_at().timestamp()),
+                    call.uuid,
+                    peer,
+                    direction as i8,
+                    duration,
+                    call.accepted,
+                    state.get_index() as i8,
+                ),
+                DbError::FailedToAdd,
+            )
+            .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CallRepository for ScyllaCalls {
+    async fn add(&self, call: &MediaCall, state: CallState) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.calls (uuid, sender, receiver, duration, accepted, state, secret, created_at) VALUES(?, ?, ?, ?, ?, ?, ?, ?);",
+            (
+                call.uuid,
+                call.sides.get_sender(),
+                call.sides.get_receiver(),
+                0i64,
+                false,
+                state.get_index() as i8,
+                call.secret,
+                timestamp(call.get_created_at().timestamp()),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await?;
+
+        self.log_call(call, state, 0).await
+    }
+
+    async fn update(
+        &self,
+        call: &MediaCall,
+        state: CallState,
+        duration: i64,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "UPDATE nexus.calls SET duration = ?, accepted = ?, state = ? WHERE uuid = ? AND created_at = ? IF EXISTS;",
+            (
+                duration,
+                call.accepted,
+                state.get_index() as i8,
+                call.uuid,
+                timestamp(call.get_created_at().timestamp()),
+            ),
+            DbError::FailedToUpdate,
+        )
+        .await?;
+
+        self.log_call(call, state, duration)
+            .await
+            .map_err(|_| DbError::FailedToUpdate)
+    }
+
+    async fn log(
+        &self,
+        user_uuid: &Uuid,
+        before: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<CallLogEntry>, DbError> {
+        let limit = limit as i32;
+        let rows = match before {
+            Some(before) => {
+                select_all::<LogRow>(
+                    &self.session,
+                    "SELECT call, peer, direction, duration, accepted, state, created_at FROM nexus.call_log WHERE user = ? AND created_at < ? LIMIT ?;",
+                    (user_uuid, timestamp(before), limit),
+                )
+                .await?
+            }
+            None => {
+                select_all::<LogRow>(
+                    &self.session,
+                    "SELECT call, peer, direction, duration, accepted, state, created_at FROM nexus.call_log WHERE user = ? LIMIT ?;",
+                    (user_uuid, limit),
+                )
+                .await?
+            }
+        };
+
+        rows.into_iter()
+            .map(
+                |(call, peer, direction, duration, accepted, state, created_at)| {
+                    Ok(CallLogEntry {
+                        call,
+                        peer,
+                        direction: CallDirection::from_index(direction as u8)
+                            .ok_or(DbError::FailedToConvertRow)?,
+                        state: CallState::from_index(state as u8)
+                            .ok_or(DbError::FailedToConvertRow)?,
+                        duration,
+                        accepted,
+                        created_at: created_at.num_seconds(),
+                    })
+                },
+            )
+            .collect()
+    }
+
+    async fn add_stats(&self, stats: &CallStats) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.call_stats (call, packets, bytes, dropped, participants, streams, ended_at) VALUES(?, ?, ?, ?, ?, ?, ?);",
+            (
+                stats.call,
+                stats.packets as i64,
+                stats.bytes as i64,
+                stats.dropped as i64,
+                serde_json::to_string(&stats.participants).unwrap(),
+                serde_json::to_string(&stats.streams).unwrap(),
+                now(),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn stats(&self, call_uuid: &Uuid) -> Result<Option<CallStats>, DbError> {
+        let row = select_one::<(i64, i64, i64, String, String)>(
+            &self.session,
+            "SELECT packets, bytes, dropped, participants, streams FROM nexus.call_stats WHERE call = ?;",
+            (call_uuid,),
+        )
+        .await?;
+
+        Ok(row.map(
+            |(packets, bytes, dropped, participants, streams)| CallStats {
+                call: *call_uuid,
+                packets: packets as u64,
+                bytes: bytes as u64,
+                dropped: dropped as u64,
+                streams: serde_json::from_str::<Vec<StreamStats>>(&streams).unwrap_or_default(),
+                participants: serde_json::from_str::<Vec<ParticipantStats>>(&participants)
+                    .unwrap_or_default(),
+            },
+        ))
+    }
+
+    async fn queue_missed(&self, call: &MediaCall) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.missed_calls (user, created_at, call, content) VALUES(?, ?, ?, ?);",
+            (
+                call.sides.get_receiver(),
+                timestamp(call.get_created_at().timestamp()),
+                call.uuid,
+                call.as_bytes(),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn missed(&self, user_uuid: &Uuid) -> Result<Vec<MediaCall>, DbError> {
+        select_all::<(Vec<u8>,)>(
+            &self.session,
+            "SELECT content FROM nexus.missed_calls WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await
+        .map(|calls| {
+            calls
+                .into_iter()
+                .map(|(content,)| MediaCall::from_bytes(content))
+                .collect()
+        })
+    }
+
+    async fn remove_missed(&self, user_uuid: &Uuid, call: &MediaCall) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.missed_calls WHERE user = ? AND created_at = ? AND call = ?;",
+            (
+                user_uuid,
+                timestamp(call.get_created_at().timestamp()),
+                call.uuid,
+            ),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        // calls of both sides
+        for query in [
+            "SELECT uuid FROM nexus.calls WHERE sender = ? ALLOW FILTERING;",
+            "SELECT uuid FROM nexus.calls WHERE receiver = ? ALLOW FILTERING;",
+        ] {
+            let calls = select_all::<(Uuid,)>(&self.session, query, (user_uuid,)).await?;
+            for (call_uuid,) in calls {
+                for query in [
+                    "DELETE FROM nexus.calls WHERE uuid = ?;",
+                    "DELETE FROM nexus.call_stats WHERE call = ?;",
+                ] {
+                    run(&self.session, query, (call_uuid,), DbError::FailedToUpdate).await?;
+                }
+            }
+        }
+
+        // history entries of the other sides
+        let entries = select_all::<(Uuid, Duration, Uuid)>(
+            &self.session,
+            "SELECT user, created_at, call FROM nexus.call_log WHERE peer = ? ALLOW FILTERING;",
+            (user_uuid,),
+        )
+        .await?;
+        for (peer, created_at, call_uuid) in entries {
+            run(
+                &self.session,
+                "DELETE FROM nexus.call_log WHERE user = ? AND created_at = ? AND call = ?;",
+                (peer, Timestamp(created_at), call_uuid),
+                DbError::FailedToUpdate,
+            )
+            .await?;
+        }
+
+        for query in [
+            "DELETE FROM nexus.call_log WHERE user = ?;",
+            "DELETE FROM nexus.missed_calls WHERE user = ?;",
+        ] {
+            run(&self.session, query, (user_uuid,), DbError::FailedToUpdate).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use scylla::{frame::value::Counter, Session};
+use uuid::Uuid;
+
+use nexuslib::models::{file::media_file::MediaFile, message::media::MediaPreview};
+
+use crate::{
+    db::repository::{Blob, MediaEntry, MediaRepository, PendingMedia},
+    errors::db::DbError,
+};
+
+use super::{run, run_lwt, select_all, select_one, timestamp, ttl};
+
+/// Media in `nexus.media` and the tables of its previews, blobs, usage and uploads
+pub struct ScyllaMedia {
+    session: Arc<Session>,
+}
+
+impl ScyllaMedia {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self { session }
+    }
+}
+
+#[async_trait]
+impl MediaRepository for ScyllaMedia {
+    async fn add(&self, media: &MediaEntry, created_at: i64) -> Result<(), DbError> {
+        let applied = run_lwt(
+            &self.session,
+            "INSERT INTO nexus.media (uuid, name, path, sender, type, created_at) VALUES(?, ?, ?, ?, ?, ?) IF NOT EXISTS;",
+            (
+                media.uuid,
+                &media.name,
+                &media.path,
+                media.sender,
+                media.media_type.get_index() as i8,
+                timestamp(created_at),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await?;
+
+        match applied {
+            true => Ok(()),
+            false => Err(DbError::AlreadyExists),
+        }
+    }
+
+    async fn get(&self, media_uuid: &Uuid) -> Result<MediaEntry, DbError> {
+        select_one::<MediaEntry>(
+            &self.session,
+            "SELECT uuid, name, path, type, sender FROM nexus.media WHERE uuid = ?;",
+            (media_uuid,),
+        )
+        .await?
+        .ok_or(DbError::NotFound)
+    }
+
+    async fn with_path(&self, object: &str) -> Result<Vec<MediaEntry>, DbError> {
+        select_all::<MediaEntry>(
+            &self.session,
+            "SELECT uuid, name, path, type, sender FROM nexus.media WHERE path = ? ALLOW FILTERING;",
+            (object,),
+        )
+        .await
+    }
+
+    async fn sent_by(&self, user_uuid: &Uuid) -> Result<Vec<MediaEntry>, DbError> {
+        select_all::<MediaEntry>(
+            &self.session,
+            "SELECT uuid, name, path, type, sender FROM nexus.media WHERE sender = ? ALLOW FILTERING;",
+            (user_uuid,),
+        )
+        .await
+    }
+
+    async fn remove(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        for query in [
+            "DELETE FROM nexus.media WHERE uuid = ?;",
+            "DELETE FROM nexus.media_previews WHERE media = ?;",
+        ] {
+            run(&self.session, query, (media_uuid,), DbError::FailedToUpdate).await?;
+        }
+        Ok(())
+    }
+
+    async fn add_preview(&self, media_uuid: &Uuid, preview: &MediaPreview) -> Result<(), DbError> {
+        let preview = serde_json::to_string(preview).map_err(|_| DbError::FailedToAdd)?;
+        run(
+            &self.session,
+            "INSERT INTO nexus.media_previews (media, preview) VALUES(?, ?);",
+            (media_uuid, preview),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn preview(&self, media_uuid: &Uuid) -> Result<Option<MediaPreview>, DbError> {
+        let row = select_one::<(String,)>(
+            &self.session,
+            "SELECT preview FROM nexus.media_previews WHERE media = ?;",
+            (media_uuid,),
+        )
+        .await?;
+
+        match row {
+            Some((preview,)) => serde_json::from_str(&preview)
+                .map(Some)
+                .map_err(|_| DbError::FailedToConvertRow),
+            None => Ok(None),
+        }
+    }
+
+    async fn usage(&self, user_uuid: &Uuid) -> Result<u64, DbError> {
+        let row = select_one::<(Counter,)>(
+            &self.session,
+            "SELECT used FROM nexus.storage_usage WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?;
+
+        Ok(row.map_or(0, |(Counter(used),)| used.max(0) as u64))
+    }
+
+    async fn add_usage(&self, user_uuid: &Uuid, len_bytes: usize) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "UPDATE nexus.storage_usage SET used = used + ? WHERE user = ?;",
+            (len_bytes as i64, user_uuid),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove_usage(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.storage_usage WHERE user = ?;",
+            (user_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn blob(&self, bucket: &str, hash: &str) -> Result<Blob, DbError> {
+        let (object, size, refs) = select_one::<(String, i64, i32)>(
+            &self.session,
+            "SELECT object, size, refs FROM nexus.blobs WHERE bucket = ? AND hash = ?;",
+            (bucket, hash),
+        )
+        .await?
+        .ok_or(DbError::NotFound)?;
+
+        Ok(Blob {
+            object,
+            size: size as usize,
+            refs,
+        })
+    }
+
+    async fn add_blob(&self, bucket: &str, hash: &str, blob: &Blob) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "INSERT INTO nexus.blobs (bucket, hash, object, size, refs) VALUES(?, ?, ?, ?, ?) IF NOT EXISTS;",
+            (bucket, hash, &blob.object, blob.size as i64, blob.refs),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn set_blob_refs(
+        &self,
+        bucket: &str,
+        hash: &str,
+        refs: i32,
+        expected: i32,
+    ) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "UPDATE nexus.blobs SET refs = ? WHERE bucket = ? AND hash = ? IF refs = ?;",
+            (refs, bucket, hash, expected),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn unreferenced_blobs(&self) -> Result<Vec<(String, String, String)>, DbError> {
+        select_all::<(String, String, String)>(
+            &self.session,
+            "SELECT bucket, hash, object FROM nexus.blobs WHERE refs = 0 ALLOW FILTERING;",
+            (),
+        )
+        .await
+    }
+
+    async fn remove_unreferenced_blob(&self, bucket: &str, hash: &str) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "DELETE FROM nexus.blobs WHERE bucket = ? AND hash = ? IF refs = 0;",
+            (bucket, hash),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn link_blob(&self, media_uuid: &Uuid, bucket: &str, hash: &str) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.media_blobs (media, bucket, hash) VALUES(?, ?, ?);",
+            (media_uuid, bucket, hash),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn blob_of(&self, media_uuid: &Uuid) -> Result<Option<(String, String)>, DbError> {
+        select_one::<(String, String)>(
+            &self.session,
+            "SELECT bucket, hash FROM nexus.media_blobs WHERE media = ?;",
+            (media_uuid,),
+        )
+        .await
+    }
+
+    async fn unlink_blob(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.media_blobs WHERE media = ?;",
+            (media_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn add_pending(&self, pending: &PendingMedia) -> Result<(), DbError> {
+        let applied = run_lwt(
+            &self.session,
+            format!(
+                "INSERT INTO nexus.pending_media (uuid, sender, file, object, content_type, expires_at) VALUES(?, ?, ?, ?, ?, ?) IF NOT EXISTS USING TTL {};",
+                ttl(pending.expires_at)
+            ),
+            (
+                pending.file.uuid,
+                pending.file.sender,
+                pending.file.as_bytes(),
+                &pending.object,
+                &pending.content_type,
+                timestamp(pending.expires_at),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await?;
+
+        match applied {
+            true => Ok(()),
+            false => Err(DbError::AlreadyExists),
+        }
+    }
+
+    async fn pending(&self, media_uuid: &Uuid) -> Result<PendingMedia, DbError> {
+        let (file, object, content_type, expires_at) =
+            select_one::<(Vec<u8>, String, String, Duration)>(
+                &self.session,
+                "SELECT file, object, content_type, expires_at FROM nexus.pending_media WHERE uuid = ?;",
+                (media_uuid,),
+            )
+            .await?
+            .ok_or(DbError::NotFound)?;
+
+        Ok(PendingMedia {
+            file: MediaFile::from_bytes(file),
+            object,
+            content_type,
+            expires_at: expires_at.num_seconds(),
+        })
+    }
+
+    async fn claim_pending(&self, media_uuid: &Uuid) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "DELETE FROM nexus.pending_media WHERE uuid = ? IF EXISTS;",
+            (media_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use scylla::{frame::value::Timestamp, Session};
+use uuid::Uuid;
+
+use nexuslib::{
+    models::message::{MessageBody, MessageContent},
+    Message,
+};
+
+use crate::{
+    db::{models_wrapper::MessageDB, repository::MessageRepository},
+    errors::db::DbError,
+};
+
+use super::{run, select_all, select_one, timestamp};
+
+/// Messages in `nexus.messages` and `nexus.attachments`
+pub struct ScyllaMessages {
+    session: Arc<Session>,
+}
+
+impl ScyllaMessages {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self { session }
+    }
+}
+
+#[async_trait]
+impl MessageRepository for ScyllaMessages {
+    async fn add(&self, message: &Message<MessageBody>) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.messages (uuid, text, nonce, media, voice, sender, receiver, sent, read, edited, created_at) VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?);",
+            (
+                message.uuid,
+                message.content.get_text().unwrap_or_default(),
+                message.get_nonce(),
+                // the messages without media or voice notes have an empty string
+                message
+                    .media
+                    .as_ref()
+                    .and_then(|media| serde_json::to_string(media).ok())
+                    .unwrap_or_default(),
+                message
+                    .content
+                    .get_voice()
+                    .and_then(|voice| serde_json::to_string(voice).ok())
+                    .unwrap_or_default(),
+                message.sides.get_sender(),
+                message.sides.get_receiver(),
+                message.status.get_sent(),
+                message.status.get_read(),
+                message.status.get_edited(),
+                timestamp(message.get_created_at().timestamp()),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn between(
+        &self,
+        sender: &Uuid,
+        receiver: &Uuid,
+    ) -> Result<Vec<Message<MessageBody>>, DbError> {
+        select_all::<MessageDB>(
+            &self.session,
+            "SELECT uuid, text, media, voice, nonce, sender, receiver, sent, read, edited, created_at FROM nexus.messages WHERE sender = ? AND receiver = ? ALLOW FILTERING;",
+            (sender, receiver),
+        )
+        .await
+        .map(|messages| messages.into_iter().map(MessageDB::get_message).collect())
+    }
+
+    async fn remove_for_user(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        for query in [
+            "SELECT created_at, sender, uuid FROM nexus.messages WHERE sender = ? ALLOW FILTERING;",
+            "SELECT created_at, sender, uuid FROM nexus.messages WHERE receiver = ? ALLOW FILTERING;",
+        ] {
+            let messages =
+                select_all::<(Duration, Uuid, Uuid)>(&self.session, query, (user_uuid,)).await?;
+            for (created_at, sender, message_uuid) in messages {
+                run(
+                    &self.session,
+                    "DELETE FROM nexus.messages WHERE created_at = ? AND sender = ? AND uuid = ?;",
+                    (Timestamp(created_at), sender, message_uuid),
+                    DbError::FailedToUpdate,
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn add_attachment(
+        &self,
+        media_uuid: &Uuid,
+        receiver: &Uuid,
+        message_uuid: &Uuid,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.attachments (media, receiver, message) VALUES(?, ?, ?);",
+            (media_uuid, receiver, message_uuid),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn is_attached_for(&self, media_uuid: &Uuid, user_uuid: &Uuid) -> Result<bool, DbError> {
+        select_one::<(Uuid,)>(
+            &self.session,
+            "SELECT message FROM nexus.attachments WHERE media = ? AND receiver = ? LIMIT 1;",
+            (media_uuid, user_uuid),
+        )
+        .await
+        .map(|row| row.is_some())
+    }
+
+    async fn remove_attachments(&self, media_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.attachments WHERE media = ?;",
+            (media_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove_received(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        let attachments = select_all::<(Uuid, Uuid)>(
+            &self.session,
+            "SELECT media, message FROM nexus.attachments WHERE receiver = ? ALLOW FILTERING;",
+            (user_uuid,),
+        )
+        .await?;
+
+        for (media_uuid, message_uuid) in attachments {
+            run(
+                &self.session,
+                "DELETE FROM nexus.attachments WHERE media = ? AND receiver = ? AND message = ?;",
+                (media_uuid, user_uuid, message_uuid),
+                DbError::FailedToUpdate,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
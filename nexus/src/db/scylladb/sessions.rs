@@ -0,0 +1,223 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use scylla::Session;
+use uuid::Uuid;
+
+use nexuslib::request::auth::AuthRequestMeta;
+
+use crate::{
+    db::repository::{LoginChallenge, PasswordReset, SessionRepository},
+    errors::db::DbError,
+};
+
+use super::{now, run, run_lwt, select_all, select_one, timestamp, ttl};
+
+/// Sessions in `nexus.sessions`, `nexus.login_challenges` and `nexus.password_resets`
+pub struct ScyllaSessions {
+    session: Arc<Session>,
+}
+
+impl ScyllaSessions {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self { session }
+    }
+}
+
+#[async_trait]
+impl SessionRepository for ScyllaSessions {
+    async fn add(
+        &self,
+        token: &str,
+        user_uuid: &Uuid,
+        meta: &AuthRequestMeta,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.sessions (jwt, user, location, device_name, device_type, device_os, created_at) VALUES(?, ?, ?, ?, ?, ?, ?);",
+            (
+                token,
+                user_uuid,
+                &meta.location,
+                &meta.device_name,
+                &meta.device_type,
+                &meta.device_os,
+                now(),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn user_of(&self, token: &str) -> Result<Uuid, DbError> {
+        select_one::<(Uuid,)>(
+            &self.session,
+            "SELECT user FROM nexus.sessions WHERE jwt = ?;",
+            (token,),
+        )
+        .await?
+        .map(|(user,)| user)
+        .ok_or(DbError::NotFound)
+    }
+
+    async fn tokens_of(&self, user_uuid: &Uuid) -> Result<Vec<String>, DbError> {
+        select_all::<(String,)>(
+            &self.session,
+            "SELECT jwt FROM nexus.sessions WHERE user = ? ALLOW FILTERING;",
+            (user_uuid,),
+        )
+        .await
+        .map(|tokens| tokens.into_iter().map(|(token,)| token).collect())
+    }
+
+    async fn remove(&self, token: &str) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.sessions WHERE jwt = ?;",
+            (token,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn add_challenge(
+        &self,
+        token_hash: &str,
+        challenge: &LoginChallenge,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            format!(
+                "INSERT INTO nexus.login_challenges (token_hash, user, attempts, location, device_name, device_type, device_os, expires_at) VALUES(?, ?, ?, ?, ?, ?, ?, ?) USING TTL {};",
+                ttl(challenge.expires_at)
+            ),
+            (
+                token_hash,
+                challenge.user,
+                challenge.attempts,
+                &challenge.meta.location,
+                &challenge.meta.device_name,
+                &challenge.meta.device_type,
+                &challenge.meta.device_os,
+                timestamp(challenge.expires_at),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn challenge(&self, token_hash: &str) -> Result<Option<LoginChallenge>, DbError> {
+        let row = select_one::<(Uuid, i32, String, String, String, String, Duration)>(
+            &self.session,
+            "SELECT user, attempts, location, device_name, device_type, device_os, expires_at FROM nexus.login_challenges WHERE token_hash = ?;",
+            (token_hash,),
+        )
+        .await?;
+
+        Ok(row.map(
+            |(user, attempts, location, device_name, device_type, device_os, expires_at)| {
+                LoginChallenge {
+                    user,
+                    attempts,
+                    meta: AuthRequestMeta {
+                        location,
+                        device_name,
+                        device_type,
+                        device_os,
+                    },
+                    expires_at: expires_at.num_seconds(),
+                }
+            },
+        ))
+    }
+
+    async fn set_challenge_attempts(
+        &self,
+        token_hash: &str,
+        attempts: i32,
+        expires_at: i64,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            format!(
+                "UPDATE nexus.login_challenges USING TTL {} SET attempts = ? WHERE token_hash = ?;",
+                ttl(expires_at)
+            ),
+            (attempts, token_hash),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove_challenge(&self, token_hash: &str) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.login_challenges WHERE token_hash = ?;",
+            (token_hash,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn claim_challenge(&self, token_hash: &str) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "DELETE FROM nexus.login_challenges WHERE token_hash = ? IF EXISTS;",
+            (token_hash,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn add_password_reset(
+        &self,
+        token_hash: &str,
+        reset: &PasswordReset,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            format!(
+                "INSERT INTO nexus.password_resets (token_hash, user, issued_by, created_at, expires_at) VALUES(?, ?, ?, ?, ?) USING TTL {};",
+                ttl(reset.expires_at)
+            ),
+            (
+                token_hash,
+                reset.user,
+                reset.issued_by,
+                timestamp(reset.created_at),
+                timestamp(reset.expires_at),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn password_reset(&self, token_hash: &str) -> Result<Option<PasswordReset>, DbError> {
+        let row = select_one::<(Uuid, Uuid, Duration, Duration)>(
+            &self.session,
+            "SELECT user, issued_by, created_at, expires_at FROM nexus.password_resets WHERE token_hash = ?;",
+            (token_hash,),
+        )
+        .await?;
+
+        Ok(
+            row.map(|(user, issued_by, created_at, expires_at)| PasswordReset {
+                user,
+                issued_by,
+                created_at: created_at.num_seconds(),
+                expires_at: expires_at.num_seconds(),
+            }),
+        )
+    }
+
+    async fn claim_password_reset(&self, token_hash: &str) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "DELETE FROM nexus.password_resets WHERE token_hash = ? IF EXISTS;",
+            (token_hash,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+}
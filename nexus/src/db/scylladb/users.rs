@@ -0,0 +1,454 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::Duration;
+use scylla::{batch::Batch, Session};
+use uuid::Uuid;
+
+use nexuslib::{
+    crypto::totp,
+    models::{
+        moderation::{AuditEntry, RestrictionKind, UserRestriction},
+        user::{profile::UserProfile, role::Role, settings::UserSettings, User},
+    },
+};
+
+use crate::{
+    db::{
+        models_wrapper::UserDB,
+        repository::{audit_month, TotpEntry, UserRepository},
+    },
+    errors::db::DbError,
+};
+
+use super::{now, run, run_lwt, select_all, select_one, timestamp};
+
+type RestrictionRow = (Uuid, i8, String, Uuid, Duration, Option<Duration>);
+
+/// Users in `nexus.users` and the tables keyed by the user
+pub struct ScyllaUsers {
+    session: Arc<Session>,
+}
+
+impl ScyllaUsers {
+    pub fn new(session: Arc<Session>) -> Self {
+        Self { session }
+    }
+}
+
+#[async_trait]
+impl UserRepository for ScyllaUsers {
+    async fn list(&self) -> Result<Vec<User>, DbError> {
+        select_all::<UserDB>(&self.session, "SELECT * FROM nexus.users;", ())
+            .await
+            .map(|users| users.iter().map(UserDB::get_user).collect())
+    }
+
+    async fn get(&self, user_uuid: &Uuid) -> Result<User, DbError> {
+        select_one::<UserDB>(
+            &self.session,
+            "SELECT * FROM nexus.users WHERE uuid = ?;",
+            (user_uuid,),
+        )
+        .await?
+        .map(|user| user.get_user())
+        .ok_or(DbError::NotFound)
+    }
+
+    async fn get_by_username(&self, username: &str) -> Result<User, DbError> {
+        select_one::<UserDB>(
+            &self.session,
+            "SELECT * FROM nexus.users WHERE username = ? ALLOW FILTERING;",
+            (username,),
+        )
+        .await?
+        .map(|user| user.get_user())
+        .ok_or(DbError::NotFound)
+    }
+
+    async fn create(&self, user: &User, secret: &[u8]) -> Result<(), DbError> {
+        let mut batch: Batch = Default::default();
+        batch.append_statement(
+            "INSERT INTO nexus.users (uuid, username, password, role, public_key, created_at) VALUES(?, ?, ?, ?, ?, ?);",
+        );
+        batch.append_statement("INSERT INTO nexus.secret_keys (user, private_key) VALUES(?, ?);");
+
+        let user_values = (
+            user.uuid,
+            &user.username,
+            &user.password,
+            user.role.get_index() as i8,
+            user.public_key_str(),
+            timestamp(user.created_at),
+        );
+        let secret_values = (user.uuid, secret.to_vec());
+
+        self.session
+            .batch(&batch, (user_values, secret_values))
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                log::error!("{e:?}");
+                DbError::FailedToAdd
+            })
+    }
+
+    async fn rename(&self, user: &User, username: &str) -> Result<(), DbError> {
+        // the username is a part of the primary key
+        let mut batch: Batch = Default::default();
+        batch.append_statement("DELETE FROM nexus.users WHERE uuid = ? AND username = ?;");
+        batch.append_statement(
+            "INSERT INTO nexus.users (uuid, username, password, role, public_key, created_at) VALUES(?, ?, ?, ?, ?, ?);",
+        );
+
+        let batch_values = (
+            (user.uuid, &user.username),
+            (
+                user.uuid,
+                username,
+                &user.password,
+                user.role.get_index() as i8,
+                user.public_key_str(),
+                timestamp(user.created_at),
+            ),
+        );
+
+        self.session
+            .batch(&batch, batch_values)
+            .await
+            .map(|_| ())
+            .map_err(|e| {
+                log::error!("{e:?}");
+                DbError::FailedToUpdate
+            })
+    }
+
+    async fn set_password(&self, user: &User, password: &str) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "UPDATE nexus.users SET password = ? WHERE uuid = ? AND username = ?;",
+            (password, user.uuid, &user.username),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn set_role(&self, user: &User, role: Role) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "UPDATE nexus.users SET role = ? WHERE uuid = ? AND username = ?;",
+            (role.get_index() as i8, user.uuid, &user.username),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.users WHERE uuid = ?;",
+            (user_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn claim_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError> {
+        let applied = run_lwt(
+            &self.session,
+            "INSERT INTO nexus.usernames (username, user) VALUES(?, ?) IF NOT EXISTS;",
+            (username, user_uuid),
+            DbError::FailedToAdd,
+        )
+        .await?;
+
+        match applied {
+            true => Ok(()),
+            false => Err(DbError::AlreadyExists),
+        }
+    }
+
+    async fn release_username(&self, username: &str, user_uuid: &Uuid) -> Result<(), DbError> {
+        run_lwt(
+            &self.session,
+            "DELETE FROM nexus.usernames WHERE username = ? IF user = ?;",
+            (username, user_uuid),
+            DbError::FailedToUpdate,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    async fn secret_key(&self, user_uuid: &Uuid) -> Result<Vec<u8>, DbError> {
+        select_one::<(Vec<u8>,)>(
+            &self.session,
+            "SELECT private_key FROM nexus.secret_keys WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?
+        .map(|(key,)| key)
+        .ok_or(DbError::NotFound)
+    }
+
+    async fn profile(&self, user_uuid: &Uuid) -> Result<Option<UserProfile>, DbError> {
+        let row = select_one::<(Uuid, Option<String>, Option<String>, Option<Uuid>)>(
+            &self.session,
+            "SELECT user, display_name, bio, avatar FROM nexus.profiles WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?;
+
+        Ok(row.map(|(user, display_name, bio, avatar)| UserProfile {
+            user,
+            display_name,
+            bio,
+            avatar,
+        }))
+    }
+
+    async fn save_profile(&self, profile: &UserProfile) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.profiles (user, display_name, bio, avatar, updated_at) VALUES(?, ?, ?, ?, ?);",
+            (
+                profile.user,
+                &profile.display_name,
+                &profile.bio,
+                profile.avatar,
+                now(),
+            ),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn settings(&self, user_uuid: &Uuid) -> Result<Option<UserSettings>, DbError> {
+        let row = select_one::<(String, String)>(
+            &self.session,
+            "SELECT language, theme FROM nexus.settings WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?;
+
+        Ok(row.map(|(language, theme)| UserSettings { language, theme }))
+    }
+
+    async fn save_settings(
+        &self,
+        user_uuid: &Uuid,
+        settings: &UserSettings,
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.settings (user, language, theme) VALUES(?, ?, ?);",
+            (user_uuid, &settings.language, &settings.theme),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn totp(&self, user_uuid: &Uuid) -> Result<Option<TotpEntry>, DbError> {
+        let row = select_one::<(String, bool, i64, Option<Vec<String>>)>(
+            &self.session,
+            "SELECT secret, enabled, last_step, recovery_codes FROM nexus.totp WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?;
+
+        match row {
+            Some((secret, enabled, last_step, recovery_codes)) => Ok(Some(TotpEntry {
+                secret: totp::decode_secret(&secret).ok_or(DbError::FailedToConvertRow)?,
+                enabled,
+                last_step,
+                recovery_codes: recovery_codes.unwrap_or_default(),
+            })),
+            None => Ok(None),
+        }
+    }
+
+    async fn enroll_totp(&self, user_uuid: &Uuid, secret: &[u8]) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.totp (user, secret, enabled, last_step, created_at) VALUES(?, ?, ?, ?, ?);",
+            (user_uuid, totp::encode_secret(secret), false, 0i64, now()),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn enable_totp(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        recovery_codes: &[String],
+    ) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "UPDATE nexus.totp SET enabled = ?, last_step = ?, recovery_codes = ? WHERE user = ?;",
+            (true, step, recovery_codes, user_uuid),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn remove_totp(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.totp WHERE user = ?;",
+            (user_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn advance_totp_step(
+        &self,
+        user_uuid: &Uuid,
+        step: i64,
+        last_step: i64,
+    ) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "UPDATE nexus.totp SET last_step = ? WHERE user = ? IF last_step = ?;",
+            (step, user_uuid, last_step),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn use_recovery_code(
+        &self,
+        user_uuid: &Uuid,
+        code_hash: &str,
+        recovery_codes: &[String],
+    ) -> Result<bool, DbError> {
+        run_lwt(
+            &self.session,
+            "UPDATE nexus.totp SET recovery_codes = recovery_codes - ? WHERE user = ? IF recovery_codes = ?;",
+            (vec![code_hash], user_uuid, recovery_codes),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn restriction(&self, user_uuid: &Uuid) -> Result<Option<UserRestriction>, DbError> {
+        select_one::<RestrictionRow>(
+            &self.session,
+            "SELECT user, kind, reason, issued_by, created_at, expires_at FROM nexus.restrictions WHERE user = ?;",
+            (user_uuid,),
+        )
+        .await?
+        .map(restriction_from_row)
+        .transpose()
+    }
+
+    async fn restrictions(&self) -> Result<Vec<UserRestriction>, DbError> {
+        select_all::<RestrictionRow>(
+            &self.session,
+            "SELECT user, kind, reason, issued_by, created_at, expires_at FROM nexus.restrictions;",
+            (),
+        )
+        .await?
+        .into_iter()
+        .map(restriction_from_row)
+        .collect()
+    }
+
+    async fn restrict(&self, restriction: &UserRestriction) -> Result<(), DbError> {
+        // the row expires together with the restriction, TTL 0 means no expiration
+        let ttl = restriction.expires_at.map_or(0, super::ttl);
+
+        run(
+            &self.session,
+            format!(
+                "INSERT INTO nexus.restrictions (user, kind, reason, issued_by, created_at, expires_at) VALUES(?, ?, ?, ?, ?, ?) USING TTL {ttl};"
+            ),
+            (
+                restriction.user,
+                restriction.kind.get_index() as i8,
+                &restriction.reason,
+                restriction.issued_by,
+                timestamp(restriction.created_at),
+                restriction.expires_at.map(timestamp),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn lift_restriction(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "DELETE FROM nexus.restrictions WHERE user = ?;",
+            (user_uuid,),
+            DbError::FailedToUpdate,
+        )
+        .await
+    }
+
+    async fn add_audit(&self, entry: &AuditEntry) -> Result<(), DbError> {
+        run(
+            &self.session,
+            "INSERT INTO nexus.admin_audit (month, uuid, actor, target, action, details, created_at) VALUES(?, ?, ?, ?, ?, ?, ?);",
+            (
+                audit_month(entry.created_at),
+                entry.uuid,
+                entry.actor,
+                entry.target,
+                &entry.action,
+                &entry.details,
+                timestamp(entry.created_at),
+            ),
+            DbError::FailedToAdd,
+        )
+        .await
+    }
+
+    async fn audit(&self, month: &str, limit: usize) -> Result<Vec<AuditEntry>, DbError> {
+        let rows = select_all::<(Uuid, Uuid, Option<Uuid>, String, Option<String>, Duration)>(
+            &self.session,
+            "SELECT uuid, actor, target, action, details, created_at FROM nexus.admin_audit WHERE month = ? LIMIT ?;",
+            (month, limit as i32),
+        )
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(
+                |(uuid, actor, target, action, details, created_at)| AuditEntry {
+                    uuid,
+                    actor,
+                    target,
+                    action,
+                    details: details.unwrap_or_default(),
+                    created_at: created_at.num_seconds(),
+                },
+            )
+            .collect())
+    }
+
+    async fn remove_data(&self, user_uuid: &Uuid) -> Result<(), DbError> {
+        for query in [
+            "DELETE FROM nexus.secret_keys WHERE user = ?;",
+            "DELETE FROM nexus.profiles WHERE user = ?;",
+            "DELETE FROM nexus.settings WHERE user = ?;",
+            "DELETE FROM nexus.restrictions WHERE user = ?;",
+        ] {
+            run(&self.session, query, (user_uuid,), DbError::FailedToUpdate).await?;
+        }
+        Ok(())
+    }
+}
+
+fn restriction_from_row(
+    (user, kind, reason, issued_by, created_at, expires_at): RestrictionRow,
+) -> Result<UserRestriction, DbError> {
+    Ok(UserRestriction {
+        user,
+        kind: RestrictionKind::from_index(kind as u8).ok_or(DbError::FailedToConvertRow)?,
+        reason,
+        issued_by,
+        created_at: created_at.num_seconds(),
+        expires_at: expires_at.map(|expires_at| expires_at.num_seconds()),
+    })
+}
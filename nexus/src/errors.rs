@@ -5,8 +5,13 @@ use warp::{hyper::StatusCode, Rejection, Reply};
 
 use self::jwt::JWTError;
 
+pub mod call;
 pub mod db;
+pub mod file;
 pub mod jwt;
+pub mod message;
+pub mod processing;
+pub mod storage;
 
 #[derive(Serialize)]
 struct ErrorResponse {
@@ -22,6 +27,7 @@ pub async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible>
             JWTError::WrongCredentials => (StatusCode::FORBIDDEN, e.to_string()),
             JWTError::JWTToken => (StatusCode::UNAUTHORIZED, e.to_string()),
             JWTError::NoPermission => (StatusCode::UNAUTHORIZED, e.to_string()),
+            JWTError::Restricted => (StatusCode::FORBIDDEN, e.to_string()),
             JWTError::JWTTokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
             _ => (StatusCode::BAD_REQUEST, e.to_string()),
         }
@@ -0,0 +1,27 @@
+use nexuslib::{
+    models::call::state::CallState,
+    request::index_token::{IndexToken, RoomToken},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum CallError {
+    #[error("Call already exists")]
+    AlreadyExists,
+    #[error("Call not found")]
+    NotFound,
+    #[error("Not a participant of the call")]
+    NotParticipant,
+    #[error("Token {0:?} is sent only by the server")]
+    ServerOnly(IndexToken),
+    #[error("Illegal transition: {token:?} in state {state}")]
+    IllegalTransition { state: CallState, token: IndexToken },
+    #[error("Token {0:?} is sent only by the server")]
+    RoomServerOnly(RoomToken),
+    #[error("Already joined the room")]
+    AlreadyJoined,
+    #[error("The room is full")]
+    RoomFull,
+    #[error("Already in another call")]
+    InAnotherCall,
+}
@@ -8,4 +8,5 @@ pub enum DbError {
     FailedToConvertRow,
     WrongCredentials,
     NotFound,
+    Restricted,
 }
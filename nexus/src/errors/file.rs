@@ -0,0 +1,28 @@
+use nexuslib::{errors::file::UploadError, request::index_token::FileToken};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum FileError {
+    #[error("Upload not found")]
+    NotFound,
+    #[error("Not the owner of the upload")]
+    NotOwner,
+    #[error("UUID of the media is already taken")]
+    Taken,
+    #[error("Token {0:?} is sent only by the server")]
+    ServerOnly(FileToken),
+    #[error("Request without the {0}")]
+    Missing(&'static str),
+    #[error("Invalid upload: {0:?}")]
+    Invalid(UploadError),
+    #[error("Failed to store the file")]
+    Storage,
+    #[error("Rejected: {0}")]
+    Rejected(UploadError),
+}
+
+impl From<UploadError> for FileError {
+    fn from(e: UploadError) -> Self {
+        FileError::Invalid(e)
+    }
+}
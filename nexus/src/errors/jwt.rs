@@ -16,6 +16,8 @@ pub enum JWTError {
     InvalidAuthHeader,
     #[error("No Permission")]
     NoPermission,
+    #[error("Account is restricted")]
+    Restricted,
 }
 
 impl Reject for JWTError {}
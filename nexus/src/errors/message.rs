@@ -0,0 +1,20 @@
+use thiserror::Error;
+use uuid::Uuid;
+
+#[derive(Debug, Error)]
+pub enum MessageError {
+    #[error("The user is not the sender of the message")]
+    NotSender,
+    #[error("Too many attachments")]
+    TooManyAttachments,
+    #[error("Attachment {0} not found")]
+    AttachmentNotFound(Uuid),
+    #[error("Attachment {0} is not owned by the sender")]
+    NotAttachmentOwner(Uuid),
+    #[error("Failed to add the attachments")]
+    Attachments,
+    #[error("Invalid duration or waveform of the voice note")]
+    InvalidVoiceNote,
+    #[error("Voice note {0} is not an attached audio")]
+    VoiceNotAttached(Uuid),
+}
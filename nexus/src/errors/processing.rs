@@ -0,0 +1,21 @@
+use thiserror::Error;
+
+use super::storage::StorageError;
+
+#[derive(Debug, Error)]
+pub enum ProcessingError {
+    #[error("Media is too large to be processed")]
+    TooLarge,
+    #[error("Failed to decode the media: {0}")]
+    Decode(String),
+    #[error("Failed to encode the preview: {0}")]
+    Encode(String),
+    #[error("External tool failed: {0}")]
+    Tool(String),
+    #[error("Processing was aborted")]
+    Aborted,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Storage error: {0}")]
+    Storage(#[from] StorageError),
+}
@@ -0,0 +1,17 @@
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Storage is not configured: {0}")]
+    Config(String),
+    #[error("Object not found")]
+    NotFound,
+    #[error("Invalid object key `{0}`")]
+    InvalidKey(String),
+    #[error("Operation is not supported by the backend")]
+    Unsupported,
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("S3 error: {0}")]
+    S3(String),
+}
@@ -0,0 +1,10 @@
+pub mod api;
+pub mod db;
+pub mod errors;
+pub mod ops;
+pub mod processing;
+pub mod result;
+pub mod state;
+pub mod storage;
+pub mod stream;
+pub mod tls;
@@ -3,27 +3,19 @@ use std::{
     sync::{mpsc::channel, Arc},
 };
 
-use api::run_http;
 use dotenv::dotenv;
 use env_logger::Env;
-use scylla::Session;
-use storage::minio_setup;
 use tokio::sync::Mutex;
 
-use db::session_setup;
-use result::Result;
-use state::connection::ConnectionState;
-use stream::{tcp::run_tcp, udp::run_udp};
-
-mod api;
-mod db;
-mod errors;
-mod ops;
-mod result;
-mod state;
-mod storage;
-mod stream;
-mod tls;
+use nexus::{
+    api::run_http,
+    db::{db_setup, Database},
+    ops::blob::spawn_blob_gc,
+    result::Result,
+    state::connection::ConnectionState,
+    storage::storage_setup,
+    stream::{tcp::run_tcp, udp::run_udp},
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -43,22 +35,23 @@ async fn main() -> Result<()> {
         process::exit(0);
     });
 
-    // DB session
-    let _session = session_setup().await;
-    let session: Arc<Mutex<Session>> = Arc::new(Mutex::new(_session));
+    // DB backend
+    let db: Arc<Database> = Arc::new(db_setup().await);
 
     // Storage client
-    minio_setup().await;
+    storage_setup().await;
+    // Removal of the unreferenced media
+    spawn_blob_gc(db.clone());
 
     // Active connections state
     let state: Arc<Mutex<ConnectionState>> = Arc::new(Mutex::new(ConnectionState::new()));
 
     // HTTP server
-    run_http(session.clone()).await;
+    run_http(db.clone(), Arc::clone(&state)).await;
     // UDP Server
     run_udp(Arc::clone(&state)).await;
     // TCP Server
-    run_tcp(session, state).await;
+    run_tcp(db, state).await;
 
     Ok(())
 }
@@ -1,3 +1,5 @@
+pub mod blob;
 pub mod call;
 pub mod file;
 pub mod message;
+pub mod room;
@@ -0,0 +1,242 @@
+use std::{sync::Arc, time::Duration};
+
+use uuid::Uuid;
+
+use nexuslib::models::file::media_file::MediaFile;
+
+use crate::{
+    db::{repository::Blob, Database},
+    errors::db::DbError,
+    ops::file::add_file,
+    storage::{bucket_name, remove_object},
+};
+
+/// Time between the runs of the garbage collection
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Attempts of a compare-and-set of the references before giving up
+const CAS_ATTEMPTS: usize = 8;
+
+/// Stores the file as one more reference to the same content, so it is not uploaded again
+///
+/// Only the content the user already has access to is reused:
+/// knowing the hash of a file must not be enough to get the file.
+/// Returns `None` if the file has to be uploaded
+pub async fn reuse_blob(
+    db: Arc<Database>,
+    file: &MediaFile,
+    user_uuid: Uuid,
+) -> Result<Option<String>, DbError> {
+    if file.secret {
+        return Ok(None);
+    }
+    let (bucket, hash) = (bucket_name(file.media_type), file.hash.to_lowercase());
+
+    let blob = match db.media.blob(&bucket, &hash).await {
+        Ok(blob) if blob.size == file.len_bytes => blob,
+        Ok(_) | Err(DbError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !can_access(db.clone(), &blob.object, user_uuid).await? {
+        return Ok(None);
+    }
+
+    // the blob may have been collected meanwhile
+    let blob = match acquire_blob(db.clone(), &bucket, &hash).await {
+        Ok(blob) => blob,
+        Err(DbError::NotFound) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if let Err(e) = add_file(db.clone(), file, &blob.object, user_uuid).await {
+        let _ = release_blob(db, &bucket, &hash).await;
+        return Err(e);
+    }
+
+    Ok(Some(blob.object))
+}
+
+/// Makes the uploaded object the blob of the file's content,
+/// returns the key of the object the media points to
+///
+/// If the same content has been committed meanwhile,
+/// the object is removed and the existing blob is referenced instead.
+/// The reference is linked to the media by `add_file`
+pub async fn share_object(
+    db: Arc<Database>,
+    file: &MediaFile,
+    object: &str,
+) -> Result<String, DbError> {
+    if file.secret {
+        return Ok(object.to_owned());
+    }
+    let (bucket, hash) = (bucket_name(file.media_type), file.hash.to_lowercase());
+
+    let blob = register_blob(db, &bucket, &hash, object, file.len_bytes).await?;
+    if blob.object != object {
+        if let Err(err) = remove_object(&bucket, object).await {
+            log::error!("Error removing the object `{object}`: {err}");
+        }
+    }
+
+    Ok(blob.object)
+}
+
+/// Drops the object returned by `share_object` for the media that could not be added
+///
+/// The blob loses the reference and is collected later,
+/// the object of an encrypted file is removed at once
+pub async fn discard_object(db: Arc<Database>, file: &MediaFile, object: &str) {
+    let bucket = bucket_name(file.media_type);
+    if file.secret {
+        if let Err(err) = remove_object(&bucket, object).await {
+            log::error!("Error removing the object `{object}`: {err}");
+        }
+        return;
+    }
+
+    if let Err(e) = release_blob(db, &bucket, &file.hash.to_lowercase()).await {
+        log::error!("Error releasing the blob of `{object}`: {e:?}");
+    }
+}
+
+/// Links the added media to the blob of its content, so the reference is released with it
+///
+/// Only the media that has been added is linked: another upload of the same UUID
+/// must not take over the link of the media
+pub async fn link_media(db: Arc<Database>, file: &MediaFile) -> Result<(), DbError> {
+    if file.secret {
+        return Ok(());
+    }
+    let (bucket, hash) = (bucket_name(file.media_type), file.hash.to_lowercase());
+
+    db.media.link_blob(&file.uuid, &bucket, &hash).await
+}
+
+/// Releases the blob of the media, returns `false` if the media has none
+pub async fn unlink_media(db: Arc<Database>, media_uuid: Uuid) -> Result<bool, DbError> {
+    let Some((bucket, hash)) = db.media.blob_of(&media_uuid).await? else {
+        return Ok(false);
+    };
+    release_blob(db.clone(), &bucket, &hash).await?;
+    db.media.unlink_blob(&media_uuid).await?;
+
+    Ok(true)
+}
+
+/// Removes the blobs without references and their objects
+///
+/// The references are changed only with compare-and-set,
+/// so a blob referenced again meanwhile is kept
+pub async fn collect_garbage(db: Arc<Database>) -> Result<usize, DbError> {
+    let blobs = db.media.unreferenced_blobs().await?;
+
+    let mut collected = 0;
+    for (bucket, hash, object) in blobs {
+        if !db.media.remove_unreferenced_blob(&bucket, &hash).await? {
+            continue;
+        }
+
+        match remove_object(&bucket, &object).await {
+            Ok(()) => collected += 1,
+            Err(err) => log::error!("Error removing the object `{object}`: {err}"),
+        }
+    }
+
+    Ok(collected)
+}
+
+/// Runs the garbage collection of the blobs in the background
+pub fn spawn_blob_gc(db: Arc<Database>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            match collect_garbage(db.clone()).await {
+                Ok(0) => (),
+                Ok(collected) => log::info!("Removed {collected} unreferenced blob(s)"),
+                Err(e) => log::error!("Error collecting the blobs: {e:?}"),
+            }
+        }
+    });
+}
+
+/// Records the object as the blob of the content with a single reference,
+/// or takes a reference to the blob stored meanwhile
+async fn register_blob(
+    db: Arc<Database>,
+    bucket: &str,
+    hash: &str,
+    object: &str,
+    size: usize,
+) -> Result<Blob, DbError> {
+    for _ in 0..CAS_ATTEMPTS {
+        let blob = Blob {
+            object: object.to_owned(),
+            size,
+            refs: 1,
+        };
+        if db.media.add_blob(bucket, hash, &blob).await? {
+            return Ok(blob);
+        }
+
+        match acquire_blob(db.clone(), bucket, hash).await {
+            // collected between the two queries
+            Err(DbError::NotFound) => continue,
+            result => return result,
+        }
+    }
+
+    Err(DbError::FailedToAdd)
+}
+
+/// Adds a reference to the blob, `NotFound` if there is no blob anymore
+async fn acquire_blob(db: Arc<Database>, bucket: &str, hash: &str) -> Result<Blob, DbError> {
+    change_refs(db, bucket, hash, 1).await
+}
+
+/// Removes a reference of the blob, the unreferenced ones are left to `collect_garbage`
+async fn release_blob(db: Arc<Database>, bucket: &str, hash: &str) -> Result<(), DbError> {
+    match change_refs(db, bucket, hash, -1).await {
+        Ok(_) | Err(DbError::NotFound) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Changes the references with compare-and-set, returns the updated blob
+async fn change_refs(
+    db: Arc<Database>,
+    bucket: &str,
+    hash: &str,
+    delta: i32,
+) -> Result<Blob, DbError> {
+    for _ in 0..CAS_ATTEMPTS {
+        let mut blob = db.media.blob(bucket, hash).await?;
+        let refs = (blob.refs + delta).max(0);
+
+        if db
+            .media
+            .set_blob_refs(bucket, hash, refs, blob.refs)
+            .await?
+        {
+            blob.refs = refs;
+            return Ok(blob);
+        }
+    }
+
+    Err(DbError::FailedToUpdate)
+}
+
+/// Checks that the user has sent or received a media pointing to the object
+async fn can_access(db: Arc<Database>, object: &str, user_uuid: Uuid) -> Result<bool, DbError> {
+    let media = db.media.with_path(object).await?;
+
+    if media.iter().any(|media| media.sender == user_uuid) {
+        return Ok(true);
+    }
+    for media in media {
+        if db.messages.is_attached_for(&media.uuid, &user_uuid).await? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
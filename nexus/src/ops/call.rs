@@ -1,233 +1,413 @@
 use std::{error::Error, sync::Arc};
 
-use chrono::Duration;
+use chrono::Utc;
 use nexuslib::{
-    models::call::media_call::MediaCall,
-    request::{call::CallRequest, index_token::IndexToken, Request},
-};
-use scylla::{
-    frame::value::Timestamp, prepared_statement::PreparedStatement, QueryResult, Session,
+    models::call::{
+        media_call::MediaCall,
+        relay::RelayTicket,
+        state::{CallSide, CallState},
+    },
+    request::{call::CallRequest, index_token::IndexToken, sides::RequestSidesOpt, Request},
+    response::{Response, ResponseStatus},
 };
 use tokio::sync::Mutex;
 use uuid::Uuid;
 
-use crate::{errors::db::DbError, state::connection::ConnectionState};
+use crate::{
+    db::Database,
+    errors::{call::CallError, db::DbError},
+    state::{call::ActiveCall, connection::ConnectionState, relay::RelayAllocation},
+};
+
+/// Time in seconds after which an unanswered call is ended
+const RINGING_TIMEOUT: u64 = 30;
 
+/// Handles the call signaling
+///
+/// The server keeps the state of every active call and moves it only by
+/// the legal transitions, the copy of the call sent by a client is not trusted.
+/// An illegal request is answered with an error to the session that sent it
 pub async fn connect_call(
     call: String,
-    session: Arc<Mutex<scylla::Session>>,
+    db: Arc<Database>,
     state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
     peer_uuid: Uuid,
 ) -> Result<(), Box<dyn Error>> {
-    let mut call_request: Request<CallRequest<MediaCall>> = serde_json::from_str(&call).unwrap();
-
-    println!("{:#?}", call_request);
-
-    // extract the call
-    // let mut call = call_request.body.call;
-    // check if the call is secret
-    if !call_request.body.call.secret {
-        // if it is not a secret and
-        if call_request.body.index == IndexToken::Start {
-            // if this is an initial call request
-            // => add to the DB
-            if add_call(session, &call_request.body.call).await.is_err() {
-                log::error!("Error adding call to the DB!");
-            }
-        } else if call_request.body.index == IndexToken::Accept
-            || call_request.body.index == IndexToken::End
-        {
-            // if this is a cancel call request
-            // => update the call entry in the DB
-            if update_call(session, &call_request.body.call).await.is_err() {
-                log::error!("Error updating call in the DB!");
-            }
+    let call_request: Request<CallRequest<MediaCall>> = serde_json::from_str(&call)?;
+    let call_request = call_request.body;
+
+    log::debug!(
+        "connect_call: {:?} for {} from {user_uuid}",
+        call_request.index,
+        call_request.call.uuid
+    );
+
+    let result = match call_request.index {
+        IndexToken::Start => {
+            start_call(call_request, db, state.clone(), user_uuid, peer_uuid).await
+        }
+        IndexToken::Accepted | IndexToken::Timeout | IndexToken::Missed => {
+            Err(CallError::ServerOnly(call_request.index))
         }
+        _ => advance_call(call_request, db, state.clone(), user_uuid, peer_uuid).await,
+    };
+
+    if let Err(e) = result {
+        log::debug!("connect_call: {e}");
+        let response = serde_json::to_string(&Response::new(ResponseStatus::Err, e.to_string()))?;
+        notify_peer(&state, &user_uuid, &peer_uuid, &response).await;
     }
 
-    if call_request.body.index == IndexToken::Start {
-        call_request.body.call.peers.set_sender(peer_uuid);
-    } else if call_request.body.index == IndexToken::Accept {
-        call_request.body.call.peers.set_receiver(peer_uuid);
+    Ok(())
+}
+
+/// Ends the calls of the session that was closed
+pub async fn drop_peer_calls(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) {
+    let dropped = {
+        let mut state = state.lock().await;
+        let calls = state
+            .calls
+            .values()
+            .filter(|call| call.has_peer(&peer_uuid))
+            .map(|call| call.call.uuid)
+            .collect::<Vec<_>>();
+
+        calls
+            .into_iter()
+            .filter_map(|uuid| state.end_call(&uuid))
+            .collect::<Vec<_>>()
+    };
+
+    for mut active in dropped {
+        let side = active.side_of(&user_uuid).unwrap_or(CallSide::Caller);
+        if let Some(next) = active.state.next(IndexToken::End, side) {
+            active.state = next;
+        }
+        finish_call(db.clone(), &state, &active).await;
+        notify_sides(
+            &state,
+            &CallRequest::new(active.call, IndexToken::End),
+            None,
+        )
+        .await;
     }
-    let call_str = serde_json::to_string(&call_request.body).unwrap();
+}
 
-    match call_request.body.index {
-        // if index message is START => notify all receiver sessions
-        IndexToken::Start => {
-            // notify all receiver sessions
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_receiver())
-                .unwrap()
-                .iter_mut()
-            {
-                // sending the call to the receiver sessions
-                let _ = peer.1.tcp_sender.send(call_str.clone());
-            }
+/// Starts ringing, or answers `Busy` if the callee is in another call
+///
+/// The caller can not start a call while it takes part in another one
+async fn start_call(
+    mut request: CallRequest<MediaCall>,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), CallError> {
+    let sender = request.call.sides.get_sender();
+    let receiver = request.call.sides.get_receiver();
+    if sender != user_uuid || receiver == user_uuid {
+        return Err(CallError::NotParticipant);
+    }
 
-            // notify all sender sessions except the one that had sent the message
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_sender())
-                .unwrap()
-                .iter_mut()
-            {
-                if peer.0 != &peer_uuid {
-                    // sending the call to the sender session
-                    let _ = peer.1.tcp_sender.send(call_str.clone());
-                }
-            }
+    request.call.accepted = false;
+    request.call.peers = RequestSidesOpt::new();
+    request.call.peers.set_sender(peer_uuid);
+
+    let busy = {
+        let mut state = state.lock().await;
+        if state.calls.contains_key(&request.call.uuid) {
+            return Err(CallError::AlreadyExists);
+        }
+        if state.in_call(&sender) {
+            return Err(CallError::InAnotherCall);
         }
-        // if index message is ACCEPT => notify the receiver session and other
-        // as receiver as also the other sender sessions that the call is accepted
-        IndexToken::Accept => {
-            // notify the sender sessions
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_sender())
-                .unwrap()
-                .iter_mut()
-            {
-                if *peer.0 != call_request.body.call.peers.get_sender().unwrap() {
-                    // sending the call to the other sender sessions
-                    call_request.body.index = IndexToken::Accepted;
-                    let call_str = serde_json::to_string(&call_request.body).unwrap();
-                    let _ = peer.1.tcp_sender.send(call_str.clone());
-                } else {
-                    // sending the call to the sender session
-                    let _ = peer.1.tcp_sender.send(call_str.clone());
-                }
-            }
 
-            // notify the other receiver sessions
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_receiver())
-                .unwrap()
-                .iter_mut()
-            {
-                // sending the call to the other receiver sessions
-                if *peer.0 != call_request.body.call.peers.get_receiver().unwrap() {
-                    call_request.body.index = IndexToken::Accepted;
-                    let call_str = serde_json::to_string(&call_request.body).unwrap();
-                    let _ = peer.1.tcp_sender.send(call_str.clone());
-                }
-            }
+        let busy = state.in_call(&receiver);
+        if !busy {
+            state.calls.insert(
+                request.call.uuid,
+                ActiveCall::new(request.call.clone(), peer_uuid),
+            );
         }
-        // server never receives such index message
-        // if the call is ended from any side => notify everyone
-        IndexToken::End => {
-            // notify the sender sessions
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_sender())
-                .unwrap()
-                .iter_mut()
-            {
-                // sending the call to the sender session
-                let _ = peer.1.tcp_sender.send(call_str.clone());
-            }
+        busy
+    };
 
-            // notify the other receiver sessions
-            for peer in state
-                .lock()
-                .await
-                .peers
-                .get_mut(&call_request.body.call.sides.get_receiver())
-                .unwrap()
-                .iter_mut()
-            {
-                let _ = peer.1.tcp_sender.send(call_str.clone());
-            }
+    if busy {
+        let mut active = ActiveCall::new(request.call.clone(), peer_uuid);
+        active.state = CallState::Busy;
+        if !active.call.secret && db.calls.add(&active.call, active.state).await.is_err() {
+            log::error!("Error adding call to the DB!");
         }
-        // includes IndexToken::Accepted
-        // server sends it itself hence it will never receive this token
-        _ => (),
+
+        request.index = IndexToken::Busy;
+        let call_str = serde_json::to_string(&request).unwrap();
+        notify_peer(&state, &sender, &peer_uuid, &call_str).await;
+        return Ok(());
     }
 
+    if !request.call.secret
+        && db
+            .calls
+            .add(&request.call, CallState::Ringing)
+            .await
+            .is_err()
+    {
+        log::error!("Error adding call to the DB!");
+    }
+
+    // notify all receiver sessions and the other sender sessions
+    let call_str = serde_json::to_string(&request).unwrap();
+    notify_user(&state, &receiver, None, &call_str).await;
+    notify_user(&state, &sender, Some(&peer_uuid), &call_str).await;
+
+    ring_timeout(request.call.uuid, db, state);
+
     Ok(())
 }
 
-/// Adds a call to the DB
-pub async fn add_call(
-    session: Arc<Mutex<Session>>,
-    call: &MediaCall,
-) -> Result<QueryResult, DbError> {
-    let prepared: PreparedStatement = session
-        .lock()
-        .await
-        .prepare(
-            "INSERT INTO nexus.calls (uuid, sender, receiver, duration, accepted, secret, created_at) VALUES(?, ?, ?, ?, ?, ?, ?, ?);",
-        )
-        .await
-        .unwrap();
+/// Moves the call to the next state by the token
+async fn advance_call(
+    request: CallRequest<MediaCall>,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), CallError> {
+    let token = request.index;
 
-    match session
-        .lock()
-        .await
-        .execute(
-            &prepared,
-            (
-                call.uuid,
-                call.sides.get_sender(),
-                call.sides.get_receiver(),
-                0i64,
-                false,
-                call.secret,
-                Timestamp(Duration::try_seconds(call.get_created_at().timestamp()).unwrap()),
-            ),
+    let active = {
+        let mut state = state.lock().await;
+        let active = state
+            .calls
+            .get_mut(&request.call.uuid)
+            .ok_or(CallError::NotFound)?;
+
+        let side = active
+            .side_of(&user_uuid)
+            .ok_or(CallError::NotParticipant)?;
+        let next = active
+            .state
+            .next(token, side)
+            .ok_or(CallError::IllegalTransition {
+                state: active.state,
+                token,
+            })?;
+
+        active.state = next;
+        if next == CallState::Accepted {
+            active.callee_peer = Some(peer_uuid);
+            active.accepted_at = Some(Utc::now().timestamp());
+            active.call.accepted = true;
+            active.call.peers.set_receiver(peer_uuid);
+        }
+
+        if next.is_terminal() {
+            state.end_call(&request.call.uuid).unwrap()
+        } else {
+            let active = active.clone();
+            // the relay is allocated once, so the bound participants keep their tokens
+            if next == CallState::Accepted {
+                let users = [
+                    active.call.sides.get_sender(),
+                    active.call.sides.get_receiver(),
+                ];
+                state
+                    .relays
+                    .entry(active.call.uuid)
+                    .or_insert_with(|| RelayAllocation::new(active.call.uuid, &users));
+            }
+            active
+        }
+    };
+
+    if active.state.is_terminal() {
+        finish_call(db, &state, &active).await;
+        notify_sides(&state, &CallRequest::new(active.call, token), None).await;
+        return Ok(());
+    }
+
+    // ACCEPT => the caller session gets `Accept`, the other sessions
+    // of both sides get `Accepted`, so they stop ringing.
+    // The sessions that take part in the call get their relay tickets
+    let sender = active.call.sides.get_sender();
+    let (sender_token, receiver_token) = {
+        let state = state.lock().await;
+        let relay = state.relays.get(&active.call.uuid);
+        (
+            relay.and_then(|relay| relay.token_of(&sender)),
+            relay.and_then(|relay| relay.token_of(&user_uuid)),
         )
+    };
+
+    // the caller checks the candidates of the callee before it falls back to the relay
+    let mut accept = CallRequest::new(active.call.clone(), token).with_ice(request.ice.clone());
+    if let Some(token) = sender_token {
+        accept = accept.with_relay(RelayTicket::new(active.call.uuid, token));
+    }
+    let accept_str = serde_json::to_string(&accept).unwrap();
+    notify_peer(&state, &sender, &active.caller_peer, &accept_str).await;
+
+    let mut accepted = CallRequest::new(active.call.clone(), IndexToken::Accepted);
+    if let Some(token) = receiver_token {
+        accepted = accepted.with_relay(RelayTicket::new(active.call.uuid, token));
+    }
+    let accepted_str = serde_json::to_string(&accepted).unwrap();
+    notify_peer(&state, &user_uuid, &peer_uuid, &accepted_str).await;
+
+    let accepted = CallRequest::new(active.call.clone(), IndexToken::Accepted);
+    let except = [active.caller_peer, peer_uuid];
+    notify_sides(&state, &accepted, Some(&except)).await;
+
+    Ok(())
+}
+
+/// Ends the call if it is still ringing after `RINGING_TIMEOUT`
+fn ring_timeout(call_uuid: Uuid, db: Arc<Database>, state: Arc<Mutex<ConnectionState>>) {
+    tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_secs(RINGING_TIMEOUT)).await;
+
+        let active = {
+            let mut state = state.lock().await;
+            match state.calls.get(&call_uuid) {
+                Some(active) if active.state == CallState::Ringing => state.end_call(&call_uuid),
+                _ => None,
+            }
+        };
+
+        if let Some(mut active) = active {
+            active.state = CallState::TimedOut;
+            finish_call(db, &state, &active).await;
+            notify_sides(
+                &state,
+                &CallRequest::new(active.call, IndexToken::Timeout),
+                None,
+            )
+            .await;
+        }
+    });
+}
+
+/// Records the final state of the call
+///
+/// A missed call is queued for the callee if none of its sessions is online
+async fn finish_call(db: Arc<Database>, state: &Arc<Mutex<ConnectionState>>, active: &ActiveCall) {
+    if active.call.secret {
+        return;
+    }
+
+    let duration = active.duration(Utc::now().timestamp());
+    if db
+        .calls
+        .update(&active.call, active.state, duration)
         .await
+        .is_err()
     {
-        Ok(result) => Ok(result),
-        Err(_e) => {
-            log::error!("{_e:?}");
-            Err(DbError::FailedToAdd)
+        log::error!("Error updating call in the DB!");
+    }
+    if let Some(stats) = &active.stats {
+        if db.calls.add_stats(stats).await.is_err() {
+            log::error!("Error adding call stats to the DB!");
         }
     }
-}
 
-/// Updates the call (duration, accepted) in the DB
-pub async fn update_call(
-    session: Arc<Mutex<Session>>,
-    call: &MediaCall,
-) -> Result<QueryResult, DbError> {
-    let prepared: PreparedStatement = session
+    if !active.state.is_missed() {
+        return;
+    }
+    let receiver = active.call.sides.get_receiver();
+    let online = state
         .lock()
         .await
-        .prepare("UPDATE nexus.calls SET duration = ?, accepted = ? WHERE uuid = ? AND created_at = ? IF EXISTS;")
-        .await
-        .unwrap();
+        .peers
+        .get(&receiver)
+        .is_some_and(|sessions| !sessions.is_empty());
+    if !online && queue_missed_call(db, &active.call).await.is_err() {
+        log::error!("Error queueing missed call in the DB!");
+    }
+}
 
-    match session
+/// Sends the calls missed while the user was offline to the session
+/// that has connected, the delivered calls are removed from the queue
+pub async fn deliver_missed_calls(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), DbError> {
+    for call in db.calls.missed(&user_uuid).await? {
+        let request = CallRequest::new(call, IndexToken::Missed);
+        let call_str = serde_json::to_string(&request).unwrap();
+        notify_peer(&state, &user_uuid, &peer_uuid, &call_str).await;
+
+        db.calls.remove_missed(&user_uuid, &request.call).await?;
+    }
+
+    Ok(())
+}
+
+/// Sends the call to all sessions of both sides except the given ones
+async fn notify_sides(
+    state: &Arc<Mutex<ConnectionState>>,
+    request: &CallRequest<MediaCall>,
+    except: Option<&[Uuid]>,
+) {
+    let call_str = serde_json::to_string(request).unwrap();
+    let sides = [
+        request.call.sides.get_sender(),
+        request.call.sides.get_receiver(),
+    ];
+
+    let state = state.lock().await;
+    for user in sides {
+        if let Some(sessions) = state.peers.get(&user) {
+            for (peer, socket) in sessions.iter() {
+                if except.is_none_or(|except| !except.contains(peer)) {
+                    let _ = socket.tcp_sender.send(call_str.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Sends the message to all sessions of the user except the given one
+async fn notify_user(
+    state: &Arc<Mutex<ConnectionState>>,
+    user: &Uuid,
+    except: Option<&Uuid>,
+    msg: &str,
+) {
+    if let Some(sessions) = state.lock().await.peers.get(user) {
+        for (peer, socket) in sessions.iter() {
+            if Some(peer) != except {
+                let _ = socket.tcp_sender.send(msg.to_owned());
+            }
+        }
+    }
+}
+
+/// Sends the message to a single session of the user
+async fn notify_peer(state: &Arc<Mutex<ConnectionState>>, user: &Uuid, peer: &Uuid, msg: &str) {
+    if let Some(socket) = state
         .lock()
         .await
-        .execute(
-            &prepared,
-            (
-                call.duration(),
-                call.accepted,
-                call.uuid,
-                Timestamp(Duration::try_seconds(call.get_created_at().timestamp()).unwrap()),
-            ),
-        )
-        .await
+        .peers
+        .get(user)
+        .and_then(|sessions| sessions.get(peer))
     {
-        Ok(result) => Ok(result),
-        Err(_e) => {
-            log::error!("{_e:?}");
-            Err(DbError::FailedToUpdate)
-        }
+        let _ = socket.tcp_sender.send(msg.to_owned());
     }
 }
+
+/// Queues the missed call for the callee, without the media key
+async fn queue_missed_call(db: Arc<Database>, call: &MediaCall) -> Result<(), DbError> {
+    let mut call = call.clone();
+    call.message.clear();
+    call.nonce.clear();
+
+    db.calls.queue_missed(&call).await
+}
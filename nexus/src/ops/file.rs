@@ -1,146 +1,386 @@
-use std::{error::Error, fs, path::Path, sync::Arc};
+use std::{error::Error, io::SeekFrom, path::Path, sync::Arc};
 
-use chrono::Duration;
-use futures::StreamExt;
-use scylla::{
-    frame::value::Timestamp, prepared_statement::PreparedStatement, QueryResult, Session,
+use chrono::Utc;
+use tokio::{
+    fs,
+    io::{AsyncSeekExt, AsyncWriteExt},
+    sync::Mutex,
 };
-use tokio::{io::AsyncWriteExt, net::TcpStream, sync::Mutex};
-use tokio_util::codec::{BytesCodec, Framed};
 use uuid::Uuid;
 
 use nexuslib::{
-    models::file::{media_file::MediaFile, FileContent},
-    request::{file::FileRequest, Request},
+    errors::file::UploadError,
+    models::file::{format::SNIFF_LEN, media_file::MediaFile, upload::UploadProgress},
+    request::{file::FileRequest, index_token::FileToken, Request},
+    response::{Response, ResponseStatus},
 };
 
-use crate::{errors::db::DbError, state::connection::ConnectionState};
+use crate::{
+    db::{repository::MediaEntry, Database},
+    errors::{db::DbError, file::FileError},
+    ops::blob::{discard_object, link_media, reuse_blob, share_object},
+    processing::spawn_previews,
+    state::{connection::ConnectionState, upload::ActiveUpload},
+    storage::{
+        bucket_name,
+        policy::{storage_quota, upload_policy},
+        put_object_file, remove_object, upload_dir,
+    },
+};
+
+/// Time in seconds after which an upload without requests is removed
+const UPLOAD_TTL: i64 = 24 * 60 * 60;
 
-pub async fn stream_file(
-    stream: TcpStream,
+/// Handles the chunked upload of a file
+///
+/// The upload is started with the description of the file, then the chunks
+/// are sent in order and each one is acknowledged with the index of the next one.
+/// `Start` with the id of an upload resumes it, so an interrupted upload
+/// goes on from the last acknowledged chunk.
+/// The file is stored once `Commit` has verified its hash
+pub async fn upload_file(
     file: String,
-    session: Arc<Mutex<scylla::Session>>,
+    db: Arc<Database>,
     state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
     peer_uuid: Uuid,
-) -> Result<Framed<TcpStream, BytesCodec>, Box<dyn Error>> {
-    let file_request: Request<FileRequest> = serde_json::from_str(&file).unwrap();
+) -> Result<(), Box<dyn Error>> {
+    let file_request: Request<FileRequest> = serde_json::from_str(&file)?;
+    let file_request = file_request.body;
+
+    log::debug!(
+        "upload_file: {:?} for {:?} from {user_uuid}",
+        file_request.index,
+        file_request.upload
+    );
+
+    let upload = file_request.upload;
+    let result = match (file_request.index, file_request.upload) {
+        (FileToken::Start, Some(upload)) => resume_upload(upload, &state, user_uuid).await,
+        (FileToken::Start, None) => start_upload(file_request, db, &state, user_uuid).await,
+        (FileToken::Chunk, _) => store_chunk(file_request, &state, user_uuid).await,
+        (FileToken::Commit, _) => commit_upload(file_request, db, &state, user_uuid).await,
+        (token, _) => Err(FileError::ServerOnly(token)),
+    };
+
+    let response = match result {
+        Ok(response) => serde_json::to_string(&response)?,
+        // the client gets the reason and does not retry
+        Err(FileError::Rejected(e)) => {
+            log::debug!("upload_file: rejected, {e}");
+            serde_json::to_string(&FileRequest::new(FileToken::Rejected, upload).with_error(e))?
+        }
+        Err(e) => {
+            log::debug!("upload_file: {e}");
+            serde_json::to_string(&Response::new(ResponseStatus::Err, e.to_string()))?
+        }
+    };
+    notify_peer(&state, &user_uuid, &peer_uuid, &response).await;
+
+    Ok(())
+}
+
+/// Checks the description of the file and creates its part file
+///
+/// The file is committed at once if the same content is already stored
+async fn start_upload(
+    request: FileRequest,
+    db: Arc<Database>,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+) -> Result<FileRequest, FileError> {
+    let file = request.file.ok_or(FileError::Missing("file"))?;
+    if file.sender != user_uuid {
+        return Err(FileError::NotOwner);
+    }
+    let progress = UploadProgress::new(file).map_err(FileError::Rejected)?;
+    upload_policy(progress.file.media_type)
+        .check_size(&progress.file)
+        .map_err(FileError::Rejected)?;
+    check_quota(db.clone(), user_uuid, progress.file.len_bytes).await?;
+    check_unused(db.clone(), &progress.file.uuid).await?;
+
+    match reuse_blob(db.clone(), &progress.file, user_uuid).await {
+        Ok(Some(object)) => {
+            spawn_previews(db, &progress.file, &object);
+            return Ok(FileRequest::new(FileToken::Committed, None).with_file(progress.file));
+        }
+        Ok(None) => (),
+        Err(e) => {
+            log::error!("Error reusing the stored content: {e:?}");
+            return Err(FileError::Storage);
+        }
+    }
+
+    let upload = Uuid::new_v4();
+    let dir = upload_dir();
+    fs::create_dir_all(dir)
+        .await
+        .map_err(|_| FileError::Storage)?;
+    let path = dir.join(format!("{upload}.part"));
+    fs::File::create(&path)
+        .await
+        .map_err(|_| FileError::Storage)?;
 
-    let mut bytes = Framed::new(stream, BytesCodec::new());
+    let now = Utc::now().timestamp();
+    let stale = {
+        let mut state = state.lock().await;
+        let stale = state
+            .uploads
+            .iter()
+            .filter(|(_, active)| now - active.updated_at > UPLOAD_TTL)
+            .map(|(upload, _)| *upload)
+            .collect::<Vec<_>>();
 
-    let file = file_request.body.file;
-    let filename_split = file.name.split('.').collect::<Vec<&str>>();
-    let ext = filename_split[filename_split.len() - 1];
-    let object_name = file.uuid.to_string() + "." + ext;
-    let filename = Path::new(&std::env::var("STORAGE_MEDIA").unwrap()).join(&object_name);
+        state
+            .uploads
+            .insert(upload, ActiveUpload::new(user_uuid, progress, path, now));
+        stale
+            .iter()
+            .filter_map(|upload| state.uploads.remove(upload))
+            .collect::<Vec<_>>()
+    };
+    for active in stale {
+        let _ = fs::remove_file(&active.path).await;
+    }
 
-    fs::create_dir_all(Path::new(&std::env::var("STORAGE_MEDIA").unwrap())).unwrap();
+    Ok(FileRequest::new(FileToken::Ack, Some(upload)).with_next_chunk(0))
+}
 
-    let mut written_file = tokio::fs::File::create(&filename).await.unwrap();
+/// Answers with the chunk the upload expects next
+async fn resume_upload(
+    upload: Uuid,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+) -> Result<FileRequest, FileError> {
+    let mut state = state.lock().await;
+    let active = owned_upload(&mut state, &upload, &user_uuid)?;
+    active.updated_at = Utc::now().timestamp();
+
+    Ok(
+        FileRequest::new(FileToken::Ack, Some(upload))
+            .with_next_chunk(active.progress.next_chunk()),
+    )
+}
 
-    while let Some(result) = bytes.next().await {
-        match result {
-            Ok(chunk) => {
-                if let Err(e) = written_file.write_all(&chunk).await {
-                    eprintln!("Error writing to file: {:?}", e);
-                    break;
+/// Verifies the chunk and writes it to the part file
+async fn store_chunk(
+    request: FileRequest,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+) -> Result<FileRequest, FileError> {
+    let upload = request.upload.ok_or(FileError::Missing("upload"))?;
+    let chunk = request.chunk.ok_or(FileError::Missing("chunk"))?;
+
+    let (path, next_chunk) = {
+        let mut state = state.lock().await;
+        let active = owned_upload(&mut state, &upload, &user_uuid)?;
+        active.updated_at = Utc::now().timestamp();
+
+        match active.progress.accept(&chunk) {
+            // the content is known from the first chunk
+            Ok(()) if chunk.index == 0 => {
+                let file = &active.progress.file;
+                let head = &chunk.data[..chunk.data.len().min(SNIFF_LEN)];
+                match upload_policy(file.media_type).check_content(file, head) {
+                    Ok(_) => (active.path.clone(), active.progress.next_chunk()),
+                    Err(e) => {
+                        let removed = state.uploads.remove(&upload).unwrap();
+                        drop(state);
+                        let _ = fs::remove_file(&removed.path).await;
+                        return Err(FileError::Rejected(e));
+                    }
                 }
             }
-            Err(e) => {
-                eprintln!("Error reading from stream: {}", e);
-                break;
+            Ok(()) => (active.path.clone(), active.progress.next_chunk()),
+            // the chunk was sent again since its ack was lost => the sender goes on
+            Err(UploadError::OutOfOrder { expected }) if chunk.index < expected => {
+                return Ok(FileRequest::new(FileToken::Ack, Some(upload)).with_next_chunk(expected));
             }
+            Err(e) => return Err(e.into()),
+        }
+    };
+
+    // the progress has already moved on => the upload can not go on without the chunk
+    if let Err(e) = write_chunk(&path, chunk.offset(), &chunk.data).await {
+        log::error!("Failed to write the chunk of the upload {upload}: {e}");
+        let removed = state.lock().await.uploads.remove(&upload);
+        if let Some(active) = removed {
+            let _ = fs::remove_file(&active.path).await;
         }
+        return Err(FileError::Storage);
     }
 
-    // storage
-    //     .upload_object(
-    //         &mut UploadObjectArgs::new(
-    //             &file.media_type.to_string().to_lowercase(),
-    //             &object_name,
-    //             &filename.to_str().unwrap(),
-    //         )
-    //         .unwrap(),
-    //     )
-    //     .await
-    //     .unwrap();
-
-    if add_file(session, &file, &object_name, "", peer_uuid)
-        .await
-        .is_err()
-    {
-        log::error!("Error adding message to the DB!");
+    Ok(FileRequest::new(FileToken::Ack, Some(upload)).with_next_chunk(next_chunk))
+}
+
+/// Verifies the whole file, uploads it to the bucket of its type and adds it to the DB
+async fn commit_upload(
+    request: FileRequest,
+    db: Arc<Database>,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+) -> Result<FileRequest, FileError> {
+    let upload = request.upload.ok_or(FileError::Missing("upload"))?;
+
+    let (active, verified) = {
+        let mut state = state.lock().await;
+        let verified = owned_upload(&mut state, &upload, &user_uuid)?
+            .progress
+            .finish();
+
+        match verified {
+            // the missing chunks can still be sent
+            Err(UploadError::Incomplete) => return Err(UploadError::Incomplete.into()),
+            _ => (state.uploads.remove(&upload).unwrap(), verified),
+        }
+    };
+
+    if let Err(e) = verified {
+        let _ = fs::remove_file(&active.path).await;
+        return Err(FileError::Rejected(e));
     }
 
-    // check if the file is secret
-    // if !file_request.body.file.secret {
-    //     // if it is not a secret and
-    //     if file_request.body.index == IndexToken::Start {
-    //         // if this is an initial file request
-    //         // => add to the DB
-    //         if add_file(session, &file_request.body.file).await.is_err() {
-    //             log::error!("Error adding file to the DB!");
-    //         }
-    //     }
-    // }
-
-    for peer in state
+    let file = active.progress.file;
+    // the UUID may have been taken by another upload meanwhile
+    if let Err(e) = check_unused(db.clone(), &file.uuid).await {
+        let _ = fs::remove_file(&active.path).await;
+        return Err(e);
+    }
+    let object_name = object_name(&file, &upload);
+    let bucket = bucket_name(file.media_type);
+
+    let stored = put_object_file(&bucket, &object_name, &active.path).await;
+    let _ = fs::remove_file(&active.path).await;
+    if let Err(e) = stored {
+        log::error!("Failed to store the upload {upload}: {e}");
+        return Err(FileError::Storage);
+    }
+
+    // the same content may have been committed meanwhile
+    let object_name = match share_object(db.clone(), &file, &object_name).await {
+        Ok(object) => object,
+        Err(e) => {
+            log::error!("Error sharing the upload {upload}: {e:?}");
+            let _ = remove_object(&bucket, &object_name).await;
+            return Err(FileError::Storage);
+        }
+    };
+
+    if let Err(e) = add_file(db.clone(), &file, &object_name, user_uuid).await {
+        log::error!("Error adding file to the DB: {e:?}");
+        discard_object(db, &file, &object_name).await;
+        return Err(match e {
+            DbError::AlreadyExists => FileError::Taken,
+            _ => FileError::Storage,
+        });
+    }
+    spawn_previews(db, &file, &object_name);
+
+    Ok(FileRequest::new(FileToken::Committed, Some(upload)).with_file(file))
+}
+
+/// Returns the key of the upload in its bucket: the UUID of the upload with the extension of the name
+///
+/// Every upload has its own key, so a failed upload can not remove
+/// the object of another upload of the same media
+pub fn object_name(file: &MediaFile, upload: &Uuid) -> String {
+    let ext = Path::new(&file.name)
+        .extension()
+        .map(|ext| format!(".{}", ext.to_string_lossy()))
+        .unwrap_or_default();
+    format!("{upload}{ext}")
+}
+
+/// Returns the upload if it belongs to the user
+fn owned_upload<'a>(
+    state: &'a mut ConnectionState,
+    upload: &Uuid,
+    user_uuid: &Uuid,
+) -> Result<&'a mut ActiveUpload, FileError> {
+    let active = state.uploads.get_mut(upload).ok_or(FileError::NotFound)?;
+    match active.owner == *user_uuid {
+        true => Ok(active),
+        false => Err(FileError::NotOwner),
+    }
+}
+
+/// Writes the chunk at its offset, so the order of the writes does not matter
+async fn write_chunk(path: &Path, offset: u64, data: &[u8]) -> std::io::Result<()> {
+    let mut file = fs::OpenOptions::new().write(true).open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    file.write_all(data).await?;
+    file.flush().await
+}
+
+/// Sends the message to a single session of the user
+async fn notify_peer(state: &Arc<Mutex<ConnectionState>>, user: &Uuid, peer: &Uuid, msg: &str) {
+    if let Some(socket) = state
         .lock()
         .await
         .peers
-        .get_mut(&file.sender)
-        .unwrap()
-        .iter_mut()
+        .get(user)
+        .and_then(|sessions| sessions.get(peer))
     {
-        if peer.0 == &peer_uuid {
-            // sending the call to the sender session
-            // let _ = peer.1.tcp_sender.send(call_str.clone());
-        }
+        let _ = socket.tcp_sender.send(msg.to_owned());
     }
-
-    Ok(bytes)
 }
 
 /// Add file to the DB
+///
+/// `path` is the key of the object in the bucket of the file type,
+/// the media is linked to the blob of its content once it is added
 pub async fn add_file(
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     file: &MediaFile,
-    filename: &str,
-    filepath: &str,
+    path: &str,
     sender: Uuid,
-) -> Result<QueryResult, DbError> {
-    let prepared: PreparedStatement = session
-        .lock()
-        .await
-        .prepare(
-            "
-            INSERT INTO nexus.media 
-            (uuid, name, path, sender, type, created_at) 
-            VALUES(?, ?, ?, ?, ?, ?);
-        ",
-        )
-        .await
-        .unwrap();
+) -> Result<(), DbError> {
+    let media = MediaEntry {
+        uuid: file.uuid,
+        name: file.name.clone(),
+        path: path.to_owned(),
+        media_type: file.media_type,
+        sender,
+    };
+    db.media
+        .add(&media, file.get_created_at().timestamp())
+        .await?;
 
-    match session
-        .lock()
-        .await
-        .execute(
-            &prepared,
-            (
-                file.uuid,
-                filename,
-                filepath,
-                sender,
-                file.get_type().unwrap().get_index() as i8,
-                Timestamp(Duration::try_seconds(file.get_created_at().timestamp()).unwrap()),
-            ),
-        )
+    // without the link the blob is never collected, which is safer than releasing it
+    if let Err(e) = link_media(db.clone(), file).await {
+        log::error!("Error linking the media {} to its blob: {e:?}", file.uuid);
+    }
+    // the usage is only informative, the file is already stored
+    if let Err(e) = db.media.add_usage(&sender, file.len_bytes).await {
+        log::error!("Error updating the storage usage of {sender}: {e:?}");
+    }
+    Ok(())
+}
+
+/// Checks that no media has the UUID, so the upload can not take over another user's media
+async fn check_unused(db: Arc<Database>, media_uuid: &Uuid) -> Result<(), FileError> {
+    match db.media.get(media_uuid).await {
+        Err(DbError::NotFound) => Ok(()),
+        Ok(_) => Err(FileError::Taken),
+        Err(_) => Err(FileError::Storage),
+    }
+}
+
+/// Checks that the file fits into the storage quota of the user
+pub async fn check_quota(
+    db: Arc<Database>,
+    user_uuid: Uuid,
+    len_bytes: usize,
+) -> Result<(), FileError> {
+    let used = db
+        .media
+        .usage(&user_uuid)
         .await
-    {
-        Ok(result) => Ok(result),
-        Err(_e) => {
-            log::debug!("{_e:?}");
-            Err(DbError::FailedToAdd)
-        }
+        .map_err(|_| FileError::Storage)?;
+
+    match used + len_bytes as u64 > storage_quota() {
+        true => Err(FileError::Rejected(UploadError::QuotaExceeded)),
+        false => Ok(()),
     }
 }
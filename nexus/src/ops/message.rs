@@ -1,41 +1,64 @@
-use std::{error::Error, fmt::Debug, sync::Arc};
+use std::{error::Error, sync::Arc};
 
-use chrono::Duration;
-use scylla::{
-    frame::value::Timestamp, prepared_statement::PreparedStatement, QueryResult, Session,
-};
 use tokio::sync::Mutex;
 
 use nexuslib::{
-    models::message::{text::TextMessage, MessageContent},
+    models::message::{
+        media::{Media, MediaType, MAX_ATTACHMENTS},
+        MessageBody, MessageContent,
+    },
     request::{message::MessageRequest, Request},
+    response::{Response, ResponseStatus},
     Message,
 };
 use uuid::Uuid;
 
-use crate::{errors::db::DbError, state::connection::ConnectionState};
+use crate::{
+    db::Database,
+    errors::{db::DbError, message::MessageError},
+    state::connection::ConnectionState,
+};
 
 /// Sends a message to other user
 ///
 /// Requires:
-/// - Session
+/// - Database
 /// - Message
+///
+/// The attachments have to be files uploaded by the sender,
+/// the receiver gets access to them with the message
 pub async fn send_message(
-    message: (String, Uuid),
-    session: Arc<Mutex<Session>>,
+    message: String,
+    db: Arc<Database>,
     state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
 ) -> Result<(), Box<dyn Error>> {
-    let (message, peer_uuid) = message;
-    let message: Request<MessageRequest<TextMessage>> = serde_json::from_str(&message).unwrap();
+    let message: Request<MessageRequest<MessageBody>> = serde_json::from_str(&message).unwrap();
 
     // when message arrives on the server, mark it as `sent`
     let mut message = message.body.message;
     message.status.set_sent();
 
+    if let Err(e) = attach_media(db.clone(), &mut message, user_uuid).await {
+        log::debug!("send_message: {e}");
+        let response = serde_json::to_string(&Response::new(ResponseStatus::Err, e.to_string()))?;
+        if let Some(socket) = state
+            .lock()
+            .await
+            .peers
+            .get(&user_uuid)
+            .and_then(|sessions| sessions.get(&peer_uuid))
+        {
+            let _ = socket.tcp_sender.send(response);
+        }
+        return Ok(());
+    }
+
     // checks if the message is not ment to be sent directly (secretly)
     if !message.secret {
         // add the message to the DB
-        if add_message(session, &message).await.is_err() {
+        if db.messages.add(&message).await.is_err() {
             log::error!("Error adding message to the DB!");
         }
     }
@@ -75,48 +98,82 @@ pub async fn send_message(
     Ok(())
 }
 
-/// Adds message to the DB
-pub async fn add_message<T: MessageContent + Debug>(
-    session: Arc<Mutex<Session>>,
-    message: &Message<T>,
-) -> Result<QueryResult, DbError> {
-    let prepared: PreparedStatement = session
-        .lock()
-        .await
-        .prepare(
-            "
-            INSERT INTO nexus.messages 
-            (uuid, text, nonce, media, sender, receiver, sent, read, edited, created_at) 
-            VALUES(?, ?, ?, ?, ?, ?, ?, ?, ?, ?);
-        ",
-        )
-        .await
-        .unwrap();
-
-    match session
-        .lock()
-        .await
-        .execute(
-            &prepared,
-            (
-                message.uuid,
-                message.content.get_text().unwrap(),
-                message.get_nonce(),
-                "".to_owned(), // UUIDs array of media (in a form of string, empy for now)
-                message.sides.get_sender(),
-                message.sides.get_receiver(),
-                message.status.get_sent(),
-                message.status.get_read(),
-                message.status.get_edited(),
-                Timestamp(Duration::try_seconds(message.get_created_at().timestamp()).unwrap()),
-            ),
-        )
-        .await
+/// Checks the attachments of the message and grants the receiver access to them
+///
+/// The name, the path and the type of each attachment are taken from the stored media.
+/// The audio of a voice note has to be one of the attachments
+async fn attach_media<T: MessageContent>(
+    db: Arc<Database>,
+    message: &mut Message<T>,
+    user_uuid: Uuid,
+) -> Result<(), MessageError> {
+    if message.sides.get_sender() != user_uuid {
+        return Err(MessageError::NotSender);
+    }
+    if message
+        .content
+        .get_voice()
+        .is_some_and(|voice| !voice.is_valid())
     {
-        Ok(result) => Ok(result),
-        Err(_e) => {
-            log::debug!("{_e:?}");
-            Err(DbError::FailedToAdd)
+        return Err(MessageError::InvalidVoiceNote);
+    }
+    let media = match message.media.as_mut() {
+        Some(media) if !media.attachments.is_empty() => media,
+        _ => {
+            message.media = None;
+            return match message.content.get_voice() {
+                Some(voice) => Err(MessageError::VoiceNotAttached(voice.media)),
+                None => Ok(()),
+            };
+        }
+    };
+    if media.attachments.len() > MAX_ATTACHMENTS {
+        return Err(MessageError::TooManyAttachments);
+    }
+
+    for attachment in media.attachments.iter_mut() {
+        let stored = match db.media.get(&attachment.uuid).await {
+            Ok(stored) => stored,
+            Err(DbError::NotFound) => {
+                return Err(MessageError::AttachmentNotFound(attachment.uuid))
+            }
+            Err(_) => return Err(MessageError::Attachments),
+        };
+        if stored.sender != user_uuid {
+            return Err(MessageError::NotAttachmentOwner(attachment.uuid));
+        }
+        attachment.name = stored.name;
+        attachment.path = stored.path;
+        attachment.media_type = stored.media_type;
+        // the previews may still be generated
+        attachment.preview = db.media.preview(&attachment.uuid).await.unwrap_or_default();
+    }
+
+    if let Some(voice) = message.content.get_voice() {
+        let attached = media.attachments.iter().any(|attachment| {
+            attachment.uuid == voice.media && attachment.media_type == MediaType::Audio
+        });
+        if !attached {
+            return Err(MessageError::VoiceNotAttached(voice.media));
         }
     }
+
+    add_attachments(db, media, message.uuid, message.sides.get_receiver())
+        .await
+        .map_err(|_| MessageError::Attachments)
+}
+
+/// Records that the media was sent to the receiver
+async fn add_attachments(
+    db: Arc<Database>,
+    media: &Media,
+    message_uuid: Uuid,
+    receiver: Uuid,
+) -> Result<(), DbError> {
+    for attachment in &media.attachments {
+        db.messages
+            .add_attachment(&attachment.uuid, &receiver, &message_uuid)
+            .await?;
+    }
+    Ok(())
 }
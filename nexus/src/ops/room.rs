@@ -0,0 +1,270 @@
+use std::{error::Error, sync::Arc};
+
+use nexuslib::{
+    models::call::{
+        relay::RelayTicket,
+        room::{CallRoom, MAX_ROOM_PARTICIPANTS},
+    },
+    request::{index_token::RoomToken, room::RoomRequest, Request},
+    response::{Response, ResponseStatus},
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    db::Database,
+    errors::call::CallError,
+    state::{connection::ConnectionState, relay::RelayAllocation, room::ActiveRoom},
+};
+
+/// Handles the signaling of group calls
+///
+/// A room exists while somebody takes part in it. Every change of
+/// the participants is pushed to all sessions of the invited users,
+/// the joined session gets the ticket of the relay that forwards
+/// its media to all the other participants
+pub async fn connect_room(
+    room: String,
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), Box<dyn Error>> {
+    let room_request: Request<RoomRequest> = serde_json::from_str(&room)?;
+    let room_request = room_request.body;
+
+    log::debug!(
+        "connect_room: {:?} for {} from {user_uuid}",
+        room_request.index,
+        room_request.room.uuid
+    );
+
+    let result = match room_request.index {
+        RoomToken::Create => create_room(room_request.room, &state, user_uuid, peer_uuid).await,
+        RoomToken::Join => join_room(&room_request.room.uuid, &state, user_uuid, peer_uuid).await,
+        RoomToken::Leave => {
+            leave_room(&room_request.room.uuid, &db, &state, user_uuid, peer_uuid).await
+        }
+        token => Err(CallError::RoomServerOnly(token)),
+    };
+
+    if let Err(e) = result {
+        log::debug!("connect_room: {e}");
+        let response = serde_json::to_string(&Response::new(ResponseStatus::Err, e.to_string()))?;
+        notify_peer(&state, &user_uuid, &peer_uuid, &response).await;
+    }
+
+    Ok(())
+}
+
+/// Leaves the rooms the closed session takes part in
+pub async fn drop_peer_rooms(
+    db: Arc<Database>,
+    state: Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) {
+    let rooms = state
+        .lock()
+        .await
+        .rooms
+        .values()
+        .filter(|room| room.has_peer(&user_uuid, &peer_uuid))
+        .map(|room| room.room.uuid)
+        .collect::<Vec<_>>();
+
+    for room in rooms {
+        if let Err(e) = leave_room(&room, &db, &state, user_uuid, peer_uuid).await {
+            log::debug!("drop_peer_rooms: {e}");
+        }
+    }
+}
+
+/// Creates the room with its owner as the first participant
+/// and sends the invitation to the invited users
+async fn create_room(
+    room: CallRoom,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), CallError> {
+    if room.owner != user_uuid {
+        return Err(CallError::NotParticipant);
+    }
+    if room.invited().len() > MAX_ROOM_PARTICIPANTS {
+        return Err(CallError::RoomFull);
+    }
+
+    let (room, token) = {
+        let mut state = state.lock().await;
+        if state.rooms.contains_key(&room.uuid) {
+            return Err(CallError::AlreadyExists);
+        }
+        if state.in_call(&user_uuid) {
+            return Err(CallError::InAnotherCall);
+        }
+
+        let mut active = ActiveRoom::new(room);
+        active.join(user_uuid, peer_uuid);
+        let relay = RelayAllocation::new(active.room.uuid, &[user_uuid]);
+        let token = relay.token_of(&user_uuid).unwrap();
+
+        let room = active.room.clone();
+        state.relays.insert(room.uuid, relay);
+        state.rooms.insert(room.uuid, active);
+        (room, token)
+    };
+
+    joined(state, room.clone(), token, user_uuid, peer_uuid).await;
+    notify_invited(
+        state,
+        &RoomRequest::new(room, RoomToken::Create),
+        Some(&peer_uuid),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Adds the session of an invited user to the room
+async fn join_room(
+    room_uuid: &Uuid,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), CallError> {
+    let (room, token) = {
+        let mut state = state.lock().await;
+        let in_call = state.in_call(&user_uuid);
+
+        let active = state.rooms.get_mut(room_uuid).ok_or(CallError::NotFound)?;
+        if !active.room.is_invited(&user_uuid) {
+            return Err(CallError::NotParticipant);
+        }
+        if active.peers.contains_key(&user_uuid) {
+            return Err(CallError::AlreadyJoined);
+        }
+        if in_call {
+            return Err(CallError::InAnotherCall);
+        }
+        if active.peers.len() >= MAX_ROOM_PARTICIPANTS {
+            return Err(CallError::RoomFull);
+        }
+
+        active.join(user_uuid, peer_uuid);
+        let room = active.room.clone();
+        let token = state
+            .relays
+            .get_mut(room_uuid)
+            .map(|relay| relay.add_participant(user_uuid))
+            .ok_or(CallError::NotFound)?;
+        (room, token)
+    };
+
+    joined(state, room.clone(), token, user_uuid, peer_uuid).await;
+    notify_invited(
+        state,
+        &RoomRequest::new(room, RoomToken::Participants),
+        Some(&peer_uuid),
+    )
+    .await;
+
+    Ok(())
+}
+
+/// Removes the participant, the room is closed when the last one has left
+///
+/// The stats of the relay of a closed room are written to the DB
+async fn leave_room(
+    room_uuid: &Uuid,
+    db: &Arc<Database>,
+    state: &Arc<Mutex<ConnectionState>>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) -> Result<(), CallError> {
+    let (room, closed, stats) = {
+        let mut state = state.lock().await;
+        let active = state.rooms.get_mut(room_uuid).ok_or(CallError::NotFound)?;
+        if !active.has_peer(&user_uuid, &peer_uuid) {
+            return Err(CallError::NotParticipant);
+        }
+
+        active.leave(&user_uuid);
+        let (room, closed) = (active.room.clone(), active.is_empty());
+
+        let mut stats = None;
+        if closed {
+            stats = state.close_room(room_uuid).and_then(|active| active.stats);
+        } else {
+            let endpoint = state
+                .relays
+                .get_mut(room_uuid)
+                .and_then(|relay| relay.remove_participant(&user_uuid));
+            if let Some(endpoint) = endpoint {
+                state.relay_endpoints.remove(&endpoint);
+            }
+        }
+        (room, closed, stats)
+    };
+
+    if let Some(stats) = stats {
+        if db.calls.add_stats(&stats).await.is_err() {
+            log::error!("Error adding room stats to the DB!");
+        }
+    }
+
+    let token = match closed {
+        true => RoomToken::Closed,
+        false => RoomToken::Participants,
+    };
+    notify_invited(state, &RoomRequest::new(room, token), None).await;
+
+    Ok(())
+}
+
+/// Sends the relay ticket to the session that joined the room
+async fn joined(
+    state: &Arc<Mutex<ConnectionState>>,
+    room: CallRoom,
+    token: Vec<u8>,
+    user_uuid: Uuid,
+    peer_uuid: Uuid,
+) {
+    let ticket = RelayTicket::new(room.uuid, token);
+    let request = RoomRequest::new(room, RoomToken::Joined).with_relay(ticket);
+    let request_str = serde_json::to_string(&request).unwrap();
+    notify_peer(state, &user_uuid, &peer_uuid, &request_str).await;
+}
+
+/// Sends the room to all sessions of the invited users except the given one
+async fn notify_invited(
+    state: &Arc<Mutex<ConnectionState>>,
+    request: &RoomRequest,
+    except: Option<&Uuid>,
+) {
+    let request_str = serde_json::to_string(request).unwrap();
+
+    let state = state.lock().await;
+    for user in request.room.invited() {
+        if let Some(sessions) = state.peers.get(&user) {
+            for (peer, socket) in sessions.iter() {
+                if Some(peer) != except {
+                    let _ = socket.tcp_sender.send(request_str.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Sends the message to a single session of the user
+async fn notify_peer(state: &Arc<Mutex<ConnectionState>>, user: &Uuid, peer: &Uuid, msg: &str) {
+    if let Some(socket) = state
+        .lock()
+        .await
+        .peers
+        .get(user)
+        .and_then(|sessions| sessions.get(peer))
+    {
+        let _ = socket.tcp_sender.send(msg.to_owned());
+    }
+}
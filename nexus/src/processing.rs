@@ -0,0 +1,180 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use async_trait::async_trait;
+use tokio::{fs, io::AsyncWriteExt};
+use uuid::Uuid;
+
+use nexuslib::models::{
+    file::media_file::MediaFile,
+    message::media::{MediaPreview, MediaType, Thumbnail, THUMBNAIL_SIZES},
+};
+
+use crate::{
+    db::Database,
+    errors::processing::ProcessingError,
+    storage::{bucket_name, get_object_range, object_size, put_object, remove_object},
+};
+
+use self::{image::ImageProcessor, video::VideoProcessor};
+
+pub mod image;
+pub mod video;
+
+/// Size of the ranges the original is downloaded in
+const DOWNLOAD_RANGE: usize = 8 * 1024 * 1024;
+
+/// The processors of the server, the first one accepting a type is used
+static PROCESSORS: OnceLock<Vec<Box<dyn MediaProcessor>>> = OnceLock::new();
+
+/// Thumbnail made by a processor before it is stored
+pub struct RenderedThumbnail {
+    /// One of `THUMBNAIL_SIZES`
+    pub size: u32,
+    pub width: u32,
+    pub height: u32,
+    /// JPEG of the thumbnail
+    pub data: Vec<u8>,
+}
+
+/// Previews of a media made by a processor
+pub struct Processed {
+    pub thumbnails: Vec<RenderedThumbnail>,
+    pub blurhash: Option<String>,
+}
+
+/// Generates the previews of the uploaded media
+#[async_trait]
+pub trait MediaProcessor: Send + Sync {
+    /// Name of the processor
+    fn name(&self) -> &'static str;
+
+    /// Checks that the processor handles the media of the type
+    fn accepts(&self, media_type: MediaType) -> bool;
+
+    /// Makes the previews of the media stored in the local file
+    async fn process(&self, source: &Path) -> Result<Processed, ProcessingError>;
+}
+
+/// Returns the processors of the server
+pub fn processors() -> &'static [Box<dyn MediaProcessor>] {
+    PROCESSORS.get_or_init(|| {
+        vec![
+            Box::new(ImageProcessor),
+            Box::new(VideoProcessor::from_env()),
+        ]
+    })
+}
+
+/// Generates the previews of the committed media in the background
+///
+/// Encrypted files are skipped, the server can not read them
+pub fn spawn_previews(db: Arc<Database>, file: &MediaFile, object: &str) {
+    if file.secret {
+        return;
+    }
+    let Some(processor) = processors()
+        .iter()
+        .find(|processor| processor.accepts(file.media_type))
+    else {
+        return;
+    };
+
+    let (media_uuid, media_type, object) = (file.uuid, file.media_type, object.to_owned());
+    tokio::spawn(async move {
+        let bucket = bucket_name(media_type);
+        let preview = match make_preview(processor.as_ref(), &bucket, &object, media_uuid).await {
+            Ok(preview) => preview,
+            Err(err) => {
+                log::warn!(
+                    "Previews of the media {media_uuid} were not made by `{}`: {err}",
+                    processor.name()
+                );
+                remove_thumbnails(&bucket, &media_uuid).await;
+                return;
+            }
+        };
+
+        if db.media.add_preview(&media_uuid, &preview).await.is_err() {
+            log::error!("Error adding the preview of the media {media_uuid} to the DB");
+            remove_thumbnails(&bucket, &media_uuid).await;
+        }
+    });
+}
+
+/// Returns the key of the thumbnail, it is stored in the bucket of the original
+pub fn thumbnail_name(media_uuid: &Uuid, size: u32) -> String {
+    format!("{media_uuid}_{size}.jpg")
+}
+
+/// Removes the thumbnails of all sizes, the missing ones are skipped
+pub async fn remove_thumbnails(bucket: &str, media_uuid: &Uuid) {
+    for size in THUMBNAIL_SIZES {
+        let object = thumbnail_name(media_uuid, size);
+        if let Err(err) = remove_object(bucket, &object).await {
+            log::error!("Error removing the object `{object}`: {err}");
+        }
+    }
+}
+
+/// Processes the original and stores the thumbnails next to it
+async fn make_preview(
+    processor: &dyn MediaProcessor,
+    bucket: &str,
+    object: &str,
+    media_uuid: Uuid,
+) -> Result<MediaPreview, ProcessingError> {
+    let source = source_path(&media_uuid);
+    let processed = match download(bucket, object, &source).await {
+        Ok(()) => processor.process(&source).await,
+        Err(err) => Err(err),
+    };
+    let _ = fs::remove_file(&source).await;
+    let processed = processed?;
+
+    let mut thumbnails = Vec::with_capacity(processed.thumbnails.len());
+    for rendered in processed.thumbnails {
+        let path = thumbnail_name(&media_uuid, rendered.size);
+        put_object(bucket, &path, rendered.data).await?;
+        thumbnails.push(Thumbnail {
+            size: rendered.size,
+            width: rendered.width,
+            height: rendered.height,
+            path,
+        });
+    }
+
+    Ok(MediaPreview {
+        thumbnails,
+        blurhash: processed.blurhash,
+    })
+}
+
+/// Downloads the object to the local file range by range
+async fn download(bucket: &str, object: &str, path: &Path) -> Result<(), ProcessingError> {
+    let size = object_size(bucket, object).await?;
+    let mut file = fs::File::create(path).await?;
+
+    let mut offset = 0;
+    while offset < size {
+        let length = DOWNLOAD_RANGE.min(size - offset);
+        let data = get_object_range(bucket, object, offset, length).await?;
+        if data.is_empty() {
+            return Err(ProcessingError::Decode(
+                "the object is truncated".to_owned(),
+            ));
+        }
+        file.write_all(&data).await?;
+        offset += data.len();
+    }
+    file.flush().await?;
+
+    Ok(())
+}
+
+/// Local copy of the original, removed once it is processed
+fn source_path(media_uuid: &Uuid) -> PathBuf {
+    std::env::temp_dir().join(format!("nexus-{media_uuid}.source"))
+}
@@ -0,0 +1,119 @@
+use std::{io::Cursor, path::Path};
+
+use async_trait::async_trait;
+use image::{io::Limits, io::Reader, DynamicImage, GenericImageView, ImageOutputFormat};
+
+use nexuslib::models::message::media::{MediaType, THUMBNAIL_SIZES};
+
+use crate::errors::processing::ProcessingError;
+
+use super::{MediaProcessor, Processed, RenderedThumbnail};
+
+/// Larger files are not decoded
+const MAX_SOURCE_SIZE: u64 = 64 * 1024 * 1024;
+/// Larger images are not decoded
+const MAX_DIMENSION: u32 = 16 * 1024;
+const JPEG_QUALITY: u8 = 80;
+/// Size of the image the blurhash is computed from
+const BLURHASH_SOURCE_SIZE: u32 = 32;
+
+/// Makes the thumbnails and the blurhash of the images
+pub struct ImageProcessor;
+
+#[async_trait]
+impl MediaProcessor for ImageProcessor {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn accepts(&self, media_type: MediaType) -> bool {
+        matches!(media_type, MediaType::Image)
+    }
+
+    async fn process(&self, source: &Path) -> Result<Processed, ProcessingError> {
+        let source = source.to_owned();
+        // decoding and resizing would block the runtime
+        tokio::task::spawn_blocking(move || render(&decode(&source)?))
+            .await
+            .map_err(|_| ProcessingError::Aborted)?
+    }
+}
+
+/// Decodes the image, the format is guessed from the content
+fn decode(path: &Path) -> Result<DynamicImage, ProcessingError> {
+    if std::fs::metadata(path)?.len() > MAX_SOURCE_SIZE {
+        return Err(ProcessingError::TooLarge);
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+
+    let mut reader = Reader::open(path)?.with_guessed_format()?;
+    reader.limits(limits);
+    reader
+        .decode()
+        .map_err(|err| ProcessingError::Decode(err.to_string()))
+}
+
+/// Makes the thumbnails and the blurhash of the decoded image
+///
+/// The thumbnails are encoded anew, so EXIF and the other
+/// metadata of the original are not kept
+pub fn render(image: &DynamicImage) -> Result<Processed, ProcessingError> {
+    let longer = image.width().max(image.height());
+
+    let mut thumbnails = Vec::new();
+    for (index, size) in THUMBNAIL_SIZES.into_iter().enumerate() {
+        // the image is not upscaled, a small one gets only the smallest thumbnail
+        if index > 0 && size > longer {
+            break;
+        }
+        let thumbnail = match size < longer {
+            true => image.thumbnail(size, size),
+            false => image.clone(),
+        };
+        let thumbnail = DynamicImage::ImageRgb8(thumbnail.to_rgb8());
+
+        let mut data = Vec::new();
+        thumbnail
+            .write_to(
+                &mut Cursor::new(&mut data),
+                ImageOutputFormat::Jpeg(JPEG_QUALITY),
+            )
+            .map_err(|err| ProcessingError::Encode(err.to_string()))?;
+
+        let (width, height) = thumbnail.dimensions();
+        thumbnails.push(RenderedThumbnail {
+            size,
+            width,
+            height,
+            data,
+        });
+    }
+
+    Ok(Processed {
+        thumbnails,
+        blurhash: blurhash_of(image),
+    })
+}
+
+/// Encodes the blurhash with more components along the longer side
+fn blurhash_of(image: &DynamicImage) -> Option<String> {
+    let small = image
+        .thumbnail(BLURHASH_SOURCE_SIZE, BLURHASH_SOURCE_SIZE)
+        .to_rgba8();
+    let (components_x, components_y) = match small.width() >= small.height() {
+        true => (4, 3),
+        false => (3, 4),
+    };
+
+    blurhash::encode(
+        components_x,
+        components_y,
+        small.width(),
+        small.height(),
+        small.as_raw(),
+    )
+    .ok()
+}
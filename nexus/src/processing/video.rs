@@ -0,0 +1,85 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use image::ImageFormat;
+use tokio::{process::Command, time::timeout};
+
+use nexuslib::models::message::media::MediaType;
+
+use crate::errors::processing::ProcessingError;
+
+use super::{image::render, MediaProcessor, Processed};
+
+/// Time `ffmpeg` has to extract the frame
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Takes the poster frame of the videos with `ffmpeg`,
+/// the thumbnails of a video are the ones of its poster frame
+pub struct VideoProcessor {
+    ffmpeg: PathBuf,
+}
+
+impl VideoProcessor {
+    pub fn new(ffmpeg: impl Into<PathBuf>) -> Self {
+        Self {
+            ffmpeg: ffmpeg.into(),
+        }
+    }
+
+    /// Runs `FFMPEG_PATH`, or `ffmpeg` from `PATH` if it is not set
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_owned()))
+    }
+
+    /// Returns the poster frame as PNG
+    ///
+    /// The `thumbnail` filter picks the most representative
+    /// of the first frames, so a black intro frame is skipped
+    async fn poster_frame(&self, source: &Path) -> Result<Vec<u8>, ProcessingError> {
+        let output = Command::new(&self.ffmpeg)
+            .args(["-nostdin", "-v", "error", "-i"])
+            .arg(source)
+            .args(["-vf", "thumbnail", "-frames:v", "1"])
+            .args(["-f", "image2pipe", "-c:v", "png", "-"])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+
+        let output = timeout(FFMPEG_TIMEOUT, output)
+            .await
+            .map_err(|_| ProcessingError::Tool("ffmpeg timed out".to_owned()))??;
+        if !output.status.success() || output.stdout.is_empty() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(ProcessingError::Tool(stderr.trim().to_owned()));
+        }
+
+        Ok(output.stdout)
+    }
+}
+
+#[async_trait]
+impl MediaProcessor for VideoProcessor {
+    fn name(&self) -> &'static str {
+        "video"
+    }
+
+    fn accepts(&self, media_type: MediaType) -> bool {
+        matches!(media_type, MediaType::Video)
+    }
+
+    async fn process(&self, source: &Path) -> Result<Processed, ProcessingError> {
+        let frame = self.poster_frame(source).await?;
+
+        tokio::task::spawn_blocking(move || {
+            let frame = image::load_from_memory_with_format(&frame, ImageFormat::Png)
+                .map_err(|err| ProcessingError::Decode(err.to_string()))?;
+            render(&frame)
+        })
+        .await
+        .map_err(|_| ProcessingError::Aborted)?
+    }
+}
@@ -1 +1 @@
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
\ No newline at end of file
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
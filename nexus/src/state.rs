@@ -1,2 +1,6 @@
+pub mod call;
 pub mod connection;
 pub mod peer;
+pub mod relay;
+pub mod room;
+pub mod upload;
@@ -0,0 +1,56 @@
+use nexuslib::models::call::{
+    media_call::MediaCall,
+    state::{CallSide, CallState},
+    stats::CallStats,
+};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+/// Call that is ringing or in progress
+pub struct ActiveCall {
+    pub call: MediaCall,
+    pub state: CallState,
+    /// Session of the caller that started the call
+    pub caller_peer: Uuid,
+    /// Session of the callee that accepted the call
+    pub callee_peer: Option<Uuid>,
+    pub accepted_at: Option<i64>,
+    /// Final stats of the relay, set when the call ends
+    pub stats: Option<CallStats>,
+}
+
+impl ActiveCall {
+    pub fn new(call: MediaCall, caller_peer: Uuid) -> Self {
+        Self {
+            call,
+            state: CallState::Ringing,
+            caller_peer,
+            callee_peer: None,
+            accepted_at: None,
+            stats: None,
+        }
+    }
+
+    /// Returns the side of the user in the call
+    pub fn side_of(&self, user: &Uuid) -> Option<CallSide> {
+        if *user == self.call.sides.get_sender() {
+            Some(CallSide::Caller)
+        } else if *user == self.call.sides.get_receiver() {
+            Some(CallSide::Callee)
+        } else {
+            None
+        }
+    }
+
+    /// Checks whether the session takes part in the call
+    pub fn has_peer(&self, peer: &Uuid) -> bool {
+        self.caller_peer == *peer || self.callee_peer.as_ref() == Some(peer)
+    }
+
+    /// Returns the talk time in seconds
+    pub fn duration(&self, timestamp: i64) -> i64 {
+        self.accepted_at
+            .map(|accepted_at| timestamp - accepted_at)
+            .unwrap_or_default()
+    }
+}
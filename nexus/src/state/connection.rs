@@ -1,19 +1,130 @@
 use std::net::SocketAddr;
 
 use hashbrown::HashMap;
+use nexuslib::models::call::stats::CallStats;
 use tokio::sync::mpsc::UnboundedSender;
 use uuid::Uuid;
 
+use super::{call::ActiveCall, relay::RelayAllocation, room::ActiveRoom, upload::ActiveUpload};
+
 pub struct ConnectionState {
     pub peers: HashMap<Uuid, HashMap<Uuid, SessionSocket>>,
+    /// Calls that are ringing or in progress
+    pub calls: HashMap<Uuid, ActiveCall>,
+    /// Group calls by the uuid of the room
+    pub rooms: HashMap<Uuid, ActiveRoom>,
+    /// Media relays of the accepted calls and of the rooms
+    pub relays: HashMap<Uuid, RelayAllocation>,
+    /// Calls of the bound UDP addresses, RTP does not carry the call id
+    pub relay_endpoints: HashMap<SocketAddr, Uuid>,
+    /// Chunked uploads that were not committed yet
+    pub uploads: HashMap<Uuid, ActiveUpload>,
+}
+
+impl Default for ConnectionState {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl ConnectionState {
     pub fn new() -> Self {
         Self {
             peers: HashMap::new(),
+            calls: HashMap::new(),
+            rooms: HashMap::new(),
+            relays: HashMap::new(),
+            relay_endpoints: HashMap::new(),
+            uploads: HashMap::new(),
         }
     }
+
+    /// Removes the call together with its relay
+    pub fn end_call(&mut self, call: &Uuid) -> Option<ActiveCall> {
+        let stats = self.release_relay(call);
+        self.calls.remove(call).map(|mut active| {
+            active.stats = stats;
+            active
+        })
+    }
+
+    /// Removes the room together with its relay
+    pub fn close_room(&mut self, room: &Uuid) -> Option<ActiveRoom> {
+        let stats = self.release_relay(room);
+        self.rooms.remove(room).map(|mut active| {
+            active.stats = stats;
+            active
+        })
+    }
+
+    /// Removes the relay of the call or the room, logs and returns its stats
+    fn release_relay(&mut self, call: &Uuid) -> Option<CallStats> {
+        self.relays.remove(call).map(|relay| {
+            self.relay_endpoints
+                .retain(|_, relay_call| relay_call != call);
+
+            let stats = relay.stats();
+            log::info!(
+                "Relay of the call {call} released: {} packets, {} bytes, {} dropped",
+                stats.packets,
+                stats.bytes,
+                stats.dropped
+            );
+            for stream in stats.streams.iter() {
+                log::info!(
+                    "Stream {:08x} of the call {call}: {:.1}% lost, {} lost in total, jitter {}",
+                    stream.ssrc,
+                    stream.loss_percent(),
+                    stream.cumulative_lost,
+                    stream.jitter
+                );
+            }
+            stats
+        })
+    }
+
+    /// Binds the address to the participant of the call that owns the token
+    ///
+    /// The previous address of the participant is released, so it does not
+    /// reach the call anymore. Returns `false` if the token is unknown
+    pub fn bind_relay(&mut self, call: &Uuid, token: &[u8], endpoint: SocketAddr) -> bool {
+        let previous = match self
+            .relays
+            .get_mut(call)
+            .and_then(|relay| relay.bind(token, endpoint))
+        {
+            Some(previous) => previous,
+            None => return false,
+        };
+
+        if let Some(previous) = previous.filter(|previous| *previous != endpoint) {
+            self.relay_endpoints.remove(&previous);
+        }
+        self.relay_endpoints.insert(endpoint, *call);
+        true
+    }
+
+    /// Checks whether the user takes part in a call or in a room
+    pub fn in_call(&self, user: &Uuid) -> bool {
+        self.calls.values().any(|call| call.side_of(user).is_some())
+            || self
+                .rooms
+                .values()
+                .any(|room| room.peers.contains_key(user))
+    }
+
+    /// Drops all live sessions of the user
+    ///
+    /// Dropping the `SessionSocket` closes its channel,
+    /// which makes the TCP handler close the stream
+    ///
+    /// Returns the number of the closed sessions
+    pub fn disconnect_user(&mut self, user: &Uuid) -> usize {
+        self.peers
+            .remove(user)
+            .map(|sessions| sessions.len())
+            .unwrap_or_default()
+    }
 }
 
 pub struct SessionSocket {
@@ -0,0 +1,198 @@
+use std::net::SocketAddr;
+
+use hashbrown::HashMap;
+use rand_core::{OsRng, RngCore};
+use uuid::Uuid;
+
+use nexuslib::{
+    media::{metrics::CallMetrics, rtcp::ReportBlock, rtp::AudioLevel, speaker::SpeakerDetector},
+    models::call::stats::{CallStats, StreamStats},
+};
+
+/// Length of a relay token in bytes
+pub const RELAY_TOKEN_LEN: usize = 16;
+
+#[derive(Debug, Clone)]
+/// Participant of a relayed call
+pub struct RelayParticipant {
+    pub token: Vec<u8>,
+    /// UDP address registered with the `Bind` packet
+    pub endpoint: Option<SocketAddr>,
+}
+
+impl RelayParticipant {
+    /// Creates the participant with a new token
+    fn new() -> Self {
+        let mut token = vec![0u8; RELAY_TOKEN_LEN];
+        OsRng.fill_bytes(&mut token);
+
+        Self {
+            token,
+            endpoint: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+/// Relay of the media of one call, created when the call is accepted
+///
+/// The relay of a room forwards the packets of each participant
+/// to all the others and detects the active speaker
+pub struct RelayAllocation {
+    pub call: Uuid,
+    pub participants: HashMap<Uuid, RelayParticipant>,
+    /// Counters of the relayed media
+    pub bytes: u64,
+    pub packets: u64,
+    /// Datagrams dropped since the source is not a participant
+    pub dropped: u64,
+    /// Latest RTCP reports by the SSRC of the stream
+    pub streams: HashMap<u32, StreamStats>,
+    /// Quality of the media of each participant
+    pub metrics: CallMetrics,
+    pub speakers: SpeakerDetector<Uuid>,
+}
+
+impl RelayAllocation {
+    /// Allocates the relay with a new token for each user
+    pub fn new(call: Uuid, users: &[Uuid]) -> Self {
+        let participants = users
+            .iter()
+            .map(|user| (*user, RelayParticipant::new()))
+            .collect();
+
+        Self {
+            call,
+            participants,
+            bytes: 0,
+            packets: 0,
+            dropped: 0,
+            streams: HashMap::new(),
+            metrics: CallMetrics::new(),
+            speakers: SpeakerDetector::new(),
+        }
+    }
+
+    /// Adds the user that joined the room, returns its token
+    pub fn add_participant(&mut self, user: Uuid) -> Vec<u8> {
+        let participant = RelayParticipant::new();
+        let token = participant.token.clone();
+        self.participants.insert(user, participant);
+        token
+    }
+
+    /// Removes the user that left the room, returns its bound address
+    pub fn remove_participant(&mut self, user: &Uuid) -> Option<SocketAddr> {
+        self.speakers.remove(user);
+        self.participants
+            .remove(user)
+            .and_then(|participant| participant.endpoint)
+    }
+
+    /// Takes the audio level of the packet sent from the address
+    ///
+    /// Returns the new active speaker if it has changed
+    pub fn record_level(
+        &mut self,
+        endpoint: &SocketAddr,
+        level: AudioLevel,
+        now_ms: u64,
+    ) -> Option<Uuid> {
+        let participant = self.participant_at(endpoint)?;
+        self.speakers.update(participant, level, now_ms)
+    }
+
+    /// Returns the token of the user
+    pub fn token_of(&self, user: &Uuid) -> Option<Vec<u8>> {
+        self.participants
+            .get(user)
+            .map(|participant| participant.token.clone())
+    }
+
+    /// Registers the address of the participant that owns the token
+    ///
+    /// Returns the address the participant was bound to before,
+    /// `None` if the token is unknown
+    pub fn bind(&mut self, token: &[u8], endpoint: SocketAddr) -> Option<Option<SocketAddr>> {
+        self.participants
+            .values_mut()
+            .find(|participant| constant_time_eq(&participant.token, token))
+            .map(|participant| participant.endpoint.replace(endpoint))
+    }
+
+    /// Returns the participant bound to the address
+    pub fn participant_at(&self, endpoint: &SocketAddr) -> Option<Uuid> {
+        self.participants
+            .iter()
+            .find(|(_, participant)| participant.endpoint.as_ref() == Some(endpoint))
+            .map(|(user, _)| *user)
+    }
+
+    /// Stores the report blocks of the RTCP sent by the participant
+    pub fn record_reports(&mut self, reporter: Uuid, reports: &[ReportBlock], now_ms: u64) {
+        let now = (now_ms / 1000) as i64;
+        for report in reports {
+            self.streams
+                .insert(report.ssrc, StreamStats::from_report(reporter, report, now));
+        }
+        self.metrics.record_reports(reporter, reports, now_ms);
+    }
+
+    /// Counts the datagram of the source forwarded to the targets
+    pub fn record_forward(
+        &mut self,
+        source: &SocketAddr,
+        targets: &[SocketAddr],
+        len: usize,
+        now_ms: u64,
+    ) {
+        self.packets += 1;
+        self.bytes += len as u64;
+
+        if let Some(sender) = self.participant_at(source) {
+            self.metrics.record_in(sender, len, now_ms);
+        }
+        for target in targets {
+            if let Some(receiver) = self.participant_at(target) {
+                self.metrics.record_out(receiver, len);
+            }
+        }
+    }
+
+    /// Returns the counters with the latest reports
+    pub fn stats(&self) -> CallStats {
+        CallStats {
+            call: self.call,
+            packets: self.packets,
+            bytes: self.bytes,
+            dropped: self.dropped,
+            streams: self.streams.values().cloned().collect(),
+            participants: self.metrics.participants(),
+        }
+    }
+
+    /// Returns the addresses the datagram from the source has to be forwarded to
+    ///
+    /// `None` means that the source is not a participant of the call
+    pub fn targets(&self, source: &SocketAddr) -> Option<Vec<SocketAddr>> {
+        let is_participant = self
+            .participants
+            .values()
+            .any(|participant| participant.endpoint.as_ref() == Some(source));
+        if !is_participant {
+            return None;
+        }
+
+        Some(
+            self.participants
+                .values()
+                .filter_map(|participant| participant.endpoint)
+                .filter(|endpoint| endpoint != source)
+                .collect(),
+        )
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
@@ -0,0 +1,57 @@
+use hashbrown::HashMap;
+use nexuslib::models::call::{room::CallRoom, stats::CallStats};
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+/// Group call that has at least one participant
+pub struct ActiveRoom {
+    pub room: CallRoom,
+    /// Session of each participant, a user takes part with a single session
+    pub peers: HashMap<Uuid, Uuid>,
+    /// Final stats of the relay, set when the room is closed
+    pub stats: Option<CallStats>,
+}
+
+impl ActiveRoom {
+    pub fn new(mut room: CallRoom) -> Self {
+        room.participants.clear();
+        room.speaker = None;
+
+        Self {
+            room,
+            peers: HashMap::new(),
+            stats: None,
+        }
+    }
+
+    pub fn join(&mut self, user: Uuid, peer: Uuid) {
+        self.peers.insert(user, peer);
+        if !self.room.participants.contains(&user) {
+            self.room.participants.push(user);
+        }
+    }
+
+    /// Removes the participant, returns `false` if the user did not take part
+    pub fn leave(&mut self, user: &Uuid) -> bool {
+        if self.peers.remove(user).is_none() {
+            return false;
+        }
+
+        self.room
+            .participants
+            .retain(|participant| participant != user);
+        if self.room.speaker.as_ref() == Some(user) {
+            self.room.speaker = None;
+        }
+        true
+    }
+
+    /// Checks whether the user takes part with the session
+    pub fn has_peer(&self, user: &Uuid, peer: &Uuid) -> bool {
+        self.peers.get(user) == Some(peer)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+}
@@ -0,0 +1,25 @@
+use std::path::PathBuf;
+
+use nexuslib::models::file::upload::UploadProgress;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+/// Chunked upload in progress, its chunks are written to a part file
+pub struct ActiveUpload {
+    pub owner: Uuid,
+    pub progress: UploadProgress,
+    pub path: PathBuf,
+    /// Time of the latest request, stale uploads are removed
+    pub updated_at: i64,
+}
+
+impl ActiveUpload {
+    pub fn new(owner: Uuid, progress: UploadProgress, path: PathBuf, updated_at: i64) -> Self {
+        Self {
+            owner,
+            progress,
+            path,
+            updated_at,
+        }
+    }
+}
@@ -1,46 +1,134 @@
-use std::{io::stdout, process};
+use std::{
+    io::{self, stdout, Cursor, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+    sync::OnceLock,
+};
 
+use async_trait::async_trait;
 use crossterm::{
     cursor::{self, MoveToColumn, SavePosition},
     execute,
     style::{Color, Print, ResetColor, SetAttribute, SetForegroundColor},
     terminal::{Clear, ClearType},
 };
-use minio::s3::{
-    args::{BucketExistsArgs, MakeBucketArgs},
-    client::Client,
-    creds::StaticProvider,
-    error::Error,
-    http::BaseUrl,
-};
+use futures::{stream, Stream};
+use tokio::io::AsyncRead;
+
 use nexuslib::models::message::media::MediaType;
 
-pub fn get_client() -> Result<Client, Box<Error>> {
-    let host = std::env::var("MINIO_HOST").unwrap();
-    let port = std::env::var("MINIO_PORT").unwrap();
-    let endpoint = format!("http://{host}:{port}").parse::<BaseUrl>().unwrap();
+use crate::errors::storage::StorageError;
+
+use self::{fs::FsStore, s3::S3Store};
+
+pub mod fs;
+pub mod policy;
+pub mod s3;
+
+/// Size of the ranges the objects are streamed by
+const STREAM_RANGE: usize = 1024 * 1024;
+
+/// The store used by the server, set once at startup
+static STORE: OnceLock<Box<dyn MediaStore>> = OnceLock::new();
+/// Directory of the parts of the chunked uploads
+static UPLOAD_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// HTTP method allowed by a presigned URL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+/// Metadata of a stored object
+#[derive(Debug, Clone)]
+pub struct ObjectStat {
+    pub size: usize,
+    /// MIME type the object was stored with, if the backend keeps it
+    pub content_type: Option<String>,
+}
+
+/// Storage of the media objects grouped by buckets
+///
+/// Every `MediaType` has its own bucket, see `bucket_name`
+#[async_trait]
+pub trait MediaStore: Send + Sync {
+    /// Name of the backend
+    fn name(&self) -> &'static str;
+
+    /// Creates the bucket if it does not exist
+    async fn create_bucket(&self, bucket: &str) -> Result<(), StorageError>;
 
-    let provider = StaticProvider::new(
-        &std::env::var("MINIO_ROOT_USER").unwrap(),
-        &std::env::var("MINIO_ROOT_PASSWORD").unwrap(),
-        None,
-    );
+    /// Writes `size` bytes read from the stream to the object
+    async fn put(
+        &self,
+        bucket: &str,
+        object: &str,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        size: usize,
+    ) -> Result<(), StorageError>;
 
-    let client = Client::new(endpoint, Some(Box::new(provider)), None, None);
-    if let Err(err) = client {
-        return Err(Box::new(err));
+    /// Reads `length` bytes of the object starting at `offset`
+    async fn get_range(
+        &self,
+        bucket: &str,
+        object: &str,
+        offset: usize,
+        length: usize,
+    ) -> Result<Vec<u8>, StorageError>;
+
+    /// Removes the object, removing a missing one is not an error
+    async fn delete(&self, bucket: &str, object: &str) -> Result<(), StorageError>;
+
+    /// Returns the size and the type of the object
+    async fn stat(&self, bucket: &str, object: &str) -> Result<ObjectStat, StorageError>;
+
+    /// Returns the URL that allows the method on the object
+    /// without credentials for `expires` seconds
+    async fn presign(
+        &self,
+        method: PresignMethod,
+        bucket: &str,
+        object: &str,
+        expires: u32,
+    ) -> Result<String, StorageError>;
+}
+
+/// Returns the store of the server
+///
+/// Panics if `storage_setup` has not been called
+pub fn store() -> &'static dyn MediaStore {
+    STORE.get().expect("Storage is not set up").as_ref()
+}
+
+/// Creates the backend selected by `STORAGE_BACKEND`: `s3` (default) or `fs`
+pub fn store_from_env() -> Result<Box<dyn MediaStore>, StorageError> {
+    match std::env::var("STORAGE_BACKEND").as_deref().unwrap_or("s3") {
+        "s3" => Ok(Box::new(S3Store::from_env()?)),
+        "fs" => Ok(Box::new(FsStore::from_env()?)),
+        backend => Err(StorageError::Config(format!("unknown backend `{backend}`"))),
     }
-    Ok(client.unwrap())
 }
 
-/// Performs connection to MinIO
+/// Returns the directory the chunked uploads are written to before they are stored
+///
+/// `<STORAGE_MEDIA>/uploads` if it is set, otherwise a directory in the temporary one,
+/// so it does not depend on the backend
+pub fn upload_dir() -> &'static Path {
+    UPLOAD_DIR.get_or_init(|| match std::env::var("STORAGE_MEDIA") {
+        Ok(media) => PathBuf::from(media).join("uploads"),
+        Err(_) => std::env::temp_dir().join("nexus-uploads"),
+    })
+}
+
+/// Sets up the storage backend
 ///
 /// Calls initializations inside to create mandatory buckets (folders)
-pub async fn minio_setup() {
+pub async fn storage_setup() {
     let mut stdout = stdout();
     execute!(stdout, cursor::Hide).unwrap();
 
-    let action = String::from("MinIO session ");
+    let action = String::from("Storage session ");
     let action_len = action.len() as u16;
 
     execute!(
@@ -54,58 +142,150 @@ pub async fn minio_setup() {
     )
     .unwrap();
 
-    let _client = get_client();
-    if let Ok(client) = _client {
+    let result = match store_from_env() {
         // create buckets
-        minio_init(&client).await;
-        execute!(
-            stdout,
-            MoveToColumn(action_len),
-            Clear(ClearType::UntilNewLine),
-            MoveToColumn(action_len),
-            SetForegroundColor(Color::Green),
-            Print("\tconnected\n"),
-            ResetColor,
-            cursor::Show
-        )
-        .unwrap();
-    } else {
-        execute!(
-            stdout,
-            MoveToColumn(action_len),
-            Clear(ClearType::UntilNewLine),
-            MoveToColumn(action_len),
-            SetForegroundColor(Color::Red),
-            Print("\tfailed\n"),
-            ResetColor,
-            cursor::Show
-        )
-        .unwrap();
-        log::error!("Exiting, due to: {err}", err = _client.unwrap_err());
-        process::exit(1);
+        Ok(store) => storage_init(store.as_ref()).await.map(|_| store),
+        Err(err) => Err(err),
+    };
+    let result = match result {
+        Ok(store) => tokio::fs::create_dir_all(upload_dir())
+            .await
+            .map(|_| store)
+            .map_err(StorageError::from),
+        Err(err) => Err(err),
+    };
+
+    match result {
+        Ok(store) => {
+            execute!(
+                stdout,
+                MoveToColumn(action_len),
+                Clear(ClearType::UntilNewLine),
+                MoveToColumn(action_len),
+                SetForegroundColor(Color::Green),
+                Print(format!("\tconnected ({})\n", store.name())),
+                ResetColor,
+                cursor::Show
+            )
+            .unwrap();
+            let _ = STORE.set(store);
+        }
+        Err(err) => {
+            execute!(
+                stdout,
+                MoveToColumn(action_len),
+                Clear(ClearType::UntilNewLine),
+                MoveToColumn(action_len),
+                SetForegroundColor(Color::Red),
+                Print("\tfailed\n"),
+                ResetColor,
+                cursor::Show
+            )
+            .unwrap();
+            log::error!("Exiting, due to: {err}");
+            process::exit(1);
+        }
     }
 }
 
-/// Necessary initialization for MinIO
-pub async fn minio_init(client: &Client) {
-    let buckets = MediaType::str_variants_vec();
-
-    for mut bucket in buckets {
+/// Necessary initialization of the store
+pub async fn storage_init(store: &dyn MediaStore) -> Result<(), StorageError> {
+    for mut bucket in MediaType::str_variants_vec() {
         bucket.push('s');
-        if !client
-            .bucket_exists(&BucketExistsArgs::new(&bucket).unwrap())
-            .await
-            .unwrap()
-        {
-            let res = client
-                .make_bucket(&MakeBucketArgs::new(&bucket).unwrap())
-                .await;
-            if res.is_err() {
-                log::error!(
-                    "Error while creating a bucket `{bucket}`: {err}",
-                    err = res.unwrap_err()
-                );
-            }
+        if let Err(err) = store.create_bucket(&bucket).await {
+            log::error!("Error while creating a bucket `{bucket}`: {err}");
+            return Err(err);
         }
     }
+    Ok(())
+}
+
+/// Returns the name of the bucket where the media of the type is stored
+pub fn bucket_name(media_type: MediaType) -> String {
+    let mut bucket = media_type.to_string().to_lowercase();
+    bucket.push('s');
+    bucket
+}
+
+/// Uploads the object to the bucket
+pub async fn put_object(bucket: &str, object: &str, data: Vec<u8>) -> Result<(), StorageError> {
+    let size = data.len();
+    store()
+        .put(bucket, object, &mut Cursor::new(data), size)
+        .await
+}
+
+/// Uploads the file to the bucket, the file is read part by part
+pub async fn put_object_file(bucket: &str, object: &str, path: &Path) -> Result<(), StorageError> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let size = file.metadata().await?.len() as usize;
+    store().put(bucket, object, &mut file, size).await
+}
+
+/// Returns the size of the object in bytes
+pub async fn object_size(bucket: &str, object: &str) -> Result<usize, StorageError> {
+    Ok(store().stat(bucket, object).await?.size)
+}
+
+/// Returns the metadata of the object
+pub async fn stat_object(bucket: &str, object: &str) -> Result<ObjectStat, StorageError> {
+    store().stat(bucket, object).await
+}
+
+/// Returns the URL that allows the method on the object for `expires` seconds
+pub async fn presign_object(
+    method: PresignMethod,
+    bucket: &str,
+    object: &str,
+    expires: u32,
+) -> Result<String, StorageError> {
+    store().presign(method, bucket, object, expires).await
+}
+
+/// Downloads `length` bytes of the object starting at `offset`
+pub async fn get_object_range(
+    bucket: &str,
+    object: &str,
+    offset: usize,
+    length: usize,
+) -> Result<Vec<u8>, StorageError> {
+    store().get_range(bucket, object, offset, length).await
+}
+
+/// Streams `length` bytes of the object starting at `offset` range by range,
+/// so the object is never held in memory as a whole
+pub fn stream_object_range(
+    bucket: String,
+    object: String,
+    offset: usize,
+    length: usize,
+) -> impl Stream<Item = Result<Vec<u8>, StorageError>> + Send + 'static {
+    let end = offset + length;
+    stream::try_unfold(offset, move |offset| {
+        let (bucket, object) = (bucket.clone(), object.clone());
+        async move {
+            if offset >= end {
+                return Ok(None);
+            }
+
+            let data =
+                get_object_range(&bucket, &object, offset, STREAM_RANGE.min(end - offset)).await?;
+            if data.is_empty() {
+                return Err(io::Error::from(ErrorKind::UnexpectedEof).into());
+            }
+            let next = offset + data.len();
+            Ok(Some((data, next)))
+        }
+    })
+}
+
+/// Downloads the whole object from the bucket
+pub async fn get_object(bucket: &str, object: &str) -> Result<Vec<u8>, StorageError> {
+    let size = store().stat(bucket, object).await?.size;
+    store().get_range(bucket, object, 0, size).await
+}
+
+/// Removes the object from the bucket
+pub async fn remove_object(bucket: &str, object: &str) -> Result<(), StorageError> {
+    store().delete(bucket, object).await
 }
@@ -0,0 +1,138 @@
+use std::{
+    io::{self, ErrorKind, SeekFrom},
+    path::PathBuf,
+};
+
+use async_trait::async_trait;
+use tokio::{
+    fs,
+    io::{AsyncRead, AsyncReadExt, AsyncSeekExt},
+};
+
+use crate::errors::storage::StorageError;
+
+use super::{MediaStore, ObjectStat, PresignMethod};
+
+/// Stores the objects as files in `<root>/<bucket>/<object>`
+///
+/// Meant for development and tests, so the server can run without MinIO
+pub struct FsStore {
+    root: PathBuf,
+}
+
+impl FsStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Stores the objects in the `STORAGE_MEDIA` directory
+    pub fn from_env() -> Result<Self, StorageError> {
+        std::env::var("STORAGE_MEDIA")
+            .map(Self::new)
+            .map_err(|_| StorageError::Config("`STORAGE_MEDIA` is not set".to_owned()))
+    }
+
+    /// Returns the path of the object, the names can not leave the root
+    fn object_path(&self, bucket: &str, object: &str) -> Result<PathBuf, StorageError> {
+        for name in [bucket, object] {
+            if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+                return Err(StorageError::InvalidKey(name.to_owned()));
+            }
+        }
+        Ok(self.root.join(bucket).join(object))
+    }
+}
+
+/// Missing files are missing objects
+fn io_error(e: io::Error) -> StorageError {
+    match e.kind() {
+        ErrorKind::NotFound => StorageError::NotFound,
+        _ => StorageError::Io(e),
+    }
+}
+
+#[async_trait]
+impl MediaStore for FsStore {
+    fn name(&self) -> &'static str {
+        "fs"
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<(), StorageError> {
+        fs::create_dir_all(self.root.join(bucket)).await?;
+        Ok(())
+    }
+
+    async fn put(
+        &self,
+        bucket: &str,
+        object: &str,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        size: usize,
+    ) -> Result<(), StorageError> {
+        let path = self.object_path(bucket, object)?;
+        // readers never see a partly written object
+        let part = path.with_file_name(format!(".{object}.part"));
+
+        let mut file = fs::File::create(&part).await.map_err(io_error)?;
+        let written = tokio::io::copy(&mut stream.take(size as u64), &mut file).await;
+        drop(file);
+
+        match written {
+            Ok(written) if written as usize == size => {
+                fs::rename(&part, &path).await?;
+                Ok(())
+            }
+            Ok(_) => {
+                let _ = fs::remove_file(&part).await;
+                Err(io::Error::from(ErrorKind::UnexpectedEof).into())
+            }
+            Err(e) => {
+                let _ = fs::remove_file(&part).await;
+                Err(e.into())
+            }
+        }
+    }
+
+    async fn get_range(
+        &self,
+        bucket: &str,
+        object: &str,
+        offset: usize,
+        length: usize,
+    ) -> Result<Vec<u8>, StorageError> {
+        let path = self.object_path(bucket, object)?;
+
+        let mut file = fs::File::open(&path).await.map_err(io_error)?;
+        file.seek(SeekFrom::Start(offset as u64)).await?;
+        let mut data = Vec::with_capacity(length);
+        file.take(length as u64).read_to_end(&mut data).await?;
+
+        Ok(data)
+    }
+
+    async fn delete(&self, bucket: &str, object: &str) -> Result<(), StorageError> {
+        match fs::remove_file(self.object_path(bucket, object)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    async fn stat(&self, bucket: &str, object: &str) -> Result<ObjectStat, StorageError> {
+        let path = self.object_path(bucket, object)?;
+        let metadata = fs::metadata(&path).await.map_err(io_error)?;
+        Ok(ObjectStat {
+            size: metadata.len() as usize,
+            content_type: None,
+        })
+    }
+
+    async fn presign(
+        &self,
+        _method: PresignMethod,
+        _bucket: &str,
+        _object: &str,
+        _expires: u32,
+    ) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported)
+    }
+}
@@ -0,0 +1,69 @@
+use std::sync::OnceLock;
+
+use nexuslib::models::{
+    file::{policy::UploadPolicy, upload::MAX_FILE_SIZE},
+    message::media::MediaType,
+};
+
+/// Storage quota of a user in bytes unless `STORAGE_QUOTA` is set
+const DEFAULT_STORAGE_QUOTA: u64 = 1024 * 1024 * 1024;
+
+const MEDIA_TYPES: [MediaType; 4] = [
+    MediaType::Audio,
+    MediaType::File,
+    MediaType::Image,
+    MediaType::Video,
+];
+
+static POLICIES: OnceLock<Vec<(MediaType, UploadPolicy)>> = OnceLock::new();
+static STORAGE_QUOTA: OnceLock<u64> = OnceLock::new();
+
+/// Returns the upload policy of the type
+///
+/// `UPLOAD_<TYPE>_MAX_SIZE` sets the size limit in bytes and `UPLOAD_<TYPE>_FORMATS`
+/// the allowed MIME types separated by commas, `*` allows any format
+pub fn upload_policy(media_type: MediaType) -> &'static UploadPolicy {
+    let policies = POLICIES.get_or_init(|| {
+        MEDIA_TYPES
+            .into_iter()
+            .map(|media_type| (media_type, policy_from_env(media_type)))
+            .collect()
+    });
+    policies
+        .iter()
+        .find(|(policy_type, _)| *policy_type == media_type)
+        .map(|(_, policy)| policy)
+        .unwrap()
+}
+
+/// Returns the number of bytes a user can store
+pub fn storage_quota() -> u64 {
+    *STORAGE_QUOTA.get_or_init(|| match std::env::var("STORAGE_QUOTA") {
+        Ok(quota) => quota.parse().unwrap_or_else(|_| {
+            log::warn!("Invalid `STORAGE_QUOTA`, using the default one");
+            DEFAULT_STORAGE_QUOTA
+        }),
+        Err(_) => DEFAULT_STORAGE_QUOTA,
+    })
+}
+
+fn policy_from_env(media_type: MediaType) -> UploadPolicy {
+    let prefix = format!("UPLOAD_{}", media_type.to_string().to_uppercase());
+    let mut policy = UploadPolicy::default_for(media_type);
+
+    if let Ok(max_size) = std::env::var(format!("{prefix}_MAX_SIZE")) {
+        match max_size.parse::<usize>() {
+            Ok(max_size) => policy.max_size = max_size.min(MAX_FILE_SIZE),
+            Err(_) => log::warn!("Invalid `{prefix}_MAX_SIZE`, using the default one"),
+        }
+    }
+    if let Ok(formats) = std::env::var(format!("{prefix}_FORMATS")) {
+        policy.formats = formats
+            .split(',')
+            .map(|mime| mime.trim().to_lowercase())
+            .filter(|mime| !mime.is_empty() && mime != "*")
+            .collect();
+    }
+
+    policy
+}
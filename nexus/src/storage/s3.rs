@@ -0,0 +1,235 @@
+use async_trait::async_trait;
+use minio::s3::{
+    args::{
+        AbortMultipartUploadArgs, BucketExistsArgs, CompleteMultipartUploadArgs,
+        CreateMultipartUploadArgs, GetObjectArgs, GetPresignedObjectUrlArgs, MakeBucketArgs,
+        PutObjectApiArgs, RemoveObjectArgs, StatObjectArgs, UploadPartArgs,
+    },
+    client::Client,
+    creds::StaticProvider,
+    error::Error,
+    http::BaseUrl,
+    types::Part,
+};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use warp::http::Method;
+
+use crate::errors::storage::StorageError;
+
+use super::{MediaStore, ObjectStat, PresignMethod};
+
+/// Size of the parts of the multipart uploads,
+/// smaller objects are uploaded with a single request
+const PART_SIZE: usize = 16 * 1024 * 1024;
+
+/// Stores the objects in an S3-compatible storage like MinIO
+pub struct S3Store {
+    client: Client,
+}
+
+impl S3Store {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+
+    /// Connects with `MINIO_HOST`, `MINIO_PORT`, `MINIO_ROOT_USER` and `MINIO_ROOT_PASSWORD`
+    pub fn from_env() -> Result<Self, StorageError> {
+        let endpoint = format!("http://{}:{}", env("MINIO_HOST")?, env("MINIO_PORT")?)
+            .parse::<BaseUrl>()
+            .map_err(s3_error)?;
+
+        let provider =
+            StaticProvider::new(&env("MINIO_ROOT_USER")?, &env("MINIO_ROOT_PASSWORD")?, None);
+
+        let client =
+            Client::new(endpoint, Some(Box::new(provider)), None, None).map_err(s3_error)?;
+        Ok(Self::new(client))
+    }
+
+    /// Uploads the stream part by part, returns the uploaded parts
+    async fn put_parts(
+        &self,
+        bucket: &str,
+        object: &str,
+        upload_id: &str,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        size: usize,
+    ) -> Result<Vec<Part>, StorageError> {
+        let mut parts = Vec::new();
+        let mut buffer = vec![0; PART_SIZE];
+        let mut sent = 0;
+
+        while sent < size {
+            let len = PART_SIZE.min(size - sent);
+            stream.read_exact(&mut buffer[..len]).await?;
+
+            let number = parts.len() as u16 + 1;
+            let args = UploadPartArgs::new(bucket, object, upload_id, number, &buffer[..len])
+                .map_err(s3_error)?;
+            let response = self.client.upload_part(&args).await.map_err(s3_error)?;
+
+            parts.push(Part {
+                number,
+                etag: response.etag,
+            });
+            sent += len;
+        }
+
+        Ok(parts)
+    }
+}
+
+fn env(name: &str) -> Result<String, StorageError> {
+    std::env::var(name).map_err(|_| StorageError::Config(format!("`{name}` is not set")))
+}
+
+fn s3_error(err: Error) -> StorageError {
+    match err {
+        Error::S3Error(response) if response.code == "NoSuchKey" => StorageError::NotFound,
+        err => StorageError::S3(err.to_string()),
+    }
+}
+
+#[async_trait]
+impl MediaStore for S3Store {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    async fn create_bucket(&self, bucket: &str) -> Result<(), StorageError> {
+        let exists = self
+            .client
+            .bucket_exists(&BucketExistsArgs::new(bucket).map_err(s3_error)?)
+            .await
+            .map_err(s3_error)?;
+
+        if !exists {
+            self.client
+                .make_bucket(&MakeBucketArgs::new(bucket).map_err(s3_error)?)
+                .await
+                .map_err(s3_error)?;
+        }
+        Ok(())
+    }
+
+    async fn put(
+        &self,
+        bucket: &str,
+        object: &str,
+        stream: &mut (dyn AsyncRead + Unpin + Send),
+        size: usize,
+    ) -> Result<(), StorageError> {
+        if size <= PART_SIZE {
+            let mut data = vec![0; size];
+            stream.read_exact(&mut data).await?;
+
+            let args = PutObjectApiArgs::new(bucket, object, &data).map_err(s3_error)?;
+            self.client.put_object_api(&args).await.map_err(s3_error)?;
+            return Ok(());
+        }
+
+        let upload_id = self
+            .client
+            .create_multipart_upload(
+                &CreateMultipartUploadArgs::new(bucket, object).map_err(s3_error)?,
+            )
+            .await
+            .map_err(s3_error)?
+            .upload_id;
+
+        match self
+            .put_parts(bucket, object, &upload_id, stream, size)
+            .await
+        {
+            Ok(parts) => {
+                let args = CompleteMultipartUploadArgs::new(bucket, object, &upload_id, &parts)
+                    .map_err(s3_error)?;
+                self.client
+                    .complete_multipart_upload(&args)
+                    .await
+                    .map_err(s3_error)?;
+                Ok(())
+            }
+            Err(e) => {
+                // the uploaded parts are removed
+                if let Ok(args) = AbortMultipartUploadArgs::new(bucket, object, &upload_id) {
+                    let _ = self.client.abort_multipart_upload(&args).await;
+                }
+                Err(e)
+            }
+        }
+    }
+
+    async fn get_range(
+        &self,
+        bucket: &str,
+        object: &str,
+        offset: usize,
+        length: usize,
+    ) -> Result<Vec<u8>, StorageError> {
+        if length == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut args = GetObjectArgs::new(bucket, object).map_err(s3_error)?;
+        args.offset = Some(offset);
+        args.length = Some(length);
+
+        let response = self.client.get_object(&args).await.map_err(s3_error)?;
+        let bytes = response
+            .bytes()
+            .await
+            .map_err(|err| s3_error(Error::from(err)))?;
+
+        Ok(bytes.to_vec())
+    }
+
+    async fn delete(&self, bucket: &str, object: &str) -> Result<(), StorageError> {
+        self.client
+            .remove_object(&RemoveObjectArgs::new(bucket, object).map_err(s3_error)?)
+            .await
+            .map_err(s3_error)?;
+        Ok(())
+    }
+
+    async fn stat(&self, bucket: &str, object: &str) -> Result<ObjectStat, StorageError> {
+        let stat = self
+            .client
+            .stat_object(&StatObjectArgs::new(bucket, object).map_err(s3_error)?)
+            .await
+            .map_err(s3_error)?;
+
+        let content_type = stat
+            .headers
+            .get("content-type")
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        Ok(ObjectStat {
+            size: stat.size,
+            content_type,
+        })
+    }
+
+    async fn presign(
+        &self,
+        method: PresignMethod,
+        bucket: &str,
+        object: &str,
+        expires: u32,
+    ) -> Result<String, StorageError> {
+        let method = match method {
+            PresignMethod::Get => Method::GET,
+            PresignMethod::Put => Method::PUT,
+        };
+
+        let mut args = GetPresignedObjectUrlArgs::new(bucket, object, method).map_err(s3_error)?;
+        args.expiry_seconds = Some(expires);
+
+        let response = self
+            .client
+            .get_presigned_object_url(&args)
+            .await
+            .map_err(s3_error)?;
+        Ok(response.url)
+    }
+}
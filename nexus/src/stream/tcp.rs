@@ -12,23 +12,22 @@ use crossterm::{
     style::{Color, Print, ResetColor, SetAttribute, SetForegroundColor},
     terminal::{Clear, ClearType},
 };
-use scylla::Session;
 use tokio::{net::TcpListener, sync::Mutex};
 
-use crate::state::connection::ConnectionState;
+use crate::{db::Database, state::connection::ConnectionState};
 
 use super::tcp_handler::handle_stream;
 
-pub async fn run_tcp(session: Arc<Mutex<Session>>, state: Arc<Mutex<ConnectionState>>) {
+pub async fn run_tcp(db: Arc<Database>, state: Arc<Mutex<ConnectionState>>) {
     let listener = tcp_listener_setup().await.unwrap();
 
     // TCP Listener
     while let Ok((stream, socket)) = listener.accept().await {
-        let session: Arc<Mutex<Session>> = session.clone();
+        let db = db.clone();
         let state = Arc::clone(&state);
 
         tokio::spawn(async move {
-            match handle_stream(stream, socket, session, state).await {
+            match handle_stream(stream, socket, db, state).await {
                 Ok(_) => (),
                 Err(_) => {
                     log::error!("Error handling request");
@@ -2,13 +2,12 @@ use std::{error::Error, net::SocketAddr, sync::Arc};
 
 use futures::{SinkExt, StreamExt};
 use hashbrown::HashMap;
-use scylla::Session;
 use tokio::{net::TcpStream, sync::Mutex};
 use uuid::Uuid;
 
 use nexuslib::{
     errors::stream::StreamError,
-    models::command::Command,
+    models::{command::Command, file::chunk::CHUNK_SIZE},
     request::{EmptyRequestBody, Request},
     response::{Response, ResponseStatus, ResponseStatusCode},
 };
@@ -16,13 +15,23 @@ use tokio_util::codec::{Framed, LinesCodec};
 
 use crate::{
     api::{filters::auth::check_token, handlers::users::get_uuid_by_token},
-    ops::{call::connect_call, file::stream_file, message::send_message},
+    db::Database,
+    errors::jwt::JWTError,
+    ops::{
+        call::{connect_call, deliver_missed_calls, drop_peer_calls},
+        file::upload_file,
+        message::send_message,
+        room::{connect_room, drop_peer_rooms},
+    },
     state::{
         connection::{ConnectionState, SessionSocket},
         peer::Peer,
     },
 };
 
+/// Longest accepted line, the chunks of uploads are the largest requests
+const MAX_LINE_LEN: usize = 2 * CHUNK_SIZE + 4 * 1024;
+
 /// This function handles stream and peer.
 ///
 /// It sends and receives the messages.
@@ -31,11 +40,12 @@ use crate::{
 pub async fn handle_stream(
     stream: TcpStream,
     socket_addr: SocketAddr,
-    session: Arc<Mutex<Session>>,
+    db: Arc<Database>,
     state: Arc<Mutex<ConnectionState>>,
 ) -> Result<(), StreamError> {
     // this will allow to process lines instead of bytes in a stream
-    let mut lines: Framed<TcpStream, LinesCodec> = Framed::new(stream, LinesCodec::new());
+    let mut lines: Framed<TcpStream, LinesCodec> =
+        Framed::new(stream, LinesCodec::new_with_max_length(MAX_LINE_LEN));
     // reading initial request
     let buf = match lines.next().await {
         // received something
@@ -49,8 +59,18 @@ pub async fn handle_stream(
     let req_empty: Request<EmptyRequestBody> = serde_json::from_str(&buf).unwrap();
     let token = req_empty.token;
 
+    // checking the token and whether the user is restricted
+    if let Err(e) = check_token(db.clone(), &token).await {
+        let reply = match e {
+            JWTError::Restricted => e.to_string(),
+            _ => "Invalid JWT".to_string(),
+        };
+        lines.send(reply).await.unwrap();
+        return Ok(());
+    }
+
     // getting uuid of the user
-    let user_uuid = get_uuid_by_token(session.clone(), &token).await;
+    let user_uuid = get_uuid_by_token(db.clone(), &token).await;
     if user_uuid.is_err() {
         // if error => send it to the client and cancel the stream
         lines.send("Invalid JWT").await.unwrap();
@@ -66,13 +86,20 @@ pub async fn handle_stream(
         .await
         .unwrap();
 
+    // the calls missed while the user was offline
+    if let Err(e) = deliver_missed_calls(db.clone(), state.clone(), user_uuid, peer_uuid).await {
+        log::error!("Error delivering missed calls: {e:?}");
+    }
+
     // infinite loop to sustain stream between server and client
     loop {
         tokio::select! {
             // received message from peer
-            Some(msg) = peer.rx.recv() => {
+            msg = peer.rx.recv() => match msg {
                 // send the message to the receiver
-                peer.lines.send(&msg).await.unwrap();
+                Some(msg) => peer.lines.send(&msg).await.unwrap(),
+                // the session was dropped from the state (e.g. account deletion)
+                None => break,
             },
             // received message from user
             result = peer.lines.next() => match result {
@@ -83,24 +110,17 @@ pub async fn handle_stream(
 
                     // verifying whether the token is valid
                     // TODO: check this method
-                    let token_verify = check_token(session.clone(), &req_empty.token).await;
+                    let token_verify = check_token(db.clone(), &req_empty.token).await;
                     if token_verify.is_err() {
-                        return Ok(());
+                        break;
                     }
 
                     // matches the operation from command
                     match req_command {
-                        Command::Message => send_message((msg, peer.peer_uuid), session.clone(), state.clone()).await.unwrap(),
-                        Command::Call => connect_call(msg, session.clone(), state.clone(), peer_uuid).await.unwrap(),
-                        Command::File => {
-                            let stream = peer.lines.into_inner();
-                            let stream = stream_file(stream, msg, session.clone(), state.clone(), peer_uuid)
-                                .await
-                                .unwrap()
-                                .into_inner();
-                            let lines: Framed<TcpStream, LinesCodec> = Framed::new(stream, LinesCodec::new());
-                            peer.lines = lines;
-                        },
+                        Command::Message => send_message(msg, db.clone(), state.clone(), user_uuid, peer_uuid).await.unwrap(),
+                        Command::Call => connect_call(msg, db.clone(), state.clone(), user_uuid, peer_uuid).await.unwrap(),
+                        Command::Room => connect_room(msg, db.clone(), state.clone(), user_uuid, peer_uuid).await.unwrap(),
+                        Command::File => upload_file(msg, db.clone(), state.clone(), user_uuid, peer_uuid).await.unwrap(),
                     }
                 },
                 // error receiving a message
@@ -118,6 +138,10 @@ pub async fn handle_stream(
         remove_peer(state.clone(), user_uuid, peer_uuid).await;
     }
 
+    // the calls of the closed session cannot go on
+    drop_peer_calls(db.clone(), state.clone(), user_uuid, peer_uuid).await;
+    drop_peer_rooms(db, state, user_uuid, peer_uuid).await;
+
     Ok(())
 }
 
@@ -184,12 +208,8 @@ async fn add_peer(
 /// - User UUID
 /// - Token
 async fn remove_peer(state: Arc<Mutex<ConnectionState>>, user_uuid: Uuid, session_id: Uuid) {
-    state
-        .clone()
-        .lock()
-        .await
-        .peers
-        .get_mut(&user_uuid)
-        .unwrap()
-        .remove(&session_id);
+    // the user entry may already be gone if the sessions were dropped from the state
+    if let Some(sessions) = state.lock().await.peers.get_mut(&user_uuid) {
+        sessions.remove(&session_id);
+    }
 }
@@ -1,53 +1,241 @@
 use std::{error::Error, net::SocketAddr, sync::Arc};
 
-use tokio::{
-    net::UdpSocket,
-    sync::{mpsc, Mutex},
-};
+use tokio::{net::UdpSocket, sync::Mutex};
 
-use nexuslib::models::call::media_call::MediaCall;
+use chrono::Utc;
+use nexuslib::{
+    media::{
+        rtcp::RtcpPacket,
+        rtp::{is_rtcp, is_rtp_or_rtcp, RtpHeader},
+        srtp::srtcp_plaintext,
+        stun::{is_stun, StunMessage, StunType},
+    },
+    models::call::packet::{MediaHeader, PacketKind, MAX_DATAGRAM_LEN, MEDIA_HEADER_LEN},
+    request::{index_token::RoomToken, room::RoomRequest},
+};
+use uuid::Uuid;
 
 use crate::state::connection::ConnectionState;
 
 /// Handles UDP stream for calls
+///
+/// Runs for the whole lifetime of the server. STUN binding requests are
+/// answered, so the clients learn their addresses behind NAT.
+/// A participant of a call first sends a `Bind` packet with
+/// the token of its `RelayTicket`, after that its
+/// SRTP/SRTCP packets are forwarded to the other participants of the call
+/// as they are. The relay can not decrypt the media, it only reads
+/// the reports of the SRTCP packets that are sent unencrypted
+/// and the audio levels of the SRTP headers
 pub async fn handle_udp(
     sock: UdpSocket,
     state: Arc<Mutex<ConnectionState>>,
 ) -> Result<(), Box<dyn Error>> {
-    let r = Arc::new(sock);
-    let s = r.clone();
-    let (tx, mut rx) = mpsc::channel::<(MediaCall, SocketAddr)>(1_000);
-
-    let mut buf = [0; 1024];
-    tokio::select! {
-        // received a call from a peer
-        Some((call, addr)) = rx.recv() => {
-            // transmit the call frame to the session receiver
-            let len = s.send_to(&call.message, addr).await.unwrap();
-            println!("{:?} bytes sent", len);
-        }
-        // received a call frame from a user
-        result = r.recv_from(&mut buf) => match result {
-            Ok((len, addr)) => {
-                println!("{:?} bytes received from {:?}", len, addr);
-
-                // deserializing call and extracting the receiver
-                let call = MediaCall::from_bytes(buf.to_vec());
-                let receiver = &call.sides.get_receiver().to_owned();
-                let receiver_peer = &call.peers.get_receiver().to_owned().unwrap();
-                let recv_addr = state.lock().await.peers
-                                    .get(receiver).unwrap()
-                                    .get(receiver_peer).unwrap()
-                                    .socket_addr;
-
-                // sending the call frame to the receiver session peer
-                tx.send((call.clone(), recv_addr)).await.unwrap();
-            }
+    // one byte more, so a datagram that does not fit is noticed
+    let mut buf = [0; MAX_DATAGRAM_LEN + 1];
+
+    loop {
+        let (len, addr) = match sock.recv_from(&mut buf).await {
+            Ok(received) => received,
             Err(e) => {
                 log::error!("Got error while receiving UDP Stream! {e}");
-            },
+                continue;
+            }
+        };
+
+        // larger datagrams are truncated by the socket, they are not relayed
+        if len > MAX_DATAGRAM_LEN {
+            log::debug!("Dropped an oversized datagram from {addr}");
+            continue;
+        }
+        let datagram = &buf[..len];
+
+        // the address a binding request came from is
+        // the server reflexive candidate of the client
+        if is_stun(datagram) {
+            answer_binding(&sock, datagram, addr).await;
+            continue;
+        }
+
+        let targets = match is_rtp_or_rtcp(datagram) {
+            true => {
+                let call = match state.lock().await.relay_endpoints.get(&addr) {
+                    Some(call) => *call,
+                    // the source is not bound
+                    None => continue,
+                };
+                if is_rtcp(datagram) {
+                    record_reports(&state, &call, datagram, addr).await;
+                } else {
+                    detect_speaker(&state, &call, datagram, addr).await;
+                }
+                relay_targets(&state, &call, len, addr).await
+            }
+            false => {
+                let header = match MediaHeader::decode(datagram) {
+                    Some(header) => header,
+                    // not a relay datagram
+                    None => continue,
+                };
+
+                match header.kind {
+                    PacketKind::Bind => {
+                        let token = &datagram[MEDIA_HEADER_LEN..];
+                        if bind(&state, &header, token, addr).await {
+                            // the packet is echoed as an acknowledgement
+                            if let Err(e) = sock.send_to(datagram, addr).await {
+                                log::error!("Failed to acknowledge the bind of {addr}: {e}");
+                            }
+                        }
+                        continue;
+                    }
+                    PacketKind::Media => relay_targets(&state, &header.call, len, addr).await,
+                }
+            }
+        };
+
+        for target in targets {
+            if let Err(e) = sock.send_to(datagram, target).await {
+                log::error!("Failed to relay to {target}: {e}");
+            }
+        }
+    }
+}
+
+/// Answers the STUN binding request with the address it came from
+async fn answer_binding(sock: &UdpSocket, datagram: &[u8], addr: SocketAddr) {
+    let request = match StunMessage::decode(datagram) {
+        Ok(request) if request.kind == StunType::BindingRequest => request,
+        _ => return,
+    };
+
+    let response = StunMessage::binding_success(&request, addr).encode(None);
+    if let Err(e) = sock.send_to(&response, addr).await {
+        log::error!("Failed to answer the binding request of {addr}: {e}");
+    }
+}
+
+/// Registers the source address for the participant that owns the token
+async fn bind(
+    state: &Arc<Mutex<ConnectionState>>,
+    header: &MediaHeader,
+    token: &[u8],
+    addr: SocketAddr,
+) -> bool {
+    let bound = state.lock().await.bind_relay(&header.call, token, addr);
+    if !bound {
+        log::debug!("Rejected bind to the call {} from {addr}", header.call);
+    }
+    bound
+}
+
+/// Stores the reports of the SRTCP packet sent by a participant
+/// and takes the round trip time from them
+///
+/// Encrypted packets are skipped since the relay can not read them
+async fn record_reports(
+    state: &Arc<Mutex<ConnectionState>>,
+    call: &Uuid,
+    datagram: &[u8],
+    addr: SocketAddr,
+) {
+    let packets = match srtcp_plaintext(datagram).map(RtcpPacket::decode_compound) {
+        Some(Ok(packets)) => packets,
+        _ => return,
+    };
+
+    let mut state = state.lock().await;
+    let relay = match state.relays.get_mut(call) {
+        Some(relay) => relay,
+        None => return,
+    };
+    let reporter = match relay.participant_at(&addr) {
+        Some(reporter) => reporter,
+        None => return,
+    };
+
+    let now_ms = Utc::now().timestamp_millis() as u64;
+    for packet in packets {
+        // the receivers refer to the SR in their reports, which gives the RTT
+        if let RtcpPacket::SenderReport { ssrc, info, .. } = &packet {
+            relay
+                .metrics
+                .record_sender_report(*ssrc, info.ntp_timestamp, now_ms);
         }
+        relay.record_reports(reporter, packet.reports(), now_ms);
     }
+}
 
-    Ok(())
+/// Updates the active speaker of the room by the audio level of the packet
+///
+/// The participants of the room are notified over TCP when the speaker changes
+async fn detect_speaker(
+    state: &Arc<Mutex<ConnectionState>>,
+    room: &Uuid,
+    datagram: &[u8],
+    addr: SocketAddr,
+) {
+    // the header of an SRTP packet is not encrypted
+    let level = match RtpHeader::decode(datagram).map(|(header, _)| header.audio_level()) {
+        Ok(Some(level)) => level,
+        _ => return,
+    };
+
+    let mut state = state.lock().await;
+    if !state.rooms.contains_key(room) {
+        return;
+    }
+    let now_ms = Utc::now().timestamp_millis() as u64;
+    let speaker = match state
+        .relays
+        .get_mut(room)
+        .and_then(|relay| relay.record_level(&addr, level, now_ms))
+    {
+        Some(speaker) => speaker,
+        None => return,
+    };
+
+    let active = state.rooms.get_mut(room).unwrap();
+    active.room.speaker = Some(speaker);
+    let request = RoomRequest::new(active.room.clone(), RoomToken::Speaker);
+    let request_str = serde_json::to_string(&request).unwrap();
+
+    let active = &state.rooms[room];
+    for (user, peer) in active.peers.iter() {
+        if let Some(socket) = state
+            .peers
+            .get(user)
+            .and_then(|sessions| sessions.get(peer))
+        {
+            let _ = socket.tcp_sender.send(request_str.clone());
+        }
+    }
+}
+
+/// Returns the addresses of the other participants and counts the datagram
+///
+/// Datagrams of unknown calls and of sources that are not bound are dropped
+async fn relay_targets(
+    state: &Arc<Mutex<ConnectionState>>,
+    call: &Uuid,
+    len: usize,
+    addr: SocketAddr,
+) -> Vec<SocketAddr> {
+    let mut state = state.lock().await;
+    let relay = match state.relays.get_mut(call) {
+        Some(relay) => relay,
+        None => return Vec::new(),
+    };
+
+    match relay.targets(&addr) {
+        Some(targets) => {
+            let now_ms = Utc::now().timestamp_millis() as u64;
+            relay.record_forward(&addr, &targets, len, now_ms);
+            targets
+        }
+        None => {
+            relay.dropped += 1;
+            Vec::new()
+        }
+    }
 }
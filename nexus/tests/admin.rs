@@ -0,0 +1,91 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::hyper::StatusCode;
+
+use nexus::{
+    api::handlers::admin::{ban_user, lift_restriction, suspend_user},
+    db::Database,
+    state::connection::ConnectionState,
+};
+use nexuslib::{
+    models::{
+        moderation::RestrictionKind,
+        user::{role::Role, User},
+    },
+    request::admin::RestrictionRequest,
+};
+
+async fn add_user(db: &Database, username: &str, role: Role) -> Uuid {
+    let (user, secret) = User::new(username, "password123", Some(role));
+    db.users.create(&user, &secret).await.unwrap();
+    user.uuid
+}
+
+fn request(expires_at: Option<i64>) -> RestrictionRequest {
+    RestrictionRequest {
+        reason: "spam".to_owned(),
+        expires_at,
+    }
+}
+
+fn state() -> Arc<Mutex<ConnectionState>> {
+    Arc::new(Mutex::new(ConnectionState::new()))
+}
+
+#[tokio::test]
+async fn moderator_cannot_replace_ban_with_suspension() {
+    let db = Arc::new(Database::memory());
+    let admin = add_user(&db, "admin", Role::Admin).await;
+    let moderator = add_user(&db, "moderator", Role::Moderator).await;
+    let user = add_user(&db, "user", Role::User).await;
+
+    let response = ban_user(user.to_string(), admin, request(None), db.clone(), state())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    let expires_at = Some(Utc::now().timestamp() + 60);
+    let response = suspend_user(
+        user.to_string(),
+        moderator,
+        request(expires_at),
+        db.clone(),
+        state(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let restriction = db.users.restriction(&user).await.unwrap().unwrap();
+    assert_eq!(restriction.kind, RestrictionKind::Banned);
+    assert_eq!(restriction.expires_at, None);
+
+    let response = lift_restriction(user.to_string(), moderator, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn moderator_can_extend_suspension() {
+    let db = Arc::new(Database::memory());
+    let moderator = add_user(&db, "moderator", Role::Moderator).await;
+    let user = add_user(&db, "user", Role::User).await;
+
+    for minutes in [1, 5] {
+        let expires_at = Some(Utc::now().timestamp() + minutes * 60);
+        let response = suspend_user(
+            user.to_string(),
+            moderator,
+            request(expires_at),
+            db.clone(),
+            state(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
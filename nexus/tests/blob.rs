@@ -0,0 +1,198 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+
+use nexus::{
+    db::Database,
+    errors::storage::StorageError,
+    ops::{
+        blob::{collect_garbage, discard_object, reuse_blob, share_object, unlink_media},
+        file::{add_file, object_name},
+    },
+    storage::{bucket_name, object_size, put_object},
+};
+use nexuslib::{
+    crypto::hasher::get_bytes_hash,
+    models::{file::media_file::MediaFile, message::media::MediaType, user::role::Role},
+};
+
+mod common;
+
+use common::{add_user, setup_storage};
+
+fn media_file(content: &[u8], sender: Uuid, secret: bool) -> MediaFile {
+    MediaFile::new(
+        Uuid::new_v4(),
+        content.len(),
+        "photo.png".to_owned(),
+        MediaType::Image,
+        secret,
+        sender,
+        get_bytes_hash(content),
+    )
+}
+
+/// Uploads the file as the chunked and the presigned uploads do, returns its object
+async fn upload(db: &Arc<Database>, file: &MediaFile, content: &[u8]) -> String {
+    let object = object_name(file, &Uuid::new_v4());
+    put_object(&bucket_name(file.media_type), &object, content.to_vec())
+        .await
+        .unwrap();
+
+    let object = share_object(db.clone(), file, &object).await.unwrap();
+    add_file(db.clone(), file, &object, file.sender)
+        .await
+        .unwrap();
+    object
+}
+
+async fn refs(db: &Database, file: &MediaFile) -> i32 {
+    db.media
+        .blob(&bucket_name(file.media_type), &file.hash)
+        .await
+        .unwrap()
+        .refs
+}
+
+async fn is_stored(file: &MediaFile, object: &str) -> bool {
+    match object_size(&bucket_name(file.media_type), object).await {
+        Ok(_) => true,
+        Err(StorageError::NotFound) => false,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+#[tokio::test]
+async fn same_content_shares_one_object() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let (alice, bob) = (
+        add_user(&db, "alice", Role::User).await,
+        add_user(&db, "bob", Role::User).await,
+    );
+    let content = b"the same picture".to_vec();
+
+    let first = media_file(&content, alice, false);
+    let object = upload(&db, &first, &content).await;
+    assert_eq!(refs(&db, &first).await, 1);
+
+    // bob can not reuse the content of alice by its hash, his upload is dropped instead
+    let second = media_file(&content, bob, false);
+    assert_eq!(reuse_blob(db.clone(), &second, bob).await.unwrap(), None);
+    let uploaded = object_name(&second, &Uuid::new_v4());
+    put_object(&bucket_name(second.media_type), &uploaded, content.clone())
+        .await
+        .unwrap();
+    let shared = share_object(db.clone(), &second, &uploaded).await.unwrap();
+    add_file(db.clone(), &second, &shared, bob).await.unwrap();
+
+    assert_eq!(shared, object);
+    assert_eq!(refs(&db, &first).await, 2);
+    assert!(is_stored(&first, &object).await);
+    assert!(!is_stored(&second, &uploaded).await);
+    assert_eq!(db.media.get(&second.uuid).await.unwrap().path, object);
+
+    // alice already has the content, so nothing is uploaded
+    let third = media_file(&content, alice, false);
+    assert_eq!(
+        reuse_blob(db.clone(), &third, alice).await.unwrap(),
+        Some(object)
+    );
+    assert_eq!(refs(&db, &first).await, 3);
+}
+
+#[tokio::test]
+async fn discarded_media_keeps_the_object() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let alice = add_user(&db, "alice", Role::User).await;
+    let content = b"a picture sent twice".to_vec();
+
+    let first = media_file(&content, alice, false);
+    let object = upload(&db, &first, &content).await;
+
+    // the media of the second upload can not be added, e.g. its UUID is taken
+    let mut second = media_file(&content, alice, false);
+    second.uuid = first.uuid;
+    let uploaded = object_name(&second, &Uuid::new_v4());
+    put_object(&bucket_name(second.media_type), &uploaded, content.clone())
+        .await
+        .unwrap();
+    let shared = share_object(db.clone(), &second, &uploaded).await.unwrap();
+    assert_eq!(refs(&db, &first).await, 2);
+    assert!(add_file(db.clone(), &second, &shared, alice).await.is_err());
+    discard_object(db.clone(), &second, &shared).await;
+
+    assert_eq!(refs(&db, &first).await, 1);
+    assert_eq!(collect_garbage(db.clone()).await.unwrap(), 0);
+    assert!(is_stored(&first, &object).await);
+    assert_eq!(db.media.get(&first.uuid).await.unwrap().path, object);
+    assert_eq!(
+        db.media.blob_of(&first.uuid).await.unwrap(),
+        Some((bucket_name(first.media_type), first.hash.clone()))
+    );
+}
+
+#[tokio::test]
+async fn garbage_collection_removes_only_unreferenced_blobs() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let alice = add_user(&db, "alice", Role::User).await;
+    let (kept_content, removed_content) = (b"kept".to_vec(), b"removed".to_vec());
+
+    let kept = [
+        media_file(&kept_content, alice, false),
+        media_file(&kept_content, alice, false),
+    ];
+    let kept_object = upload(&db, &kept[0], &kept_content).await;
+    upload(&db, &kept[1], &kept_content).await;
+    let removed = media_file(&removed_content, alice, false);
+    let removed_object = upload(&db, &removed, &removed_content).await;
+
+    assert!(unlink_media(db.clone(), kept[0].uuid).await.unwrap());
+    assert!(unlink_media(db.clone(), removed.uuid).await.unwrap());
+    // the media without a blob is not released twice
+    assert!(!unlink_media(db.clone(), removed.uuid).await.unwrap());
+    assert_eq!(refs(&db, &kept[0]).await, 1);
+    assert_eq!(refs(&db, &removed).await, 0);
+
+    assert_eq!(collect_garbage(db.clone()).await.unwrap(), 1);
+    assert!(is_stored(&kept[0], &kept_object).await);
+    assert!(!is_stored(&removed, &removed_object).await);
+    assert!(db
+        .media
+        .blob(&bucket_name(removed.media_type), &removed.hash)
+        .await
+        .is_err());
+    assert_eq!(collect_garbage(db).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn secret_files_are_never_shared() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let alice = add_user(&db, "alice", Role::User).await;
+    let content = b"ciphertext".to_vec();
+
+    let plain = media_file(&content, alice, false);
+    upload(&db, &plain, &content).await;
+
+    let secret = media_file(&content, alice, true);
+    assert_eq!(reuse_blob(db.clone(), &secret, alice).await.unwrap(), None);
+    let object = upload(&db, &secret, &content).await;
+    assert!(is_stored(&secret, &object).await);
+    assert_ne!(db.media.get(&plain.uuid).await.unwrap().path, object);
+    assert_eq!(refs(&db, &plain).await, 1);
+    assert_eq!(db.media.blob_of(&secret.uuid).await.unwrap(), None);
+
+    // the object of a secret file is removed at once
+    let other = media_file(&content, alice, true);
+    let uploaded = object_name(&other, &Uuid::new_v4());
+    put_object(&bucket_name(other.media_type), &uploaded, content.clone())
+        .await
+        .unwrap();
+    discard_object(db.clone(), &other, &uploaded).await;
+    assert!(!is_stored(&other, &uploaded).await);
+    assert!(is_stored(&secret, &object).await);
+    assert_eq!(refs(&db, &plain).await, 1);
+}
@@ -0,0 +1,124 @@
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::sync::{mpsc::UnboundedReceiver, Mutex};
+use uuid::Uuid;
+
+use nexus::{db::Database, ops::call::connect_call, state::connection::ConnectionState};
+use nexuslib::{
+    models::{
+        call::{media_call::MediaCall, state::CallState},
+        command::Command,
+    },
+    request::{call::CallRequest, index_token::IndexToken, Request},
+};
+
+mod common;
+
+use common::connect;
+
+async fn send(
+    db: &Arc<Database>,
+    state: &Arc<Mutex<ConnectionState>>,
+    call: &MediaCall,
+    token: IndexToken,
+    (user, peer): (Uuid, Uuid),
+) {
+    let request = Request::new(
+        Command::Call,
+        CallRequest::new(call.clone(), token),
+        String::new(),
+    );
+    connect_call(
+        serde_json::to_string(&request).unwrap(),
+        db.clone(),
+        state.clone(),
+        user,
+        peer,
+    )
+    .await
+    .unwrap();
+}
+
+/// Returns the last message sent to the session
+fn last_message(rx: &mut UnboundedReceiver<String>) -> Option<String> {
+    let mut last = None;
+    while let Ok(msg) = rx.try_recv() {
+        last = Some(msg);
+    }
+    last
+}
+
+#[tokio::test]
+async fn caller_in_a_call_cannot_start_another() {
+    let db = Arc::new(Database::memory());
+    let state = Arc::new(Mutex::new(ConnectionState::new()));
+    let (alice, bob, carol) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let (alice_peer, mut alice_rx) = connect(&state, alice).await;
+    let (bob_peer, mut bob_rx) = connect(&state, bob).await;
+    connect(&state, carol).await;
+
+    let call = MediaCall::new(alice, bob, vec![], vec![], false);
+    send(&db, &state, &call, IndexToken::Start, (alice, alice_peer)).await;
+    assert_eq!(
+        state.lock().await.calls[&call.uuid].state,
+        CallState::Ringing
+    );
+
+    // the caller is ringing, the callee is being called
+    let other = MediaCall::new(alice, carol, vec![], vec![], false);
+    send(&db, &state, &other, IndexToken::Start, (alice, alice_peer)).await;
+    assert!(last_message(&mut alice_rx)
+        .unwrap()
+        .contains("Already in another call"));
+
+    let other = MediaCall::new(bob, carol, vec![], vec![], false);
+    send(&db, &state, &other, IndexToken::Start, (bob, bob_peer)).await;
+    assert!(last_message(&mut bob_rx)
+        .unwrap()
+        .contains("Already in another call"));
+
+    let state = state.lock().await;
+    assert_eq!(state.calls.len(), 1);
+    assert!(!state.in_call(&carol));
+}
+
+#[tokio::test]
+async fn relay_is_allocated_once_on_accept() {
+    let db = Arc::new(Database::memory());
+    let state = Arc::new(Mutex::new(ConnectionState::new()));
+    let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    let (alice_peer, _alice_rx) = connect(&state, alice).await;
+    let (bob_peer, _bob_rx) = connect(&state, bob).await;
+
+    let call = MediaCall::new(alice, bob, vec![], vec![], false);
+    send(&db, &state, &call, IndexToken::Start, (alice, alice_peer)).await;
+    assert!(!state.lock().await.relays.contains_key(&call.uuid));
+
+    send(&db, &state, &call, IndexToken::Accept, (bob, bob_peer)).await;
+    let token = {
+        let mut state = state.lock().await;
+        let relay = state.relays.get_mut(&call.uuid).unwrap();
+        let token = relay.token_of(&alice).unwrap();
+        assert!(relay
+            .bind(&token, SocketAddr::from(([127, 0, 0, 1], 1000)))
+            .is_some());
+        token
+    };
+
+    // a repeated accept is refused and keeps the binding
+    send(&db, &state, &call, IndexToken::Accept, (bob, bob_peer)).await;
+    {
+        let state = state.lock().await;
+        let relay = &state.relays[&call.uuid];
+        assert_eq!(relay.token_of(&alice), Some(token));
+        assert_eq!(
+            relay.participant_at(&SocketAddr::from(([127, 0, 0, 1], 1000))),
+            Some(alice)
+        );
+    }
+
+    send(&db, &state, &call, IndexToken::End, (alice, alice_peer)).await;
+    let state = state.lock().await;
+    assert!(state.calls.is_empty());
+    assert!(state.relays.is_empty());
+}
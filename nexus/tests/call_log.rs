@@ -0,0 +1,149 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::hyper::{body::to_bytes, StatusCode};
+
+use nexus::{
+    api::handlers::calls::list_calls,
+    db::Database,
+    ops::call::{connect_call, deliver_missed_calls},
+    state::connection::ConnectionState,
+};
+use nexuslib::{
+    models::{
+        call::{log::CallLogEntry, media_call::MediaCall, state::CallState},
+        command::Command,
+    },
+    request::{
+        call::{CallLogQuery, CallRequest},
+        index_token::IndexToken,
+        Request,
+    },
+};
+
+mod common;
+
+use common::connect;
+
+/// Creates the call started at the timestamp
+fn call_at(sender: Uuid, receiver: Uuid, created_at: i64) -> MediaCall {
+    let mut call =
+        serde_json::to_value(MediaCall::new(sender, receiver, vec![], vec![], false)).unwrap();
+    call["created_at"] = created_at.into();
+    serde_json::from_value(call).unwrap()
+}
+
+async fn list(
+    db: &Arc<Database>,
+    user: Uuid,
+    before: Option<i64>,
+    limit: Option<i32>,
+) -> (StatusCode, Vec<CallLogEntry>) {
+    let response = list_calls(CallLogQuery { before, limit }, user, db.clone())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).unwrap_or_default())
+}
+
+#[tokio::test]
+async fn pages_the_call_log() {
+    let db = Arc::new(Database::memory());
+    let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    for created_at in 1..=250 {
+        db.calls
+            .add(&call_at(alice, bob, created_at), CallState::Ended)
+            .await
+            .unwrap();
+    }
+
+    // the latest first, 50 by default
+    let (status, page) = list(&db, alice, None, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(page.len(), 50);
+    assert_eq!(page[0].created_at, 250);
+    assert_eq!(page[49].created_at, 201);
+
+    // the next page starts before the oldest entry
+    let (_, next) = list(&db, alice, Some(page[49].created_at), Some(10)).await;
+    assert_eq!(
+        next.iter()
+            .map(|entry| entry.created_at)
+            .collect::<Vec<_>>(),
+        (191..=200).rev().collect::<Vec<_>>()
+    );
+    let (_, last) = list(&db, alice, Some(3), None).await;
+    assert_eq!(last.len(), 2);
+
+    // both sides have the call
+    let (_, page) = list(&db, bob, None, Some(1)).await;
+    assert_eq!(page[0].peer, alice);
+
+    // the limit is clamped
+    assert_eq!(list(&db, alice, None, Some(0)).await.1.len(), 1);
+    assert_eq!(list(&db, alice, None, Some(-5)).await.1.len(), 1);
+    assert_eq!(list(&db, alice, None, Some(1000)).await.1.len(), 200);
+
+    assert_eq!(
+        list(&db, alice, Some(i64::MAX), None).await.0,
+        StatusCode::BAD_REQUEST
+    );
+    assert!(list(&db, Uuid::new_v4(), None, None).await.1.is_empty());
+}
+
+#[tokio::test(start_paused = true)]
+async fn missed_call_is_delivered_once() {
+    let db = Arc::new(Database::memory());
+    let state = Arc::new(Mutex::new(ConnectionState::new()));
+    let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+    let (alice_peer, _alice_rx) = connect(&state, alice).await;
+
+    // bob is offline, so nobody answers
+    let call = MediaCall::new(alice, bob, b"key".to_vec(), b"nonce".to_vec(), false);
+    let request = Request::new(
+        Command::Call,
+        CallRequest::new(call.clone(), IndexToken::Start),
+        String::new(),
+    );
+    connect_call(
+        serde_json::to_string(&request).unwrap(),
+        db.clone(),
+        state.clone(),
+        alice,
+        alice_peer,
+    )
+    .await
+    .unwrap();
+    tokio::time::sleep(Duration::from_secs(60)).await;
+
+    assert!(state.lock().await.calls.is_empty());
+    let missed = db.calls.missed(&bob).await.unwrap();
+    assert_eq!(missed.len(), 1);
+    assert_eq!(missed[0].uuid, call.uuid);
+    // the media key is not kept
+    assert!(missed[0].message.is_empty() && missed[0].nonce.is_empty());
+    assert_eq!(
+        list(&db, bob, None, None).await.1[0].state,
+        CallState::TimedOut
+    );
+
+    let (bob_peer, mut bob_rx) = connect(&state, bob).await;
+    deliver_missed_calls(db.clone(), state.clone(), bob, bob_peer)
+        .await
+        .unwrap();
+    let delivered: CallRequest<MediaCall> =
+        serde_json::from_str(&bob_rx.try_recv().unwrap()).unwrap();
+    assert_eq!(delivered.index, IndexToken::Missed);
+    assert_eq!(delivered.call.uuid, call.uuid);
+
+    // the delivered call is removed from the queue
+    assert!(db.calls.missed(&bob).await.unwrap().is_empty());
+    let (other_peer, mut other_rx) = connect(&state, bob).await;
+    deliver_missed_calls(db.clone(), state.clone(), bob, other_peer)
+        .await
+        .unwrap();
+    assert!(other_rx.try_recv().is_err());
+    assert!(bob_rx.try_recv().is_err());
+}
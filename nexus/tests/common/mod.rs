@@ -0,0 +1,54 @@
+#![allow(dead_code)]
+
+use std::{net::SocketAddr, sync::Arc};
+
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    Mutex, OnceCell,
+};
+use uuid::Uuid;
+
+use nexus::{
+    db::Database,
+    state::connection::{ConnectionState, SessionSocket},
+    storage::storage_setup,
+};
+use nexuslib::models::user::{role::Role, User};
+
+static STORAGE: OnceCell<()> = OnceCell::const_new();
+
+/// Sets up the file system store in a temporary directory
+pub async fn setup_storage() {
+    STORAGE
+        .get_or_init(|| async {
+            let root = std::env::temp_dir().join(format!("nexus-media-{}", Uuid::new_v4()));
+            std::env::set_var("STORAGE_BACKEND", "fs");
+            std::env::set_var("STORAGE_MEDIA", &root);
+            storage_setup().await;
+        })
+        .await;
+}
+
+/// Adds the user with the password `password123`
+pub async fn add_user(db: &Database, username: &str, role: Role) -> Uuid {
+    let (user, secret) = User::new(username, "password123", Some(role));
+    db.users.create(&user, &secret).await.unwrap();
+    user.uuid
+}
+
+/// Connects a session of the user, returns the session and the messages sent to it
+pub async fn connect(
+    state: &Arc<Mutex<ConnectionState>>,
+    user: Uuid,
+) -> (Uuid, UnboundedReceiver<String>) {
+    let (peer, (tx, rx)) = (Uuid::new_v4(), mpsc::unbounded_channel());
+    let addr = SocketAddr::from(([127, 0, 0, 1], 0));
+    state
+        .lock()
+        .await
+        .peers
+        .entry(user)
+        .or_default()
+        .insert(peer, SessionSocket::new(addr, tx));
+    (peer, rx)
+}
@@ -0,0 +1,269 @@
+use std::{io::Cursor, sync::Arc};
+
+use image::{DynamicImage, ImageOutputFormat};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Filter, Reply};
+
+use nexus::{
+    api::{handlers::me::AVATAR_THUMBNAIL_SIZE, routes::get_routes},
+    db::Database,
+    state::connection::ConnectionState,
+};
+use nexuslib::{
+    models::user::{profile::UserProfile, role::Role, settings::UserSettings},
+    request::{
+        auth::{AuthRequest, AuthRequestMeta},
+        profile::{ProfileUpdateRequest, SettingsUpdateRequest},
+    },
+    response::auth::AuthResponse,
+};
+
+mod common;
+
+use common::{add_user, setup_storage};
+
+fn routes(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl Reply,), Error = std::convert::Infallible> + Clone {
+    get_routes(db, Arc::new(Mutex::new(ConnectionState::new())))
+}
+
+/// Adds the user and logs in, returns the user and the `Authorization` header
+async fn login(db: &Arc<Database>, username: &str) -> (Uuid, String) {
+    let user = add_user(db, username, Role::User).await;
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login")
+        .json(&AuthRequest {
+            username: username.to_owned(),
+            password: "password123".to_owned(),
+            meta: AuthRequestMeta {
+                location: "local".to_owned(),
+                device_name: "test".to_owned(),
+                device_type: "desktop".to_owned(),
+                device_os: "linux".to_owned(),
+            },
+        })
+        .reply(&routes(db.clone()))
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let auth: AuthResponse = serde_json::from_slice(response.body()).unwrap();
+    (user, format!("Bearer {}", auth.token))
+}
+
+fn png(width: u32, height: u32) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    DynamicImage::new_rgb8(width, height)
+        .write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn updates_the_profile() {
+    let db = Arc::new(Database::memory());
+    let routes = routes(db.clone());
+    let (user, auth) = login(&db, "dave").await;
+
+    let response = warp::test::request()
+        .path("/api/me/profile")
+        .header("Authorization", &auth)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: UserProfile = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(profile.user, user);
+    assert_eq!(profile.display_name, None);
+
+    let response = warp::test::request()
+        .method("PATCH")
+        .path("/api/me/profile")
+        .header("Authorization", &auth)
+        .json(&ProfileUpdateRequest {
+            display_name: Some("  Dave  ".to_owned()),
+            bio: Some("hello".to_owned()),
+        })
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // the fields which are not sent are kept, the empty ones are cleared
+    let response = warp::test::request()
+        .method("PATCH")
+        .path("/api/me/profile")
+        .header("Authorization", &auth)
+        .json(&ProfileUpdateRequest {
+            display_name: None,
+            bio: Some(String::new()),
+        })
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .method("PATCH")
+        .path("/api/me/profile")
+        .header("Authorization", &auth)
+        .json(&ProfileUpdateRequest {
+            display_name: Some("d".repeat(65)),
+            bio: None,
+        })
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = warp::test::request()
+        .path("/api/me/profile")
+        .header("Authorization", &auth)
+        .reply(&routes)
+        .await;
+    let profile: UserProfile = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(profile.display_name.as_deref(), Some("Dave"));
+    assert_eq!(profile.bio, None);
+
+    let response = warp::test::request()
+        .path("/api/me/profile")
+        .header("Authorization", "Bearer invalid")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn updates_the_settings() {
+    let db = Arc::new(Database::memory());
+    let routes = routes(db.clone());
+    let (_, auth) = login(&db, "erin").await;
+
+    let response = warp::test::request()
+        .path("/api/me/settings")
+        .header("Authorization", &auth)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let settings: UserSettings = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(
+        (settings.language.as_str(), settings.theme.as_str()),
+        ("en", "system")
+    );
+
+    let response = warp::test::request()
+        .method("PATCH")
+        .path("/api/me/settings")
+        .header("Authorization", &auth)
+        .json(&SettingsUpdateRequest {
+            language: Some("pt-BR".to_owned()),
+            theme: Some("dark".to_owned()),
+        })
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let invalid = [
+        (Some("en_US"), None),
+        (Some(""), None),
+        (None, Some("blue")),
+    ];
+    for (language, theme) in invalid {
+        let response = warp::test::request()
+            .method("PATCH")
+            .path("/api/me/settings")
+            .header("Authorization", &auth)
+            .json(&SettingsUpdateRequest {
+                language: language.map(str::to_owned),
+                theme: theme.map(str::to_owned),
+            })
+            .reply(&routes)
+            .await;
+        assert_eq!(
+            response.status(),
+            StatusCode::BAD_REQUEST,
+            "{language:?} {theme:?}"
+        );
+    }
+
+    let response = warp::test::request()
+        .path("/api/me/settings")
+        .header("Authorization", &auth)
+        .reply(&routes)
+        .await;
+    let settings: UserSettings = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(
+        (settings.language.as_str(), settings.theme.as_str()),
+        ("pt-BR", "dark")
+    );
+}
+
+#[tokio::test]
+async fn serves_the_avatar_thumbnail() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let routes = routes(db.clone());
+    let (_, auth) = login(&db, "frank").await;
+    let (_, viewer) = login(&db, "grace").await;
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/api/me/avatar")
+        .header("Authorization", &auth)
+        .body("not an image")
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/api/me/avatar")
+        .header("Authorization", &auth)
+        .body(png(512, 256))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: UserProfile = serde_json::from_slice(response.body()).unwrap();
+    let first = profile.avatar.unwrap();
+
+    let response = warp::test::request()
+        .path(&format!("/api/media/avatars/{first}"))
+        .header("Authorization", &viewer)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["content-type"], "image/png");
+    let thumbnail = image::load_from_memory(response.body()).unwrap();
+    assert_eq!(thumbnail.width(), AVATAR_THUMBNAIL_SIZE);
+    assert_eq!(thumbnail.height(), AVATAR_THUMBNAIL_SIZE / 2);
+
+    // the replaced avatar is removed
+    let response = warp::test::request()
+        .method("PUT")
+        .path("/api/me/avatar")
+        .header("Authorization", &auth)
+        .body(png(64, 64))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: UserProfile = serde_json::from_slice(response.body()).unwrap();
+    let second = profile.avatar.unwrap();
+    assert_ne!(second, first);
+    assert!(db.media.get(&first).await.is_err());
+
+    let response = warp::test::request()
+        .path(&format!("/api/media/avatars/{first}"))
+        .header("Authorization", &viewer)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = warp::test::request()
+        .path(&format!("/api/media/avatars/{second}"))
+        .header("Authorization", &viewer)
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let thumbnail = image::load_from_memory(response.body()).unwrap();
+    assert_eq!(
+        (thumbnail.width(), thumbnail.height()),
+        (AVATAR_THUMBNAIL_SIZE, AVATAR_THUMBNAIL_SIZE)
+    );
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use uuid::Uuid;
+use warp::hyper::{body::to_bytes, StatusCode};
+
+use nexus::{
+    api::handlers::media::{get_media, parse_range, ByteRange},
+    db::{repository::MediaEntry, Database},
+    storage::{bucket_name, put_object},
+};
+use nexuslib::models::{message::media::MediaType, user::profile::UserProfile};
+
+mod common;
+
+use common::setup_storage;
+
+/// Stores the content as a file of the user
+async fn add_file(db: &Database, user: Uuid, content: &[u8]) -> Uuid {
+    let media = MediaEntry {
+        uuid: Uuid::new_v4(),
+        name: "notes.txt".to_owned(),
+        path: format!("{}.txt", Uuid::new_v4()),
+        media_type: MediaType::File,
+        sender: user,
+    };
+    put_object(
+        &bucket_name(media.media_type),
+        &media.path,
+        content.to_vec(),
+    )
+    .await
+    .unwrap();
+    db.media.add(&media, 0).await.unwrap();
+    media.uuid
+}
+
+#[test]
+fn parses_ranges() {
+    let cases = [
+        ("bytes=0-9", ByteRange::Partial(0, 9)),
+        ("bytes=10-", ByteRange::Partial(10, 99)),
+        ("bytes=90-200", ByteRange::Partial(90, 99)),
+        // the suffix ranges
+        ("bytes=-10", ByteRange::Partial(90, 99)),
+        ("bytes=-500", ByteRange::Partial(0, 99)),
+        ("bytes=-0", ByteRange::Unsatisfiable),
+        // the start is past the end
+        ("bytes=100-", ByteRange::Unsatisfiable),
+        ("bytes=150-160", ByteRange::Unsatisfiable),
+        // the header is ignored
+        ("bytes=0-1,5-6", ByteRange::Full),
+        ("bytes=9-5", ByteRange::Full),
+        ("bytes=a-b", ByteRange::Full),
+        ("items=0-9", ByteRange::Full),
+    ];
+
+    for (range, expected) in cases {
+        assert_eq!(parse_range(range, 100), expected, "{range}");
+    }
+    assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+    assert_eq!(parse_range("bytes=-5", 0), ByteRange::Unsatisfiable);
+}
+
+#[tokio::test]
+async fn streams_the_requested_bytes() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let user = Uuid::new_v4();
+    // larger than a single range of the stream
+    let content = (0..3 * 1024 * 1024 + 17)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let media = add_file(&db, user, &content).await;
+
+    let response = get_media(media.to_string(), None, user, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap().as_ref(),
+        content
+    );
+
+    let range = Some("bytes=1048570-1048580".to_owned());
+    let response = get_media(media.to_string(), range, user, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers()["content-range"],
+        format!("bytes 1048570-1048580/{}", content.len())
+    );
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap().as_ref(),
+        &content[1048570..=1048580]
+    );
+
+    // several ranges are answered with the whole file
+    let range = Some("bytes=0-1,5-6".to_owned());
+    let response = get_media(media.to_string(), range, user, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap().len(),
+        content.len()
+    );
+
+    let range = Some(format!("bytes={}-", content.len()));
+    let response = get_media(media.to_string(), range, user, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+
+    // only the sender and the receivers can download the file
+    let response = get_media(media.to_string(), None, Uuid::new_v4(), db)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn attachments_are_only_for_participants() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let (sender, receiver, stranger) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+    let media = add_file(&db, sender, b"attached").await;
+    db.messages
+        .add_attachment(&media, &receiver, &Uuid::new_v4())
+        .await
+        .unwrap();
+
+    for (user, status) in [
+        (sender, StatusCode::OK),
+        (receiver, StatusCode::OK),
+        (stranger, StatusCode::FORBIDDEN),
+    ] {
+        let response = get_media(media.to_string(), None, user, db.clone())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status);
+    }
+}
+
+#[tokio::test]
+async fn only_the_profile_avatar_is_public() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let (sender, stranger) = (Uuid::new_v4(), Uuid::new_v4());
+
+    // the name of the media is chosen by the client
+    let named = add_file(&db, sender, b"not an avatar").await;
+    let mut entry = db.media.get(&named).await.unwrap();
+    entry.uuid = Uuid::new_v4();
+    entry.name = "avatar".to_owned();
+    db.media.add(&entry, 0).await.unwrap();
+    let response = get_media(entry.uuid.to_string(), None, stranger, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    let avatar = add_file(&db, sender, b"avatar").await;
+    let mut profile = UserProfile::new(sender);
+    profile.avatar = Some(avatar);
+    db.users.save_profile(&profile).await.unwrap();
+    let response = get_media(avatar.to_string(), None, stranger, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        to_bytes(response.into_body()).await.unwrap().as_ref(),
+        b"avatar"
+    );
+}
@@ -0,0 +1,156 @@
+use std::sync::Arc;
+
+use tokio::sync::Mutex;
+use uuid::Uuid;
+use warp::{hyper::StatusCode, Filter, Reply};
+
+use nexus::{
+    api::routes::get_routes,
+    db::{repository::MediaEntry, Database},
+    errors::db::DbError,
+    state::connection::ConnectionState,
+};
+use nexuslib::{
+    models::{message::media::MediaType, user::profile::UserProfile},
+    request::auth::{AuthRequest, AuthRequestMeta},
+    response::auth::AuthResponse,
+};
+
+fn routes(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl Reply,), Error = std::convert::Infallible> + Clone {
+    get_routes(db, Arc::new(Mutex::new(ConnectionState::new())))
+}
+
+fn auth_request(username: &str, password: &str) -> AuthRequest {
+    AuthRequest {
+        username: username.to_owned(),
+        password: password.to_owned(),
+        meta: AuthRequestMeta {
+            location: "local".to_owned(),
+            device_name: "test".to_owned(),
+            device_type: "desktop".to_owned(),
+            device_os: "linux".to_owned(),
+        },
+    }
+}
+
+#[tokio::test]
+async fn registers_and_logs_in_without_scylla() {
+    let db = Arc::new(Database::memory());
+    let routes = routes(db.clone());
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/register")
+        .json(&auth_request("alice", "password123"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let registered: AuthResponse = serde_json::from_slice(response.body()).unwrap();
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login")
+        .json(&auth_request("alice", "password123"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let logged_in: AuthResponse = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(logged_in.uuid, registered.uuid);
+
+    let response = warp::test::request()
+        .path("/api/me/profile")
+        .header("Authorization", format!("Bearer {}", logged_in.token))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+    let profile: UserProfile = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(profile.user, registered.uuid);
+
+    assert_eq!(
+        db.sessions.user_of(&logged_in.token).await.unwrap(),
+        registered.uuid
+    );
+}
+
+#[tokio::test]
+async fn rejects_wrong_password_and_taken_username() {
+    let routes = routes(Arc::new(Database::memory()));
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/register")
+        .json(&auth_request("bob", "password123"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/register")
+        .json(&auth_request("bob", "another_password"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login")
+        .json(&auth_request("bob", "wrong_password"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login")
+        .json(&auth_request("nobody", "password123"))
+        .reply(&routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn usernames_are_claimed_once() {
+    let db = Database::memory();
+    let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+
+    db.users.claim_username("carol", &first).await.unwrap();
+    assert!(matches!(
+        db.users.claim_username("carol", &second).await,
+        Err(DbError::AlreadyExists)
+    ));
+
+    db.users.release_username("carol", &first).await.unwrap();
+    db.users.claim_username("carol", &second).await.unwrap();
+}
+
+#[tokio::test]
+async fn media_uuid_is_not_taken_over() {
+    let db = Database::memory();
+    let (owner, attacker) = (Uuid::new_v4(), Uuid::new_v4());
+    let media = MediaEntry {
+        uuid: Uuid::new_v4(),
+        name: "photo.png".to_owned(),
+        path: "photo.png".to_owned(),
+        media_type: MediaType::Image,
+        sender: owner,
+    };
+    db.media.add(&media, 0).await.unwrap();
+
+    let taken = MediaEntry {
+        name: "other.png".to_owned(),
+        path: "other.png".to_owned(),
+        sender: attacker,
+        ..media.clone()
+    };
+    assert!(matches!(
+        db.media.add(&taken, 0).await,
+        Err(DbError::AlreadyExists)
+    ));
+
+    let stored = db.media.get(&media.uuid).await.unwrap();
+    assert_eq!(stored.sender, owner);
+    assert_eq!(stored.path, media.path);
+}
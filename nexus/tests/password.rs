@@ -0,0 +1,183 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use uuid::Uuid;
+use warp::hyper::{body::to_bytes, StatusCode};
+
+use nexus::{
+    api::handlers::{
+        admin::issue_password_reset,
+        auth::{check_credentials, reset_password},
+        me::change_password,
+    },
+    db::{repository::PasswordReset, Database},
+    errors::db::DbError,
+};
+use nexuslib::{
+    crypto::hasher::get_hash,
+    models::user::role::Role,
+    request::auth::{AuthRequest, AuthRequestMeta, PasswordChangeRequest, PasswordResetRequest},
+    response::auth::PasswordResetResponse,
+};
+
+mod common;
+
+use common::add_user;
+
+fn meta() -> AuthRequestMeta {
+    AuthRequestMeta {
+        location: "local".to_owned(),
+        device_name: "test".to_owned(),
+        device_type: "desktop".to_owned(),
+        device_os: "linux".to_owned(),
+    }
+}
+
+/// Adds the sessions of the user, returns their tokens
+async fn add_sessions(db: &Database, user: Uuid, count: usize) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for _ in 0..count {
+        let token = Uuid::new_v4().to_string();
+        db.sessions.add(&token, &user, &meta()).await.unwrap();
+        tokens.push(token);
+    }
+    tokens
+}
+
+async fn can_login(db: &Arc<Database>, username: &str, password: &str) -> bool {
+    let request = AuthRequest {
+        username: username.to_owned(),
+        password: password.to_owned(),
+        meta: meta(),
+    };
+    match check_credentials(db.clone(), &request).await {
+        Ok(_) => true,
+        Err(DbError::WrongCredentials) => false,
+        Err(e) => panic!("{e:?}"),
+    }
+}
+
+async fn reset(db: &Arc<Database>, token: &str, new_password: &str) -> StatusCode {
+    let request = PasswordResetRequest {
+        token: token.to_owned(),
+        new_password: new_password.to_owned(),
+    };
+    reset_password(db.clone(), request).await.unwrap().status()
+}
+
+#[tokio::test]
+async fn reset_token_is_used_once() {
+    let db = Arc::new(Database::memory());
+    let admin = add_user(&db, "admin", Role::Admin).await;
+    let user = add_user(&db, "erin", Role::User).await;
+    add_sessions(&db, user, 2).await;
+
+    let response = issue_password_reset(user.to_string(), admin, db.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = to_bytes(response.into_body()).await.unwrap();
+    let issued: PasswordResetResponse = serde_json::from_slice(&body).unwrap();
+
+    // only the hash of the token is stored
+    assert!(db
+        .sessions
+        .password_reset(&issued.token)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(db
+        .sessions
+        .password_reset(&get_hash(&issued.token))
+        .await
+        .unwrap()
+        .is_some());
+
+    assert_eq!(
+        reset(&db, &issued.token, "short").await,
+        StatusCode::BAD_REQUEST
+    );
+    assert_eq!(
+        reset(&db, &issued.token, "new_password").await,
+        StatusCode::NO_CONTENT
+    );
+    assert_eq!(
+        reset(&db, &issued.token, "another_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+
+    assert!(can_login(&db, "erin", "new_password").await);
+    assert!(!can_login(&db, "erin", "password123").await);
+    assert!(!can_login(&db, "erin", "another_password").await);
+    // the sessions with the old password are revoked
+    assert!(db.sessions.tokens_of(&user).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn expired_reset_token_is_rejected() {
+    let db = Arc::new(Database::memory());
+    let admin = add_user(&db, "admin", Role::Admin).await;
+    let user = add_user(&db, "erin", Role::User).await;
+
+    let now = Utc::now().timestamp();
+    let reset_entry = PasswordReset {
+        user,
+        issued_by: admin,
+        created_at: now - 120,
+        expires_at: now - 60,
+    };
+    db.sessions
+        .add_password_reset(&get_hash("expired"), &reset_entry)
+        .await
+        .unwrap();
+
+    assert_eq!(
+        reset(&db, "expired", "new_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        reset(&db, "unknown", "new_password").await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert!(can_login(&db, "erin", "password123").await);
+}
+
+#[tokio::test]
+async fn password_change_keeps_only_the_current_session() {
+    let db = Arc::new(Database::memory());
+    let user = add_user(&db, "erin", Role::User).await;
+    let tokens = add_sessions(&db, user, 3).await;
+
+    let change = |current: &str, new: &str| PasswordChangeRequest {
+        current_password: current.to_owned(),
+        new_password: new.to_owned(),
+    };
+
+    let response = change_password(
+        user,
+        tokens[0].clone(),
+        change("wrong_password", "new_password"),
+        db.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(db.sessions.tokens_of(&user).await.unwrap().len(), 3);
+    assert!(can_login(&db, "erin", "password123").await);
+
+    let response = change_password(
+        user,
+        tokens[0].clone(),
+        change("password123", "new_password"),
+        db.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        db.sessions.tokens_of(&user).await.unwrap(),
+        vec![tokens[0].clone()]
+    );
+    assert!(can_login(&db, "erin", "new_password").await);
+    assert!(!can_login(&db, "erin", "password123").await);
+}
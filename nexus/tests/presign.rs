@@ -0,0 +1,176 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use uuid::Uuid;
+use warp::hyper::{body::to_bytes, StatusCode};
+
+use nexus::{
+    api::handlers::media::commit_upload,
+    db::{repository::PendingMedia, Database},
+    errors::storage::StorageError,
+    ops::file::object_name,
+    storage::{bucket_name, object_size, put_object},
+};
+use nexuslib::{
+    crypto::hasher::get_bytes_hash,
+    errors::file::UploadError,
+    models::{file::media_file::MediaFile, message::media::MediaType},
+};
+
+mod common;
+
+use common::setup_storage;
+
+/// Content sniffed as PNG
+fn png() -> Vec<u8> {
+    let mut content = b"\x89PNG\r\n\x1a\n".to_vec();
+    content.extend((0..200u8).collect::<Vec<_>>());
+    content
+}
+
+/// Presigns the upload of the image as `presign_upload` does,
+/// the fs backend can not presign, so the object is written directly
+async fn presign(db: &Database, sender: Uuid, content: &[u8]) -> PendingMedia {
+    let file = MediaFile::new(
+        Uuid::new_v4(),
+        content.len(),
+        "photo.png".to_owned(),
+        MediaType::Image,
+        false,
+        sender,
+        get_bytes_hash(content),
+    );
+    let pending = PendingMedia {
+        object: object_name(&file, &Uuid::new_v4()),
+        file,
+        content_type: "image/png".to_owned(),
+        expires_at: Utc::now().timestamp() + 60,
+    };
+    db.media.add_pending(&pending).await.unwrap();
+    pending
+}
+
+async fn store(pending: &PendingMedia, content: &[u8]) {
+    put_object(
+        &bucket_name(pending.file.media_type),
+        &pending.object,
+        content.to_vec(),
+    )
+    .await
+    .unwrap();
+}
+
+async fn is_stored(pending: &PendingMedia) -> bool {
+    match object_size(&bucket_name(pending.file.media_type), &pending.object).await {
+        Ok(_) => true,
+        Err(StorageError::NotFound) => false,
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Commits the upload, returns the status and the error in the body
+async fn commit(
+    db: &Arc<Database>,
+    pending: &PendingMedia,
+    user: Uuid,
+) -> (StatusCode, Option<UploadError>) {
+    let response = commit_upload(pending.file.uuid.to_string(), user, db.clone())
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = to_bytes(response.into_body()).await.unwrap();
+    (status, serde_json::from_slice(&body).ok())
+}
+
+#[tokio::test]
+async fn commits_the_verified_object() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let sender = Uuid::new_v4();
+    let pending = presign(&db, sender, &png()).await;
+
+    // the object has not been uploaded yet, the commit can be retried
+    assert_eq!(
+        commit(&db, &pending, sender).await,
+        (StatusCode::CONFLICT, Some(UploadError::Incomplete))
+    );
+    store(&pending, &png()).await;
+    // only the sender commits
+    assert_eq!(
+        commit(&db, &pending, Uuid::new_v4()).await.0,
+        StatusCode::FORBIDDEN
+    );
+
+    assert_eq!(commit(&db, &pending, sender).await.0, StatusCode::OK);
+    let media = db.media.get(&pending.file.uuid).await.unwrap();
+    assert_eq!(media.path, pending.object);
+    assert!(is_stored(&pending).await);
+
+    // the pending upload is gone
+    assert_eq!(commit(&db, &pending, sender).await.0, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn removes_the_rejected_objects() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let sender = Uuid::new_v4();
+
+    let mut longer = png();
+    longer.push(0);
+    let mut corrupted = png();
+    corrupted[100] ^= 0x01;
+    let text = vec![b'a'; png().len()];
+    let cases = [
+        (longer, UploadError::SizeMismatch),
+        (corrupted, UploadError::Corrupted),
+        // the content is not an image
+        (text, UploadError::TypeMismatch),
+    ];
+
+    for (content, error) in cases {
+        let pending = presign(&db, sender, &png()).await;
+        store(&pending, &content).await;
+
+        assert_eq!(
+            commit(&db, &pending, sender).await,
+            (StatusCode::UNPROCESSABLE_ENTITY, Some(error))
+        );
+        assert!(!is_stored(&pending).await, "{error:?}");
+        assert!(db.media.get(&pending.file.uuid).await.is_err());
+        assert!(db.media.pending(&pending.file.uuid).await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn commits_only_once() {
+    setup_storage().await;
+    let db = Arc::new(Database::memory());
+    let sender = Uuid::new_v4();
+    let pending = presign(&db, sender, &png()).await;
+    store(&pending, &png()).await;
+
+    let (first, second) =
+        tokio::join!(commit(&db, &pending, sender), commit(&db, &pending, sender));
+    let mut statuses = [first.0, second.0];
+    statuses.sort();
+    assert_eq!(statuses[0], StatusCode::OK);
+    // the other commit lost the claim or came after it
+    assert!(matches!(
+        statuses[1],
+        StatusCode::CONFLICT | StatusCode::NOT_FOUND
+    ));
+
+    let blob = db
+        .media
+        .blob(&bucket_name(pending.file.media_type), &pending.file.hash)
+        .await
+        .unwrap();
+    assert_eq!(blob.refs, 1);
+    assert!(is_stored(&pending).await);
+
+    // the claim is taken once
+    let other = presign(&db, sender, &png()).await;
+    assert!(db.media.claim_pending(&other.file.uuid).await.unwrap());
+    assert!(!db.media.claim_pending(&other.file.uuid).await.unwrap());
+}
@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+
+use uuid::Uuid;
+
+use nexus::state::{
+    connection::ConnectionState,
+    relay::{RelayAllocation, RELAY_TOKEN_LEN},
+};
+
+fn addr(port: u16) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], port))
+}
+
+#[test]
+fn allocates_a_token_for_each_user() {
+    let (caller, callee) = (Uuid::new_v4(), Uuid::new_v4());
+    let relay = RelayAllocation::new(Uuid::new_v4(), &[caller, callee]);
+
+    let caller_token = relay.token_of(&caller).unwrap();
+    let callee_token = relay.token_of(&callee).unwrap();
+    assert_eq!(caller_token.len(), RELAY_TOKEN_LEN);
+    assert_eq!(callee_token.len(), RELAY_TOKEN_LEN);
+    assert_ne!(caller_token, callee_token);
+    assert_eq!(relay.token_of(&Uuid::new_v4()), None);
+
+    // nobody is bound until the first `Bind`
+    assert!(relay
+        .participants
+        .values()
+        .all(|participant| participant.endpoint.is_none()));
+    assert_eq!(relay.targets(&addr(1000)), None);
+}
+
+#[test]
+fn binds_only_matching_tokens() {
+    let (caller, callee) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut relay = RelayAllocation::new(Uuid::new_v4(), &[caller, callee]);
+    let token = relay.token_of(&caller).unwrap();
+
+    let mut wrong = token.clone();
+    wrong[RELAY_TOKEN_LEN - 1] ^= 0x01;
+    let mut longer = token.clone();
+    longer.push(0);
+    for rejected in [&wrong[..], &token[..RELAY_TOKEN_LEN - 1], &longer, &[]] {
+        assert_eq!(relay.bind(rejected, addr(1000)), None);
+    }
+    assert_eq!(relay.participant_at(&addr(1000)), None);
+
+    assert_eq!(relay.bind(&token, addr(1000)), Some(None));
+    assert_eq!(relay.participant_at(&addr(1000)), Some(caller));
+
+    // binding again moves the participant to the new address
+    assert_eq!(relay.bind(&token, addr(1001)), Some(Some(addr(1000))));
+    assert_eq!(relay.participant_at(&addr(1000)), None);
+    assert_eq!(relay.participant_at(&addr(1001)), Some(caller));
+}
+
+#[test]
+fn forwards_between_bound_participants() {
+    let users = [Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4()];
+    let mut relay = RelayAllocation::new(Uuid::new_v4(), &users[..2]);
+    let token = relay.token_of(&users[0]).unwrap();
+    assert!(relay.bind(&token, addr(1000)).is_some());
+
+    // the other side is not bound yet
+    assert_eq!(relay.targets(&addr(1000)), Some(vec![]));
+
+    let token = relay.token_of(&users[1]).unwrap();
+    assert!(relay.bind(&token, addr(1001)).is_some());
+    assert_eq!(relay.targets(&addr(1000)), Some(vec![addr(1001)]));
+    assert_eq!(relay.targets(&addr(1001)), Some(vec![addr(1000)]));
+    assert_eq!(relay.targets(&addr(1002)), None);
+
+    // a user joining the room gets its own token
+    let token = relay.add_participant(users[2]);
+    assert!(relay.bind(&token, addr(1002)).is_some());
+    let mut targets = relay.targets(&addr(1000)).unwrap();
+    targets.sort();
+    assert_eq!(targets, vec![addr(1001), addr(1002)]);
+
+    assert_eq!(relay.remove_participant(&users[2]), Some(addr(1002)));
+    assert_eq!(relay.bind(&token, addr(1002)), None);
+    assert_eq!(relay.targets(&addr(1002)), None);
+}
+
+#[test]
+fn rebinding_releases_the_old_address() {
+    let (caller, callee) = (Uuid::new_v4(), Uuid::new_v4());
+    let call = Uuid::new_v4();
+    let mut state = ConnectionState::new();
+    state
+        .relays
+        .insert(call, RelayAllocation::new(call, &[caller, callee]));
+    let token = state.relays[&call].token_of(&caller).unwrap();
+
+    assert!(!state.bind_relay(&call, &[0; RELAY_TOKEN_LEN], addr(1000)));
+    assert!(!state.bind_relay(&Uuid::new_v4(), &token, addr(1000)));
+    assert!(state.relay_endpoints.is_empty());
+
+    assert!(state.bind_relay(&call, &token, addr(1000)));
+    // the same address again keeps the binding
+    assert!(state.bind_relay(&call, &token, addr(1000)));
+    assert_eq!(state.relay_endpoints.get(&addr(1000)), Some(&call));
+
+    // the participant moved, e.g. its NAT mapping has changed
+    assert!(state.bind_relay(&call, &token, addr(1001)));
+    assert_eq!(state.relay_endpoints.get(&addr(1000)), None);
+    assert_eq!(state.relay_endpoints.get(&addr(1001)), Some(&call));
+    assert_eq!(state.relay_endpoints.len(), 1);
+}
@@ -0,0 +1,96 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+use warp::{hyper::StatusCode, Filter, Reply};
+
+use nexus::{api::routes::get_routes, db::Database, state::connection::ConnectionState};
+use nexuslib::{
+    crypto::totp::{code_at, generate_secret, step_at},
+    models::user::User,
+    request::auth::{AuthRequest, AuthRequestMeta, TwoFactorLoginRequest},
+    response::auth::{AuthResponse, TwoFactorChallengeResponse},
+};
+
+fn routes(
+    db: Arc<Database>,
+) -> impl Filter<Extract = (impl Reply,), Error = std::convert::Infallible> + Clone {
+    get_routes(db, Arc::new(Mutex::new(ConnectionState::new())))
+}
+
+fn auth_request() -> AuthRequest {
+    AuthRequest {
+        username: "dave".to_owned(),
+        password: "password123".to_owned(),
+        meta: AuthRequestMeta {
+            location: "local".to_owned(),
+            device_name: "test".to_owned(),
+            device_type: "desktop".to_owned(),
+            device_os: "linux".to_owned(),
+        },
+    }
+}
+
+/// Logs in with the password and then with the code
+async fn login_with_code(
+    routes: &(impl Filter<Extract = (impl Reply,), Error = std::convert::Infallible> + Clone + 'static),
+    code: &str,
+) -> StatusCode {
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login")
+        .json(&auth_request())
+        .reply(routes)
+        .await;
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let challenge: TwoFactorChallengeResponse = serde_json::from_slice(response.body()).unwrap();
+
+    let response = warp::test::request()
+        .method("POST")
+        .path("/api/auth/login/2fa")
+        .json(&TwoFactorLoginRequest {
+            challenge: challenge.challenge,
+            code: code.to_owned(),
+        })
+        .reply(routes)
+        .await;
+    if response.status().is_success() {
+        let _: AuthResponse = serde_json::from_slice(response.body()).unwrap();
+    }
+    response.status()
+}
+
+#[tokio::test]
+async fn totp_code_is_accepted_once() {
+    let db = Arc::new(Database::memory());
+    let routes = routes(db.clone());
+
+    let request = auth_request();
+    let (user, secret) = User::new(&request.username, &request.password, None);
+    db.users.create(&user, &secret).await.unwrap();
+
+    let totp_secret = generate_secret();
+    let step = step_at(Utc::now().timestamp());
+    db.users
+        .enroll_totp(&user.uuid, &totp_secret)
+        .await
+        .unwrap();
+    db.users
+        .enable_totp(&user.uuid, step - 2, &[])
+        .await
+        .unwrap();
+
+    // the step before the last accepted one is within the window, but can not be used
+    let old_code = code_at(&totp_secret, step - 1);
+    let code = code_at(&totp_secret, step);
+
+    assert_eq!(login_with_code(&routes, &code).await, StatusCode::OK);
+    assert_eq!(
+        login_with_code(&routes, &code).await,
+        StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        login_with_code(&routes, &old_code).await,
+        StatusCode::UNAUTHORIZED
+    );
+}
@@ -1,4 +1,9 @@
-use std::{io::Write, net::SocketAddr};
+use std::{
+    io::Write,
+    net::SocketAddr,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use env_logger::Env;
 use futures::StreamExt;
@@ -7,39 +12,73 @@ use ops::start_session::start_session;
 use reqwest::Client;
 use sysinfo::{System, SystemExt};
 use tokio::{
-    fs::File,
-    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader, BufWriter},
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
     net::{TcpStream, UdpSocket},
+    time::interval,
 };
 
 use nexuslib::{
+    media::{
+        audio::{AudioFormat, AudioSource, ToneSource, WavSink, WavSource},
+        codec::Codec,
+        ice::{IceAgent, IceState},
+        jitter::JitterConfig,
+        pipeline::AudioPipeline,
+        rtp::is_rtp_or_rtcp,
+        session::{Incoming, MediaSession},
+        srtp::SrtpMasterKey,
+        stun::{is_stun, StunMessage},
+    },
     models::{
-        call::media_call::MediaCall, command::Command, file::media_file::MediaFile,
-        message::media::MediaType, user::User,
+        call::{
+            candidate::{CandidateKind, IceCandidate, IceParameters},
+            media_call::MediaCall,
+            packet::{MediaPacket, PacketKind, MAX_DATAGRAM_LEN},
+            relay::RelayTicket,
+            room::{CallRoom, RoomKey},
+        },
+        command::Command,
+        user::User,
     },
     request::{
         auth::{AuthRequest, AuthRequestMeta},
         call::CallRequest,
-        file::FileRequest,
-        index_token::IndexToken,
+        index_token::{IndexToken, RoomToken},
+        room::RoomRequest,
         EmptyRequestBody, Request, RequestBody,
     },
-    response::auth::AuthResponse,
+    response::Response,
 };
 use tokio_util::codec::{FramedRead, LinesCodec};
 use uuid::Uuid;
-use x25519_dalek::StaticSecret;
-
-use crate::ops::{send_message::send_message, user::get_users};
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+use crate::ops::{
+    login::login,
+    send_message::send_message,
+    server_host,
+    upload::send_attachment,
+    user::get_users,
+    voice::{describe_voice, send_voice},
+};
 
 mod ops;
 
+/// Audio sent during a call, a tone is sent if there is no such file
+const CALL_INPUT: &str = "call_input.wav";
+/// Audio received during a call
+const CALL_OUTPUT: &str = "call_output.wav";
+/// Bitrate of the Opus audio in bits per second
+#[cfg(feature = "opus")]
+const CALL_BITRATE: i32 = 32_000;
+
 #[tokio::main]
 async fn main() {
     // Logger
     env_logger::Builder::from_env(Env::default().default_filter_or("info")).init();
 
-    let remote_addr: SocketAddr = "127.0.0.1:8083".parse().unwrap();
+    let host = server_host();
+    let remote_addr: SocketAddr = format!("{host}:8083").parse().unwrap();
 
     // use the same port as for tcp
     // let local_addr: SocketAddr = if remote_addr.is_ipv4() {
@@ -50,13 +89,14 @@ async fn main() {
     // .parse()
     // .unwrap();
 
-    let mut stream = TcpStream::connect("127.0.0.1:8081")
+    let mut stream = TcpStream::connect(format!("{host}:8081"))
         .await
         .expect("Not connected");
 
-    let socket = UdpSocket::bind(stream.local_addr().unwrap()).await.unwrap();
+    // the socket is not connected since the media may go directly to the other side
+    let local_ip = stream.local_addr().unwrap().ip();
+    let socket = UdpSocket::bind(SocketAddr::new(local_ip, 0)).await.unwrap();
     // const MAX_DATAGRAM_SIZE: usize = 65_000;
-    socket.connect(&remote_addr).await.unwrap();
 
     let command: Command = Command::File;
 
@@ -89,15 +129,13 @@ async fn main() {
         .build()
         .unwrap();
 
-    let resp = client
-        .post("https://127.0.0.1:8082/api/auth/login".to_owned())
-        .body(auth_req_json)
-        .send()
-        .await
-        .unwrap()
-        .json::<AuthResponse>()
-        .await
-        .unwrap();
+    let resp = match login(&client, auth_req_json).await {
+        Ok(resp) => resp,
+        Err(e) => {
+            log::error!("Login failed: {e}");
+            std::process::exit(1);
+        }
+    };
 
     // starting a tcp session with the server
     let start_req: Request<EmptyRequestBody> =
@@ -105,7 +143,7 @@ async fn main() {
     start_session(&mut stream, start_req).await.unwrap();
 
     let user = client
-        .get(format!("https://127.0.0.1:8082/api/users/{}", resp.uuid))
+        .get(format!("https://{host}:8082/api/users/{}", resp.uuid))
         .bearer_auth(&resp.token)
         .send()
         .await
@@ -118,10 +156,7 @@ async fn main() {
 
     // get the secret key
     let secret = client
-        .post(format!(
-            "https://127.0.0.1:8082/api/users/key/{}",
-            user.uuid
-        ))
+        .post(format!("https://{host}:8082/api/users/key/{}", user.uuid))
         .bearer_auth(&resp.token)
         .send()
         .await
@@ -145,7 +180,7 @@ async fn main() {
                 .await
                 .unwrap();
         }
-        Command::Call => {
+        Command::Call | Command::Room => {
             let (reader, writer) = stream.split();
             let mut reader = BufReader::new(reader);
             let mut writer = BufWriter::new(writer);
@@ -154,6 +189,32 @@ async fn main() {
             let mut lines = FramedRead::new(stream, LinesCodec::new());
 
             let mut call_stack: Vec<MediaCall> = Vec::new();
+            let mut udp_buf = [0u8; MAX_DATAGRAM_LEN];
+
+            // the SRTP master key is sealed with the key shared with the receiver
+            let shared_key = shared_key_with(&secret, &receiver);
+            let mut media: Option<AudioPipeline> = None;
+            let jitter_config = JitterConfig::default();
+            let (codec, format) = call_codec();
+
+            // the captured audio is sent and the received one is recorded
+            let mut source: Box<dyn AudioSource> = Box::new(ToneSource::new(format, 440.0, 8000));
+            let mut sink: Option<WavSink> = None;
+
+            // frames are sent and played once per frame duration
+            let mut ticker = interval(Duration::from_millis(jitter_config.frame_ms as u64));
+            let mut streaming = false;
+            let mut frames: u64 = 0;
+
+            // rooms this user was invited to and the one it takes part in
+            let mut rooms: Vec<CallRoom> = Vec::new();
+            let mut current_room: Option<Uuid> = None;
+
+            // the other side is checked directly, the relay is the fallback
+            let candidates = gather_candidates(&socket, remote_addr).await;
+            let mut ice: Option<IceAgent> = None;
+            let mut remote_ice: Option<IceParameters> = None;
+            let mut ice_state = IceState::New;
 
             // let (tx, mut rx) = mpsc::channel::<(MediaCall, u32)>(1_000);
 
@@ -163,64 +224,247 @@ async fn main() {
 
                 tokio::select! {
                     result = reader.read_line(&mut buf) => {
-                        let _result = result.unwrap();
+                        // the server closed the stream
+                        if result.unwrap() == 0 {
+                            break;
+                        }
 
-                        let call_req: CallRequest<MediaCall> = serde_json::from_str(&buf).unwrap();
+                        let call_req: CallRequest<MediaCall> = match serde_json::from_str(&buf) {
+                            Ok(call_req) => call_req,
+                            Err(_) => {
+                                if let Ok(room_req) = serde_json::from_str::<RoomRequest>(&buf) {
+                                    let room = room_req.room;
+                                    match room_req.index {
+                                        RoomToken::Create => {
+                                            println!("Invited to the room {} by {}", room.uuid, room.owner);
+                                            rooms.push(room);
+                                        }
+                                        RoomToken::Joined => {
+                                            println!("Joined the room {}", room.uuid);
+                                            current_room = Some(room.uuid);
+                                            if let Some(ticket) = room_req.relay {
+                                                (source, sink) = bind_relay(&socket, remote_addr, ticket, format).await;
+                                                streaming = media.is_some();
+                                                frames = 0;
+                                            }
+                                        }
+                                        RoomToken::Participants => {
+                                            println!("Participants of the room {}: {:?}", room.uuid, room.participants);
+                                        }
+                                        RoomToken::Speaker => {
+                                            if let Some(speaker) = room.speaker {
+                                                println!("Speaking: {speaker}");
+                                            }
+                                        }
+                                        RoomToken::Closed => {
+                                            println!("The room {} was closed", room.uuid);
+                                            rooms.retain(|r| r.uuid != room.uuid);
+                                        }
+                                        _ => (),
+                                    }
+                                    continue;
+                                }
+
+                                // illegal call requests are answered with an error
+                                let response: Response<String> = serde_json::from_str(&buf).unwrap();
+                                println!("Error: {}", response.content);
+                                continue;
+                            }
+                        };
                         let req_act = &call_req.index;
 
                         println!("Received: {req_act:#?}");
 
+                        // delivered on connection, does not touch the current call
+                        if call_req.index == IndexToken::Missed {
+                            let call = call_req.call;
+                            println!("Missed call from {} at {}", call.sides.get_sender(), call.get_created_at());
+                            continue;
+                        }
+
                         let call = call_req.call;
+                        call_stack.retain(|c| c.uuid != call.uuid);
+
+                        // candidates of the caller come with `Start`, of the callee with `Accept`
+                        if let Some(params) = call_req.ice {
+                            match ice.as_mut() {
+                                Some(agent) => agent.set_remote(params),
+                                None => remote_ice = Some(params),
+                            }
+                        }
 
-                        call_stack.push(call);
+                        // the callee gets the master key with the call
+                        if media.is_none() && !call.message.is_empty() {
+                            match SrtpMasterKey::open(shared_key.as_bytes(), &call.message, &call.nonce) {
+                                Ok(master) => match AudioPipeline::new(&master, codec, format, jitter_config) {
+                                    Ok(pipeline) => media = Some(pipeline),
+                                    Err(e) => println!("Failed to start the audio: {e:?}"),
+                                },
+                                Err(e) => println!("Failed to open the media key: {e:?}"),
+                            }
+                        }
 
-                        if call_req.index == IndexToken::Accept {
-                            let message = "Hello!".as_bytes().to_vec();
-                            let data = MediaCall::new(user.uuid, receiver.uuid, message, vec![], false);
-                            let data = bincode::serialize(&data).unwrap();
+                        // this session takes part in the call => register at the relay and stream
+                        if let Some(ticket) = call_req.relay {
+                            (source, sink) = bind_relay(&socket, remote_addr, ticket, format).await;
+                            streaming = media.is_some();
+                            frames = 0;
+                        }
 
-                            socket.send(&data).await.unwrap();
+                        match call_req.index {
+                            IndexToken::Start | IndexToken::Accept | IndexToken::Accepted => call_stack.push(call),
+                            // the call is over
+                            _ => {
+                                stop_media(&mut media, &mut sink);
+                                streaming = false;
+                                ice = None;
+                                remote_ice = None;
+                                ice_state = IceState::New;
+                            }
                         }
                     }
-                    result = lines.next() => {
-                        let _result = result.unwrap().unwrap().clone();
-                        if _result.contains('y') {
-                            let mut call = call_stack.last().unwrap().clone();
-                            call.accepted = true;
-                            let call_req = CallRequest::new(call, IndexToken::Accept);
-                            let req = Request::new(call_req.op(), call_req, token);
-
-                            let req_act = &req.body.index;
-                            println!("Sending: {req_act:#?}");
-                            let mut req_json = serde_json::to_vec(&req).unwrap();
-                            // Appending `\n` in the end of the request
-                            let mut new_line = String::from("\n").as_bytes().to_vec();
-                            req_json.append(&mut new_line);
+                    _ = ticker.tick(), if streaming => {
+                        let media = media.as_mut().unwrap();
+                        frames += 1;
+
+                        // checks run until the other side answers or all of them fail
+                        let mut target = remote_addr;
+                        if let Some(agent) = ice.as_mut() {
+                            for (address, check) in agent.poll(now_ms()) {
+                                socket.send_to(&check, address).await.unwrap();
+                            }
+                            if agent.state() != ice_state {
+                                ice_state = agent.state();
+                                match ice_state {
+                                    IceState::Connected(address) => println!("Connected directly to {address}"),
+                                    IceState::Failed => println!("No direct path, the media goes through the relay"),
+                                    _ => (),
+                                }
+                            }
+                            target = agent.selected().unwrap_or(remote_addr);
+                        }
 
-                            // Sends the Request
-                            writer.write_all(&req_json).await.unwrap();
-                            writer.flush().await.unwrap();
-                        } else if _result.contains('c') {
-                            let mut call = call_stack.pop().unwrap();
-                            call.accepted = true;
-                            let call_req = CallRequest::new(call, IndexToken::End);
-                            let req = Request::new(call_req.op(), call_req, token);
+                        // the source has ended => only the other side is played
+                        if let Some(datagram) = media.capture(source.as_mut()).unwrap() {
+                            socket.send_to(&datagram, target).await.unwrap();
+                        }
+                        if let Some(sink) = sink.as_mut() {
+                            if let Err(e) = media.play(sink) {
+                                log::debug!("Failed to play the audio: {e:?}");
+                            }
+                        }
 
-                            let req_act = &req.body.index;
-                            println!("Sending: {req_act:#?}");
-                            let mut req_json = serde_json::to_vec(&req).unwrap();
-                            // Appending `\n` in the end of the request
-                            let mut new_line = String::from("\n").as_bytes().to_vec();
-                            req_json.append(&mut new_line);
+                        // reports are sent every 5 seconds
+                        if frames.is_multiple_of(250) {
+                            socket.send_to(&media.session.report(now_ms()), target).await.unwrap();
+                        }
+                    }
+                    result = socket.recv_from(&mut udp_buf) => {
+                        let (len, from) = result.unwrap();
+                        let datagram = &udp_buf[..len];
+
+                        // connectivity checks of the other side are answered
+                        if is_stun(datagram) {
+                            if let Some(response) = ice.as_mut().and_then(|agent| agent.handle(datagram, from)) {
+                                socket.send_to(&response, from).await.unwrap();
+                            }
+                            continue;
+                        }
+                        if !is_rtp_or_rtcp(datagram) {
+                            if let Some(packet) = MediaPacket::decode(datagram) {
+                                if packet.header.kind == PacketKind::Bind {
+                                    println!("Bound to the relay");
+                                }
+                            }
+                            continue;
+                        }
+                        let media = match media.as_mut() {
+                            Some(media) => media,
+                            None => continue,
+                        };
+
+                        match media.session.receive(datagram, now_ms()) {
+                            Ok(Incoming::Reports(reports)) => {
+                                for report in reports {
+                                    println!(
+                                        "Report: {:.1}% lost, {} lost in total, jitter {}",
+                                        report.fraction_lost as f32 * 100.0 / 256.0,
+                                        report.cumulative_lost,
+                                        report.jitter
+                                    );
+                                }
+                            }
+                            Ok(Incoming::Media(..)) => (),
+                            Err(e) => log::debug!("Dropped media: {e:?}"),
+                        }
+                    }
+                    result = lines.next() => {
+                        let _result = result.unwrap().unwrap().clone();
 
-                            // Sends the Request
-                            writer.write_all(&req_json).await.unwrap();
-                            writer.flush().await.unwrap();
+                        // g - group call with all users, j - join the last invited room, l - leave the room
+                        let room_index = if _result.contains('g') {
+                            Some(RoomToken::Create)
+                        } else if _result.contains('j') {
+                            Some(RoomToken::Join)
+                        } else if _result.contains('l') {
+                            Some(RoomToken::Leave)
                         } else {
-                            let call = MediaCall::new(user.uuid, receiver.uuid, vec![], vec![], false);
-                            let req_body = CallRequest::new(call, IndexToken::Start);
-
-                            let req = Request::new(req_body.op(), req_body, token);
+                            None
+                        };
+
+                        if let Some(index) = room_index {
+                            let room = match index {
+                                RoomToken::Create => {
+                                    // the master key is sealed for each invited user
+                                    let master = SrtpMasterKey::generate();
+                                    let keys = users
+                                        .iter()
+                                        .filter(|u| u.uuid != user.uuid)
+                                        .map(|u| {
+                                            let (sealed, nonce) = master.seal(shared_key_with(&secret, u).as_bytes());
+                                            RoomKey::new(u.uuid, sealed, nonce)
+                                        })
+                                        .collect();
+                                    media = AudioPipeline::new(&master, codec, format, jitter_config).ok();
+
+                                    let room = CallRoom::new(user.uuid, keys);
+                                    rooms.push(room.clone());
+                                    room
+                                }
+                                RoomToken::Join => match rooms.last() {
+                                    Some(room) => {
+                                        let owner = users.iter().find(|u| u.uuid == room.owner);
+                                        let master = match (owner, room.key_of(&user.uuid)) {
+                                            (Some(owner), Some(key)) => {
+                                                SrtpMasterKey::open(shared_key_with(&secret, owner).as_bytes(), &key.message, &key.nonce).ok()
+                                            }
+                                            _ => None,
+                                        };
+                                        media = master.and_then(|master| AudioPipeline::new(&master, codec, format, jitter_config).ok());
+                                        room.clone()
+                                    }
+                                    None => {
+                                        println!("No invitations");
+                                        continue;
+                                    }
+                                },
+                                _ => match rooms.iter().find(|room| Some(room.uuid) == current_room) {
+                                    Some(room) => {
+                                        stop_media(&mut media, &mut sink);
+                                        streaming = false;
+                                        current_room = None;
+                                        room.clone()
+                                    }
+                                    None => {
+                                        println!("Not in a room");
+                                        continue;
+                                    }
+                                },
+                            };
+
+                            let room_req = RoomRequest::new(room, index);
+                            let req = Request::new(room_req.op(), room_req, token);
+                            println!("Sending: {index:#?}");
                             let mut req_json = serde_json::to_vec(&req).unwrap();
                             // Appending `\n` in the end of the request
                             let mut new_line = String::from("\n").as_bytes().to_vec();
@@ -229,50 +473,113 @@ async fn main() {
                             // Sends the Request
                             writer.write_all(&req_json).await.unwrap();
                             writer.flush().await.unwrap();
+                            continue;
                         }
+
+                        // y - accept, r - reject, b - busy, c - end, anything else - call
+                        let index = if _result.contains('y') {
+                            IndexToken::Accept
+                        } else if _result.contains('r') {
+                            IndexToken::Reject
+                        } else if _result.contains('b') {
+                            IndexToken::Busy
+                        } else if _result.contains('c') {
+                            IndexToken::End
+                        } else {
+                            IndexToken::Start
+                        };
+
+                        let call = match index {
+                            IndexToken::Start => {
+                                let master = SrtpMasterKey::generate();
+                                let (sealed, nonce) = master.seal(shared_key.as_bytes());
+                                media = AudioPipeline::new(&master, codec, format, jitter_config).ok();
+
+                                let call = MediaCall::new(user.uuid, receiver.uuid, sealed, nonce, false);
+                                call_stack.push(call.clone());
+                                call
+                            }
+                            _ => match call_stack.last() {
+                                Some(call) => call.clone(),
+                                None => {
+                                    println!("No active call");
+                                    continue;
+                                }
+                            },
+                        };
+
+                        // the caller is the controlling side of the checks
+                        let params = match index {
+                            IndexToken::Start | IndexToken::Accept => {
+                                let mut agent = IceAgent::new(index == IndexToken::Start, candidates.clone());
+                                if let Some(remote) = remote_ice.take() {
+                                    agent.set_remote(remote);
+                                }
+                                let params = agent.local().clone();
+                                ice = Some(agent);
+                                Some(params)
+                            }
+                            _ => None,
+                        };
+
+                        let call_req = CallRequest::new(call, index).with_ice(params);
+                        let req = Request::new(call_req.op(), call_req, token);
+
+                        let req_act = &req.body.index;
+                        println!("Sending: {req_act:#?}");
+                        let mut req_json = serde_json::to_vec(&req).unwrap();
+                        // Appending `\n` in the end of the request
+                        let mut new_line = String::from("\n").as_bytes().to_vec();
+                        req_json.append(&mut new_line);
+
+                        // Sends the Request
+                        writer.write_all(&req_json).await.unwrap();
+                        writer.flush().await.unwrap();
                     }
                 }
             }
         }
+        // a voice note is recorded from `NEXUS_VOICE`, a WAV or raw PCM file
+        Command::File if std::env::var("NEXUS_VOICE").is_ok() => {
+            let path = std::env::var("NEXUS_VOICE").unwrap();
+            let shared_key = shared_key_with(&secret, &receiver);
+            match send_voice(
+                &mut stream,
+                Path::new(&path),
+                shared_key.as_bytes(),
+                resp.token,
+                user.uuid,
+                receiver.uuid,
+            )
+            .await
+            {
+                Ok(voice) => println!(
+                    "Sent a voice note to {}: {}",
+                    receiver.username,
+                    describe_voice(&voice)
+                ),
+                Err(e) => println!("Failed to send the voice note {path}: {e}"),
+            }
+        }
         Command::File => {
-            let (_, writer) = stream.split();
-            // let reader = BufReader::new(reader);
-            let mut writer = BufWriter::new(writer);
-            // let framed = FramedRead::new(reader, BytesCodec::new());
-
-            let mut file = File::open("/home/spectre/Pictures/picture.png")
-                .await
-                .unwrap();
-            let metadata = file.metadata().await.unwrap();
-            let file_size = metadata.len();
-
-            let f = MediaFile::new(
-                Uuid::new_v4(),
-                file_size as usize,
-                (file_size as f64 / 1024.0).ceil() as usize,
-                "picture.png".to_owned(),
-                MediaType::Image,
-                false,
+            let path = std::env::var("NEXUS_UPLOAD")
+                .unwrap_or_else(|_| "/home/spectre/Pictures/picture.png".to_owned());
+            let shared_key = shared_key_with(&secret, &receiver);
+            match send_attachment(
+                &mut stream,
+                Path::new(&path),
+                shared_key.as_bytes(),
+                resp.token,
                 user.uuid,
-            );
-            let req_body = FileRequest::new(f);
-            let req = Request::new(req_body.op(), req_body, resp.token);
-            let mut req_json = serde_json::to_vec(&req).unwrap();
-            // Appending `\n` in the end of the request
-            let mut new_line = String::from("\n").as_bytes().to_vec();
-            req_json.append(&mut new_line);
-
-            // Sends the Request
-            writer.write_all(&req_json).await.unwrap();
-            writer.flush().await.unwrap();
-
-            let mut buffer = vec![0; 1024];
-            let mut bytes_sent: u64 = 0;
-
-            while bytes_sent < file_size {
-                let bytes_read = file.read(&mut buffer).await.unwrap();
-                writer.write_all(&buffer[..bytes_read]).await.unwrap();
-                bytes_sent += bytes_read as u64;
+                receiver.uuid,
+            )
+            .await
+            {
+                Ok(file) => println!(
+                    "Sent {} to {} ({} bytes encrypted)",
+                    file.name, receiver.username, file.len_bytes
+                ),
+                Err(e) => println!("Failed to send {path}: {e}"),
             }
         }
     }
@@ -281,3 +588,111 @@ async fn main() {
 pub fn slice_to_arr(slice: &[u8]) -> [u8; 32] {
     slice.try_into().expect("Wrong slice length")
 }
+
+/// Returns the key shared with the user
+fn shared_key_with(secret: &StaticSecret, user: &User) -> SharedSecret {
+    let pub_key: [u8; 32] = user.public_key().as_slice().try_into().unwrap();
+    secret.diffie_hellman(&PublicKey::from(pub_key))
+}
+
+/// Returns the host, the server reflexive and the relay candidates of the socket
+///
+/// The server reflexive one is learned from the STUN server of the relay
+async fn gather_candidates(socket: &UdpSocket, server: SocketAddr) -> Vec<IceCandidate> {
+    let mut candidates = vec![IceCandidate::new(
+        CandidateKind::Host,
+        socket.local_addr().unwrap(),
+    )];
+
+    let request = StunMessage::binding_request();
+    socket.send_to(&request.encode(None), server).await.unwrap();
+
+    let mut buf = [0u8; MAX_DATAGRAM_LEN];
+    let response = tokio::time::timeout(Duration::from_secs(1), socket.recv_from(&mut buf)).await;
+    if let Ok(Ok((len, _))) = response {
+        let reflexive = StunMessage::decode(&buf[..len])
+            .ok()
+            .filter(|response| response.transaction == request.transaction)
+            .and_then(|response| response.mapped_address());
+        match reflexive {
+            // the same as the host one when there is no NAT
+            Some(address) if address != candidates[0].address => {
+                candidates.push(IceCandidate::new(CandidateKind::ServerReflexive, address))
+            }
+            Some(_) => (),
+            None => log::debug!("Unexpected answer of the STUN server"),
+        }
+    }
+
+    candidates.push(IceCandidate::new(CandidateKind::Relay, server));
+    candidates
+}
+
+/// Registers at the relay and opens the captured and the recorded audio
+async fn bind_relay(
+    socket: &UdpSocket,
+    relay: SocketAddr,
+    ticket: RelayTicket,
+    format: AudioFormat,
+) -> (Box<dyn AudioSource>, Option<WavSink>) {
+    let bind = MediaPacket::bind(ticket.call, ticket.token);
+    socket.send_to(&bind.encode(), relay).await.unwrap();
+
+    let source: Box<dyn AudioSource> = match WavSource::open(CALL_INPUT) {
+        Ok(wav) if wav.format() == format => Box::new(wav),
+        // nothing to capture => a tone is sent
+        _ => Box::new(ToneSource::new(format, 440.0, 8000)),
+    };
+    (source, WavSink::create(CALL_OUTPUT, format).ok())
+}
+
+/// Stops the audio of the call that is over
+fn stop_media(media: &mut Option<AudioPipeline>, sink: &mut Option<WavSink>) {
+    if let Some(media) = media.take() {
+        print_media_stats(&media.session);
+    }
+    if let Some(sink) = sink.take() {
+        sink.finish().unwrap();
+        println!("Recorded the call to {CALL_OUTPUT}");
+    }
+}
+
+/// Returns the current unix time in milliseconds
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+/// Returns the codec of the calls with the format of the captured audio
+#[cfg(feature = "opus")]
+fn call_codec() -> (Codec, AudioFormat) {
+    let codec = Codec::Opus {
+        bitrate: CALL_BITRATE,
+    };
+    (codec, AudioFormat::VOICE)
+}
+
+/// Returns the codec of the calls with the format of the captured audio
+///
+/// Without Opus the audio is sent uncompressed, so it has to fit into a datagram
+#[cfg(not(feature = "opus"))]
+fn call_codec() -> (Codec, AudioFormat) {
+    (Codec::L16, AudioFormat::new(16_000, 1))
+}
+
+/// Prints the statistics of the received streams when the call ends
+fn print_media_stats(media: &MediaSession) {
+    for (ssrc, source) in media.sources() {
+        let stats = source.jitter.stats();
+        println!(
+            "{ssrc:08x}: {} played, {} lost, {} late, {} underruns, jitter {:.1} ms",
+            stats.played,
+            stats.lost,
+            stats.late,
+            stats.underruns,
+            source.jitter.jitter_ms()
+        );
+    }
+}
@@ -1,5 +1,13 @@
 pub mod call;
+pub mod login;
 pub mod register;
 pub mod send_message;
 pub mod start_session;
+pub mod upload;
 pub mod user;
+pub mod voice;
+
+/// Returns the host of the server, `NEXUS_HOST` or the local one
+pub fn server_host() -> String {
+    std::env::var("NEXUS_HOST").unwrap_or_else(|_| "127.0.0.1".to_owned())
+}
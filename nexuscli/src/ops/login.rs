@@ -0,0 +1,73 @@
+use std::io::{Error, ErrorKind, Result, Write};
+
+use nexuslib::{
+    request::auth::TwoFactorLoginRequest,
+    response::auth::{AuthResponse, TwoFactorChallengeResponse},
+};
+use reqwest::{Client, Response, StatusCode};
+
+use super::server_host;
+
+/// Logs in with the credentials
+///
+/// If the account has two-factor authentication enabled,
+/// the server answers with a challenge and the code is asked
+pub async fn login(client: &Client, auth_req_json: String) -> Result<AuthResponse> {
+    let resp = client
+        .post(format!("https://{}:8082/api/auth/login", server_host()))
+        .body(auth_req_json)
+        .send()
+        .await
+        .map_err(Error::other)?;
+
+    if resp.status() != StatusCode::ACCEPTED {
+        return auth_response(resp).await;
+    }
+
+    let challenge = resp
+        .json::<TwoFactorChallengeResponse>()
+        .await
+        .map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+
+    // either the code from the authenticator app or a recovery code
+    let mut code = String::from("");
+    print!("2FA code: ");
+    std::io::stdout().flush()?;
+    std::io::stdin().read_line(&mut code)?;
+
+    let req = TwoFactorLoginRequest {
+        challenge: challenge.challenge,
+        code: code.trim().to_owned(),
+    };
+
+    let resp = client
+        .post(format!("https://{}:8082/api/auth/login/2fa", server_host()))
+        .json(&req)
+        .send()
+        .await
+        .map_err(Error::other)?;
+
+    auth_response(resp).await
+}
+
+/// Reads the session from the response, a refused login is an error
+async fn auth_response(resp: Response) -> Result<AuthResponse> {
+    match resp.status() {
+        StatusCode::OK => resp
+            .json::<AuthResponse>()
+            .await
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e)),
+        StatusCode::UNAUTHORIZED => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "wrong username, password or code",
+        )),
+        StatusCode::FORBIDDEN => Err(Error::new(
+            ErrorKind::PermissionDenied,
+            "the account is restricted",
+        )),
+        status => {
+            let reason = resp.text().await.unwrap_or_default();
+            Err(Error::other(format!("login failed: {status} {reason}")))
+        }
+    }
+}
@@ -7,7 +7,7 @@ use nexuslib::models::user::User;
 /// Receives: stream: &mut TcpStream, user: User
 ///
 /// Returns Result
-/// 
+///
 #[allow(unused)]
 pub async fn register(user: User) -> Result<()> {
     let client = reqwest::Client::builder()
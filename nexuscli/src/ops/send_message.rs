@@ -4,7 +4,10 @@ use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
 use ansi_term::Color;
 use futures::StreamExt;
 use nexuslib::{
-    models::{message::text::TextMessage, user::User},
+    models::{
+        message::{payload::MessagePayload, text::TextMessage, MessageBody, MessageContent},
+        user::User,
+    },
     request::{message::MessageRequest, Request, RequestBody},
     utils::{string_to_vec, vec_to_string},
     Message,
@@ -18,6 +21,8 @@ use tokio::{
 use tokio_util::codec::{FramedRead, LinesCodec};
 use x25519_dalek::{PublicKey, StaticSecret};
 
+use super::voice::describe_voice;
+
 pub async fn send_message(
     stream: &mut TcpStream,
     secret: StaticSecret,
@@ -49,7 +54,7 @@ pub async fn send_message(
                 }
                 log::debug!("> {}", buf);
                 let buf = buf.replace('\n', "");
-                let message: Message<TextMessage> = serde_json::from_str(&buf).unwrap();
+                let message: Message<MessageBody> = serde_json::from_str(&buf).unwrap();
 
                 let pub_key: [u8; 32] = receiver.public_key().as_slice().try_into().unwrap();
                 let shared_key = secret.diffie_hellman(&PublicKey::from(pub_key));
@@ -59,15 +64,25 @@ pub async fn send_message(
 
                 let cipher = Aes256Gcm::new_from_slice(shared_key.as_bytes().as_slice()).unwrap();
 
-                let decrypted = cipher.decrypt(nonce, string_to_vec(message.content.text.clone()).as_ref()).unwrap();
-                let msg = String::from_utf8(decrypted).unwrap();
+                let decrypted = cipher.decrypt(nonce, string_to_vec(message.content.get_text().unwrap_or_default()).as_ref()).unwrap();
+                let payload = MessagePayload::from_bytes(&decrypted);
 
                 let display_name = if message.sides.get_sender() == user.uuid {
                     Color::Green.bold().paint("Me".to_owned())
                 } else {
                     Color::Red.bold().paint(receiver.username.clone())
                 };
-                println!("> {}: {}", display_name, Color::Blue.paint(msg));
+                match message.content.get_voice() {
+                    Some(voice) => println!("> {}: {}", display_name, Color::Blue.paint(describe_voice(voice))),
+                    None => println!("> {}: {}", display_name, Color::Blue.paint(&payload.text)),
+                }
+                for attachment in message.media.iter().flat_map(|media| &media.attachments) {
+                    // the encrypted attachments have their names in the payload
+                    let name = payload
+                        .secret_of(&attachment.uuid)
+                        .map_or(&attachment.name, |secret| &secret.name);
+                    println!("  {} {name} ({})", Color::Yellow.paint("attachment:"), attachment.uuid);
+                }
             }
             // input
             result = lines.next() => {
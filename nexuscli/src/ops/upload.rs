@@ -0,0 +1,236 @@
+use std::{
+    io::{Error, ErrorKind, Result},
+    path::Path,
+};
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use nexuslib::{
+    crypto::{
+        attachment::{encrypt_attachment, encrypted_len, AttachmentKey},
+        hasher::get_bytes_hash,
+    },
+    models::{
+        file::{
+            chunk::{FileChunk, CHUNK_SIZE},
+            media_file::MediaFile,
+        },
+        message::{
+            media::{Media, MediaType},
+            payload::MessagePayload,
+            text::TextMessage,
+            MessageContent,
+        },
+    },
+    request::{
+        file::FileRequest, index_token::FileToken, message::MessageRequest, Request, RequestBody,
+    },
+    response::Response,
+    utils::vec_to_string,
+    Message,
+};
+use rand_core::RngCore;
+use serde::Serialize;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter},
+    net::TcpStream,
+};
+use uuid::Uuid;
+
+/// Number of errors after which the upload is given up
+const MAX_RETRIES: usize = 3;
+
+/// Encrypts the file, uploads it and sends it to the receiver
+///
+/// The key of the file goes inside the encrypted body of the message,
+/// so the server gets only the ciphertext
+pub async fn send_attachment(
+    stream: &mut TcpStream,
+    path: &Path,
+    shared_key: &[u8],
+    token: String,
+    sender: Uuid,
+    receiver: Uuid,
+) -> Result<MediaFile> {
+    let bytes = tokio::fs::read(path).await?;
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let (mut file, payload) =
+        upload_encrypted(stream, &bytes, &name, MediaType::File, &token, sender).await?;
+
+    let (sealed, nonce) = seal_payload(&payload, shared_key)?;
+    let message = Message::new(TextMessage::new(&sealed), nonce, sender, receiver)
+        .with_media(Media::new(vec![(&file).into()]));
+    send_message_request(stream, message, token).await?;
+
+    file.name = name;
+    Ok(file)
+}
+
+/// Encrypts the bytes with a new key and uploads them as a file of the type
+///
+/// Returns the uploaded file and the payload with its name and key,
+/// the text of the payload is the name
+pub async fn upload_encrypted(
+    stream: &mut TcpStream,
+    bytes: &[u8],
+    name: &str,
+    media_type: MediaType,
+    token: &str,
+    sender: Uuid,
+) -> Result<(MediaFile, MessagePayload)> {
+    let key = AttachmentKey::generate();
+    let mut encrypted = Vec::with_capacity(encrypted_len(bytes.len()));
+    let digest = encrypt_attachment(&key, bytes, &mut encrypted)
+        .map_err(|e| Error::other(format!("{e:?}")))?;
+
+    let uuid = Uuid::new_v4();
+    let file = MediaFile::new(
+        uuid,
+        encrypted.len(),
+        format!("{uuid}.bin"),
+        media_type,
+        true,
+        sender,
+        get_bytes_hash(&encrypted),
+    );
+    let file = upload_file(stream, &file, &encrypted, token).await?;
+
+    // the name and the key of the file are sent encrypted
+    let payload = MessagePayload::new(name).with_attachment(file.uuid, name, &key, &digest);
+    Ok((file, payload))
+}
+
+/// Encrypts the payload with the key shared with the receiver,
+/// returns the ciphertext as the text of a message and the nonce
+pub fn seal_payload(payload: &MessagePayload, shared_key: &[u8]) -> Result<(String, Vec<u8>)> {
+    let mut raw_nonce = [0u8; 12];
+    rand_core::OsRng.fill_bytes(&mut raw_nonce);
+    let cipher = Aes256Gcm::new_from_slice(shared_key).map_err(|_| ErrorKind::InvalidInput)?;
+    let sealed = cipher
+        .encrypt(Nonce::from_slice(&raw_nonce), payload.to_bytes().as_ref())
+        .map_err(|_| ErrorKind::InvalidData)?;
+
+    Ok((vec_to_string(sealed), raw_nonce.to_vec()))
+}
+
+/// Sends the message over the stream, the server does not answer it
+pub async fn send_message_request<T>(
+    stream: &mut TcpStream,
+    message: Message<T>,
+    token: String,
+) -> Result<()>
+where
+    T: MessageContent + Serialize,
+{
+    let body = MessageRequest::new(message);
+    let request = Request::new(body.op(), body, token);
+    let mut request_json = serde_json::to_vec(&request)?;
+    request_json.push(b'\n');
+    stream.write_all(&request_json).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Uploads the bytes of the file in chunks over the stream
+///
+/// The server acknowledges every chunk with the one it expects next,
+/// so after an error the upload is resumed from there
+pub async fn upload_file(
+    stream: &mut TcpStream,
+    file: &MediaFile,
+    bytes: &[u8],
+    token: &str,
+) -> Result<MediaFile> {
+    let (reader, writer) = stream.split();
+    let mut reader = BufReader::new(reader);
+    let mut writer = BufWriter::new(writer);
+
+    let start = FileRequest::new(FileToken::Start, None).with_file(file.clone());
+    let mut ack = exchange(&mut reader, &mut writer, start, token).await?;
+    match ack.index {
+        // the server already has the same content
+        FileToken::Committed => return ack.file.ok_or(ErrorKind::InvalidData.into()),
+        FileToken::Rejected => return Err(rejection(ack)),
+        _ => (),
+    }
+    let upload = ack.upload.ok_or(ErrorKind::InvalidData)?;
+
+    let mut retries = 0;
+    loop {
+        let next_chunk = ack.next_chunk.unwrap_or_default();
+        let request = match next_chunk < file.len_chunks {
+            true => {
+                let start = next_chunk * CHUNK_SIZE;
+                let end = (start + CHUNK_SIZE).min(bytes.len());
+                let chunk = FileChunk::new(next_chunk, bytes[start..end].to_vec());
+                FileRequest::new(FileToken::Chunk, Some(upload)).with_chunk(chunk)
+            }
+            false => FileRequest::new(FileToken::Commit, Some(upload)),
+        };
+
+        match exchange(&mut reader, &mut writer, request, token).await {
+            Ok(response) if response.index == FileToken::Committed => {
+                return response.file.ok_or(ErrorKind::InvalidData.into())
+            }
+            // retrying does not help
+            Ok(response) if response.index == FileToken::Rejected => {
+                return Err(rejection(response))
+            }
+            Ok(response) => ack = response,
+            Err(e) if retries < MAX_RETRIES => {
+                log::debug!("Resuming the upload {upload}: {e}");
+                retries += 1;
+                let resume = FileRequest::new(FileToken::Start, Some(upload));
+                ack = exchange(&mut reader, &mut writer, resume, token).await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Returns the reason the server rejected the upload with
+fn rejection(response: FileRequest) -> Error {
+    match response.error {
+        Some(e) => Error::other(format!("upload rejected: {e}")),
+        None => Error::other("upload rejected"),
+    }
+}
+
+/// Sends the request and waits for the answer of the server
+///
+/// The other messages pushed to the session meanwhile are skipped
+async fn exchange<R, W>(
+    reader: &mut BufReader<R>,
+    writer: &mut BufWriter<W>,
+    body: FileRequest,
+    token: &str,
+) -> Result<FileRequest>
+where
+    R: tokio::io::AsyncRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    let request = Request::new(body.op(), body, token.to_owned());
+    let mut request_json = serde_json::to_vec(&request)?;
+    request_json.push(b'\n');
+    writer.write_all(&request_json).await?;
+    writer.flush().await?;
+
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(ErrorKind::UnexpectedEof.into());
+        }
+
+        if let Ok(response) = serde_json::from_str::<FileRequest>(&line) {
+            if response.index.is_server_only() {
+                return Ok(response);
+            }
+        }
+        if let Ok(response) = serde_json::from_str::<Response<String>>(&line) {
+            return Err(Error::other(response.content));
+        }
+    }
+}
@@ -4,6 +4,8 @@ use nexuslib::models::user::User;
 use reqwest::Client;
 use uuid::Uuid;
 
+use super::server_host;
+
 /// Register a new user
 ///
 /// Receives: stream: &mut TcpStream, user: User
@@ -11,12 +13,16 @@ use uuid::Uuid;
 /// Returns Result
 #[allow(unused)]
 pub async fn get_user(user_uuid: Uuid) -> Result<Option<User>> {
-    let body = reqwest::get(format!("https://127.0.0.1:8082/api/users/{0}", user_uuid))
-        .await
-        .unwrap()
-        .text()
-        .await
-        .unwrap();
+    let body = reqwest::get(format!(
+        "https://{}:8082/api/users/{}",
+        server_host(),
+        user_uuid
+    ))
+    .await
+    .unwrap()
+    .text()
+    .await
+    .unwrap();
 
     match serde_json::from_str::<User>(&body) {
         Ok(user) => Ok(Some(user)),
@@ -26,7 +32,7 @@ pub async fn get_user(user_uuid: Uuid) -> Result<Option<User>> {
 
 pub async fn get_users(client: Client, token: String) -> Result<Vec<User>> {
     let body = client
-        .get("https://127.0.0.1:8082/api/users".to_owned())
+        .get(format!("https://{}:8082/api/users", server_host()))
         .bearer_auth(&token)
         .send()
         .await
@@ -0,0 +1,86 @@
+use std::{
+    io::{Error, Result},
+    path::Path,
+};
+
+use nexuslib::{
+    media::{
+        audio::{AudioFormat, AudioSource, PcmSource, WavSource},
+        voice::{record_voice, VoiceRecording},
+    },
+    models::message::{
+        media::{Media, MediaType},
+        voice::{VoiceMessage, VoiceNote},
+    },
+    Message,
+};
+use tokio::net::TcpStream;
+use uuid::Uuid;
+
+use super::upload::{seal_payload, send_message_request, upload_encrypted};
+
+/// Name of the audio of a voice note, the receiver sees it in the payload
+const VOICE_NAME: &str = "voice.wav";
+
+/// Levels of the bars of a drawn waveform
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Records the voice note from the WAV file, or from raw PCM in the format of calls,
+/// uploads the encrypted audio and sends the note to the receiver
+pub async fn send_voice(
+    stream: &mut TcpStream,
+    path: &Path,
+    shared_key: &[u8],
+    token: String,
+    sender: Uuid,
+    receiver: Uuid,
+) -> Result<VoiceNote> {
+    let recorded = std::env::temp_dir().join(format!("nexus-voice-{}.wav", Uuid::new_v4()));
+    let recording = record(path, &recorded);
+    let bytes = tokio::fs::read(&recorded).await;
+    let _ = tokio::fs::remove_file(&recorded).await;
+    let (recording, bytes) = (recording?, bytes?);
+
+    let (file, payload) =
+        upload_encrypted(stream, &bytes, VOICE_NAME, MediaType::Audio, &token, sender).await?;
+    let voice = VoiceNote::new(file.uuid, recording.duration_ms, recording.waveform);
+
+    let (sealed, nonce) = seal_payload(&payload, shared_key)?;
+    let message = Message::new(
+        VoiceMessage::new(&sealed, voice.clone()),
+        nonce,
+        sender,
+        receiver,
+    )
+    .with_media(Media::new(vec![(&file).into()]));
+    send_message_request(stream, message, token).await?;
+
+    Ok(voice)
+}
+
+/// Draws the waveform of the voice note with its duration
+pub fn describe_voice(voice: &VoiceNote) -> String {
+    let bars = voice
+        .waveform
+        .iter()
+        .map(|level| BARS[*level as usize * BARS.len() / 256])
+        .collect::<String>();
+    let seconds = voice.duration_ms / 1000;
+    format!("{bars} {}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Records the source to the WAV file, the format is told by the extension
+fn record(source: &Path, recorded: &Path) -> Result<VoiceRecording> {
+    let is_wav = source
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("wav"));
+    let mut source: Box<dyn AudioSource> = match is_wav {
+        true => Box::new(WavSource::open(source).map_err(|e| Error::other(format!("{e:?}")))?),
+        false => Box::new(
+            PcmSource::open(source, AudioFormat::VOICE)
+                .map_err(|e| Error::other(format!("{e:?}")))?,
+        ),
+    };
+
+    record_voice(source.as_mut(), recorded).map_err(|e| Error::other(format!("{e:?}")))
+}
@@ -1 +1,3 @@
+pub mod attachment;
 pub mod hasher;
+pub mod totp;
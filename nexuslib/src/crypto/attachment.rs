@@ -0,0 +1,219 @@
+use std::io::{ErrorKind, Read, Write};
+
+use aes_gcm::{aead::Aead, Aes256Gcm, KeyInit, Nonce};
+use rand_core::{OsRng, RngCore};
+use sha3::{Digest, Sha3_256};
+
+use crate::errors::file::AttachmentError;
+
+/// Length of the key of an attachment
+pub const ATTACHMENT_KEY_LEN: usize = 32;
+/// Size of the plaintext encrypted at once
+pub const ATTACHMENT_CHUNK_SIZE: usize = 64 * 1024;
+/// Length of the authentication tag of every encrypted chunk
+pub const ATTACHMENT_TAG_LEN: usize = 16;
+/// Content type of the encrypted files, the server does not know the real one
+pub const ENCRYPTED_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// Returns the size of the encrypted file
+///
+/// Every chunk gets a tag, an empty file is a single empty chunk
+pub fn encrypted_len(len: usize) -> usize {
+    len + len.div_ceil(ATTACHMENT_CHUNK_SIZE).max(1) * ATTACHMENT_TAG_LEN
+}
+
+/// Random key of a single attachment
+///
+/// Sent to the receiver inside the encrypted body of the message,
+/// so the server stores only the ciphertext
+#[derive(Clone, PartialEq)]
+pub struct AttachmentKey([u8; ATTACHMENT_KEY_LEN]);
+
+impl AttachmentKey {
+    /// Generates a random key
+    pub fn generate() -> Self {
+        let mut key = [0u8; ATTACHMENT_KEY_LEN];
+        OsRng.fill_bytes(&mut key);
+        Self(key)
+    }
+
+    pub fn from_hex(key: &str) -> Result<Self, AttachmentError> {
+        let key = hex::decode(key).map_err(|_| AttachmentError::Malformed)?;
+        key.try_into()
+            .map(Self)
+            .map_err(|_| AttachmentError::Malformed)
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0)
+    }
+
+    fn cipher(&self) -> Aes256Gcm {
+        Aes256Gcm::new_from_slice(&self.0).unwrap()
+    }
+}
+
+/// Nonce of the chunk: its index and whether it is the last one
+///
+/// The key is used for a single file, so the nonce only has to be unique
+/// inside of it. The flag of the last chunk makes a truncated file fail
+fn chunk_nonce(index: u32, last: bool) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[7..11].copy_from_slice(&index.to_be_bytes());
+    nonce[11] = last as u8;
+    nonce
+}
+
+/// Encrypts a file chunk by chunk (STREAM construction)
+pub struct AttachmentEncryptor {
+    cipher: Aes256Gcm,
+    index: u32,
+}
+
+impl AttachmentEncryptor {
+    pub fn new(key: &AttachmentKey) -> Self {
+        Self {
+            cipher: key.cipher(),
+            index: 0,
+        }
+    }
+
+    /// Encrypts the next chunk of at most `ATTACHMENT_CHUNK_SIZE` bytes
+    pub fn encrypt_chunk(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>, AttachmentError> {
+        if chunk.len() > ATTACHMENT_CHUNK_SIZE {
+            return Err(AttachmentError::Malformed);
+        }
+
+        let nonce = chunk_nonce(self.index, last);
+        self.index += 1;
+        self.cipher
+            .encrypt(Nonce::from_slice(&nonce), chunk)
+            .map_err(|_| AttachmentError::Malformed)
+    }
+}
+
+/// Decrypts a file encrypted by `AttachmentEncryptor`
+pub struct AttachmentDecryptor {
+    cipher: Aes256Gcm,
+    index: u32,
+}
+
+impl AttachmentDecryptor {
+    pub fn new(key: &AttachmentKey) -> Self {
+        Self {
+            cipher: key.cipher(),
+            index: 0,
+        }
+    }
+
+    /// Decrypts the next chunk of at most `ATTACHMENT_CHUNK_SIZE` + `ATTACHMENT_TAG_LEN` bytes
+    pub fn decrypt_chunk(&mut self, chunk: &[u8], last: bool) -> Result<Vec<u8>, AttachmentError> {
+        if chunk.len() > ATTACHMENT_CHUNK_SIZE + ATTACHMENT_TAG_LEN {
+            return Err(AttachmentError::Malformed);
+        }
+
+        let nonce = chunk_nonce(self.index, last);
+        self.index += 1;
+        self.cipher
+            .decrypt(Nonce::from_slice(&nonce), chunk)
+            .map_err(|_| AttachmentError::Authentication)
+    }
+}
+
+/// Encrypts the file from the reader to the writer
+///
+/// Returns the SHA3-256 of the plaintext as hex,
+/// the receiver checks the decrypted file against it
+pub fn encrypt_attachment<R: Read, W: Write>(
+    key: &AttachmentKey,
+    reader: R,
+    mut writer: W,
+) -> Result<String, AttachmentError> {
+    let mut encryptor = AttachmentEncryptor::new(key);
+    let mut hasher = Sha3_256::new();
+
+    for_each_chunk(reader, ATTACHMENT_CHUNK_SIZE, |chunk, last| {
+        hasher.update(chunk);
+        let encrypted = encryptor.encrypt_chunk(chunk, last)?;
+        writer
+            .write_all(&encrypted)
+            .map_err(|_| AttachmentError::Io)
+    })?;
+    writer.flush().map_err(|_| AttachmentError::Io)?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+/// Decrypts the file from the reader to the writer and checks its digest
+///
+/// The plaintext is written as it is authenticated, so the output of
+/// a failed decryption has to be discarded
+pub fn decrypt_attachment<R: Read, W: Write>(
+    key: &AttachmentKey,
+    digest: &str,
+    reader: R,
+    mut writer: W,
+) -> Result<(), AttachmentError> {
+    let mut decryptor = AttachmentDecryptor::new(key);
+    let mut hasher = Sha3_256::new();
+
+    for_each_chunk(
+        reader,
+        ATTACHMENT_CHUNK_SIZE + ATTACHMENT_TAG_LEN,
+        |chunk, last| {
+            let decrypted = decryptor.decrypt_chunk(chunk, last)?;
+            hasher.update(&decrypted);
+            writer
+                .write_all(&decrypted)
+                .map_err(|_| AttachmentError::Io)
+        },
+    )?;
+    writer.flush().map_err(|_| AttachmentError::Io)?;
+
+    match hex::encode(hasher.finalize()) == digest.to_lowercase() {
+        true => Ok(()),
+        false => Err(AttachmentError::Digest),
+    }
+}
+
+/// Calls `f` for every chunk of the reader, the last one is marked
+///
+/// Only full chunks are followed by others, so a chunk is known to be
+/// the last one when it is shorter or when nothing follows it
+fn for_each_chunk<R, F>(mut reader: R, size: usize, mut f: F) -> Result<(), AttachmentError>
+where
+    R: Read,
+    F: FnMut(&[u8], bool) -> Result<(), AttachmentError>,
+{
+    let mut current = vec![0u8; size];
+    let mut next = vec![0u8; size];
+    let mut len = read_full(&mut reader, &mut current)?;
+
+    loop {
+        if len < size {
+            return f(&current[..len], true);
+        }
+        let next_len = read_full(&mut reader, &mut next)?;
+        if next_len == 0 {
+            return f(&current[..len], true);
+        }
+
+        f(&current[..len], false)?;
+        std::mem::swap(&mut current, &mut next);
+        len = next_len;
+    }
+}
+
+/// Reads until the buffer is full or the reader ends
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, AttachmentError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return Err(AttachmentError::Io),
+        }
+    }
+    Ok(filled)
+}
@@ -6,3 +6,8 @@ pub fn get_hash(text: &str) -> String {
     hasher.update(text);
     hex::encode(hasher.finalize())
 }
+
+/// Returns the SHA3-256 of the bytes as hex
+pub fn get_bytes_hash(bytes: &[u8]) -> String {
+    hex::encode(Sha3_256::digest(bytes))
+}
@@ -0,0 +1,101 @@
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+
+/// Number of digits in a code
+pub const TOTP_DIGITS: u32 = 6;
+/// Time step in seconds
+pub const TOTP_STEP: i64 = 30;
+/// Length of a generated secret in bytes
+pub const TOTP_SECRET_LEN: usize = 20;
+
+const ALPHABET: Alphabet = Alphabet::RFC4648 { padding: false };
+
+/// Generates a random TOTP secret
+pub fn generate_secret() -> Vec<u8> {
+    let mut secret = vec![0u8; TOTP_SECRET_LEN];
+    OsRng.fill_bytes(&mut secret);
+    secret
+}
+
+/// Encodes the secret as base32 (the form authenticator apps expect)
+pub fn encode_secret(secret: &[u8]) -> String {
+    base32::encode(ALPHABET, secret)
+}
+
+/// Decodes the base32 secret
+pub fn decode_secret(secret: &str) -> Option<Vec<u8>> {
+    base32::decode(ALPHABET, secret)
+}
+
+/// Returns the `otpauth://` URI that can be shown as a QR code
+pub fn otpauth_uri(secret: &[u8], account: &str, issuer: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{account}?secret={secret}&issuer={issuer}&algorithm=SHA1&digits={TOTP_DIGITS}&period={TOTP_STEP}",
+        issuer = escape(issuer),
+        account = escape(account),
+        secret = encode_secret(secret),
+    )
+}
+
+/// Returns the time step of the unix timestamp
+pub fn step_at(timestamp: i64) -> i64 {
+    timestamp.div_euclid(TOTP_STEP)
+}
+
+/// Computes the code for the time step (RFC 6238, HMAC-SHA1)
+pub fn code_at(secret: &[u8], step: i64) -> String {
+    let mut mac = Hmac::<Sha1>::new_from_slice(secret).expect("HMAC accepts keys of any size");
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+
+    // dynamic truncation
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+
+    format!(
+        "{:0width$}",
+        binary % 10u32.pow(TOTP_DIGITS),
+        width = TOTP_DIGITS as usize
+    )
+}
+
+/// Verifies the code allowing the clock drift of `window` steps
+///
+/// Returns the matched time step, so the caller can reject reused codes
+pub fn verify(secret: &[u8], code: &str, timestamp: i64, window: i64) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize {
+        return None;
+    }
+
+    let current = step_at(timestamp);
+    (current - window..=current + window)
+        .find(|step| constant_time_eq(&code_at(secret, *step), code))
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+fn escape(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
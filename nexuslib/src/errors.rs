@@ -1 +1,3 @@
+pub mod file;
+pub mod media;
 pub mod stream;
@@ -0,0 +1,71 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// Errors occured while uploading a file
+pub enum UploadError {
+    Empty,
+    /// The file is larger than `MAX_FILE_SIZE`
+    TooLarge,
+    /// `len_chunks` does not match `len_bytes`
+    ChunkCount,
+    /// The chunk is not the one the upload expects
+    OutOfOrder {
+        expected: usize,
+    },
+    /// The chunk is longer than `CHUNK_SIZE` or a chunk that is not
+    /// the last one is shorter, or the chunks exceed `len_bytes`
+    ChunkSize,
+    /// The data does not match its hash
+    Corrupted,
+    /// Not all the chunks were received
+    Incomplete,
+    /// The content type does not match the `MediaType`
+    ContentType,
+    /// The stored object differs in size from `len_bytes`
+    SizeMismatch,
+    /// The format of the content is not known or not allowed for the `MediaType`
+    UnsupportedFormat,
+    /// The content is of another `MediaType`
+    TypeMismatch,
+    /// The extension of the name does not fit the content
+    Extension,
+    /// The files of the user would exceed the storage quota
+    QuotaExceeded,
+}
+
+impl fmt::Display for UploadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            UploadError::Empty => "the file is empty",
+            UploadError::TooLarge => "the file is too large",
+            UploadError::ChunkCount => "the number of the chunks does not match the size",
+            UploadError::OutOfOrder { .. } => "the chunk is out of order",
+            UploadError::ChunkSize => "the chunk has a wrong size",
+            UploadError::Corrupted => "the data does not match its hash",
+            UploadError::Incomplete => "not all the chunks were received",
+            UploadError::ContentType => "the content type does not match the media type",
+            UploadError::SizeMismatch => "the stored file has a wrong size",
+            UploadError::UnsupportedFormat => "the format of the file is not allowed",
+            UploadError::TypeMismatch => "the content does not match the media type",
+            UploadError::Extension => "the extension does not match the content",
+            UploadError::QuotaExceeded => "the storage quota is exceeded",
+        };
+        f.write_str(message)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Errors occured while encrypting or decrypting an attachment
+pub enum AttachmentError {
+    /// The key or the chunk has a wrong length
+    Malformed,
+    /// The authentication tag of a chunk does not match,
+    /// also when the chunks were reordered or the file was truncated
+    Authentication,
+    /// The decrypted file does not match its digest
+    Digest,
+    /// The file cannot be read or written
+    Io,
+}
@@ -0,0 +1,16 @@
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Errors occured while processing the call media
+pub enum MediaError {
+    /// The packet cannot be parsed
+    Malformed,
+    /// The authentication tag does not match
+    Authentication,
+    /// The packet was already received
+    Replay,
+    /// The audio cannot be read or written
+    Io,
+    /// The codec failed to encode or decode the frame
+    Codec,
+    /// The payload type has no codec
+    UnsupportedCodec(u8),
+}
@@ -1,5 +1,6 @@
 pub mod crypto;
 pub mod errors;
+pub mod media;
 pub mod models;
 pub mod request;
 pub mod response;
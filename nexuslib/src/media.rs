@@ -0,0 +1,14 @@
+pub mod audio;
+pub mod codec;
+pub mod concealment;
+pub mod ice;
+pub mod jitter;
+pub mod metrics;
+pub mod pipeline;
+pub mod rtcp;
+pub mod rtp;
+pub mod session;
+pub mod speaker;
+pub mod srtp;
+pub mod stun;
+pub mod voice;
@@ -0,0 +1,214 @@
+use std::{
+    f32::consts::PI,
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read},
+    path::Path,
+};
+
+use hound::{SampleFormat, WavReader, WavSpec, WavWriter};
+
+use crate::errors::media::MediaError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Format of the PCM audio, the samples are 16-bit and interleaved
+pub struct AudioFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl AudioFormat {
+    /// Format of the audio of calls
+    pub const VOICE: AudioFormat = AudioFormat {
+        sample_rate: 48_000,
+        channels: 1,
+    };
+
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        Self {
+            sample_rate,
+            channels,
+        }
+    }
+
+    /// Returns the number of samples of all channels in a frame
+    pub fn frame_len(&self, frame_ms: u32) -> usize {
+        (self.sample_rate * frame_ms / 1000) as usize * self.channels as usize
+    }
+}
+
+/// Source of the captured audio
+pub trait AudioSource {
+    fn format(&self) -> AudioFormat;
+
+    /// Fills the frame with the next samples
+    ///
+    /// Returns `false` when the source has ended
+    fn read_frame(&mut self, frame: &mut [i16]) -> Result<bool, MediaError>;
+}
+
+/// Destination of the played audio
+pub trait AudioSink {
+    fn format(&self) -> AudioFormat;
+
+    fn write_frame(&mut self, frame: &[i16]) -> Result<(), MediaError>;
+}
+
+/// Audio read from a 16-bit PCM WAV file
+pub struct WavSource {
+    reader: WavReader<BufReader<File>>,
+    format: AudioFormat,
+}
+
+impl WavSource {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, MediaError> {
+        let reader = WavReader::open(path).map_err(|_| MediaError::Io)?;
+        let spec = reader.spec();
+        if spec.bits_per_sample != 16 || spec.sample_format != SampleFormat::Int {
+            return Err(MediaError::Io);
+        }
+
+        Ok(Self {
+            format: AudioFormat::new(spec.sample_rate, spec.channels),
+            reader,
+        })
+    }
+}
+
+impl AudioSource for WavSource {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read_frame(&mut self, frame: &mut [i16]) -> Result<bool, MediaError> {
+        let mut samples = self.reader.samples::<i16>();
+        let mut read = 0;
+        for slot in frame.iter_mut() {
+            match samples.next() {
+                Some(sample) => {
+                    *slot = sample.map_err(|_| MediaError::Io)?;
+                    read += 1;
+                }
+                None => *slot = 0,
+            }
+        }
+
+        // the last frame is padded with silence
+        Ok(read > 0)
+    }
+}
+
+/// Raw 16-bit little-endian PCM read from a file, the format is not stored in it
+pub struct PcmSource {
+    reader: BufReader<File>,
+    format: AudioFormat,
+}
+
+impl PcmSource {
+    pub fn open(path: impl AsRef<Path>, format: AudioFormat) -> Result<Self, MediaError> {
+        let file = File::open(path).map_err(|_| MediaError::Io)?;
+
+        Ok(Self {
+            reader: BufReader::new(file),
+            format,
+        })
+    }
+}
+
+impl AudioSource for PcmSource {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read_frame(&mut self, frame: &mut [i16]) -> Result<bool, MediaError> {
+        let mut read = 0;
+        for slot in frame.iter_mut() {
+            let mut sample = [0u8; 2];
+            match self.reader.read_exact(&mut sample) {
+                Ok(()) => {
+                    *slot = i16::from_le_bytes(sample);
+                    read += 1;
+                }
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof => *slot = 0,
+                Err(_) => return Err(MediaError::Io),
+            }
+        }
+
+        // the last frame is padded with silence
+        Ok(read > 0)
+    }
+}
+
+/// Audio recorded to a 16-bit PCM WAV file
+///
+/// The header of the file is completed by `finish`
+pub struct WavSink {
+    writer: WavWriter<BufWriter<File>>,
+    format: AudioFormat,
+}
+
+impl WavSink {
+    pub fn create(path: impl AsRef<Path>, format: AudioFormat) -> Result<Self, MediaError> {
+        let spec = WavSpec {
+            channels: format.channels,
+            sample_rate: format.sample_rate,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+        let writer = WavWriter::create(path, spec).map_err(|_| MediaError::Io)?;
+
+        Ok(Self { writer, format })
+    }
+
+    pub fn finish(self) -> Result<(), MediaError> {
+        self.writer.finalize().map_err(|_| MediaError::Io)
+    }
+}
+
+impl AudioSink for WavSink {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn write_frame(&mut self, frame: &[i16]) -> Result<(), MediaError> {
+        frame
+            .iter()
+            .try_for_each(|sample| self.writer.write_sample(*sample))
+            .map_err(|_| MediaError::Io)
+    }
+}
+
+/// Endless sine tone, used when there is nothing to capture
+pub struct ToneSource {
+    format: AudioFormat,
+    frequency: f32,
+    amplitude: f32,
+    position: u64,
+}
+
+impl ToneSource {
+    pub fn new(format: AudioFormat, frequency: f32, amplitude: i16) -> Self {
+        Self {
+            format,
+            frequency,
+            amplitude: amplitude as f32,
+            position: 0,
+        }
+    }
+}
+
+impl AudioSource for ToneSource {
+    fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    fn read_frame(&mut self, frame: &mut [i16]) -> Result<bool, MediaError> {
+        let channels = self.format.channels.max(1) as usize;
+        for samples in frame.chunks_mut(channels) {
+            let time = self.position as f32 / self.format.sample_rate as f32;
+            let sample = (2.0 * PI * self.frequency * time).sin() * self.amplitude;
+            samples.fill(sample as i16);
+            self.position += 1;
+        }
+        Ok(true)
+    }
+}
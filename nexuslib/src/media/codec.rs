@@ -0,0 +1,248 @@
+use crate::errors::media::MediaError;
+
+use super::{audio::AudioFormat, concealment::Concealer, rtp::AUDIO_PAYLOAD_TYPE};
+
+/// Dynamic payload type of the uncompressed audio
+pub const L16_PAYLOAD_TYPE: u8 = 96;
+/// Default bitrate of the Opus audio in bits per second
+pub const OPUS_DEFAULT_BITRATE: i32 = 32_000;
+
+/// Compresses the PCM frames into RTP payloads
+pub trait AudioEncoder {
+    fn encode(&mut self, pcm: &[i16]) -> Result<Vec<u8>, MediaError>;
+}
+
+/// Decompresses the RTP payloads into PCM frames
+pub trait AudioDecoder {
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, MediaError>;
+
+    /// Returns the frame of `len` samples that replaces a lost one
+    fn conceal(&mut self, len: usize) -> Result<Vec<i16>, MediaError>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Audio codec of a call
+pub enum Codec {
+    /// Uncompressed 16-bit PCM (RFC 3551)
+    L16,
+    /// Opus with the bitrate in bits per second (RFC 7587)
+    Opus { bitrate: i32 },
+}
+
+impl Codec {
+    /// Returns the codec of the payload type
+    pub fn from_payload_type(payload_type: u8) -> Result<Self, MediaError> {
+        match payload_type {
+            L16_PAYLOAD_TYPE => Ok(Codec::L16),
+            AUDIO_PAYLOAD_TYPE => Ok(Codec::Opus {
+                bitrate: OPUS_DEFAULT_BITRATE,
+            }),
+            _ => Err(MediaError::UnsupportedCodec(payload_type)),
+        }
+    }
+
+    pub fn payload_type(&self) -> u8 {
+        match self {
+            Codec::L16 => L16_PAYLOAD_TYPE,
+            Codec::Opus { .. } => AUDIO_PAYLOAD_TYPE,
+        }
+    }
+
+    /// Returns the RTP clock rate, Opus always uses 48 kHz
+    pub fn clock_rate(&self, format: AudioFormat) -> u32 {
+        match self {
+            Codec::L16 => format.sample_rate,
+            Codec::Opus { .. } => 48_000,
+        }
+    }
+
+    #[cfg_attr(not(feature = "opus"), allow(unused_variables))]
+    pub fn encoder(&self, format: AudioFormat) -> Result<Box<dyn AudioEncoder + Send>, MediaError> {
+        match self {
+            Codec::L16 => Ok(Box::new(L16Codec::new())),
+            #[cfg(feature = "opus")]
+            Codec::Opus { bitrate } => Ok(Box::new(opus::OpusEncoder::new(format, *bitrate)?)),
+            #[cfg(not(feature = "opus"))]
+            Codec::Opus { .. } => Err(MediaError::UnsupportedCodec(AUDIO_PAYLOAD_TYPE)),
+        }
+    }
+
+    #[cfg_attr(not(feature = "opus"), allow(unused_variables))]
+    pub fn decoder(&self, format: AudioFormat) -> Result<Box<dyn AudioDecoder + Send>, MediaError> {
+        match self {
+            Codec::L16 => Ok(Box::new(L16Codec::new())),
+            #[cfg(feature = "opus")]
+            Codec::Opus { .. } => Ok(Box::new(opus::OpusDecoder::new(format)?)),
+            #[cfg(not(feature = "opus"))]
+            Codec::Opus { .. } => Err(MediaError::UnsupportedCodec(AUDIO_PAYLOAD_TYPE)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+/// Uncompressed big-endian 16-bit PCM
+///
+/// Used where the Opus codec is not built in and for the tests
+pub struct L16Codec {
+    concealer: Concealer,
+}
+
+impl L16Codec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AudioEncoder for L16Codec {
+    fn encode(&mut self, pcm: &[i16]) -> Result<Vec<u8>, MediaError> {
+        Ok(pcm.iter().flat_map(|sample| sample.to_be_bytes()).collect())
+    }
+}
+
+impl AudioDecoder for L16Codec {
+    fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, MediaError> {
+        if !payload.len().is_multiple_of(2) {
+            return Err(MediaError::Malformed);
+        }
+
+        let pcm: Vec<i16> = payload
+            .chunks_exact(2)
+            .map(|bytes| i16::from_be_bytes([bytes[0], bytes[1]]))
+            .collect();
+        self.concealer.received(&pcm);
+        Ok(pcm)
+    }
+
+    fn conceal(&mut self, len: usize) -> Result<Vec<i16>, MediaError> {
+        Ok(self.concealer.conceal(len))
+    }
+}
+
+#[cfg(feature = "opus")]
+pub mod opus {
+    use audiopus::{
+        coder::{Decoder, Encoder},
+        packet::Packet,
+        Application, Bitrate, Channels, MutSignals, SampleRate,
+    };
+
+    use crate::{errors::media::MediaError, media::audio::AudioFormat};
+
+    use super::{AudioDecoder, AudioEncoder};
+
+    /// Maximal size of an Opus packet recommended by the libopus documentation
+    const MAX_PACKET_LEN: usize = 4000;
+
+    fn sample_rate(format: AudioFormat) -> Result<SampleRate, MediaError> {
+        SampleRate::try_from(format.sample_rate as i32).map_err(|_| MediaError::Codec)
+    }
+
+    fn channels(format: AudioFormat) -> Result<Channels, MediaError> {
+        match format.channels {
+            1 => Ok(Channels::Mono),
+            2 => Ok(Channels::Stereo),
+            _ => Err(MediaError::Codec),
+        }
+    }
+
+    /// Opus encoder tuned for the voice
+    pub struct OpusEncoder {
+        encoder: Encoder,
+    }
+
+    impl OpusEncoder {
+        pub fn new(format: AudioFormat, bitrate: i32) -> Result<Self, MediaError> {
+            let mut encoder =
+                Encoder::new(sample_rate(format)?, channels(format)?, Application::Voip)
+                    .map_err(|_| MediaError::Codec)?;
+            encoder
+                .set_bitrate(Bitrate::BitsPerSecond(bitrate))
+                .map_err(|_| MediaError::Codec)?;
+            // lets the decoder recover a lost frame from the next packet
+            encoder
+                .set_inband_fec(true)
+                .map_err(|_| MediaError::Codec)?;
+
+            Ok(Self { encoder })
+        }
+
+        /// Changes the bitrate in bits per second
+        pub fn set_bitrate(&mut self, bitrate: i32) -> Result<(), MediaError> {
+            self.encoder
+                .set_bitrate(Bitrate::BitsPerSecond(bitrate))
+                .map_err(|_| MediaError::Codec)
+        }
+
+        /// Tells the encoder the expected loss, so it adds more redundancy
+        pub fn set_packet_loss(&mut self, percent: u8) -> Result<(), MediaError> {
+            self.encoder
+                .set_packet_loss_perc(percent.min(100))
+                .map_err(|_| MediaError::Codec)
+        }
+    }
+
+    impl AudioEncoder for OpusEncoder {
+        fn encode(&mut self, pcm: &[i16]) -> Result<Vec<u8>, MediaError> {
+            let mut packet = vec![0u8; MAX_PACKET_LEN];
+            let len = self
+                .encoder
+                .encode(pcm, &mut packet)
+                .map_err(|_| MediaError::Codec)?;
+            packet.truncate(len);
+            Ok(packet)
+        }
+    }
+
+    /// Opus decoder, lost frames are concealed by the codec itself
+    pub struct OpusDecoder {
+        decoder: Decoder,
+        channels: usize,
+        /// Samples of all channels in the longest Opus frame (120 ms)
+        max_frame_len: usize,
+    }
+
+    impl OpusDecoder {
+        pub fn new(format: AudioFormat) -> Result<Self, MediaError> {
+            let decoder = Decoder::new(sample_rate(format)?, channels(format)?)
+                .map_err(|_| MediaError::Codec)?;
+
+            Ok(Self {
+                decoder,
+                channels: format.channels as usize,
+                max_frame_len: format.frame_len(120),
+            })
+        }
+
+        fn decode_into(
+            &mut self,
+            payload: Option<&[u8]>,
+            len: usize,
+        ) -> Result<Vec<i16>, MediaError> {
+            let packet = match payload {
+                Some(payload) => {
+                    Some(Packet::try_from(payload).map_err(|_| MediaError::Malformed)?)
+                }
+                None => None,
+            };
+
+            let mut pcm = vec![0i16; len];
+            let signals = MutSignals::try_from(&mut pcm).map_err(|_| MediaError::Codec)?;
+            let samples = self
+                .decoder
+                .decode(packet, signals, false)
+                .map_err(|_| MediaError::Codec)?;
+            pcm.truncate(samples * self.channels);
+            Ok(pcm)
+        }
+    }
+
+    impl AudioDecoder for OpusDecoder {
+        fn decode(&mut self, payload: &[u8]) -> Result<Vec<i16>, MediaError> {
+            self.decode_into(Some(payload), self.max_frame_len)
+        }
+
+        fn conceal(&mut self, len: usize) -> Result<Vec<i16>, MediaError> {
+            self.decode_into(None, len)
+        }
+    }
+}
@@ -0,0 +1,48 @@
+/// Gain applied to each next concealed frame
+const FADE: f32 = 0.5;
+/// Number of frames concealed before switching to silence
+const MAX_CONCEALED: u32 = 5;
+
+#[derive(Debug, Clone, Default)]
+/// Packet loss concealment of PCM audio
+///
+/// A lost frame is replaced by the last good one faded out,
+/// a longer loss turns into silence
+pub struct Concealer {
+    last: Vec<i16>,
+    consecutive: u32,
+}
+
+impl Concealer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers the frame that was received and played
+    pub fn received(&mut self, pcm: &[i16]) {
+        self.last.clear();
+        self.last.extend_from_slice(pcm);
+        self.consecutive = 0;
+    }
+
+    /// Returns the frame of `len` samples that replaces a lost one
+    pub fn conceal(&mut self, len: usize) -> Vec<i16> {
+        self.consecutive += 1;
+        if self.consecutive > MAX_CONCEALED || self.last.is_empty() {
+            return vec![0; len];
+        }
+
+        let gain = FADE.powi(self.consecutive as i32);
+        self.last
+            .iter()
+            .cycle()
+            .take(len)
+            .map(|sample| (*sample as f32 * gain) as i16)
+            .collect()
+    }
+
+    /// Returns the number of frames concealed in a row
+    pub fn consecutive(&self) -> u32 {
+        self.consecutive
+    }
+}
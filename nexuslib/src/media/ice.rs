@@ -0,0 +1,232 @@
+use std::{cmp::Reverse, net::SocketAddr};
+
+use rand_core::{OsRng, RngCore};
+
+use crate::models::call::candidate::{CandidateKind, IceCandidate, IceParameters};
+
+use super::stun::{StunAttribute, StunMessage, StunType};
+
+/// Interval between the retransmissions of a check
+pub const CHECK_INTERVAL_MS: u64 = 100;
+/// Number of the checks of a pair after which it fails
+const MAX_ATTEMPTS: u32 = 20;
+/// Kind preference of the peer reflexive candidates (RFC 8445)
+const PEER_REFLEXIVE_PREFERENCE: u32 = 110;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// State of the connectivity checks
+pub enum IceState {
+    /// The candidates of the other side are not known yet
+    New,
+    Checking,
+    /// The other side is reachable directly at the address
+    Connected(SocketAddr),
+    /// No direct path works, the media has to go through the relay
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PairState {
+    Waiting,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+#[derive(Debug, Clone)]
+struct CandidatePair {
+    remote: IceCandidate,
+    state: PairState,
+    transaction: [u8; 12],
+    attempts: u32,
+    last_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+/// Connectivity checks of one call participant
+///
+/// Both sides check the host and the server reflexive candidates of the
+/// other one with STUN binding requests authenticated by the credentials
+/// exchanged over the signaling. Each side sends its media to the best
+/// address that answered, so no nomination is needed.
+/// The relay candidates are not checked, the relay is reached with its ticket
+pub struct IceAgent {
+    local: IceParameters,
+    /// Username fragment and password of the other side
+    remote: Option<(String, String)>,
+    controlling: bool,
+    tie_breaker: u64,
+    pairs: Vec<CandidatePair>,
+}
+
+impl IceAgent {
+    /// Creates the agent with new credentials
+    ///
+    /// The side that starts the call is the controlling one
+    pub fn new(controlling: bool, candidates: Vec<IceCandidate>) -> Self {
+        let mut ufrag = [0u8; 4];
+        let mut pwd = [0u8; 12];
+        OsRng.fill_bytes(&mut ufrag);
+        OsRng.fill_bytes(&mut pwd);
+
+        Self {
+            local: IceParameters {
+                ufrag: hex::encode(ufrag),
+                pwd: hex::encode(pwd),
+                candidates,
+            },
+            remote: None,
+            controlling,
+            tie_breaker: OsRng.next_u64(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// Returns the parameters sent to the other side
+    pub fn local(&self) -> &IceParameters {
+        &self.local
+    }
+
+    /// Takes the parameters of the other side and starts the checks
+    pub fn set_remote(&mut self, remote: IceParameters) {
+        let mut candidates: Vec<IceCandidate> = remote
+            .candidates
+            .into_iter()
+            .filter(|candidate| candidate.kind != CandidateKind::Relay)
+            .collect();
+        candidates.sort_by_key(|candidate| Reverse(candidate.priority));
+        candidates.dedup_by(|a, b| a.address == b.address);
+
+        self.pairs = candidates
+            .into_iter()
+            .map(|remote| CandidatePair {
+                remote,
+                state: PairState::Waiting,
+                transaction: StunMessage::binding_request().transaction,
+                attempts: 0,
+                last_ms: 0,
+            })
+            .collect();
+        self.remote = Some((remote.ufrag, remote.pwd));
+    }
+
+    pub fn state(&self) -> IceState {
+        if self.remote.is_none() {
+            return IceState::New;
+        }
+        if let Some(selected) = self.selected() {
+            return IceState::Connected(selected);
+        }
+
+        match self
+            .pairs
+            .iter()
+            .all(|pair| pair.state == PairState::Failed)
+        {
+            true => IceState::Failed,
+            false => IceState::Checking,
+        }
+    }
+
+    /// Returns the address of the best pair that succeeded
+    pub fn selected(&self) -> Option<SocketAddr> {
+        self.pairs
+            .iter()
+            .filter(|pair| pair.state == PairState::Succeeded)
+            .max_by_key(|pair| pair.remote.priority)
+            .map(|pair| pair.remote.address)
+    }
+
+    /// Returns the checks that have to be sent now
+    ///
+    /// Called periodically, a check is retransmitted every `CHECK_INTERVAL_MS`
+    /// until it is answered, the checks stop once a pair has succeeded
+    pub fn poll(&mut self, now_ms: u64) -> Vec<(SocketAddr, Vec<u8>)> {
+        let (remote_ufrag, remote_pwd) = match &self.remote {
+            Some(remote) => remote.clone(),
+            None => return Vec::new(),
+        };
+        if self.selected().is_some() {
+            return Vec::new();
+        }
+
+        let mut checks = Vec::new();
+        for pair in self.pairs.iter_mut() {
+            if !matches!(pair.state, PairState::Waiting | PairState::InProgress) {
+                continue;
+            }
+            if pair.attempts >= MAX_ATTEMPTS {
+                pair.state = PairState::Failed;
+                continue;
+            }
+            if pair.attempts > 0 && now_ms.saturating_sub(pair.last_ms) < CHECK_INTERVAL_MS {
+                continue;
+            }
+
+            let role = match self.controlling {
+                true => StunAttribute::IceControlling(self.tie_breaker),
+                false => StunAttribute::IceControlled(self.tie_breaker),
+            };
+            let priority = PEER_REFLEXIVE_PREFERENCE << 24 | 65_535 << 8 | 255;
+            let request = StunMessage {
+                kind: StunType::BindingRequest,
+                transaction: pair.transaction,
+                attributes: Vec::new(),
+            }
+            .with(StunAttribute::Username(format!(
+                "{remote_ufrag}:{}",
+                self.local.ufrag
+            )))
+            .with(StunAttribute::Priority(priority))
+            .with(role);
+
+            pair.state = PairState::InProgress;
+            pair.attempts += 1;
+            pair.last_ms = now_ms;
+            checks.push((
+                pair.remote.address,
+                request.encode(Some(remote_pwd.as_bytes())),
+            ));
+        }
+        checks
+    }
+
+    /// Handles the STUN datagram received from the address
+    ///
+    /// Returns the response if the datagram is a check of the other side
+    pub fn handle(&mut self, datagram: &[u8], from: SocketAddr) -> Option<Vec<u8>> {
+        let message = StunMessage::decode(datagram).ok()?;
+
+        match message.kind {
+            StunType::BindingRequest => {
+                let prefix = format!("{}:", self.local.ufrag);
+                if !message.username()?.starts_with(&prefix)
+                    || !StunMessage::verify_integrity(datagram, self.local.pwd.as_bytes())
+                {
+                    return None;
+                }
+
+                let response = StunMessage::binding_success(&message, from);
+                Some(response.encode(Some(self.local.pwd.as_bytes())))
+            }
+            StunType::BindingSuccess | StunType::BindingError => {
+                let (_, remote_pwd) = self.remote.as_ref()?;
+                if !StunMessage::verify_integrity(datagram, remote_pwd.as_bytes()) {
+                    return None;
+                }
+
+                // the response has to come from the address that was checked
+                let pair = self.pairs.iter_mut().find(|pair| {
+                    pair.transaction == message.transaction
+                        && pair.state == PairState::InProgress
+                        && pair.remote.address == from
+                })?;
+                pair.state = match message.kind {
+                    StunType::BindingSuccess => PairState::Succeeded,
+                    _ => PairState::Failed,
+                };
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::collections::BTreeMap;
+
+use super::rtp::RtpPacket;
+
+/// Number of in-order packets after which the reordering depth is lowered
+const REORDER_DECAY: u32 = 50;
+
+#[derive(Debug, Clone, Copy)]
+/// Configuration of the `JitterBuffer`, the depths are in frames
+pub struct JitterConfig {
+    /// Duration of one frame in milliseconds
+    pub frame_ms: u32,
+    pub min_depth: usize,
+    pub max_depth: usize,
+}
+
+impl Default for JitterConfig {
+    fn default() -> Self {
+        Self {
+            frame_ms: 20,
+            min_depth: 2,
+            max_depth: 15,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Result of pushing a packet into the `JitterBuffer`
+pub enum Arrival {
+    Buffered,
+    /// The frame of the packet has already been played or concealed
+    Late,
+    Duplicate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Frame returned by the `JitterBuffer` for the playout
+pub enum Playout {
+    Frame(RtpPacket),
+    /// The packet with the sequence number is lost and has to be concealed
+    Lost(u16),
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+/// Counters of the `JitterBuffer`
+pub struct JitterStats {
+    pub received: u64,
+    pub played: u64,
+    pub lost: u64,
+    pub late: u64,
+    pub duplicates: u64,
+    /// Frames dropped to lower the latency
+    pub discarded: u64,
+    /// Times the buffer ran empty while playing
+    pub underruns: u64,
+}
+
+#[derive(Debug, Clone)]
+/// Jitter buffer of one RTP stream
+///
+/// Packets are reordered by their sequence numbers and played
+/// once per frame. The depth adapts to the interarrival jitter
+/// and to the reordering observed on the network, the gaps
+/// in the sequence numbers are reported as lost frames
+pub struct JitterBuffer {
+    config: JitterConfig,
+    clock_rate: u32,
+    /// Packets by the extended sequence number
+    packets: BTreeMap<u64, RtpPacket>,
+    highest: Option<u64>,
+    /// Extended sequence number of the next frame to play
+    next: Option<u64>,
+    playing: bool,
+    target_depth: usize,
+    /// Largest reordering distance seen recently
+    reorder: usize,
+    in_order: u32,
+    transit: Option<i64>,
+    /// Interarrival jitter in RTP timestamp units (RFC 3550 A.8)
+    jitter: f64,
+    stats: JitterStats,
+}
+
+impl JitterBuffer {
+    pub fn new(config: JitterConfig, clock_rate: u32) -> Self {
+        Self {
+            config,
+            clock_rate,
+            packets: BTreeMap::new(),
+            highest: None,
+            next: None,
+            playing: false,
+            target_depth: config.min_depth,
+            reorder: 0,
+            in_order: 0,
+            transit: None,
+            jitter: 0.0,
+            stats: JitterStats::default(),
+        }
+    }
+
+    /// Buffers the packet, `arrival_ms` is the local time of the arrival
+    pub fn push(&mut self, packet: RtpPacket, arrival_ms: u64) -> Arrival {
+        let index = self.extend(packet.header.seq);
+
+        if self.next.is_some_and(|next| index < next) {
+            // the buffer is too short for the network => deepen it
+            if let Some(highest) = self.highest {
+                self.reorder = self.reorder.max(highest.saturating_sub(index) as usize);
+                self.adapt();
+            }
+            self.stats.late += 1;
+            return Arrival::Late;
+        }
+        if self.packets.contains_key(&index) {
+            self.stats.duplicates += 1;
+            return Arrival::Duplicate;
+        }
+        self.stats.received += 1;
+
+        self.update_jitter(packet.header.timestamp, arrival_ms);
+        match self.highest {
+            Some(highest) if index < highest => {
+                self.reorder = self.reorder.max((highest - index) as usize);
+                self.in_order = 0;
+            }
+            _ => {
+                self.highest = Some(index);
+                self.in_order += 1;
+                if self.in_order >= REORDER_DECAY {
+                    self.reorder = self.reorder.saturating_sub(1);
+                    self.in_order = 0;
+                }
+            }
+        }
+        self.adapt();
+
+        self.packets.insert(index, packet);
+        Arrival::Buffered
+    }
+
+    /// Returns the next frame, called once per frame duration
+    ///
+    /// `None` means that the buffer is filling up and nothing has to be played
+    pub fn pop(&mut self) -> Option<Playout> {
+        if !self.playing {
+            if self.packets.len() < self.target_depth {
+                return None;
+            }
+            self.playing = true;
+            // after an underrun the playout continues where it stopped
+            let first = *self.packets.keys().next().unwrap();
+            self.next = Some(self.next.unwrap_or(first));
+        }
+
+        if self.packets.is_empty() {
+            // keep the position, the late packet may still arrive
+            self.playing = false;
+            self.stats.underruns += 1;
+            return None;
+        }
+
+        // too much delay has accumulated => drop the oldest frames
+        let mut next = self.next.unwrap();
+        let excess = self
+            .depth()
+            .saturating_sub(self.config.max_depth.max(self.target_depth * 2));
+        for _ in 0..excess {
+            if self.packets.remove(&next).is_some() {
+                self.stats.discarded += 1;
+            }
+            next += 1;
+        }
+
+        self.next = Some(next + 1);
+        match self.packets.remove(&next) {
+            Some(packet) => {
+                self.stats.played += 1;
+                Some(Playout::Frame(packet))
+            }
+            None => {
+                self.stats.lost += 1;
+                Some(Playout::Lost(next as u16))
+            }
+        }
+    }
+
+    /// Returns the number of frames between the next frame and the newest packet
+    pub fn depth(&self) -> usize {
+        match (self.next, self.highest) {
+            (Some(next), Some(highest)) if highest >= next => (highest - next + 1) as usize,
+            (None, _) => self.packets.len(),
+            _ => 0,
+        }
+    }
+
+    /// Returns the depth the buffer is adapted to
+    pub fn target_depth(&self) -> usize {
+        self.target_depth
+    }
+
+    /// Returns the interarrival jitter in milliseconds
+    pub fn jitter_ms(&self) -> f64 {
+        self.jitter * 1000.0 / self.clock_rate as f64
+    }
+
+    pub fn stats(&self) -> JitterStats {
+        self.stats
+    }
+
+    /// Extends the 16-bit sequence number to the one closest to the highest one
+    fn extend(&self, seq: u16) -> u64 {
+        let highest = match self.highest {
+            Some(highest) => highest,
+            // starting from the second cycle lets the first packets be reordered
+            None => return (1 << 16) + seq as u64,
+        };
+
+        let candidate = (highest & !0xffff) | seq as u64;
+        [
+            candidate.wrapping_sub(1 << 16),
+            candidate,
+            candidate + (1 << 16),
+        ]
+        .into_iter()
+        .min_by_key(|index| index.abs_diff(highest))
+        .unwrap()
+    }
+
+    fn update_jitter(&mut self, rtp_timestamp: u32, arrival_ms: u64) {
+        let arrival = (arrival_ms as i64) * self.clock_rate as i64 / 1000;
+        let transit = arrival - rtp_timestamp as i64;
+        if let Some(previous) = self.transit {
+            let d = (transit - previous).abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.transit = Some(transit);
+    }
+
+    /// Sets the depth that covers the jitter and the reordering
+    fn adapt(&mut self) {
+        let frame_ms = self.config.frame_ms.max(1) as f64;
+        let jitter_depth = (self.jitter_ms() * 3.0 / frame_ms).ceil() as usize + 1;
+        let reorder_depth = self.reorder + 1;
+
+        self.target_depth = jitter_depth
+            .max(reorder_depth)
+            .clamp(self.config.min_depth, self.config.max_depth);
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::models::call::stats::ParticipantStats;
+
+use super::{rtcp::ReportBlock, rtp::AUDIO_CLOCK_RATE};
+
+#[derive(Debug, Clone, Copy, Default)]
+struct ParticipantMetrics {
+    packets_in: u64,
+    bytes_in: u64,
+    packets_out: u64,
+    bytes_out: u64,
+    /// Arrival of the first and of the latest packet sent by the participant
+    first_ms: Option<u64>,
+    last_ms: u64,
+    /// Worst values of the latest report sent by the participant
+    fraction_lost: u8,
+    jitter: u32,
+    rtt_ms: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default)]
+/// Quality metrics of a relayed call
+///
+/// The relay counts the packets each participant sends and receives,
+/// and reads the loss and the jitter from the reports of the participants.
+/// The round trip time is measured between the relay and each participant:
+/// the relay remembers when it has forwarded a sender report and the
+/// participant tells in its receiver report how long it held that report
+pub struct CallMetrics {
+    participants: HashMap<Uuid, ParticipantMetrics>,
+    /// Middle 32 bits of the NTP timestamp of the latest SR of each stream
+    /// with the time it has passed the relay
+    sender_reports: HashMap<u32, (u32, u64)>,
+}
+
+impl CallMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts the packet sent by the participant to the relay
+    pub fn record_in(&mut self, participant: Uuid, len: usize, now_ms: u64) {
+        let metrics = self.participants.entry(participant).or_default();
+        metrics.packets_in += 1;
+        metrics.bytes_in += len as u64;
+        metrics.first_ms.get_or_insert(now_ms);
+        metrics.last_ms = now_ms;
+    }
+
+    /// Counts the packet forwarded by the relay to the participant
+    pub fn record_out(&mut self, participant: Uuid, len: usize) {
+        let metrics = self.participants.entry(participant).or_default();
+        metrics.packets_out += 1;
+        metrics.bytes_out += len as u64;
+    }
+
+    /// Remembers when the sender report of the stream has passed the relay
+    pub fn record_sender_report(&mut self, ssrc: u32, ntp_timestamp: u64, now_ms: u64) {
+        self.sender_reports
+            .insert(ssrc, ((ntp_timestamp >> 16) as u32, now_ms));
+    }
+
+    /// Takes the report blocks sent by the participant
+    ///
+    /// A block that refers to the latest forwarded SR gives the round trip time
+    pub fn record_reports(&mut self, reporter: Uuid, reports: &[ReportBlock], now_ms: u64) {
+        if reports.is_empty() {
+            return;
+        }
+
+        let mut rtt_ms = None;
+        for report in reports {
+            match self.sender_reports.get(&report.ssrc) {
+                Some((last_sr, forwarded_ms))
+                    if report.last_sr != 0 && report.last_sr == *last_sr =>
+                {
+                    let held_ms = (u64::from(report.delay_since_last_sr) * 1000 + 32_768) / 65_536;
+                    if let Some(rtt) = now_ms.checked_sub(forwarded_ms + held_ms) {
+                        rtt_ms = rtt_ms.max(Some(rtt as u32));
+                    }
+                }
+                _ => (),
+            }
+        }
+
+        let metrics = self.participants.entry(reporter).or_default();
+        metrics.fraction_lost = reports
+            .iter()
+            .map(|report| report.fraction_lost)
+            .max()
+            .unwrap_or_default();
+        metrics.jitter = reports
+            .iter()
+            .map(|report| report.jitter)
+            .max()
+            .unwrap_or_default();
+        if rtt_ms.is_some() {
+            metrics.rtt_ms = rtt_ms;
+        }
+    }
+
+    /// Returns the metrics of each participant
+    pub fn participants(&self) -> Vec<ParticipantStats> {
+        self.participants
+            .iter()
+            .map(|(user, metrics)| {
+                let span_ms = metrics.last_ms - metrics.first_ms.unwrap_or(metrics.last_ms);
+                let bitrate = match span_ms {
+                    0 => 0,
+                    span_ms => metrics.bytes_in * 8 * 1000 / span_ms,
+                };
+
+                ParticipantStats {
+                    user: *user,
+                    packets_in: metrics.packets_in,
+                    packets_out: metrics.packets_out,
+                    bytes_in: metrics.bytes_in,
+                    bytes_out: metrics.bytes_out,
+                    loss_percent: metrics.fraction_lost as f32 * 100.0 / 256.0,
+                    jitter_ms: metrics.jitter as f32 * 1000.0 / AUDIO_CLOCK_RATE as f32,
+                    rtt_ms: metrics.rtt_ms,
+                    bitrate,
+                }
+            })
+            .collect()
+    }
+}
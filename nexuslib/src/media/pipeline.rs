@@ -0,0 +1,116 @@
+use std::collections::{hash_map::Entry, HashMap};
+
+use crate::errors::media::MediaError;
+
+use super::{
+    audio::{AudioFormat, AudioSink, AudioSource},
+    codec::{AudioDecoder, AudioEncoder, Codec},
+    jitter::{JitterConfig, Playout},
+    rtp::AudioLevel,
+    session::MediaSession,
+    srtp::SrtpMasterKey,
+};
+
+/// Audio of one participant of a call
+///
+/// Captured frames are encoded and packetized by the `MediaSession`,
+/// received ones are decoded per source and mixed into a single frame
+pub struct AudioPipeline {
+    pub session: MediaSession,
+    format: AudioFormat,
+    frame_ms: u32,
+    encoder: Box<dyn AudioEncoder + Send>,
+    decoders: HashMap<u32, Box<dyn AudioDecoder + Send>>,
+    frame: Vec<i16>,
+}
+
+impl AudioPipeline {
+    pub fn new(
+        master: &SrtpMasterKey,
+        codec: Codec,
+        format: AudioFormat,
+        config: JitterConfig,
+    ) -> Result<Self, MediaError> {
+        let session = MediaSession::new(
+            master,
+            codec.payload_type(),
+            codec.clock_rate(format),
+            config,
+        );
+
+        Ok(Self {
+            session,
+            format,
+            frame_ms: config.frame_ms,
+            encoder: codec.encoder(format)?,
+            decoders: HashMap::new(),
+            frame: vec![0; format.frame_len(config.frame_ms)],
+        })
+    }
+
+    pub fn format(&self) -> AudioFormat {
+        self.format
+    }
+
+    /// Encodes the next frame of the source and returns the SRTP datagram
+    ///
+    /// `None` means that the source has ended
+    pub fn capture(&mut self, source: &mut dyn AudioSource) -> Result<Option<Vec<u8>>, MediaError> {
+        if source.format() != self.format {
+            return Err(MediaError::Codec);
+        }
+        if !source.read_frame(&mut self.frame)? {
+            return Ok(None);
+        }
+
+        let level = AudioLevel::measure(&self.frame);
+        let payload = self.encoder.encode(&self.frame)?;
+        Ok(Some(self.session.send_with_level(payload, level)))
+    }
+
+    /// Plays one frame of all sources mixed together
+    ///
+    /// Returns `false` if nothing was played since the jitter buffers are filling up
+    pub fn play(&mut self, sink: &mut dyn AudioSink) -> Result<bool, MediaError> {
+        let frame_len = self.format.frame_len(self.frame_ms);
+        let mut mixed: Option<Vec<i32>> = None;
+
+        for (ssrc, playout) in self.session.playout() {
+            let pcm = match playout {
+                Playout::Frame(packet) => {
+                    // the codec of a source is known from its first packet
+                    let decoder = match self.decoders.entry(ssrc) {
+                        Entry::Occupied(entry) => entry.into_mut(),
+                        Entry::Vacant(entry) => {
+                            let codec = Codec::from_payload_type(packet.header.payload_type)?;
+                            entry.insert(codec.decoder(self.format)?)
+                        }
+                    };
+                    decoder.decode(&packet.payload)?
+                }
+                Playout::Lost(_) => match self.decoders.get_mut(&ssrc) {
+                    Some(decoder) => decoder.conceal(frame_len)?,
+                    None => vec![0; frame_len],
+                },
+            };
+
+            let mixed = mixed.get_or_insert_with(|| vec![0; frame_len]);
+            mixed
+                .iter_mut()
+                .zip(pcm)
+                .for_each(|(sum, sample)| *sum += sample as i32);
+        }
+
+        match mixed {
+            Some(mixed) => {
+                let frame: Vec<i16> = mixed
+                    .into_iter()
+                    .map(|sample| sample.clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+                    .collect();
+                sink.write_frame(&frame)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
@@ -0,0 +1,345 @@
+use crate::errors::media::MediaError;
+
+use super::rtp::RTP_VERSION;
+
+/// Payload type of a sender report
+pub const RTCP_SR: u8 = 200;
+/// Payload type of a receiver report
+pub const RTCP_RR: u8 = 201;
+/// Length of a report block in bytes
+pub const REPORT_BLOCK_LEN: usize = 24;
+
+/// Seconds between the NTP epoch (1900) and the unix epoch (1970)
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+/// Sequence number gaps considered as a loss rather than a restart (RFC 3550 A.1)
+const MAX_DROPOUT: u16 = 3000;
+const MAX_MISORDER: u16 = 100;
+
+/// Converts unix time in milliseconds to the 64-bit NTP timestamp
+pub fn ntp_timestamp(unix_ms: u64) -> u64 {
+    let seconds = unix_ms / 1000 + NTP_UNIX_OFFSET;
+    let fraction = ((unix_ms % 1000) << 32) / 1000;
+    seconds << 32 | fraction
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Reception statistics of one source sent in SR and RR
+pub struct ReportBlock {
+    pub ssrc: u32,
+    /// Fraction of packets lost since the previous report, in 1/256
+    pub fraction_lost: u8,
+    /// Packets lost since the beginning of the reception (24-bit signed)
+    pub cumulative_lost: i32,
+    pub highest_seq: u32,
+    /// Interarrival jitter in RTP timestamp units
+    pub jitter: u32,
+    /// Middle 32 bits of the NTP timestamp of the last SR
+    pub last_sr: u32,
+    /// Delay since the last SR in 1/65536 seconds
+    pub delay_since_last_sr: u32,
+}
+
+impl ReportBlock {
+    fn encode_into(&self, buf: &mut Vec<u8>) {
+        let lost = (self.cumulative_lost.clamp(-0x80_0000, 0x7f_ffff) as u32) & 0x00ff_ffff;
+        buf.extend_from_slice(&self.ssrc.to_be_bytes());
+        buf.extend_from_slice(&(u32::from(self.fraction_lost) << 24 | lost).to_be_bytes());
+        buf.extend_from_slice(&self.highest_seq.to_be_bytes());
+        buf.extend_from_slice(&self.jitter.to_be_bytes());
+        buf.extend_from_slice(&self.last_sr.to_be_bytes());
+        buf.extend_from_slice(&self.delay_since_last_sr.to_be_bytes());
+    }
+
+    fn decode(bytes: &[u8]) -> Self {
+        let word =
+            |i: usize| u32::from_be_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let lost = word(4) & 0x00ff_ffff;
+        // sign extension of the 24-bit value
+        let cumulative_lost = ((lost << 8) as i32) >> 8;
+
+        Self {
+            ssrc: word(0),
+            fraction_lost: bytes[4],
+            cumulative_lost,
+            highest_seq: word(8),
+            jitter: word(12),
+            last_sr: word(16),
+            delay_since_last_sr: word(20),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Sender information of an SR
+pub struct SenderInfo {
+    pub ntp_timestamp: u64,
+    pub rtp_timestamp: u32,
+    pub packet_count: u32,
+    pub octet_count: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// RTCP packet, the types other than SR and RR are kept undecoded
+pub enum RtcpPacket {
+    SenderReport {
+        ssrc: u32,
+        info: SenderInfo,
+        reports: Vec<ReportBlock>,
+    },
+    ReceiverReport {
+        ssrc: u32,
+        reports: Vec<ReportBlock>,
+    },
+    Other {
+        payload_type: u8,
+        body: Vec<u8>,
+    },
+}
+
+impl RtcpPacket {
+    /// Serializes the packet to `bytes`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut body = Vec::new();
+        let (payload_type, count) = match self {
+            RtcpPacket::SenderReport {
+                ssrc,
+                info,
+                reports,
+            } => {
+                body.extend_from_slice(&ssrc.to_be_bytes());
+                body.extend_from_slice(&info.ntp_timestamp.to_be_bytes());
+                body.extend_from_slice(&info.rtp_timestamp.to_be_bytes());
+                body.extend_from_slice(&info.packet_count.to_be_bytes());
+                body.extend_from_slice(&info.octet_count.to_be_bytes());
+                reports
+                    .iter()
+                    .for_each(|report| report.encode_into(&mut body));
+                (RTCP_SR, reports.len())
+            }
+            RtcpPacket::ReceiverReport { ssrc, reports } => {
+                body.extend_from_slice(&ssrc.to_be_bytes());
+                reports
+                    .iter()
+                    .for_each(|report| report.encode_into(&mut body));
+                (RTCP_RR, reports.len())
+            }
+            RtcpPacket::Other {
+                payload_type,
+                body: other,
+            } => {
+                body.extend_from_slice(other);
+                (*payload_type, 0)
+            }
+        };
+        body.resize(body.len().div_ceil(4) * 4, 0);
+
+        let mut buf = Vec::with_capacity(4 + body.len());
+        buf.push(RTP_VERSION << 6 | count.min(31) as u8);
+        buf.push(payload_type);
+        // length in 32-bit words minus one
+        buf.extend_from_slice(&((body.len() / 4) as u16).to_be_bytes());
+        buf.extend_from_slice(&body);
+        buf
+    }
+
+    /// Parses a compound RTCP packet
+    pub fn decode_compound(bytes: &[u8]) -> Result<Vec<Self>, MediaError> {
+        let mut packets = Vec::new();
+        let mut rest = bytes;
+
+        while !rest.is_empty() {
+            if rest.len() < 4 || rest[0] >> 6 != RTP_VERSION {
+                return Err(MediaError::Malformed);
+            }
+            let count = (rest[0] & 0x1f) as usize;
+            let payload_type = rest[1];
+            let len = (u16::from_be_bytes([rest[2], rest[3]]) as usize + 1) * 4;
+            if rest.len() < len {
+                return Err(MediaError::Malformed);
+            }
+            let body = &rest[4..len];
+
+            let packet = match payload_type {
+                RTCP_SR => {
+                    if body.len() < 24 + count * REPORT_BLOCK_LEN {
+                        return Err(MediaError::Malformed);
+                    }
+                    let word = |i: usize| {
+                        u32::from_be_bytes([body[i], body[i + 1], body[i + 2], body[i + 3]])
+                    };
+                    RtcpPacket::SenderReport {
+                        ssrc: word(0),
+                        info: SenderInfo {
+                            ntp_timestamp: (word(4) as u64) << 32 | word(8) as u64,
+                            rtp_timestamp: word(12),
+                            packet_count: word(16),
+                            octet_count: word(20),
+                        },
+                        reports: decode_reports(&body[24..], count),
+                    }
+                }
+                RTCP_RR => {
+                    if body.len() < 4 + count * REPORT_BLOCK_LEN {
+                        return Err(MediaError::Malformed);
+                    }
+                    RtcpPacket::ReceiverReport {
+                        ssrc: u32::from_be_bytes([body[0], body[1], body[2], body[3]]),
+                        reports: decode_reports(&body[4..], count),
+                    }
+                }
+                _ => RtcpPacket::Other {
+                    payload_type,
+                    body: body.to_vec(),
+                },
+            };
+
+            packets.push(packet);
+            rest = &rest[len..];
+        }
+
+        Ok(packets)
+    }
+
+    /// Returns the report blocks of SR and RR
+    pub fn reports(&self) -> &[ReportBlock] {
+        match self {
+            RtcpPacket::SenderReport { reports, .. } => reports,
+            RtcpPacket::ReceiverReport { reports, .. } => reports,
+            RtcpPacket::Other { .. } => &[],
+        }
+    }
+}
+
+fn decode_reports(bytes: &[u8], count: usize) -> Vec<ReportBlock> {
+    bytes
+        .chunks_exact(REPORT_BLOCK_LEN)
+        .take(count)
+        .map(ReportBlock::decode)
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+/// Statistics of a received RTP stream (RFC 3550 A.1, A.3, A.8)
+///
+/// Produces the report blocks of the RR
+pub struct ReceptionStats {
+    pub ssrc: u32,
+    /// RTP clock rate of the stream
+    clock_rate: u32,
+    base_seq: u32,
+    max_seq: u16,
+    cycles: u32,
+    received: u32,
+    expected_prior: u32,
+    received_prior: u32,
+    transit: Option<i64>,
+    jitter: f64,
+    /// Middle 32 bits of the NTP timestamp of the last SR and its arrival in ms
+    last_sr: Option<(u32, u64)>,
+}
+
+impl ReceptionStats {
+    pub fn new(ssrc: u32, seq: u16, clock_rate: u32) -> Self {
+        Self {
+            ssrc,
+            clock_rate,
+            base_seq: seq as u32,
+            max_seq: seq,
+            cycles: 0,
+            received: 0,
+            expected_prior: 0,
+            received_prior: 0,
+            transit: None,
+            jitter: 0.0,
+            last_sr: None,
+        }
+    }
+
+    /// Registers a received packet, `arrival_ms` is the local time of the arrival
+    pub fn update(&mut self, seq: u16, rtp_timestamp: u32, arrival_ms: u64) {
+        let delta = seq.wrapping_sub(self.max_seq);
+        if delta < MAX_DROPOUT {
+            // in order, with a permissible gap
+            if seq < self.max_seq {
+                self.cycles += 1 << 16;
+            }
+            self.max_seq = seq;
+        } else if delta <= u16::MAX - MAX_MISORDER {
+            // a very large jump => the sender restarted
+            *self = Self {
+                last_sr: self.last_sr,
+                ..Self::new(self.ssrc, seq, self.clock_rate)
+            };
+        }
+        // otherwise a duplicate or reordered packet
+        self.received += 1;
+
+        let arrival = (arrival_ms as i64) * self.clock_rate as i64 / 1000;
+        let transit = arrival - rtp_timestamp as i64;
+        if let Some(previous) = self.transit {
+            let d = (transit - previous).abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+        }
+        self.transit = Some(transit);
+    }
+
+    /// Registers the SR of the source
+    pub fn on_sender_report(&mut self, ntp_timestamp: u64, arrival_ms: u64) {
+        self.last_sr = Some(((ntp_timestamp >> 16) as u32, arrival_ms));
+    }
+
+    /// Highest sequence number extended with the number of cycles
+    pub fn extended_max_seq(&self) -> u32 {
+        self.cycles + self.max_seq as u32
+    }
+
+    /// Number of packets lost since the beginning of the reception
+    pub fn cumulative_lost(&self) -> i32 {
+        let expected = self.expected();
+        (expected as i64 - self.received as i64) as i32
+    }
+
+    /// Interarrival jitter in RTP timestamp units
+    pub fn jitter(&self) -> u32 {
+        self.jitter as u32
+    }
+
+    /// Creates the report block and starts a new reporting interval
+    pub fn report_block(&mut self, now_ms: u64) -> ReportBlock {
+        let expected = self.expected();
+        let expected_interval = expected.wrapping_sub(self.expected_prior);
+        let received_interval = self.received.wrapping_sub(self.received_prior);
+        self.expected_prior = expected;
+        self.received_prior = self.received;
+
+        let lost_interval = expected_interval as i64 - received_interval as i64;
+        let fraction_lost = match expected_interval == 0 || lost_interval <= 0 {
+            true => 0,
+            false => ((lost_interval << 8) / expected_interval as i64).min(255) as u8,
+        };
+
+        let (last_sr, delay_since_last_sr) = match self.last_sr {
+            Some((last_sr, arrival_ms)) => {
+                let delay_ms = now_ms.saturating_sub(arrival_ms);
+                (last_sr, (delay_ms * 65536 / 1000) as u32)
+            }
+            None => (0, 0),
+        };
+
+        ReportBlock {
+            ssrc: self.ssrc,
+            fraction_lost,
+            cumulative_lost: self.cumulative_lost(),
+            highest_seq: self.extended_max_seq(),
+            jitter: self.jitter(),
+            last_sr,
+            delay_since_last_sr,
+        }
+    }
+
+    fn expected(&self) -> u32 {
+        self.extended_max_seq()
+            .wrapping_sub(self.base_seq)
+            .wrapping_add(1)
+    }
+}
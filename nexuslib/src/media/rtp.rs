@@ -0,0 +1,259 @@
+use crate::errors::media::MediaError;
+
+pub const RTP_VERSION: u8 = 2;
+/// Length of the fixed part of the RTP header
+pub const RTP_HEADER_LEN: usize = 12;
+/// Dynamic payload type used for the audio of calls
+pub const AUDIO_PAYLOAD_TYPE: u8 = 111;
+/// RTP clock rate of the audio of calls
+pub const AUDIO_CLOCK_RATE: u32 = 48_000;
+/// Profile of the header extension with one-byte headers (RFC 8285)
+pub const ONE_BYTE_EXTENSION_PROFILE: u16 = 0xbede;
+/// Id of the audio level extension, fixed since the calls do not negotiate it
+pub const AUDIO_LEVEL_EXTENSION_ID: u8 = 1;
+/// Level in -dBov up to which a frame is marked as voice
+pub const VOICE_LEVEL: u8 = 50;
+
+/// Checks whether the datagram is RTP or RTCP by the first byte (RFC 7983)
+pub fn is_rtp_or_rtcp(bytes: &[u8]) -> bool {
+    matches!(bytes.first(), Some(128..=191))
+}
+
+/// Checks whether the RTP/RTCP datagram is RTCP by the payload type (RFC 5761)
+pub fn is_rtcp(bytes: &[u8]) -> bool {
+    matches!(bytes.get(1), Some(192..=223))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Audio level of the packet (RFC 6464)
+///
+/// The header extensions are not encrypted by SRTP,
+/// so the relay reads the level to detect the active speaker
+pub struct AudioLevel {
+    /// Whether the frame contains voice
+    pub voice: bool,
+    /// Level in -dBov from 0 (the loudest) to 127 (silence)
+    pub level: u8,
+}
+
+impl AudioLevel {
+    /// Measures the level of the PCM frame
+    pub fn measure(pcm: &[i16]) -> Self {
+        let sum: f64 = pcm.iter().map(|sample| (*sample as f64).powi(2)).sum();
+        let rms = (sum / pcm.len().max(1) as f64).sqrt() / i16::MAX as f64;
+        let level = match rms > 0.0 {
+            true => (-20.0 * rms.log10()).clamp(0.0, 127.0) as u8,
+            false => 127,
+        };
+
+        Self {
+            voice: level <= VOICE_LEVEL,
+            level,
+        }
+    }
+
+    fn encode(&self) -> u8 {
+        (self.voice as u8) << 7 | self.level & 0x7f
+    }
+
+    fn decode(byte: u8) -> Self {
+        Self {
+            voice: byte & 0x80 != 0,
+            level: byte & 0x7f,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Header of an RTP packet (RFC 3550)
+///
+/// Header extensions are kept as raw bytes
+pub struct RtpHeader {
+    pub marker: bool,
+    pub payload_type: u8,
+    pub seq: u16,
+    pub timestamp: u32,
+    pub ssrc: u32,
+    pub csrc: Vec<u32>,
+    /// Profile and body of the header extension
+    pub extension: Option<(u16, Vec<u8>)>,
+}
+
+impl RtpHeader {
+    pub fn new(payload_type: u8, seq: u16, timestamp: u32, ssrc: u32) -> Self {
+        Self {
+            marker: false,
+            payload_type,
+            seq,
+            timestamp,
+            ssrc,
+            csrc: Vec::new(),
+            extension: None,
+        }
+    }
+
+    /// Attaches the audio level as a one-byte header extension
+    pub fn with_audio_level(mut self, level: AudioLevel) -> Self {
+        let element = vec![AUDIO_LEVEL_EXTENSION_ID << 4, level.encode()];
+        self.extension = Some((ONE_BYTE_EXTENSION_PROFILE, element));
+        self
+    }
+
+    /// Returns the audio level of the header extension if there is one
+    pub fn audio_level(&self) -> Option<AudioLevel> {
+        match &self.extension {
+            Some((ONE_BYTE_EXTENSION_PROFILE, body)) => {
+                extension_element(body, AUDIO_LEVEL_EXTENSION_ID)
+                    .and_then(|data| data.first())
+                    .map(|byte| AudioLevel::decode(*byte))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the length of the encoded header
+    pub fn encoded_len(&self) -> usize {
+        RTP_HEADER_LEN
+            + self.csrc.len() * 4
+            + self
+                .extension
+                .as_ref()
+                .map(|(_, body)| 4 + body.len())
+                .unwrap_or_default()
+    }
+
+    /// Writes the header to the buffer
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        let extension = self.extension.is_some() as u8;
+        buf.push(RTP_VERSION << 6 | extension << 4 | self.csrc.len() as u8 & 0x0f);
+        buf.push((self.marker as u8) << 7 | self.payload_type & 0x7f);
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+        buf.extend_from_slice(&self.ssrc.to_be_bytes());
+        for csrc in &self.csrc {
+            buf.extend_from_slice(&csrc.to_be_bytes());
+        }
+        if let Some((profile, body)) = &self.extension {
+            // the body is padded to 32-bit words
+            let words = body.len().div_ceil(4);
+            buf.extend_from_slice(&profile.to_be_bytes());
+            buf.extend_from_slice(&(words as u16).to_be_bytes());
+            buf.extend_from_slice(body);
+            buf.resize(buf.len() + words * 4 - body.len(), 0);
+        }
+    }
+
+    /// Parses the header, returns it with its length in bytes
+    pub fn decode(bytes: &[u8]) -> Result<(Self, usize), MediaError> {
+        if bytes.len() < RTP_HEADER_LEN || bytes[0] >> 6 != RTP_VERSION {
+            return Err(MediaError::Malformed);
+        }
+
+        let csrc_count = (bytes[0] & 0x0f) as usize;
+        let has_extension = bytes[0] & 0x10 != 0;
+        let mut len = RTP_HEADER_LEN + csrc_count * 4;
+        if bytes.len() < len {
+            return Err(MediaError::Malformed);
+        }
+
+        let csrc = bytes[RTP_HEADER_LEN..len]
+            .chunks_exact(4)
+            .map(|chunk| u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect();
+
+        let extension = match has_extension {
+            true => {
+                if bytes.len() < len + 4 {
+                    return Err(MediaError::Malformed);
+                }
+                let profile = u16::from_be_bytes([bytes[len], bytes[len + 1]]);
+                let words = u16::from_be_bytes([bytes[len + 2], bytes[len + 3]]) as usize;
+                let body_start = len + 4;
+                len = body_start + words * 4;
+                if bytes.len() < len {
+                    return Err(MediaError::Malformed);
+                }
+                Some((profile, bytes[body_start..len].to_vec()))
+            }
+            false => None,
+        };
+
+        let header = Self {
+            marker: bytes[1] & 0x80 != 0,
+            payload_type: bytes[1] & 0x7f,
+            seq: u16::from_be_bytes([bytes[2], bytes[3]]),
+            timestamp: u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+            ssrc: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            csrc,
+            extension,
+        };
+        Ok((header, len))
+    }
+}
+
+/// Finds the element of the one-byte header extension by its id
+fn extension_element(body: &[u8], id: u8) -> Option<&[u8]> {
+    let mut pos = 0;
+    while pos < body.len() {
+        let element_id = body[pos] >> 4;
+        match element_id {
+            // padding
+            0 => {
+                pos += 1;
+                continue;
+            }
+            // reserved, the rest is not parsed
+            15 => return None,
+            _ => (),
+        }
+
+        let len = (body[pos] & 0x0f) as usize + 1;
+        let data = body.get(pos + 1..pos + 1 + len)?;
+        if element_id == id {
+            return Some(data);
+        }
+        pos += 1 + len;
+    }
+    None
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// RTP packet
+pub struct RtpPacket {
+    pub header: RtpHeader,
+    pub payload: Vec<u8>,
+}
+
+impl RtpPacket {
+    pub fn new(header: RtpHeader, payload: Vec<u8>) -> Self {
+        Self { header, payload }
+    }
+
+    /// Serializes the packet to `bytes`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.header.encoded_len() + self.payload.len());
+        self.header.encode_into(&mut buf);
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    /// Deserializes the packet from `bytes`, the padding is removed
+    pub fn decode(bytes: &[u8]) -> Result<Self, MediaError> {
+        let (header, len) = RtpHeader::decode(bytes)?;
+        let mut end = bytes.len();
+
+        // padding: the last byte holds the number of the padding bytes
+        if bytes[0] & 0x20 != 0 {
+            let padding = *bytes.last().ok_or(MediaError::Malformed)? as usize;
+            if padding == 0 || len + padding > end {
+                return Err(MediaError::Malformed);
+            }
+            end -= padding;
+        }
+
+        Ok(Self {
+            header,
+            payload: bytes[len..end].to_vec(),
+        })
+    }
+}
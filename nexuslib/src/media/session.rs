@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+
+use rand_core::{OsRng, RngCore};
+
+use crate::errors::media::MediaError;
+
+use super::{
+    jitter::{Arrival, JitterBuffer, JitterConfig, Playout},
+    rtcp::{ntp_timestamp, ReceptionStats, ReportBlock, RtcpPacket, SenderInfo},
+    rtp::{is_rtcp, AudioLevel, RtpHeader, RtpPacket},
+    srtp::{SrtpContext, SrtpMasterKey},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// Result of receiving a datagram of the call
+pub enum Incoming {
+    /// Media of the source was buffered
+    Media(u32, Arrival),
+    /// Report blocks of the RTCP packet
+    Reports(Vec<ReportBlock>),
+}
+
+/// Stream received from one source of the call
+pub struct RemoteStream {
+    pub reception: ReceptionStats,
+    pub jitter: JitterBuffer,
+}
+
+/// Media of one participant of a call
+///
+/// Sends the frames as SRTP and plays the received ones through
+/// a jitter buffer per source. The RTCP reports are authenticated
+/// only, so the relay can read them
+pub struct MediaSession {
+    srtp: SrtpContext,
+    ssrc: u32,
+    payload_type: u8,
+    clock_rate: u32,
+    config: JitterConfig,
+    seq: u16,
+    timestamp: u32,
+    packets_sent: u32,
+    octets_sent: u32,
+    sources: HashMap<u32, RemoteStream>,
+}
+
+impl MediaSession {
+    /// Creates the session with a random SSRC
+    pub fn new(
+        master: &SrtpMasterKey,
+        payload_type: u8,
+        clock_rate: u32,
+        config: JitterConfig,
+    ) -> Self {
+        Self {
+            srtp: SrtpContext::new(master),
+            ssrc: OsRng.next_u32(),
+            payload_type,
+            clock_rate,
+            config,
+            seq: OsRng.next_u32() as u16,
+            timestamp: OsRng.next_u32(),
+            packets_sent: 0,
+            octets_sent: 0,
+            sources: HashMap::new(),
+        }
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Returns the number of samples in a frame
+    pub fn frame_samples(&self) -> u32 {
+        self.clock_rate * self.config.frame_ms / 1000
+    }
+
+    /// Packetizes the frame and returns the SRTP datagram
+    pub fn send(&mut self, payload: Vec<u8>) -> Vec<u8> {
+        self.packetize(payload, None)
+    }
+
+    /// Packetizes the frame with its audio level, which lets
+    /// the relay of a group call detect the active speaker
+    pub fn send_with_level(&mut self, payload: Vec<u8>, level: AudioLevel) -> Vec<u8> {
+        self.packetize(payload, Some(level))
+    }
+
+    fn packetize(&mut self, payload: Vec<u8>, level: Option<AudioLevel>) -> Vec<u8> {
+        self.octets_sent = self.octets_sent.wrapping_add(payload.len() as u32);
+        self.packets_sent = self.packets_sent.wrapping_add(1);
+
+        let mut header = RtpHeader::new(self.payload_type, self.seq, self.timestamp, self.ssrc);
+        if let Some(level) = level {
+            header = header.with_audio_level(level);
+        }
+        let packet = self.srtp.protect_rtp(&RtpPacket::new(header, payload));
+
+        self.seq = self.seq.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(self.frame_samples());
+        packet
+    }
+
+    /// Authenticates the SRTP/SRTCP datagram and processes it
+    pub fn receive(&mut self, datagram: &[u8], arrival_ms: u64) -> Result<Incoming, MediaError> {
+        if is_rtcp(datagram) {
+            let rtcp = self.srtp.unprotect_rtcp(datagram)?;
+            let mut reports = Vec::new();
+            for packet in RtcpPacket::decode_compound(&rtcp)? {
+                if let RtcpPacket::SenderReport { ssrc, info, .. } = &packet {
+                    if let Some(source) = self.sources.get_mut(ssrc) {
+                        source
+                            .reception
+                            .on_sender_report(info.ntp_timestamp, arrival_ms);
+                    }
+                }
+                reports.extend(
+                    packet
+                        .reports()
+                        .iter()
+                        .filter(|report| report.ssrc == self.ssrc),
+                );
+            }
+            return Ok(Incoming::Reports(reports));
+        }
+
+        let packet = self.srtp.unprotect_rtp(datagram)?;
+        let header = &packet.header;
+        let (ssrc, seq, timestamp) = (header.ssrc, header.seq, header.timestamp);
+
+        let (config, clock_rate) = (self.config, self.clock_rate);
+        let source = self.sources.entry(ssrc).or_insert_with(|| RemoteStream {
+            reception: ReceptionStats::new(ssrc, seq, clock_rate),
+            jitter: JitterBuffer::new(config, clock_rate),
+        });
+        source.reception.update(seq, timestamp, arrival_ms);
+        let arrival = source.jitter.push(packet, arrival_ms);
+
+        Ok(Incoming::Media(ssrc, arrival))
+    }
+
+    /// Returns the frames to play now for each source, called once per frame duration
+    pub fn playout(&mut self) -> Vec<(u32, Playout)> {
+        self.sources
+            .iter_mut()
+            .filter_map(|(ssrc, source)| source.jitter.pop().map(|playout| (*ssrc, playout)))
+            .collect()
+    }
+
+    /// Creates the protected RTCP report
+    ///
+    /// A sender report is created once the session has sent media
+    pub fn report(&mut self, now_ms: u64) -> Vec<u8> {
+        let reports = self
+            .sources
+            .values_mut()
+            .map(|source| source.reception.report_block(now_ms))
+            .collect();
+
+        let packet = match self.packets_sent {
+            0 => RtcpPacket::ReceiverReport {
+                ssrc: self.ssrc,
+                reports,
+            },
+            _ => RtcpPacket::SenderReport {
+                ssrc: self.ssrc,
+                info: SenderInfo {
+                    ntp_timestamp: ntp_timestamp(now_ms),
+                    rtp_timestamp: self.timestamp,
+                    packet_count: self.packets_sent,
+                    octet_count: self.octets_sent,
+                },
+                reports,
+            },
+        };
+
+        // the encoded report always has the fixed part
+        self.srtp.protect_rtcp(&packet.encode(), false).unwrap()
+    }
+
+    /// Returns the stream received from the source
+    pub fn source(&self, ssrc: u32) -> Option<&RemoteStream> {
+        self.sources.get(&ssrc)
+    }
+
+    pub fn sources(&self) -> impl Iterator<Item = (&u32, &RemoteStream)> {
+        self.sources.iter()
+    }
+}
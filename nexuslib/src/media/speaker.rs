@@ -0,0 +1,115 @@
+use std::{collections::HashMap, hash::Hash};
+
+use super::rtp::AudioLevel;
+
+/// Weight of the latest level in the smoothed loudness
+const SMOOTHING: f32 = 0.2;
+/// Smoothed loudness (127 minus the level in -dBov) treated as speech
+const SPEECH_LOUDNESS: f32 = 60.0;
+/// Time the participant has to stay the loudest to become the speaker
+const SWITCH_DELAY_MS: u64 = 300;
+/// Participants that sent nothing for this time are not considered
+const STALE_MS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy)]
+struct Activity {
+    loudness: f32,
+    last_ms: u64,
+}
+
+#[derive(Debug, Clone)]
+/// Detects the active speaker of a group call from the audio levels
+///
+/// The levels are smoothed per participant, so single loud frames are ignored,
+/// and the speaker is switched only after the other participant
+/// has been the loudest for `SWITCH_DELAY_MS`.
+/// When everybody is silent the last speaker is kept
+pub struct SpeakerDetector<K> {
+    activity: HashMap<K, Activity>,
+    speaker: Option<K>,
+    /// Loudest participant with the time since it is the loudest
+    candidate: Option<(K, u64)>,
+}
+
+impl<K: Copy + Eq + Hash> SpeakerDetector<K> {
+    pub fn new() -> Self {
+        Self {
+            activity: HashMap::new(),
+            speaker: None,
+            candidate: None,
+        }
+    }
+
+    pub fn speaker(&self) -> Option<K> {
+        self.speaker
+    }
+
+    /// Takes the level of the packet sent by the participant
+    ///
+    /// Returns the new speaker if it has changed
+    pub fn update(&mut self, participant: K, level: AudioLevel, now_ms: u64) -> Option<K> {
+        let loudness = match level.voice {
+            true => (127 - level.level.min(127)) as f32,
+            false => 0.0,
+        };
+        let activity = self.activity.entry(participant).or_insert(Activity {
+            loudness: 0.0,
+            last_ms: now_ms,
+        });
+        activity.loudness += (loudness - activity.loudness) * SMOOTHING;
+        activity.last_ms = now_ms;
+
+        let loudest = self
+            .activity
+            .iter()
+            .filter(|(_, activity)| {
+                now_ms.saturating_sub(activity.last_ms) < STALE_MS
+                    && activity.loudness >= SPEECH_LOUDNESS
+            })
+            .max_by(|(_, a), (_, b)| a.loudness.total_cmp(&b.loudness))
+            .map(|(participant, _)| *participant);
+
+        let loudest = match loudest {
+            Some(loudest) if Some(loudest) != self.speaker => loudest,
+            _ => {
+                self.candidate = None;
+                return None;
+            }
+        };
+
+        let since = match self.candidate {
+            Some((candidate, since)) if candidate == loudest => since,
+            _ => {
+                self.candidate = Some((loudest, now_ms));
+                now_ms
+            }
+        };
+
+        if self.speaker.is_none() || now_ms.saturating_sub(since) >= SWITCH_DELAY_MS {
+            self.speaker = Some(loudest);
+            self.candidate = None;
+            return Some(loudest);
+        }
+        None
+    }
+
+    /// Forgets the participant that has left
+    pub fn remove(&mut self, participant: &K) {
+        self.activity.remove(participant);
+        if self.speaker.as_ref() == Some(participant) {
+            self.speaker = None;
+        }
+        if self
+            .candidate
+            .is_some_and(|(candidate, _)| candidate == *participant)
+        {
+            self.candidate = None;
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash> Default for SpeakerDetector<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,425 @@
+use aes::{
+    cipher::{generic_array::GenericArray, BlockEncrypt},
+    Aes256,
+};
+use aes_gcm::{
+    aead::{Aead, Payload},
+    Aes256Gcm, KeyInit, Nonce,
+};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use crate::errors::media::MediaError;
+
+use super::rtp::{RtpHeader, RtpPacket};
+
+/// SRTP protection profile `AEAD_AES_256_GCM` (RFC 7714)
+pub const SRTP_MASTER_KEY_LEN: usize = 32;
+pub const SRTP_MASTER_SALT_LEN: usize = 12;
+pub const SRTP_TAG_LEN: usize = 16;
+/// Length of the `E` flag with the SRTCP index in the end of a SRTCP packet
+pub const SRTCP_INDEX_LEN: usize = 4;
+
+/// Labels of the key derivation (RFC 3711 4.3.1)
+const LABEL_RTP_KEY: u8 = 0x00;
+const LABEL_RTP_SALT: u8 = 0x02;
+const LABEL_RTCP_KEY: u8 = 0x03;
+const LABEL_RTCP_SALT: u8 = 0x05;
+
+/// Size of the replay window in packets
+const REPLAY_WINDOW: u64 = 64;
+/// Length of the fixed part of an RTCP packet (header and SSRC)
+const RTCP_FIXED_LEN: usize = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Master key of a call
+///
+/// Generated by the caller and sent to the callee encrypted over
+/// the E2E channel in `MediaCall::message`
+pub struct SrtpMasterKey {
+    pub key: Vec<u8>,
+    pub salt: Vec<u8>,
+}
+
+impl SrtpMasterKey {
+    /// Generates a random master key
+    pub fn generate() -> Self {
+        let mut key = vec![0u8; SRTP_MASTER_KEY_LEN];
+        let mut salt = vec![0u8; SRTP_MASTER_SALT_LEN];
+        OsRng.fill_bytes(&mut key);
+        OsRng.fill_bytes(&mut salt);
+        Self { key, salt }
+    }
+
+    /// Encrypts the master key with the key shared by the users
+    ///
+    /// Returns the encrypted key and the nonce
+    pub fn seal(&self, shared_key: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut raw_nonce = [0u8; 12];
+        OsRng.fill_bytes(&mut raw_nonce);
+
+        let cipher = Aes256Gcm::new_from_slice(shared_key).unwrap();
+        let plain = bincode::serialize(self).unwrap();
+        let sealed = cipher
+            .encrypt(Nonce::from_slice(&raw_nonce), plain.as_ref())
+            .unwrap();
+
+        (sealed, raw_nonce.to_vec())
+    }
+
+    /// Decrypts the master key with the key shared by the users
+    pub fn open(shared_key: &[u8], sealed: &[u8], nonce: &[u8]) -> Result<Self, MediaError> {
+        if nonce.len() != 12 {
+            return Err(MediaError::Malformed);
+        }
+
+        let cipher = Aes256Gcm::new_from_slice(shared_key).map_err(|_| MediaError::Malformed)?;
+        let plain = cipher
+            .decrypt(Nonce::from_slice(nonce), sealed)
+            .map_err(|_| MediaError::Authentication)?;
+        let master: Self = bincode::deserialize(&plain).map_err(|_| MediaError::Malformed)?;
+
+        match master.key.len() == SRTP_MASTER_KEY_LEN && master.salt.len() == SRTP_MASTER_SALT_LEN {
+            true => Ok(master),
+            false => Err(MediaError::Malformed),
+        }
+    }
+}
+
+/// Returns the RTCP part of a SRTCP packet that is authenticated but not encrypted
+///
+/// This lets the relay read the reports without the keys
+pub fn srtcp_plaintext(packet: &[u8]) -> Option<&[u8]> {
+    let trailer = SRTP_TAG_LEN + SRTCP_INDEX_LEN;
+    if packet.len() < RTCP_FIXED_LEN + trailer {
+        return None;
+    }
+
+    let encrypted = packet[packet.len() - SRTCP_INDEX_LEN] & 0x80 != 0;
+    match encrypted {
+        true => None,
+        false => Some(&packet[..packet.len() - trailer]),
+    }
+}
+
+/// Session key and salt derived from the master key
+struct SessionKeys {
+    cipher: Aes256Gcm,
+    salt: [u8; SRTP_MASTER_SALT_LEN],
+}
+
+impl SessionKeys {
+    fn derive(master: &SrtpMasterKey, key_label: u8, salt_label: u8) -> Self {
+        let key = kdf(master, key_label, SRTP_MASTER_KEY_LEN);
+        let salt = kdf(master, salt_label, SRTP_MASTER_SALT_LEN);
+        Self::new(&key, &salt).unwrap()
+    }
+
+    fn new(key: &[u8], salt: &[u8]) -> Result<Self, MediaError> {
+        if key.len() != SRTP_MASTER_KEY_LEN {
+            return Err(MediaError::Malformed);
+        }
+
+        Ok(Self {
+            cipher: Aes256Gcm::new_from_slice(key).map_err(|_| MediaError::Malformed)?,
+            salt: salt.try_into().map_err(|_| MediaError::Malformed)?,
+        })
+    }
+
+    fn nonce(&self, iv: [u8; 12]) -> [u8; 12] {
+        let mut nonce = iv;
+        nonce
+            .iter_mut()
+            .zip(self.salt)
+            .for_each(|(byte, salt)| *byte ^= salt);
+        nonce
+    }
+}
+
+/// Replay and rollover state of a remote source
+#[derive(Clone, Copy)]
+struct RemoteSource {
+    roc: u32,
+    last_seq: u16,
+    highest_index: u64,
+    window: u64,
+}
+
+/// SRTP/SRTCP context of a call participant
+///
+/// The same master key is used by both sides, the streams are
+/// separated by their SSRCs, so each side has to use its own SSRC
+pub struct SrtpContext {
+    rtp: SessionKeys,
+    rtcp: SessionKeys,
+    /// Rollover counter of the outbound stream
+    roc: u32,
+    last_seq: Option<u16>,
+    srtcp_index: u32,
+    remote: HashMap<u32, RemoteSource>,
+    remote_rtcp: HashMap<u32, u32>,
+}
+
+impl SrtpContext {
+    pub fn new(master: &SrtpMasterKey) -> Self {
+        Self {
+            rtp: SessionKeys::derive(master, LABEL_RTP_KEY, LABEL_RTP_SALT),
+            rtcp: SessionKeys::derive(master, LABEL_RTCP_KEY, LABEL_RTCP_SALT),
+            roc: 0,
+            last_seq: None,
+            srtcp_index: 0,
+            remote: HashMap::new(),
+            remote_rtcp: HashMap::new(),
+        }
+    }
+
+    /// Creates the context from the already derived session keys and salts,
+    /// as given in the test vectors of RFC 7714
+    pub fn from_session_keys(
+        rtp_key: &[u8],
+        rtp_salt: &[u8],
+        rtcp_key: &[u8],
+        rtcp_salt: &[u8],
+    ) -> Result<Self, MediaError> {
+        Ok(Self {
+            rtp: SessionKeys::new(rtp_key, rtp_salt)?,
+            rtcp: SessionKeys::new(rtcp_key, rtcp_salt)?,
+            roc: 0,
+            last_seq: None,
+            srtcp_index: 0,
+            remote: HashMap::new(),
+            remote_rtcp: HashMap::new(),
+        })
+    }
+
+    /// Encrypts the RTP packet
+    pub fn protect_rtp(&mut self, packet: &RtpPacket) -> Vec<u8> {
+        let seq = packet.header.seq;
+        if let Some(last_seq) = self.last_seq {
+            if seq < last_seq && last_seq - seq > 0x8000 {
+                self.roc = self.roc.wrapping_add(1);
+            }
+        }
+        self.last_seq = Some(seq);
+
+        let mut header = Vec::with_capacity(packet.header.encoded_len());
+        packet.header.encode_into(&mut header);
+
+        let nonce = self.rtp.nonce(rtp_iv(packet.header.ssrc, self.roc, seq));
+        let encrypted = self
+            .rtp
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &packet.payload,
+                    aad: &header,
+                },
+            )
+            .unwrap();
+
+        header.extend_from_slice(&encrypted);
+        header
+    }
+
+    /// Authenticates and decrypts the SRTP packet
+    pub fn unprotect_rtp(&mut self, bytes: &[u8]) -> Result<RtpPacket, MediaError> {
+        let (header, header_len) = RtpHeader::decode(bytes)?;
+        if bytes.len() < header_len + SRTP_TAG_LEN {
+            return Err(MediaError::Malformed);
+        }
+
+        // a forged packet must not create or move the state of the source
+        let mut source = self
+            .remote
+            .get(&header.ssrc)
+            .copied()
+            .unwrap_or(RemoteSource {
+                roc: 0,
+                last_seq: header.seq,
+                highest_index: header.seq as u64,
+                window: 0,
+            });
+        let roc = estimate_roc(source.roc, source.last_seq, header.seq);
+        let index = (roc as u64) << 16 | header.seq as u64;
+
+        // replay check
+        if index <= source.highest_index {
+            let age = source.highest_index - index;
+            if age >= REPLAY_WINDOW || source.window & (1 << age) != 0 {
+                return Err(MediaError::Replay);
+            }
+        }
+
+        let nonce = self.rtp.nonce(rtp_iv(header.ssrc, roc, header.seq));
+        let payload = self
+            .rtp
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &bytes[header_len..],
+                    aad: &bytes[..header_len],
+                },
+            )
+            .map_err(|_| MediaError::Authentication)?;
+
+        // the state is updated only for authenticated packets
+        if index > source.highest_index {
+            let shift = index - source.highest_index;
+            source.window = match shift < REPLAY_WINDOW {
+                true => source.window << shift | 1,
+                false => 1,
+            };
+            source.highest_index = index;
+            source.roc = roc;
+            source.last_seq = header.seq;
+        } else {
+            source.window |= 1 << (source.highest_index - index);
+        }
+        self.remote.insert(header.ssrc, source);
+
+        Ok(RtpPacket::new(header, payload))
+    }
+
+    /// Protects the compound RTCP packet
+    ///
+    /// When `encrypt` is `false` the packet is only authenticated,
+    /// which lets the relay read the reports
+    pub fn protect_rtcp(&mut self, rtcp: &[u8], encrypt: bool) -> Result<Vec<u8>, MediaError> {
+        if rtcp.len() < RTCP_FIXED_LEN {
+            return Err(MediaError::Malformed);
+        }
+
+        let ssrc = u32::from_be_bytes([rtcp[4], rtcp[5], rtcp[6], rtcp[7]]);
+        // the first packet has the index 1 as in the common implementations
+        self.srtcp_index = (self.srtcp_index + 1) & 0x7fff_ffff;
+        let index = self.srtcp_index;
+        let trailer = ((encrypt as u32) << 31 | index).to_be_bytes();
+
+        let nonce = self.rtcp.nonce(rtcp_iv(ssrc, index));
+        let (plain, mut out) = match encrypt {
+            true => (&rtcp[RTCP_FIXED_LEN..], rtcp[..RTCP_FIXED_LEN].to_vec()),
+            false => (&[][..], rtcp.to_vec()),
+        };
+        let mut aad = out.clone();
+        aad.extend_from_slice(&trailer);
+
+        let sealed = self
+            .rtcp
+            .cipher
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plain,
+                    aad: &aad,
+                },
+            )
+            .unwrap();
+
+        out.extend_from_slice(&sealed);
+        out.extend_from_slice(&trailer);
+        Ok(out)
+    }
+
+    /// Authenticates and, if needed, decrypts the SRTCP packet
+    pub fn unprotect_rtcp(&mut self, bytes: &[u8]) -> Result<Vec<u8>, MediaError> {
+        if bytes.len() < RTCP_FIXED_LEN + SRTP_TAG_LEN + SRTCP_INDEX_LEN {
+            return Err(MediaError::Malformed);
+        }
+
+        let (body, trailer) = bytes.split_at(bytes.len() - SRTCP_INDEX_LEN);
+        let trailer_word = u32::from_be_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let encrypted = trailer_word & 0x8000_0000 != 0;
+        let index = trailer_word & 0x7fff_ffff;
+        let ssrc = u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+        if let Some(last) = self.remote_rtcp.get(&ssrc) {
+            if index <= *last {
+                return Err(MediaError::Replay);
+            }
+        }
+
+        let (aad_part, sealed) = match encrypted {
+            true => body.split_at(RTCP_FIXED_LEN),
+            false => body.split_at(body.len() - SRTP_TAG_LEN),
+        };
+        let mut aad = aad_part.to_vec();
+        aad.extend_from_slice(trailer);
+
+        let nonce = self.rtcp.nonce(rtcp_iv(ssrc, index));
+        let plain = self
+            .rtcp
+            .cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: sealed,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| MediaError::Authentication)?;
+        self.remote_rtcp.insert(ssrc, index);
+
+        let mut rtcp = aad_part.to_vec();
+        rtcp.extend_from_slice(&plain);
+        Ok(rtcp)
+    }
+}
+
+/// Key derivation with the AES-CM PRF (RFC 3711 4.3.3), the key derivation rate is 0
+///
+/// The 96-bit master salt is padded with zeros to 112 bits (RFC 7714 11)
+fn kdf(master: &SrtpMasterKey, label: u8, len: usize) -> Vec<u8> {
+    let cipher = Aes256::new_from_slice(&master.key).unwrap();
+
+    let mut x = [0u8; 16];
+    x[..SRTP_MASTER_SALT_LEN].copy_from_slice(&master.salt);
+    x[7] ^= label;
+
+    let mut out = Vec::with_capacity(len + 16);
+    let mut counter: u16 = 0;
+    while out.len() < len {
+        let mut block = x;
+        block[14..].copy_from_slice(&counter.to_be_bytes());
+        let mut block = GenericArray::from(block);
+        cipher.encrypt_block(&mut block);
+        out.extend_from_slice(&block);
+        counter += 1;
+    }
+    out.truncate(len);
+    out
+}
+
+/// IV of a SRTP packet (RFC 7714 8.1)
+fn rtp_iv(ssrc: u32, roc: u32, seq: u16) -> [u8; 12] {
+    let mut iv = [0u8; 12];
+    iv[2..6].copy_from_slice(&ssrc.to_be_bytes());
+    iv[6..10].copy_from_slice(&roc.to_be_bytes());
+    iv[10..12].copy_from_slice(&seq.to_be_bytes());
+    iv
+}
+
+/// IV of a SRTCP packet (RFC 7714 9.1)
+fn rtcp_iv(ssrc: u32, index: u32) -> [u8; 12] {
+    let mut iv = [0u8; 12];
+    iv[2..6].copy_from_slice(&ssrc.to_be_bytes());
+    iv[8..12].copy_from_slice(&index.to_be_bytes());
+    iv
+}
+
+/// Guesses the rollover counter of the received packet (RFC 3711 3.3.1)
+fn estimate_roc(roc: u32, last_seq: u16, seq: u16) -> u32 {
+    if last_seq < 0x8000 {
+        if seq > last_seq && seq - last_seq > 0x8000 {
+            roc.wrapping_sub(1)
+        } else {
+            roc
+        }
+    } else if last_seq - 0x8000 > seq {
+        roc.wrapping_add(1)
+    } else {
+        roc
+    }
+}
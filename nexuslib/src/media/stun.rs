@@ -0,0 +1,384 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+
+use crate::errors::media::MediaError;
+
+/// Length of the STUN header
+pub const STUN_HEADER_LEN: usize = 20;
+/// Fixed value that tells STUN from the other protocols (RFC 5389)
+pub const MAGIC_COOKIE: u32 = 0x2112_a442;
+
+const ATTR_USERNAME: u16 = 0x0006;
+const ATTR_MESSAGE_INTEGRITY: u16 = 0x0008;
+const ATTR_ERROR_CODE: u16 = 0x0009;
+const ATTR_XOR_MAPPED_ADDRESS: u16 = 0x0020;
+const ATTR_PRIORITY: u16 = 0x0024;
+const ATTR_USE_CANDIDATE: u16 = 0x0025;
+const ATTR_FINGERPRINT: u16 = 0x8028;
+const ATTR_ICE_CONTROLLED: u16 = 0x8029;
+const ATTR_ICE_CONTROLLING: u16 = 0x802a;
+
+/// Length of the HMAC-SHA1 of the `MESSAGE-INTEGRITY`
+const INTEGRITY_LEN: usize = 20;
+/// Mixed into the CRC-32 of the `FINGERPRINT`
+const FINGERPRINT_XOR: u32 = 0x5354_554e;
+
+/// Checks whether the datagram is STUN by the first byte and the cookie (RFC 7983)
+pub fn is_stun(bytes: &[u8]) -> bool {
+    bytes.len() >= STUN_HEADER_LEN
+        && bytes[0] < 4
+        && u32::from_be_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) == MAGIC_COOKIE
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+/// Method and class of a STUN message, only the binding method is supported
+pub enum StunType {
+    BindingRequest,
+    BindingSuccess,
+    BindingError,
+}
+
+impl StunType {
+    fn from_u16(value: u16) -> Option<Self> {
+        match value {
+            0x0001 => Some(StunType::BindingRequest),
+            0x0101 => Some(StunType::BindingSuccess),
+            0x0111 => Some(StunType::BindingError),
+            _ => None,
+        }
+    }
+
+    fn as_u16(&self) -> u16 {
+        match self {
+            StunType::BindingRequest => 0x0001,
+            StunType::BindingSuccess => 0x0101,
+            StunType::BindingError => 0x0111,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Attribute of a STUN message
+///
+/// `MESSAGE-INTEGRITY` and `FINGERPRINT` are added by `StunMessage::encode`
+pub enum StunAttribute {
+    /// Address the request came from as seen by the responder
+    XorMappedAddress(SocketAddr),
+    /// `remote:local` fragments of the ICE credentials
+    Username(String),
+    /// Priority of the peer reflexive candidate (RFC 8445)
+    Priority(u32),
+    UseCandidate,
+    /// Tie-breaker of the controlling agent
+    IceControlling(u64),
+    /// Tie-breaker of the controlled agent
+    IceControlled(u64),
+    ErrorCode(u16, String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// STUN message (RFC 5389)
+pub struct StunMessage {
+    pub kind: StunType,
+    pub transaction: [u8; 12],
+    pub attributes: Vec<StunAttribute>,
+}
+
+impl StunMessage {
+    /// Creates the binding request with a random transaction id
+    pub fn binding_request() -> Self {
+        let mut transaction = [0u8; 12];
+        OsRng.fill_bytes(&mut transaction);
+
+        Self {
+            kind: StunType::BindingRequest,
+            transaction,
+            attributes: Vec::new(),
+        }
+    }
+
+    /// Creates the answer to the request with the address it came from
+    pub fn binding_success(request: &StunMessage, mapped: SocketAddr) -> Self {
+        Self {
+            kind: StunType::BindingSuccess,
+            transaction: request.transaction,
+            attributes: vec![StunAttribute::XorMappedAddress(mapped)],
+        }
+    }
+
+    pub fn with(mut self, attribute: StunAttribute) -> Self {
+        self.attributes.push(attribute);
+        self
+    }
+
+    pub fn mapped_address(&self) -> Option<SocketAddr> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                StunAttribute::XorMappedAddress(addr) => Some(*addr),
+                _ => None,
+            })
+    }
+
+    pub fn username(&self) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find_map(|attribute| match attribute {
+                StunAttribute::Username(username) => Some(username.as_str()),
+                _ => None,
+            })
+    }
+
+    /// Serializes the message to `bytes`
+    ///
+    /// The `MESSAGE-INTEGRITY` is added if there is a key,
+    /// the `FINGERPRINT` is always added
+    pub fn encode(&self, integrity_key: Option<&[u8]>) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(STUN_HEADER_LEN + 64);
+        buf.extend_from_slice(&self.kind.as_u16().to_be_bytes());
+        buf.extend_from_slice(&[0, 0]);
+        buf.extend_from_slice(&MAGIC_COOKIE.to_be_bytes());
+        buf.extend_from_slice(&self.transaction);
+
+        for attribute in &self.attributes {
+            self.encode_attribute(attribute, &mut buf);
+        }
+
+        // the length covers the attribute that is being computed
+        if let Some(key) = integrity_key {
+            set_length(&mut buf, 4 + INTEGRITY_LEN);
+            let integrity = hmac_sha1(key, &buf);
+            push_attribute(&mut buf, ATTR_MESSAGE_INTEGRITY, &integrity);
+        }
+        set_length(&mut buf, 8);
+        let fingerprint = crc32fast::hash(&buf) ^ FINGERPRINT_XOR;
+        push_attribute(&mut buf, ATTR_FINGERPRINT, &fingerprint.to_be_bytes());
+        buf
+    }
+
+    /// Deserializes the message from `bytes`
+    ///
+    /// The `FINGERPRINT` is verified if there is one, the attributes
+    /// that follow the `MESSAGE-INTEGRITY` are ignored
+    pub fn decode(bytes: &[u8]) -> Result<Self, MediaError> {
+        if !is_stun(bytes) {
+            return Err(MediaError::Malformed);
+        }
+        let kind = StunType::from_u16(u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or(MediaError::Malformed)?;
+        let len = u16::from_be_bytes([bytes[2], bytes[3]]) as usize;
+        if STUN_HEADER_LEN + len != bytes.len() || !len.is_multiple_of(4) {
+            return Err(MediaError::Malformed);
+        }
+        let transaction: [u8; 12] = bytes[8..STUN_HEADER_LEN].try_into().unwrap();
+
+        let mut attributes = Vec::new();
+        let mut authenticated = false;
+        for (offset, kind, value) in Attributes::new(bytes) {
+            match kind {
+                ATTR_FINGERPRINT => {
+                    let fingerprint = crc32fast::hash(&bytes[..offset]) ^ FINGERPRINT_XOR;
+                    if value != fingerprint.to_be_bytes() {
+                        return Err(MediaError::Malformed);
+                    }
+                }
+                ATTR_MESSAGE_INTEGRITY => authenticated = true,
+                _ if authenticated => (),
+                _ => {
+                    if let Some(attribute) = decode_attribute(kind, value, &transaction)? {
+                        attributes.push(attribute);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            kind,
+            transaction,
+            attributes,
+        })
+    }
+
+    /// Checks the `MESSAGE-INTEGRITY` of the encoded message with the key
+    pub fn verify_integrity(bytes: &[u8], key: &[u8]) -> bool {
+        let (offset, value) =
+            match Attributes::new(bytes).find(|(_, kind, _)| *kind == ATTR_MESSAGE_INTEGRITY) {
+                Some((offset, _, value)) => (offset, value),
+                None => return false,
+            };
+
+        let mut signed = bytes[..offset].to_vec();
+        set_length(&mut signed, 4 + INTEGRITY_LEN);
+        let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+        mac.update(&signed);
+        mac.verify_slice(value).is_ok()
+    }
+
+    fn encode_attribute(&self, attribute: &StunAttribute, buf: &mut Vec<u8>) {
+        match attribute {
+            StunAttribute::XorMappedAddress(addr) => {
+                let value = xor_address(addr, &self.transaction);
+                push_attribute(buf, ATTR_XOR_MAPPED_ADDRESS, &value);
+            }
+            StunAttribute::Username(username) => {
+                push_attribute(buf, ATTR_USERNAME, username.as_bytes())
+            }
+            StunAttribute::Priority(priority) => {
+                push_attribute(buf, ATTR_PRIORITY, &priority.to_be_bytes())
+            }
+            StunAttribute::UseCandidate => push_attribute(buf, ATTR_USE_CANDIDATE, &[]),
+            StunAttribute::IceControlling(tie_breaker) => {
+                push_attribute(buf, ATTR_ICE_CONTROLLING, &tie_breaker.to_be_bytes())
+            }
+            StunAttribute::IceControlled(tie_breaker) => {
+                push_attribute(buf, ATTR_ICE_CONTROLLED, &tie_breaker.to_be_bytes())
+            }
+            StunAttribute::ErrorCode(code, reason) => {
+                let mut value = vec![0, 0, (code / 100) as u8, (code % 100) as u8];
+                value.extend_from_slice(reason.as_bytes());
+                push_attribute(buf, ATTR_ERROR_CODE, &value);
+            }
+        }
+    }
+}
+
+/// Iterates over the attributes as `(offset, type, value)`
+struct Attributes<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Attributes<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: STUN_HEADER_LEN,
+        }
+    }
+}
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = (usize, u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let offset = self.pos;
+        let header = self.bytes.get(offset..offset + 4)?;
+        let kind = u16::from_be_bytes([header[0], header[1]]);
+        let len = u16::from_be_bytes([header[2], header[3]]) as usize;
+        let value = self.bytes.get(offset + 4..offset + 4 + len)?;
+
+        // the values are padded to 32-bit words
+        self.pos = offset + 4 + len.div_ceil(4) * 4;
+        Some((offset, kind, value))
+    }
+}
+
+fn decode_attribute(
+    kind: u16,
+    value: &[u8],
+    transaction: &[u8; 12],
+) -> Result<Option<StunAttribute>, MediaError> {
+    let attribute = match kind {
+        ATTR_XOR_MAPPED_ADDRESS => {
+            StunAttribute::XorMappedAddress(xor_address_decode(value, transaction)?)
+        }
+        ATTR_USERNAME => StunAttribute::Username(
+            String::from_utf8(value.to_vec()).map_err(|_| MediaError::Malformed)?,
+        ),
+        ATTR_PRIORITY => StunAttribute::Priority(u32::from_be_bytes(
+            value.try_into().map_err(|_| MediaError::Malformed)?,
+        )),
+        ATTR_USE_CANDIDATE => StunAttribute::UseCandidate,
+        ATTR_ICE_CONTROLLING => StunAttribute::IceControlling(u64::from_be_bytes(
+            value.try_into().map_err(|_| MediaError::Malformed)?,
+        )),
+        ATTR_ICE_CONTROLLED => StunAttribute::IceControlled(u64::from_be_bytes(
+            value.try_into().map_err(|_| MediaError::Malformed)?,
+        )),
+        ATTR_ERROR_CODE => {
+            if value.len() < 4 {
+                return Err(MediaError::Malformed);
+            }
+            let code = (value[2] & 0x07) as u16 * 100 + value[3] as u16;
+            StunAttribute::ErrorCode(code, String::from_utf8_lossy(&value[4..]).into_owned())
+        }
+        // unknown optional attributes are skipped
+        _ => return Ok(None),
+    };
+    Ok(Some(attribute))
+}
+
+/// Returns the value of the `XOR-MAPPED-ADDRESS`
+fn xor_address(addr: &SocketAddr, transaction: &[u8; 12]) -> Vec<u8> {
+    let port = addr.port() ^ (MAGIC_COOKIE >> 16) as u16;
+    let mut value = vec![0];
+    match addr.ip() {
+        IpAddr::V4(ip) => {
+            value.push(0x01);
+            value.extend_from_slice(&port.to_be_bytes());
+            value.extend_from_slice(&(u32::from(ip) ^ MAGIC_COOKIE).to_be_bytes());
+        }
+        IpAddr::V6(ip) => {
+            value.push(0x02);
+            value.extend_from_slice(&port.to_be_bytes());
+            let mask = xor_mask(transaction);
+            value.extend(ip.octets().iter().zip(mask).map(|(byte, mask)| byte ^ mask));
+        }
+    }
+    value
+}
+
+fn xor_address_decode(value: &[u8], transaction: &[u8; 12]) -> Result<SocketAddr, MediaError> {
+    if value.len() < 8 {
+        return Err(MediaError::Malformed);
+    }
+    let port = u16::from_be_bytes([value[2], value[3]]) ^ (MAGIC_COOKIE >> 16) as u16;
+
+    let ip = match (value[1], value.len()) {
+        (0x01, 8) => {
+            let ip = u32::from_be_bytes([value[4], value[5], value[6], value[7]]) ^ MAGIC_COOKIE;
+            IpAddr::V4(Ipv4Addr::from(ip))
+        }
+        (0x02, 20) => {
+            let mut octets = [0u8; 16];
+            let mask = xor_mask(transaction);
+            for (octet, (byte, mask)) in octets.iter_mut().zip(value[4..].iter().zip(mask)) {
+                *octet = byte ^ mask;
+            }
+            IpAddr::V6(Ipv6Addr::from(octets))
+        }
+        _ => return Err(MediaError::Malformed),
+    };
+    Ok(SocketAddr::new(ip, port))
+}
+
+/// The IPv6 address is masked with the cookie and the transaction id
+fn xor_mask(transaction: &[u8; 12]) -> [u8; 16] {
+    let mut mask = [0u8; 16];
+    mask[..4].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    mask[4..].copy_from_slice(transaction);
+    mask
+}
+
+fn push_attribute(buf: &mut Vec<u8>, kind: u16, value: &[u8]) {
+    buf.extend_from_slice(&kind.to_be_bytes());
+    buf.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    buf.extend_from_slice(value);
+    buf.resize(buf.len() + value.len().div_ceil(4) * 4 - value.len(), 0);
+}
+
+/// Sets the length of the header to the attributes written so far
+/// and the next attribute of `next` bytes
+fn set_length(buf: &mut [u8], next: usize) {
+    let len = (buf.len() - STUN_HEADER_LEN + next) as u16;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+}
+
+fn hmac_sha1(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
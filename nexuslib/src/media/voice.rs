@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use crate::{
+    errors::media::MediaError,
+    models::message::voice::{MAX_VOICE_DURATION_MS, WAVEFORM_LEN},
+};
+
+use super::audio::{AudioSink, AudioSource, WavSink};
+
+/// Length of the frames the peaks are measured over, in milliseconds
+const FRAME_MS: u32 = 20;
+
+#[derive(Debug, Clone, PartialEq)]
+/// Summary of a recorded voice note
+pub struct VoiceRecording {
+    pub duration_ms: u32,
+    /// See `VoiceNote::waveform`
+    pub waveform: Vec<u8>,
+}
+
+/// Records the audio of the source to a WAV file and summarizes it
+///
+/// The recording ends with the source, or after `MAX_VOICE_DURATION_MS`
+/// if the source is endless
+pub fn record_voice(
+    source: &mut dyn AudioSource,
+    path: impl AsRef<Path>,
+) -> Result<VoiceRecording, MediaError> {
+    let format = source.format();
+    let mut sink = WavSink::create(path, format)?;
+    let mut frame = vec![0; format.frame_len(FRAME_MS)];
+    if frame.is_empty() {
+        return Err(MediaError::Io);
+    }
+
+    let mut peaks = Vec::new();
+    let mut duration_ms = 0;
+    while duration_ms < MAX_VOICE_DURATION_MS && source.read_frame(&mut frame)? {
+        sink.write_frame(&frame)?;
+        peaks.push(
+            frame
+                .iter()
+                .map(|sample| sample.unsigned_abs())
+                .max()
+                .unwrap_or(0),
+        );
+        duration_ms += FRAME_MS;
+    }
+    sink.finish()?;
+
+    Ok(VoiceRecording {
+        duration_ms,
+        waveform: waveform(&peaks),
+    })
+}
+
+/// Reduces the peaks of the frames to at most `WAVEFORM_LEN` bars,
+/// scaled so the loudest one is 255
+pub fn waveform(peaks: &[u16]) -> Vec<u8> {
+    let bars = WAVEFORM_LEN.min(peaks.len());
+    let bars = (0..bars)
+        .map(|bar| {
+            let start = bar * peaks.len() / bars;
+            let end = (bar + 1) * peaks.len() / bars;
+            peaks[start..end].iter().copied().max().unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+
+    let loudest = bars.iter().copied().max().unwrap_or(0).max(1) as u32;
+    bars.into_iter()
+        .map(|peak| (peak as u32 * 255 / loudest) as u8)
+        .collect()
+}
@@ -2,4 +2,5 @@ pub mod call;
 pub mod command;
 pub mod file;
 pub mod message;
+pub mod moderation;
 pub mod user;
@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 
+pub mod candidate;
+pub mod log;
 pub mod media_call;
+pub mod packet;
+pub mod relay;
+pub mod room;
+pub mod state;
+pub mod stats;
 
 /// The structs that implement this one
 /// can be inserted into the `CallRequest`
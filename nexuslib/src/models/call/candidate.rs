@@ -0,0 +1,54 @@
+use std::net::SocketAddr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+/// Kind of the transport address of a call participant (RFC 8445)
+pub enum CandidateKind {
+    /// Address of the local socket
+    Host,
+    /// Address of the socket as seen by the STUN server behind the NAT
+    ServerReflexive,
+    /// Address of the server relay, used when no direct path works
+    Relay,
+}
+
+impl CandidateKind {
+    /// Preference of the kind in the candidate priority
+    pub fn preference(&self) -> u32 {
+        match self {
+            CandidateKind::Host => 126,
+            CandidateKind::ServerReflexive => 100,
+            CandidateKind::Relay => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Transport address the other participant can try to reach
+pub struct IceCandidate {
+    pub kind: CandidateKind,
+    pub address: SocketAddr,
+    pub priority: u32,
+}
+
+impl IceCandidate {
+    /// Creates the candidate of the only component of the call
+    pub fn new(kind: CandidateKind, address: SocketAddr) -> Self {
+        // local preference is the same for all the addresses of one kind
+        let priority = kind.preference() << 24 | 65_535 << 8 | 255;
+        Self {
+            kind,
+            address,
+            priority,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Candidates of a participant with the credentials of its connectivity checks
+pub struct IceParameters {
+    pub ufrag: String,
+    pub pwd: String,
+    pub candidates: Vec<IceCandidate>,
+}
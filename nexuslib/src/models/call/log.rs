@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use uuid::Uuid;
+
+use super::state::CallState;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize_repr, Deserialize_repr)]
+/// Direction of a call from the point of view of the user
+pub enum CallDirection {
+    Outgoing,
+    Incoming,
+}
+
+impl CallDirection {
+    /// Returns `CallDirection` by its u8 index
+    pub fn from_index(index: u8) -> Option<Self> {
+        match index {
+            0 => Some(CallDirection::Outgoing),
+            1 => Some(CallDirection::Incoming),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Entry of the call history of a user
+pub struct CallLogEntry {
+    pub call: Uuid,
+    /// The other side of the call
+    pub peer: Uuid,
+    pub direction: CallDirection,
+    pub state: CallState,
+    /// Duration in seconds, `0` if the call was not accepted
+    pub duration: i64,
+    pub accepted: bool,
+    pub created_at: i64,
+}
+
+impl CallLogEntry {
+    /// Checks whether the user did not answer the call
+    pub fn is_missed(&self) -> bool {
+        self.direction == CallDirection::Incoming && self.state.is_missed()
+    }
+}
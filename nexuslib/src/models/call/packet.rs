@@ -0,0 +1,127 @@
+use uuid::Uuid;
+
+/// Length of the `MediaHeader` in bytes
+pub const MEDIA_HEADER_LEN: usize = 25;
+/// Maximal size of a datagram accepted by the relay
+pub const MAX_DATAGRAM_LEN: usize = 1500;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Kind of a datagram sent to the relay
+///
+/// The values are taken from the range that is not used by
+/// STUN (0-3), DTLS (20-63) and RTP/RTCP (128-191),
+/// so the same socket can demultiplex all of them by the first byte
+pub enum PacketKind {
+    /// Binds the source address to a participant of the call
+    Bind = 0x40,
+    /// Media frame that is forwarded to the other participants
+    Media = 0x41,
+}
+
+impl PacketKind {
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0x40 => Some(PacketKind::Bind),
+            0x41 => Some(PacketKind::Media),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Compact header of a relayed datagram
+///
+/// Layout (big endian):
+/// - `kind`: 1 byte
+/// - `call`: 16 bytes
+/// - `seq`: 4 bytes
+/// - `timestamp`: 4 bytes
+pub struct MediaHeader {
+    pub kind: PacketKind,
+    pub call: Uuid,
+    pub seq: u32,
+    /// Media clock of the sender (e.g. milliseconds since the start of the call)
+    pub timestamp: u32,
+}
+
+impl MediaHeader {
+    /// Parses the header from the beginning of the datagram
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < MEDIA_HEADER_LEN {
+            return None;
+        }
+
+        Some(Self {
+            kind: PacketKind::from_byte(bytes[0])?,
+            call: Uuid::from_slice(&bytes[1..17]).ok()?,
+            seq: u32::from_be_bytes(bytes[17..21].try_into().ok()?),
+            timestamp: u32::from_be_bytes(bytes[21..25].try_into().ok()?),
+        })
+    }
+
+    /// Writes the header to the buffer
+    pub fn encode_into(&self, buf: &mut Vec<u8>) {
+        buf.push(self.kind as u8);
+        buf.extend_from_slice(self.call.as_bytes());
+        buf.extend_from_slice(&self.seq.to_be_bytes());
+        buf.extend_from_slice(&self.timestamp.to_be_bytes());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+/// Datagram exchanged with the relay
+pub struct MediaPacket {
+    pub header: MediaHeader,
+    pub payload: Vec<u8>,
+}
+
+impl MediaPacket {
+    /// Creates a media frame
+    pub fn media(call: Uuid, seq: u32, timestamp: u32, payload: Vec<u8>) -> Self {
+        Self {
+            header: MediaHeader {
+                kind: PacketKind::Media,
+                call,
+                seq,
+                timestamp,
+            },
+            payload,
+        }
+    }
+
+    /// Creates a bind request carrying the relay token of the `RelayTicket`
+    pub fn bind(call: Uuid, token: Vec<u8>) -> Self {
+        Self {
+            header: MediaHeader {
+                kind: PacketKind::Bind,
+                call,
+                seq: 0,
+                timestamp: 0,
+            },
+            payload: token,
+        }
+    }
+
+    /// Serializes the packet to `bytes`
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(MEDIA_HEADER_LEN + self.payload.len());
+        self.header.encode_into(&mut buf);
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    /// Deserializes the packet from `bytes`
+    ///
+    /// `None` if the datagram is truncated or larger than the relay accepts
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() > MAX_DATAGRAM_LEN {
+            return None;
+        }
+
+        Some(Self {
+            header: MediaHeader::decode(bytes)?,
+            payload: bytes[MEDIA_HEADER_LEN..].to_vec(),
+        })
+    }
+}
@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Permission to send media of the call through the relay
+///
+/// Issued to each participant over the TCP signaling when the call is accepted
+/// or the room is joined, `call` is then the uuid of the room.
+/// The `token` is sent in a `Bind` packet to register the UDP address
+pub struct RelayTicket {
+    pub call: Uuid,
+    pub token: Vec<u8>,
+}
+
+impl RelayTicket {
+    pub fn new(call: Uuid, token: Vec<u8>) -> Self {
+        Self { call, token }
+    }
+}
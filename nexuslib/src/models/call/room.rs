@@ -0,0 +1,88 @@
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::CallContent;
+
+/// Maximal number of participants of a room,
+/// the relay sends each packet to all the others
+pub const MAX_ROOM_PARTICIPANTS: usize = 16;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// SRTP master key of the room sealed for one invited user
+/// with the key shared between the owner and the user
+pub struct RoomKey {
+    pub user: Uuid,
+    pub message: Vec<u8>,
+    pub nonce: Vec<u8>,
+}
+
+impl RoomKey {
+    pub fn new(user: Uuid, message: Vec<u8>, nonce: Vec<u8>) -> Self {
+        Self {
+            user,
+            message,
+            nonce,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// The representation of a group audio call
+///
+/// The owner invites the users by sealing the master key for each of them.
+/// The participants and the active speaker are maintained by the server
+pub struct CallRoom {
+    pub uuid: Uuid,
+    pub owner: Uuid,
+    pub keys: Vec<RoomKey>,
+    #[serde(default)]
+    pub participants: Vec<Uuid>,
+    #[serde(default)]
+    pub speaker: Option<Uuid>,
+
+    created_at: i64,
+}
+
+impl CallRoom {
+    /// Creates new `CallRoom`
+    pub fn new(owner: Uuid, keys: Vec<RoomKey>) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            owner,
+            keys,
+            participants: Vec::new(),
+            speaker: None,
+            created_at: Utc::now().timestamp(),
+        }
+    }
+
+    /// Returns `timestamp` as `DateTime<Utc>` that
+    /// specifies the time when this `CallRoom` was created
+    pub fn get_created_at(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.created_at, 0).unwrap()
+    }
+
+    /// Returns the owner and the invited users
+    pub fn invited(&self) -> Vec<Uuid> {
+        let mut invited = vec![self.owner];
+        for key in &self.keys {
+            if !invited.contains(&key.user) {
+                invited.push(key.user);
+            }
+        }
+        invited
+    }
+
+    /// Checks whether the user may join the room
+    pub fn is_invited(&self, user: &Uuid) -> bool {
+        self.owner == *user || self.keys.iter().any(|key| key.user == *user)
+    }
+
+    /// Returns the master key sealed for the user
+    pub fn key_of(&self, user: &Uuid) -> Option<&RoomKey> {
+        self.keys.iter().find(|key| key.user == *user)
+    }
+}
+
+impl CallContent for CallRoom {}
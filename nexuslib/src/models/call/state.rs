@@ -0,0 +1,67 @@
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use strum_macros::Display;
+
+use crate::request::index_token::IndexToken;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize_repr, Deserialize_repr, Display)]
+#[strum(serialize_all = "snake_case")]
+/// State of a call
+///
+/// Can be represented as u8 index
+pub enum CallState {
+    Ringing,
+    Accepted,
+    Rejected,
+    Busy,
+    /// The caller hung up before the call was answered
+    Missed,
+    Ended,
+    /// Nobody answered while the call was ringing
+    TimedOut,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+/// Side of the call that sent an `IndexToken`
+pub enum CallSide {
+    Caller,
+    Callee,
+}
+
+impl CallState {
+    /// Returns u8 index of the `CallState` entry
+    pub fn get_index(&self) -> u8 {
+        serde_json::to_string(self).unwrap().parse::<u8>().unwrap()
+    }
+
+    /// Returns `CallState` by its u8 index
+    pub fn from_index(index: u8) -> Option<Self> {
+        serde_json::from_str(&index.to_string()).ok()
+    }
+
+    /// Checks whether the call is over
+    pub fn is_terminal(&self) -> bool {
+        !matches!(self, CallState::Ringing | CallState::Accepted)
+    }
+
+    /// Checks whether the call was not answered by the callee
+    pub fn is_missed(&self) -> bool {
+        matches!(self, CallState::Missed | CallState::TimedOut)
+    }
+
+    /// Returns the state the token leads to
+    ///
+    /// `None` means that the transition is illegal
+    pub fn next(&self, token: IndexToken, side: CallSide) -> Option<CallState> {
+        match (self, token, side) {
+            (CallState::Ringing, IndexToken::Accept, CallSide::Callee) => Some(CallState::Accepted),
+            (CallState::Ringing, IndexToken::Reject, CallSide::Callee) => Some(CallState::Rejected),
+            (CallState::Ringing, IndexToken::Busy, CallSide::Callee) => Some(CallState::Busy),
+            (CallState::Ringing, IndexToken::End, CallSide::Caller) => Some(CallState::Missed),
+            (CallState::Ringing, IndexToken::End, CallSide::Callee) => Some(CallState::Rejected),
+            (CallState::Ringing, IndexToken::Timeout, _) => Some(CallState::TimedOut),
+            (CallState::Accepted, IndexToken::End, _) => Some(CallState::Ended),
+            _ => None,
+        }
+    }
+}
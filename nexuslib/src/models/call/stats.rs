@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::media::rtcp::ReportBlock;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Quality of one RTP stream as reported by its receiver in RTCP
+pub struct StreamStats {
+    pub ssrc: u32,
+    /// Participant that sent the report
+    pub reporter: Uuid,
+    /// Fraction of packets lost in the last interval, in 1/256
+    pub fraction_lost: u8,
+    pub cumulative_lost: i32,
+    pub highest_seq: u32,
+    /// Interarrival jitter in RTP timestamp units
+    pub jitter: u32,
+    pub updated_at: i64,
+}
+
+impl StreamStats {
+    /// Creates `StreamStats` from the report block received from the `reporter`
+    pub fn from_report(reporter: Uuid, report: &ReportBlock, updated_at: i64) -> Self {
+        Self {
+            ssrc: report.ssrc,
+            reporter,
+            fraction_lost: report.fraction_lost,
+            cumulative_lost: report.cumulative_lost,
+            highest_seq: report.highest_seq,
+            jitter: report.jitter,
+            updated_at,
+        }
+    }
+
+    /// Returns the fraction of lost packets as percents
+    pub fn loss_percent(&self) -> f32 {
+        self.fraction_lost as f32 * 100.0 / 256.0
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Quality of the media of one participant as seen by the relay
+pub struct ParticipantStats {
+    pub user: Uuid,
+    /// Packets sent by the participant to the relay
+    pub packets_in: u64,
+    /// Packets forwarded by the relay to the participant
+    pub packets_out: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    /// Packets lost on the way to the participant, from its latest report
+    pub loss_percent: f32,
+    pub jitter_ms: f32,
+    /// Round trip time between the relay and the participant
+    pub rtt_ms: Option<u32>,
+    /// Average bitrate sent by the participant in bits per second
+    pub bitrate: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Statistics of the media relayed for a call
+pub struct CallStats {
+    pub call: Uuid,
+    pub packets: u64,
+    pub bytes: u64,
+    pub dropped: u64,
+    pub streams: Vec<StreamStats>,
+    #[serde(default)]
+    pub participants: Vec<ParticipantStats>,
+}
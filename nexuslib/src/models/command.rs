@@ -8,4 +8,6 @@ pub enum Command {
     Message,
     Call,
     File,
+    /// Group calls
+    Room,
 }
@@ -2,7 +2,11 @@ use serde::{Deserialize, Serialize};
 
 use super::message::media::MediaType;
 
+pub mod chunk;
+pub mod format;
 pub mod media_file;
+pub mod policy;
+pub mod upload;
 
 /// The structs that implement this one
 /// can be inserted into the `FileRequest`
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::crypto::hasher::get_bytes_hash;
+
+/// Size of the chunks of an upload, only the last one may be shorter
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Returns the number of the chunks of a file
+pub fn chunk_count(len_bytes: usize) -> usize {
+    len_bytes.div_ceil(CHUNK_SIZE)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// Part of a file sent in a single request
+pub struct FileChunk {
+    pub index: usize,
+    /// Sent as hex since the requests are lines of JSON
+    #[serde(serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub data: Vec<u8>,
+    /// SHA3-256 of the data as hex
+    pub hash: String,
+}
+
+impl FileChunk {
+    /// Creates the chunk with the hash of the data
+    pub fn new(index: usize, data: Vec<u8>) -> Self {
+        let hash = get_bytes_hash(&data);
+        Self { index, data, hash }
+    }
+
+    /// Returns the position of the chunk in the file
+    pub fn offset(&self) -> u64 {
+        (self.index * CHUNK_SIZE) as u64
+    }
+
+    /// Checks whether the data matches its hash
+    pub fn verify(&self) -> bool {
+        get_bytes_hash(&self.data) == self.hash
+    }
+}
+
+fn to_hex<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(data))
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let text = String::deserialize(deserializer)?;
+    hex::decode(text).map_err(serde::de::Error::custom)
+}
@@ -0,0 +1,132 @@
+use std::path::Path;
+
+use crate::models::message::media::MediaType;
+
+/// Number of the first bytes of a file its format is recognized from
+pub const SNIFF_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// File format recognized by the content
+pub struct Format {
+    pub mime: &'static str,
+    /// Extensions the name of the file may have, any one if empty
+    pub extensions: &'static [&'static str],
+    pub media_type: MediaType,
+}
+
+impl Format {
+    const fn new(
+        mime: &'static str,
+        extensions: &'static [&'static str],
+        media_type: MediaType,
+    ) -> Self {
+        Self {
+            mime,
+            extensions,
+            media_type,
+        }
+    }
+
+    /// Checks that the extension of the name fits the format,
+    /// a name without an extension fits any format
+    pub fn fits_name(&self, name: &str) -> bool {
+        match extension(name) {
+            Some(ext) => {
+                self.extensions.is_empty() || self.extensions.iter().any(|known| *known == ext)
+            }
+            None => true,
+        }
+    }
+}
+
+const PNG: Format = Format::new("image/png", &["png"], MediaType::Image);
+const JPEG: Format = Format::new("image/jpeg", &["jpg", "jpeg", "jfif"], MediaType::Image);
+const GIF: Format = Format::new("image/gif", &["gif"], MediaType::Image);
+const WEBP: Format = Format::new("image/webp", &["webp"], MediaType::Image);
+const HEIC: Format = Format::new("image/heic", &["heic", "heif"], MediaType::Image);
+const MP3: Format = Format::new("audio/mpeg", &["mp3"], MediaType::Audio);
+const OGG: Format = Format::new("audio/ogg", &["ogg", "oga", "opus"], MediaType::Audio);
+const WAV: Format = Format::new("audio/wav", &["wav"], MediaType::Audio);
+const FLAC: Format = Format::new("audio/flac", &["flac"], MediaType::Audio);
+const M4A: Format = Format::new("audio/mp4", &["m4a"], MediaType::Audio);
+const MP4: Format = Format::new("video/mp4", &["mp4", "m4v"], MediaType::Video);
+const MOV: Format = Format::new("video/quicktime", &["mov", "qt"], MediaType::Video);
+const WEBM: Format = Format::new("video/webm", &["webm"], MediaType::Video);
+const MKV: Format = Format::new("video/x-matroska", &["mkv"], MediaType::Video);
+const AVI: Format = Format::new("video/x-msvideo", &["avi"], MediaType::Video);
+const PDF: Format = Format::new("application/pdf", &["pdf"], MediaType::File);
+const ZIP: Format = Format::new(
+    "application/zip",
+    &[
+        "zip", "jar", "apk", "epub", "docx", "xlsx", "pptx", "odt", "ods", "odp",
+    ],
+    MediaType::File,
+);
+const GZIP: Format = Format::new("application/gzip", &["gz", "tgz"], MediaType::File);
+const TEXT: Format = Format::new("text/plain", &[], MediaType::File);
+
+/// Recognizes the format from the first bytes of the file
+///
+/// Returns `None` for the formats that are not known,
+/// they can be uploaded only as a `File`
+pub fn sniff(head: &[u8]) -> Option<Format> {
+    let at = |offset: usize, magic: &[u8]| head.get(offset..offset + magic.len()) == Some(magic);
+
+    let format = match head {
+        _ if at(0, b"\x89PNG\r\n\x1a\n") => PNG,
+        [0xFF, 0xD8, 0xFF, ..] => JPEG,
+        _ if at(0, b"GIF87a") || at(0, b"GIF89a") => GIF,
+        _ if at(0, b"RIFF") && at(8, b"WEBP") => WEBP,
+        _ if at(0, b"RIFF") && at(8, b"WAVE") => WAV,
+        _ if at(0, b"RIFF") && at(8, b"AVI ") => AVI,
+        // ISO base media, the brand tells the kind
+        _ if at(4, b"ftyp") => match head.get(8..12)? {
+            b"qt  " => MOV,
+            b"M4A " | b"M4B " => M4A,
+            b"heic" | b"heix" | b"mif1" | b"msf1" => HEIC,
+            _ => MP4,
+        },
+        _ if at(0, b"\x1a\x45\xdf\xa3") => match contains(head, b"webm") {
+            true => WEBM,
+            false => MKV,
+        },
+        _ if at(0, b"OggS") => OGG,
+        _ if at(0, b"fLaC") => FLAC,
+        _ if at(0, b"ID3") => MP3,
+        // MPEG audio frame sync
+        [0xFF, second, ..] if second & 0xE0 == 0xE0 => MP3,
+        _ if at(0, b"%PDF-") => PDF,
+        _ if at(0, b"PK\x03\x04") => ZIP,
+        [0x1F, 0x8B, ..] => GZIP,
+        _ if is_text(head) => TEXT,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Returns the lowercase extension of the file name
+pub fn extension(name: &str) -> Option<String> {
+    Path::new(name)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+}
+
+fn contains(head: &[u8], needle: &[u8]) -> bool {
+    head.windows(needle.len()).any(|window| window == needle)
+}
+
+/// UTF-8 without control characters other than whitespace,
+/// the last character may be cut off by the end of the head
+fn is_text(head: &[u8]) -> bool {
+    let text = match std::str::from_utf8(head) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).unwrap_or_default()
+        }
+        Err(_) => return false,
+    };
+    !text.is_empty()
+        && !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r' | '\x0c'))
+}
@@ -4,41 +4,46 @@ use uuid::Uuid;
 
 use crate::models::message::media::MediaType;
 
-use super::FileContent;
+use super::{chunk::chunk_count, FileContent};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-/// The representation of an audio call
+/// The representation of a file sent in chunks
 pub struct MediaFile {
     pub uuid: Uuid,
     pub len_bytes: usize,
     pub len_chunks: usize,
     pub name: String,
     pub media_type: MediaType,
+    /// The file is encrypted end-to-end,
+    /// `len_bytes` and `hash` are the ones of the ciphertext
     pub secret: bool,
     pub sender: Uuid,
+    /// SHA3-256 of the whole file as hex
+    pub hash: String,
 
     created_at: i64,
 }
 
 impl MediaFile {
-    /// Creates new `MediaFile`
+    /// Creates new `MediaFile` sent in chunks of `CHUNK_SIZE`
     pub fn new(
         uuid: Uuid,
         len_bytes: usize,
-        len_chunks: usize,
         name: String,
         media_type: MediaType,
         secret: bool,
         sender: Uuid,
+        hash: String,
     ) -> Self {
         Self {
             uuid,
             len_bytes,
-            len_chunks,
+            len_chunks: chunk_count(len_bytes),
             name,
             media_type,
             secret,
             sender,
+            hash,
             created_at: Utc::now().timestamp(),
         }
     }
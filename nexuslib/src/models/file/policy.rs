@@ -0,0 +1,117 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::file::UploadError, models::message::media::MediaType};
+
+use super::{
+    format::{sniff, Format},
+    media_file::MediaFile,
+    upload::MAX_FILE_SIZE,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Limits of the uploads of a `MediaType`
+pub struct UploadPolicy {
+    /// Maximal size of a file in bytes, at most `MAX_FILE_SIZE`
+    pub max_size: usize,
+    /// Allowed MIME types, any format is allowed if empty
+    pub formats: Vec<String>,
+}
+
+impl UploadPolicy {
+    pub fn new(max_size: usize) -> Self {
+        Self {
+            max_size: max_size.min(MAX_FILE_SIZE),
+            formats: Vec::new(),
+        }
+    }
+
+    pub fn with_formats(mut self, formats: &[&str]) -> Self {
+        self.formats = formats.iter().map(|mime| mime.to_lowercase()).collect();
+        self
+    }
+
+    /// The policy used unless the server configures another one
+    pub fn default_for(media_type: MediaType) -> Self {
+        match media_type {
+            MediaType::Image => Self::new(20 * 1024 * 1024).with_formats(&[
+                "image/png",
+                "image/jpeg",
+                "image/gif",
+                "image/webp",
+                "image/heic",
+            ]),
+            MediaType::Audio => Self::new(50 * 1024 * 1024).with_formats(&[
+                "audio/mpeg",
+                "audio/ogg",
+                "audio/wav",
+                "audio/flac",
+                "audio/mp4",
+            ]),
+            MediaType::Video => Self::new(MAX_FILE_SIZE).with_formats(&[
+                "video/mp4",
+                "video/quicktime",
+                "video/webm",
+                "video/x-matroska",
+            ]),
+            MediaType::File => Self::new(MAX_FILE_SIZE),
+        }
+    }
+
+    /// Checks the size of the file before it is uploaded
+    pub fn check_size(&self, file: &MediaFile) -> Result<(), UploadError> {
+        match file.len_bytes > self.max_size {
+            true => Err(UploadError::TooLarge),
+            false => Ok(()),
+        }
+    }
+
+    /// Checks that the format is allowed, an unknown one only if any is
+    pub fn allows(&self, format: Option<&Format>) -> bool {
+        match format {
+            Some(format) => self.allows_mime(format.mime),
+            None => self.formats.is_empty(),
+        }
+    }
+
+    /// Checks that the MIME type is allowed, its parameters are ignored
+    pub fn allows_mime(&self, mime: &str) -> bool {
+        let mime = mime
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_lowercase();
+        self.formats.is_empty() || self.formats.contains(&mime)
+    }
+
+    /// Checks the first bytes of the file against its description
+    ///
+    /// The content has to be of the claimed `MediaType`, any content can be sent
+    /// as a `File`. The extension of the name has to fit the recognized format.
+    /// Files encrypted end-to-end can not be checked
+    pub fn check_content(
+        &self,
+        file: &MediaFile,
+        head: &[u8],
+    ) -> Result<Option<Format>, UploadError> {
+        if file.secret {
+            return Ok(None);
+        }
+
+        let format = sniff(head);
+        match (&format, file.media_type) {
+            (_, MediaType::File) => (),
+            (Some(format), media_type) if format.media_type == media_type => (),
+            (Some(_), _) => return Err(UploadError::TypeMismatch),
+            (None, _) => return Err(UploadError::UnsupportedFormat),
+        }
+        if format.is_some_and(|format| !format.fits_name(&file.name)) {
+            return Err(UploadError::Extension);
+        }
+        if !self.allows(format.as_ref()) {
+            return Err(UploadError::UnsupportedFormat);
+        }
+
+        Ok(format)
+    }
+}
@@ -0,0 +1,96 @@
+use sha3::{Digest, Sha3_256};
+
+use crate::errors::file::UploadError;
+
+use super::{
+    chunk::{chunk_count, FileChunk, CHUNK_SIZE},
+    media_file::MediaFile,
+};
+
+/// Maximal size of an uploaded file in bytes
+pub const MAX_FILE_SIZE: usize = 100 * 1024 * 1024;
+
+/// Checks the description of the file before it is uploaded
+pub fn check_file(file: &MediaFile) -> Result<(), UploadError> {
+    if file.len_bytes == 0 {
+        return Err(UploadError::Empty);
+    }
+    if file.len_bytes > MAX_FILE_SIZE {
+        return Err(UploadError::TooLarge);
+    }
+    if file.len_chunks != chunk_count(file.len_bytes) {
+        return Err(UploadError::ChunkCount);
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+/// Progress of a chunked upload
+///
+/// The chunks have to come in order and each one is checked against its hash,
+/// the whole file is checked against the hash of the `MediaFile` on commit.
+/// The upload can be resumed from `next_chunk` at any time
+pub struct UploadProgress {
+    pub file: MediaFile,
+    next_chunk: usize,
+    received: usize,
+    hasher: Sha3_256,
+}
+
+impl UploadProgress {
+    /// Checks the size of the file before anything is received
+    pub fn new(file: MediaFile) -> Result<Self, UploadError> {
+        check_file(&file)?;
+
+        Ok(Self {
+            file,
+            next_chunk: 0,
+            received: 0,
+            hasher: Sha3_256::new(),
+        })
+    }
+
+    /// Returns the index of the chunk the upload expects
+    pub fn next_chunk(&self) -> usize {
+        self.next_chunk
+    }
+
+    /// Takes the next chunk
+    pub fn accept(&mut self, chunk: &FileChunk) -> Result<(), UploadError> {
+        if chunk.index != self.next_chunk {
+            return Err(UploadError::OutOfOrder {
+                expected: self.next_chunk,
+            });
+        }
+
+        let is_last = chunk.index + 1 == self.file.len_chunks;
+        let len = chunk.data.len();
+        if len == 0
+            || len > CHUNK_SIZE
+            || (!is_last && len != CHUNK_SIZE)
+            || self.received + len > self.file.len_bytes
+        {
+            return Err(UploadError::ChunkSize);
+        }
+        if !chunk.verify() {
+            return Err(UploadError::Corrupted);
+        }
+
+        self.hasher.update(&chunk.data);
+        self.received += len;
+        self.next_chunk += 1;
+        Ok(())
+    }
+
+    /// Checks that the whole file was received and matches its hash
+    pub fn finish(&self) -> Result<(), UploadError> {
+        if self.next_chunk != self.file.len_chunks || self.received != self.file.len_bytes {
+            return Err(UploadError::Incomplete);
+        }
+
+        match hex::encode(self.hasher.clone().finalize()) == self.file.hash {
+            true => Ok(()),
+            false => Err(UploadError::Corrupted),
+        }
+    }
+}
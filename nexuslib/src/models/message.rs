@@ -4,11 +4,18 @@ use uuid::Uuid;
 
 use crate::{request::sides::RequestSides, utils::vec_to_string};
 
-use self::{media::Media, status::MessageStatus};
+use self::{
+    media::Media,
+    status::MessageStatus,
+    text::TextMessage,
+    voice::{VoiceMessage, VoiceNote},
+};
 
 pub mod media;
+pub mod payload;
 pub mod status;
 pub mod text;
+pub mod voice;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 /// The message
@@ -46,6 +53,18 @@ impl<T: MessageContent> Message<T> {
         }
     }
 
+    /// Attaches the uploaded files to the `Message`
+    pub fn with_media(mut self, media: Media) -> Self {
+        self.media = Some(media);
+        self
+    }
+
+    /// Restores the creation time of a stored `Message`
+    pub fn with_created_at(mut self, created_at: i64) -> Self {
+        self.created_at = created_at;
+        self
+    }
+
     /// Returns nonce of the `Message`
     pub fn get_nonce(&self) -> String {
         self.nonce.to_owned()
@@ -69,6 +88,37 @@ impl<T: MessageContent> Message<T> {
 /// can be inserted into the `MessageRequest`
 pub trait MessageContent {
     fn get_text(&self) -> Option<String>;
+
+    /// Returns the voice note the content is, if it is one
+    fn get_voice(&self) -> Option<&VoiceNote> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+/// Any content of a `Message`, used where the type of the content is not known
+///
+/// The content with a voice note is a `VoiceMessage`, any other one is a `TextMessage`
+pub enum MessageBody {
+    Voice(VoiceMessage),
+    Text(TextMessage),
+}
+
+impl MessageContent for MessageBody {
+    fn get_text(&self) -> Option<String> {
+        match self {
+            MessageBody::Voice(voice) => voice.get_text(),
+            MessageBody::Text(text) => text.get_text(),
+        }
+    }
+
+    fn get_voice(&self) -> Option<&VoiceNote> {
+        match self {
+            MessageBody::Voice(voice) => voice.get_voice(),
+            MessageBody::Text(text) => text.get_voice(),
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
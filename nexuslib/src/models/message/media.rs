@@ -4,11 +4,25 @@ use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
 use uuid::Uuid;
 
+use crate::models::file::media_file::MediaFile;
+
+/// Maximal number of files attached to a single `Message`
+pub const MAX_ATTACHMENTS: usize = 10;
+
+/// Longer sides of the thumbnails generated for the images and the videos
+pub const THUMBNAIL_SIZES: [u32; 3] = [160, 320, 640];
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Media {
     pub attachments: Vec<MediaAttachment>,
 }
 
+impl Media {
+    pub fn new(attachments: Vec<MediaAttachment>) -> Self {
+        Self { attachments }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 /// The representation of a File that is a `Message`
 pub struct MediaAttachment {
@@ -16,6 +30,9 @@ pub struct MediaAttachment {
     pub name: String,
     pub path: String,
     pub media_type: MediaType,
+    /// Set by the server once the previews are generated
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preview: Option<MediaPreview>,
 }
 
 impl MediaAttachment {
@@ -26,6 +43,7 @@ impl MediaAttachment {
             name: name.to_owned(),
             path: path.to_owned(),
             media_type,
+            preview: None,
         }
     }
 
@@ -34,7 +52,47 @@ impl MediaAttachment {
     }
 }
 
-#[derive(Serialize_repr, Deserialize_repr, Debug, Clone, Copy, Display, EnumIter)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+/// Previews of an image or of the poster frame of a video
+pub struct MediaPreview {
+    /// Ordered from the smallest to the largest
+    pub thumbnails: Vec<Thumbnail>,
+    /// Placeholder shown until a thumbnail is loaded
+    pub blurhash: Option<String>,
+}
+
+impl MediaPreview {
+    /// Returns the smallest thumbnail that covers `size`,
+    /// or the largest one if none of them does
+    pub fn thumbnail_for(&self, size: u32) -> Option<&Thumbnail> {
+        self.thumbnails
+            .iter()
+            .find(|thumbnail| thumbnail.width.max(thumbnail.height) >= size)
+            .or(self.thumbnails.last())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+/// JPEG without metadata stored next to the original
+pub struct Thumbnail {
+    /// One of `THUMBNAIL_SIZES`, the longer side does not exceed it
+    pub size: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Key of the object in the bucket of the original
+    pub path: String,
+}
+
+impl From<&MediaFile> for MediaAttachment {
+    /// The attachment of the uploaded file, the `path` is set by the server
+    fn from(file: &MediaFile) -> Self {
+        Self::new(file.uuid, &file.name, "", file.media_type)
+    }
+}
+
+#[derive(
+    Serialize_repr, Deserialize_repr, Debug, Clone, Copy, PartialEq, Eq, Display, EnumIter,
+)]
 #[repr(u8)]
 pub enum MediaType {
     Audio,
@@ -49,6 +107,23 @@ impl MediaType {
         serde_json::to_string(self).unwrap().parse::<u8>().unwrap()
     }
 
+    /// Returns `MediaType` by its u8 index
+    pub fn from_index(index: u8) -> Option<Self> {
+        serde_json::from_str(&index.to_string()).ok()
+    }
+
+    /// Checks that the MIME type is a type of this media,
+    /// any content can be sent as a `File`
+    pub fn accepts(&self, content_type: &str) -> bool {
+        let prefix = match self {
+            MediaType::Audio => "audio/",
+            MediaType::Image => "image/",
+            MediaType::Video => "video/",
+            MediaType::File => return true,
+        };
+        content_type.trim().to_lowercase().starts_with(prefix)
+    }
+
     /// Returns a vector of enum variants of `MediaType`
     pub fn str_variants_vec() -> Vec<String> {
         let mut arr = vec![];
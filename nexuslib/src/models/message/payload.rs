@@ -0,0 +1,76 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::crypto::attachment::AttachmentKey;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Key and digest of an encrypted attachment
+pub struct AttachmentSecret {
+    /// UUID of the `MediaFile`
+    pub media: Uuid,
+    /// The real name of the file, the server sees only a generic one
+    pub name: String,
+    /// `AttachmentKey` as hex
+    pub key: String,
+    /// SHA3-256 of the plaintext as hex
+    pub digest: String,
+}
+
+impl AttachmentSecret {
+    pub fn get_key(&self) -> Option<AttachmentKey> {
+        AttachmentKey::from_hex(&self.key).ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Plaintext of a `Message` before it is encrypted
+///
+/// The keys of the attachments travel inside of it,
+/// so the server only ever stores the encrypted files
+pub struct MessagePayload {
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<AttachmentSecret>,
+}
+
+impl MessagePayload {
+    pub fn new(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            attachments: Vec::new(),
+        }
+    }
+
+    /// Adds the key of the encrypted attachment
+    pub fn with_attachment(
+        mut self,
+        media: Uuid,
+        name: &str,
+        key: &AttachmentKey,
+        digest: &str,
+    ) -> Self {
+        self.attachments.push(AttachmentSecret {
+            media,
+            name: name.to_owned(),
+            key: key.to_hex(),
+            digest: digest.to_owned(),
+        });
+        self
+    }
+
+    /// Returns the key and the digest of the attachment
+    pub fn secret_of(&self, media: &Uuid) -> Option<&AttachmentSecret> {
+        self.attachments
+            .iter()
+            .find(|secret| &secret.media == media)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        serde_json::to_vec(self).unwrap()
+    }
+
+    /// Parses the decrypted body, a plain text is a message without attachments
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        serde_json::from_slice(bytes).unwrap_or_else(|_| Self::new(&String::from_utf8_lossy(bytes)))
+    }
+}
@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use super::MessageContent;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// Text content of a `Message`
 pub struct TextMessage {
     pub text: String,
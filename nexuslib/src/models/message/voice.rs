@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::MessageContent;
+
+/// Number of the bars in the waveform of a voice note
+pub const WAVEFORM_LEN: usize = 64;
+/// Longest voice note in milliseconds
+pub const MAX_VOICE_DURATION_MS: u32 = 15 * 60 * 1000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+/// Playback metadata of the recorded audio
+///
+/// It is not encrypted, so the receiver can draw the note
+/// before the audio is downloaded
+pub struct VoiceNote {
+    /// UUID of the audio attached to the `Message`
+    pub media: Uuid,
+    pub duration_ms: u32,
+    /// Peak level of each part of the recording, from silence at 0 to the loudest part at 255
+    pub waveform: Vec<u8>,
+}
+
+impl VoiceNote {
+    pub fn new(media: Uuid, duration_ms: u32, waveform: Vec<u8>) -> Self {
+        Self {
+            media,
+            duration_ms,
+            waveform,
+        }
+    }
+
+    /// Checks the duration and the length of the waveform
+    pub fn is_valid(&self) -> bool {
+        (1..=MAX_VOICE_DURATION_MS).contains(&self.duration_ms)
+            && self.waveform.len() <= WAVEFORM_LEN
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Voice note content of a `Message`
+///
+/// The `text` is the encrypted `MessagePayload` with the key
+/// of the audio, the audio itself is an attachment of the message
+pub struct VoiceMessage {
+    pub text: String,
+    pub voice: VoiceNote,
+}
+
+impl VoiceMessage {
+    /// Creates a new `VoiceMessage`
+    pub fn new(text: &str, voice: VoiceNote) -> Self {
+        Self {
+            text: text.to_owned(),
+            voice,
+        }
+    }
+}
+
+impl MessageContent for VoiceMessage {
+    fn get_text(&self) -> Option<String> {
+        Some(self.text.to_owned())
+    }
+
+    fn get_voice(&self) -> Option<&VoiceNote> {
+        Some(&self.voice)
+    }
+}
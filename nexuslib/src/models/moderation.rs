@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use serde_repr::{Deserialize_repr, Serialize_repr};
+use strum_macros::Display;
+use uuid::Uuid;
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize_repr, Deserialize_repr, Display)]
+#[strum(serialize_all = "lowercase")]
+/// Kind of a restriction put on a `User` by the moderation
+///
+/// Can be represented as u8 index
+pub enum RestrictionKind {
+    Suspended,
+    Banned,
+}
+
+impl RestrictionKind {
+    /// Returns u8 index of the `RestrictionKind` entry
+    pub fn get_index(&self) -> u8 {
+        serde_json::to_string(self).unwrap().parse::<u8>().unwrap()
+    }
+
+    /// Returns `RestrictionKind` by its u8 index
+    pub fn from_index(index: u8) -> Option<Self> {
+        serde_json::from_str(&index.to_string()).ok()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Suspension or ban of a `User`
+///
+/// A restriction without `expires_at` is permanent
+pub struct UserRestriction {
+    pub user: Uuid,
+    pub kind: RestrictionKind,
+    pub reason: String,
+    pub issued_by: Uuid,
+    pub created_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+impl UserRestriction {
+    /// Checks whether the restriction is still in force at the timestamp
+    pub fn is_active(&self, timestamp: i64) -> bool {
+        self.expires_at
+            .map(|expires_at| expires_at > timestamp)
+            .unwrap_or(true)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Entry of the log of the admin actions
+pub struct AuditEntry {
+    pub uuid: Uuid,
+    pub actor: Uuid,
+    pub target: Option<Uuid>,
+    pub action: String,
+    pub details: String,
+    pub created_at: i64,
+}
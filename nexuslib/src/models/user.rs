@@ -1,3 +1,5 @@
+use core::fmt;
+
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -7,6 +9,7 @@ use crate::utils::{string_to_vec, vec_to_string};
 
 use self::role::Role;
 
+pub mod profile;
 pub mod role;
 pub mod session;
 pub mod settings;
@@ -19,8 +22,6 @@ pub struct User {
     pub password: String,
     pub role: Role,
     pub public_key: String,
-    // pub photo: Vec<u8>,
-    // pub settings: UserSettings,
     pub created_at: i64,
 }
 
@@ -56,9 +57,10 @@ impl User {
     }
 }
 
-impl ToString for User {
-    fn to_string(&self) -> String {
-        format!(
+impl fmt::Display for User {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
             "User\n\t- username: {0}\n\t- password: {1}\n\t- role: {2}",
             self.username, self.password, self.role,
         )
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Public profile of a `User`
+///
+/// `avatar` is the UUID of the image in the media storage
+pub struct UserProfile {
+    pub user: Uuid,
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+    pub avatar: Option<Uuid>,
+}
+
+impl UserProfile {
+    /// Creates an empty `UserProfile` for the user
+    pub fn new(user: Uuid) -> Self {
+        Self {
+            user,
+            display_name: None,
+            bio: None,
+            avatar: None,
+        }
+    }
+}
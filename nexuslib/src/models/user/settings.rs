@@ -1,8 +1,24 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 /// User's Settings
 pub struct UserSettings {
     pub language: String,
     pub theme: String,
 }
+
+impl UserSettings {
+    /// Creates new `UserSettings`
+    pub fn new(language: &str, theme: &str) -> Self {
+        Self {
+            language: language.to_owned(),
+            theme: theme.to_owned(),
+        }
+    }
+}
+
+impl Default for UserSettings {
+    fn default() -> Self {
+        Self::new("en", "system")
+    }
+}
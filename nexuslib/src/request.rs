@@ -2,12 +2,16 @@ use serde::{Deserialize, Serialize};
 
 use crate::models::command::Command;
 
+pub mod admin;
 pub mod auth;
 pub mod call;
 pub mod file;
 pub mod index_token;
 pub mod message;
+pub mod profile;
+pub mod room;
 pub mod sides;
+pub mod user;
 
 #[derive(Debug, Serialize, Deserialize)]
 /// `Request` is used for communication in a websockets session
@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models::user::role::Role;
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Data to suspend or ban a user
+///
+/// `expires_at` is a unix timestamp, `None` means permanently
+pub struct RestrictionRequest {
+    pub reason: String,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Data to promote or demote a user
+pub struct RoleChangeRequest {
+    pub role: Role,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Filters of the users list
+pub struct UserListQuery {
+    /// `admin`, `moderator` or `user`
+    pub role: Option<String>,
+    /// Prefix of the username
+    pub username: Option<String>,
+    /// Only users with (or without) an active restriction
+    pub restricted: Option<bool>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Filters of the audit log
+pub struct AuditQuery {
+    /// Month in the form of `YYYY-MM`, the current one by default
+    pub month: Option<String>,
+    pub limit: Option<i32>,
+}
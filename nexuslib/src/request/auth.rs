@@ -8,7 +8,7 @@ pub struct AuthRequest {
     pub meta: AuthRequestMeta,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 /// Meta-Data for authentication
 pub struct AuthRequestMeta {
     pub location: String,
@@ -22,3 +22,32 @@ pub struct AuthRequestMeta {
 pub struct LogoutRequest {
     pub token: String,
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Data to change the password of the authenticated user
+pub struct PasswordChangeRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Data to set a new password using a one-time reset token
+pub struct PasswordResetRequest {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// TOTP code to confirm or disable two-factor authentication
+pub struct TotpCodeRequest {
+    pub code: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Second step of the login when two-factor authentication is enabled
+///
+/// `code` is either a TOTP code or one of the recovery codes
+pub struct TwoFactorLoginRequest {
+    pub challenge: String,
+    pub code: String,
+}
@@ -1,7 +1,7 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
-use crate::models::call::CallContent;
+use crate::models::call::{candidate::IceParameters, relay::RelayTicket, CallContent};
 
 use super::index_token::IndexToken;
 use super::Command;
@@ -16,6 +16,12 @@ where
     pub call: T,
     pub index: IndexToken,
     pub created_at: i64,
+    /// Set by the server for the session that takes part in the accepted call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<RelayTicket>,
+    /// Candidates of the side that starts or accepts the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ice: Option<IceParameters>,
 }
 
 impl<T: CallContent> CallRequest<T> {
@@ -24,8 +30,30 @@ impl<T: CallContent> CallRequest<T> {
             call,
             index,
             created_at: Utc::now().timestamp(),
+            relay: None,
+            ice: None,
         }
     }
+
+    /// Attaches the relay ticket of the recipient
+    pub fn with_relay(mut self, relay: RelayTicket) -> Self {
+        self.relay = Some(relay);
+        self
+    }
+
+    /// Attaches the candidates of the side that sends the request
+    pub fn with_ice(mut self, ice: Option<IceParameters>) -> Self {
+        self.ice = ice;
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Page of the call history
+pub struct CallLogQuery {
+    /// Unix timestamp, only the calls started before it are listed
+    pub before: Option<i64>,
+    pub limit: Option<i32>,
 }
 
 impl<T: CallContent> RequestBody for CallRequest<T> {
@@ -1,25 +1,72 @@
 use chrono::Utc;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::models::file::media_file::MediaFile;
+use crate::{
+    errors::file::UploadError,
+    models::file::{chunk::FileChunk, media_file::MediaFile},
+};
 
+use super::index_token::FileToken;
 use super::Command;
 use super::RequestBody;
 
 #[derive(Debug, Serialize, Deserialize)]
-/// Contains a `File` of a particular type inside
+/// Step of a chunked upload
+///
+/// The server answers `Start` and every chunk with `Ack`,
+/// so after an interruption the upload goes on from its `next_chunk`
 pub struct FileRequest {
-    pub file: MediaFile,
+    pub index: FileToken,
+    /// Assigned by the server in the `Ack` of the first `Start`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub upload: Option<Uuid>,
+    /// The file that is uploaded, sent with `Start`, returned with `Committed`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<MediaFile>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chunk: Option<FileChunk>,
+    /// Set by the server in `Ack`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_chunk: Option<usize>,
+    /// Set by the server in `Rejected`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<UploadError>,
     pub created_at: i64,
 }
 
 impl FileRequest {
-    pub fn new(file: MediaFile) -> Self {
+    pub fn new(index: FileToken, upload: Option<Uuid>) -> Self {
         Self {
-            file,
+            index,
+            upload,
+            file: None,
+            chunk: None,
+            next_chunk: None,
+            error: None,
             created_at: Utc::now().timestamp(),
         }
     }
+
+    pub fn with_file(mut self, file: MediaFile) -> Self {
+        self.file = Some(file);
+        self
+    }
+
+    pub fn with_chunk(mut self, chunk: FileChunk) -> Self {
+        self.chunk = Some(chunk);
+        self
+    }
+
+    pub fn with_next_chunk(mut self, next_chunk: usize) -> Self {
+        self.next_chunk = Some(next_chunk);
+        self
+    }
+
+    pub fn with_error(mut self, error: UploadError) -> Self {
+        self.error = Some(error);
+        self
+    }
 }
 
 impl RequestBody for FileRequest {
@@ -27,3 +74,23 @@ impl RequestBody for FileRequest {
         Command::File
     }
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Asks for a presigned URL to upload the file directly to the storage
+///
+/// The object is verified against the file
+/// once the client reports that the upload is done
+pub struct PresignedUploadRequest {
+    pub file: MediaFile,
+    /// MIME type the object is uploaded with
+    pub content_type: String,
+}
+
+impl PresignedUploadRequest {
+    pub fn new(file: MediaFile, content_type: &str) -> Self {
+        Self {
+            file,
+            content_type: content_type.to_owned(),
+        }
+    }
+}
@@ -1,10 +1,69 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, PartialEq, PartialOrd, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 /// `CallToken` needed to mark the events
 pub enum IndexToken {
     Start,
     Accept,
     Accepted,
+    Reject,
+    Busy,
     End,
+    /// Sent by the server when the call was ringing for too long
+    Timeout,
+    /// Sent by the server when the user connects, for each call
+    /// that was missed while all of its sessions were offline
+    Missed,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+/// `FileToken` marks the steps of a chunked upload
+pub enum FileToken {
+    /// Starts the upload, or resumes it when the upload id is set
+    Start,
+    Chunk,
+    /// Asks the server to verify the whole file and to store it
+    Commit,
+    /// Sent by the server with the index of the chunk it expects next
+    Ack,
+    /// Sent by the server when the file is stored,
+    /// right after `Start` if the same content is already stored
+    Committed,
+    /// Sent by the server with the reason the file is not accepted,
+    /// the upload can not go on
+    Rejected,
+}
+
+impl FileToken {
+    /// Checks whether the token is sent only by the server
+    pub fn is_server_only(&self) -> bool {
+        matches!(
+            self,
+            FileToken::Ack | FileToken::Committed | FileToken::Rejected
+        )
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+/// `RoomToken` marks the events of a group call
+pub enum RoomToken {
+    /// Creates the room, the invited users get it as an invitation
+    Create,
+    Join,
+    Leave,
+    /// Sent by the server to the session that joined, with its relay ticket
+    Joined,
+    /// Sent by the server to the invited users when somebody joins or leaves
+    Participants,
+    /// Sent by the server to the participants when the active speaker changes
+    Speaker,
+    /// Sent by the server when the last participant has left
+    Closed,
+}
+
+impl RoomToken {
+    /// Checks whether the token is sent only by the server
+    pub fn is_server_only(&self) -> bool {
+        !matches!(self, RoomToken::Create | RoomToken::Join | RoomToken::Leave)
+    }
 }
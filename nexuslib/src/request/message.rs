@@ -18,6 +18,14 @@ impl<T: MessageContent> MessageRequest<T> {
     }
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Page of the conversation history
+pub struct MessageHistoryQuery {
+    /// Unix timestamp, only the messages sent before it are listed
+    pub before: Option<i64>,
+    pub limit: Option<i32>,
+}
+
 impl<T: MessageContent> RequestBody for MessageRequest<T> {
     fn op(&self) -> Command {
         Command::Message
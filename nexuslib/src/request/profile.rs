@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Partial update of the user's profile
+///
+/// Fields that are `None` stay unchanged,
+/// empty strings clear the value
+pub struct ProfileUpdateRequest {
+    pub display_name: Option<String>,
+    pub bio: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+/// Partial update of the user's settings
+///
+/// Fields that are `None` stay unchanged
+pub struct SettingsUpdateRequest {
+    pub language: Option<String>,
+    pub theme: Option<String>,
+}
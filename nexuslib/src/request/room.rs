@@ -0,0 +1,42 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::models::call::{relay::RelayTicket, room::CallRoom};
+
+use super::index_token::RoomToken;
+use super::Command;
+use super::RequestBody;
+
+#[derive(Debug, Serialize, Deserialize)]
+/// Contains a `CallRoom` with the event of the group call
+pub struct RoomRequest {
+    pub room: CallRoom,
+    pub index: RoomToken,
+    pub created_at: i64,
+    /// Set by the server for the session that joined the room
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub relay: Option<RelayTicket>,
+}
+
+impl RoomRequest {
+    pub fn new(room: CallRoom, index: RoomToken) -> Self {
+        Self {
+            room,
+            index,
+            created_at: Utc::now().timestamp(),
+            relay: None,
+        }
+    }
+
+    /// Attaches the relay ticket of the participant
+    pub fn with_relay(mut self, relay: RelayTicket) -> Self {
+        self.relay = Some(relay);
+        self
+    }
+}
+
+impl RequestBody for RoomRequest {
+    fn op(&self) -> Command {
+        Command::Room
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Data to change the username
+pub struct UsernameUpdateRequest {
+    pub username: String,
+}
+
+impl UsernameUpdateRequest {
+    /// Creates new `UsernameUpdateRequest`
+    pub fn new(username: &str) -> Self {
+        Self {
+            username: username.to_owned(),
+        }
+    }
+}
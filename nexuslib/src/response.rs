@@ -1,7 +1,9 @@
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
+pub mod admin;
 pub mod auth;
+pub mod file;
 
 #[derive(Debug, Serialize, Deserialize)]
 /// Send response from server to client using websockets
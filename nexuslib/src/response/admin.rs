@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::models::{moderation::UserRestriction, user::role::Role};
+
+#[derive(Serialize, Deserialize, Debug)]
+/// User as seen by the moderation
+pub struct UserSummary {
+    pub uuid: Uuid,
+    pub username: String,
+    pub role: Role,
+    pub created_at: i64,
+    pub restriction: Option<UserRestriction>,
+}
@@ -14,3 +14,52 @@ impl AuthResponse {
         Self { uuid, token }
     }
 }
+
+#[derive(Serialize, Deserialize, Debug)]
+/// One-time password reset token issued by an admin
+pub struct PasswordResetResponse {
+    pub user: Uuid,
+    pub token: String,
+    pub expires_at: i64,
+}
+
+impl PasswordResetResponse {
+    /// Creates new PasswordResetResponse instance
+    pub fn new(user: Uuid, token: String, expires_at: i64) -> Self {
+        Self {
+            user,
+            token,
+            expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Returned by the login when the second factor is required
+pub struct TwoFactorChallengeResponse {
+    pub challenge: String,
+    pub expires_at: i64,
+}
+
+impl TwoFactorChallengeResponse {
+    /// Creates new TwoFactorChallengeResponse instance
+    pub fn new(challenge: String, expires_at: i64) -> Self {
+        Self {
+            challenge,
+            expires_at,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// TOTP secret to be added to an authenticator app
+pub struct TotpEnrollResponse {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+/// One-time recovery codes, shown to the user only once
+pub struct RecoveryCodesResponse {
+    pub recovery_codes: Vec<String>,
+}
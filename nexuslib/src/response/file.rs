@@ -0,0 +1,22 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Serialize, Deserialize, Debug)]
+/// Short-lived URL that gives access to a single object of the storage
+pub struct PresignedUrl {
+    /// UUID of the media
+    pub uuid: Uuid,
+    pub url: String,
+    /// Unix timestamp after which the URL is rejected
+    pub expires_at: i64,
+}
+
+impl PresignedUrl {
+    pub fn new(uuid: Uuid, url: String, expires_at: i64) -> Self {
+        Self {
+            uuid,
+            url,
+            expires_at,
+        }
+    }
+}
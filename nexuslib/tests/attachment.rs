@@ -0,0 +1,66 @@
+use nexuslib::{
+    crypto::attachment::{
+        decrypt_attachment, encrypt_attachment, encrypted_len, AttachmentKey, ATTACHMENT_CHUNK_SIZE,
+    },
+    errors::file::AttachmentError,
+    models::message::payload::MessagePayload,
+};
+use uuid::Uuid;
+
+fn encrypt(key: &AttachmentKey, bytes: &[u8]) -> (Vec<u8>, String) {
+    let mut encrypted = Vec::new();
+    let digest = encrypt_attachment(key, bytes, &mut encrypted).unwrap();
+    (encrypted, digest)
+}
+
+#[test]
+fn decrypts_what_was_encrypted() {
+    let key = AttachmentKey::generate();
+    for len in [0, 1, ATTACHMENT_CHUNK_SIZE, ATTACHMENT_CHUNK_SIZE * 2 + 7] {
+        let bytes = (0..len).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+        let (encrypted, digest) = encrypt(&key, &bytes);
+        assert_eq!(encrypted.len(), encrypted_len(len));
+
+        let mut decrypted = Vec::new();
+        decrypt_attachment(&key, &digest, encrypted.as_slice(), &mut decrypted).unwrap();
+        assert_eq!(decrypted, bytes);
+    }
+}
+
+#[test]
+fn rejects_truncated_and_foreign_files() {
+    let key = AttachmentKey::generate();
+    let bytes = vec![7u8; ATTACHMENT_CHUNK_SIZE * 2];
+    let (encrypted, digest) = encrypt(&key, &bytes);
+
+    // a file cut at a chunk boundary still fails on the flag of the last chunk
+    let truncated = &encrypted[..encrypted.len() / 2];
+    let result = decrypt_attachment(&key, &digest, truncated, Vec::new());
+    assert_eq!(result, Err(AttachmentError::Authentication));
+
+    let other = AttachmentKey::generate();
+    let result = decrypt_attachment(&other, &digest, encrypted.as_slice(), Vec::new());
+    assert_eq!(result, Err(AttachmentError::Authentication));
+
+    let (_, other_digest) = encrypt(&key, b"another file");
+    let result = decrypt_attachment(&key, &other_digest, encrypted.as_slice(), Vec::new());
+    assert_eq!(result, Err(AttachmentError::Digest));
+}
+
+#[test]
+fn payload_carries_the_key() {
+    let key = AttachmentKey::generate();
+    let media = Uuid::new_v4();
+    let payload =
+        MessagePayload::new("report.pdf").with_attachment(media, "report.pdf", &key, "00");
+
+    let parsed = MessagePayload::from_bytes(&payload.to_bytes());
+    assert_eq!(parsed.text, "report.pdf");
+    let secret = parsed.secret_of(&media).unwrap();
+    assert!(secret.get_key() == Some(key));
+
+    // messages of older clients are plain text
+    let plain = MessagePayload::from_bytes(b"hello");
+    assert_eq!(plain.text, "hello");
+    assert!(plain.attachments.is_empty());
+}
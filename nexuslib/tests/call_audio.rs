@@ -0,0 +1,210 @@
+use std::{net::UdpSocket, path::Path, thread, time::Duration};
+
+use nexuslib::media::{
+    audio::{AudioFormat, AudioSink, AudioSource, ToneSource, WavSink, WavSource},
+    codec::Codec,
+    jitter::JitterConfig,
+    pipeline::AudioPipeline,
+    srtp::SrtpMasterKey,
+};
+use uuid::Uuid;
+
+mod common;
+
+use common::{LinkConditions, LinkReport, LossyLink};
+
+/// One second of 20 ms frames
+const FRAMES: usize = 50;
+/// Uncompressed 48 kHz frames do not fit into a datagram
+const L16_FORMAT: AudioFormat = AudioFormat {
+    sample_rate: 16_000,
+    channels: 1,
+};
+
+fn write_tone(path: &Path, format: AudioFormat) {
+    let mut tone = ToneSource::new(format, 440.0, 8000);
+    let mut sink = WavSink::create(path, format).unwrap();
+    let mut frame = vec![0; format.frame_len(20)];
+    for _ in 0..FRAMES {
+        tone.read_frame(&mut frame).unwrap();
+        sink.write_frame(&frame).unwrap();
+    }
+    sink.finish().unwrap();
+}
+
+fn read_wav(path: &Path) -> Vec<i16> {
+    hound::WavReader::open(path)
+        .unwrap()
+        .samples::<i16>()
+        .map(|sample| sample.unwrap())
+        .collect()
+}
+
+/// Feeds the WAV file to one pipeline and records the other side
+fn transmit(
+    codec: Codec,
+    format: AudioFormat,
+    conditions: LinkConditions,
+    input: &Path,
+    output: &Path,
+) -> LinkReport {
+    let master = SrtpMasterKey::generate();
+    // the whole stream is buffered before the playout
+    let config = JitterConfig {
+        max_depth: FRAMES,
+        ..JitterConfig::default()
+    };
+    let mut sender = AudioPipeline::new(&master, codec, format, config).unwrap();
+    let mut receiver = AudioPipeline::new(&master, codec, format, config).unwrap();
+
+    let receiver_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver_socket
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let link = LossyLink::start(receiver_socket.local_addr().unwrap(), conditions);
+
+    let output = output.to_path_buf();
+    let receiving = thread::spawn(move || {
+        let mut buf = [0u8; 1500];
+        while let Ok(len) = receiver_socket.recv(&mut buf) {
+            receiver.session.receive(&buf[..len], 0).unwrap();
+        }
+
+        let mut sink = WavSink::create(output, format).unwrap();
+        while receiver.play(&mut sink).unwrap() {}
+        sink.finish().unwrap();
+    });
+
+    let mut source = WavSource::open(input).unwrap();
+    let sender_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    while let Some(datagram) = sender.capture(&mut source).unwrap() {
+        sender_socket.send_to(&datagram, link.addr).unwrap();
+        thread::sleep(Duration::from_micros(200));
+    }
+
+    receiving.join().unwrap();
+    link.finish()
+}
+
+fn temp_path(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("nexus-{name}-{}.wav", Uuid::new_v4()))
+}
+
+#[test]
+fn records_the_same_audio_over_l16() {
+    let (input, output) = (temp_path("input"), temp_path("output"));
+    write_tone(&input, L16_FORMAT);
+
+    let conditions = LinkConditions {
+        loss: 0.0,
+        reorder: 0.1,
+        duplicate: 0.0,
+        seed: 7,
+    };
+    transmit(Codec::L16, L16_FORMAT, conditions, &input, &output);
+
+    assert_eq!(read_wav(&output), read_wav(&input));
+    std::fs::remove_file(input).unwrap();
+    std::fs::remove_file(output).unwrap();
+}
+
+#[test]
+fn conceals_lost_frames_over_l16() {
+    let (input, output) = (temp_path("input"), temp_path("output"));
+    write_tone(&input, L16_FORMAT);
+
+    let conditions = LinkConditions {
+        loss: 0.1,
+        reorder: 0.0,
+        duplicate: 0.0,
+        seed: 3,
+    };
+    let report = transmit(Codec::L16, L16_FORMAT, conditions, &input, &output);
+    assert!(report.dropped > 0);
+
+    let frame_len = L16_FORMAT.frame_len(20);
+    let (sent, recorded) = (read_wav(&input), read_wav(&output));
+    let sent: Vec<&[i16]> = sent.chunks(frame_len).collect();
+    let recorded: Vec<&[i16]> = recorded.chunks(frame_len).collect();
+
+    // the recording keeps the timing: a lost frame is replaced, not skipped
+    let offset = sent.iter().position(|frame| *frame == recorded[0]).unwrap();
+    assert!(sent.len() - recorded.len() <= report.dropped as usize);
+    let concealed = recorded
+        .iter()
+        .zip(&sent[offset..])
+        .filter(|(recorded, sent)| recorded != sent)
+        .count();
+    assert!(concealed > 0 && concealed <= report.dropped as usize);
+
+    std::fs::remove_file(input).unwrap();
+    std::fs::remove_file(output).unwrap();
+}
+
+#[cfg(feature = "opus")]
+#[test]
+fn opus_frames_round_trip() {
+    let format = AudioFormat::VOICE;
+    let codec = Codec::Opus { bitrate: 32_000 };
+    let mut encoder = codec.encoder(format).unwrap();
+    let mut decoder = codec.decoder(format).unwrap();
+
+    let mut tone = ToneSource::new(format, 440.0, 8000);
+    let mut frame = vec![0; format.frame_len(20)];
+    let (mut sent, mut decoded) = (Vec::new(), Vec::new());
+    for _ in 0..FRAMES {
+        tone.read_frame(&mut frame).unwrap();
+        let payload = encoder.encode(&frame).unwrap();
+        assert!(payload.len() < frame.len() * 2);
+
+        let pcm = decoder.decode(&payload).unwrap();
+        assert_eq!(pcm.len(), frame.len());
+        sent.extend_from_slice(&frame);
+        decoded.extend(pcm);
+    }
+    assert_eq!(decoder.conceal(frame.len()).unwrap().len(), frame.len());
+
+    // the first frames hold the delay of the codec
+    let rms = |samples: &[i16]| {
+        let sum: f64 = samples.iter().map(|sample| (*sample as f64).powi(2)).sum();
+        (sum / samples.len() as f64).sqrt()
+    };
+    let half = sent.len() / 2;
+    assert!((rms(&decoded[half..]) / rms(&sent[half..]) - 1.0).abs() < 0.2);
+    assert!(decoder.decode(&[0xff; 3]).is_err());
+}
+
+#[cfg(not(feature = "opus"))]
+#[test]
+fn opus_needs_the_feature() {
+    let codec = Codec::Opus { bitrate: 32_000 };
+    assert!(codec.encoder(AudioFormat::VOICE).is_err());
+    assert!(codec.decoder(AudioFormat::VOICE).is_err());
+}
+
+#[cfg(feature = "opus")]
+#[test]
+fn records_the_audio_over_opus() {
+    let (input, output) = (temp_path("input"), temp_path("output"));
+    write_tone(&input, AudioFormat::VOICE);
+
+    let conditions = LinkConditions {
+        loss: 0.0,
+        reorder: 0.0,
+        duplicate: 0.0,
+        seed: 1,
+    };
+    let codec = Codec::Opus { bitrate: 32_000 };
+    transmit(codec, AudioFormat::VOICE, conditions, &input, &output);
+
+    let rms = |samples: &[i16]| {
+        let sum: f64 = samples.iter().map(|sample| (*sample as f64).powi(2)).sum();
+        (sum / samples.len() as f64).sqrt()
+    };
+    let (sent, recorded) = (read_wav(&input), read_wav(&output));
+    assert_eq!(sent.len(), recorded.len());
+    assert!((rms(&recorded) / rms(&sent) - 1.0).abs() < 0.2);
+
+    std::fs::remove_file(input).unwrap();
+    std::fs::remove_file(output).unwrap();
+}
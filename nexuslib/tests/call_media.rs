@@ -0,0 +1,272 @@
+use std::{
+    net::UdpSocket,
+    thread,
+    time::{Duration, Instant},
+};
+
+use nexuslib::{
+    errors::media::MediaError,
+    media::{
+        concealment::Concealer,
+        jitter::{Arrival, JitterBuffer, JitterConfig, Playout},
+        metrics::CallMetrics,
+        rtcp::RtcpPacket,
+        rtp::{RtpHeader, RtpPacket, AUDIO_CLOCK_RATE, AUDIO_PAYLOAD_TYPE},
+        session::{Incoming, MediaSession},
+        srtp::{srtcp_plaintext, SrtpMasterKey},
+    },
+};
+use uuid::Uuid;
+
+mod common;
+
+use common::{LinkConditions, LossyLink};
+
+const FRAMES: u32 = 400;
+/// Samples in a 20 ms frame at 48 kHz
+const FRAME_SAMPLES: u32 = 960;
+
+fn packet(seq: u16, frame: u32) -> RtpPacket {
+    let header = RtpHeader::new(AUDIO_PAYLOAD_TYPE, seq, frame * FRAME_SAMPLES, 1);
+    RtpPacket::new(header, frame.to_be_bytes().to_vec())
+}
+
+/// Sends the frames through the lossy link and receives them on the other side
+///
+/// Returns the sessions of the sender and the receiver with
+/// the numbers of the dropped and the reordered datagrams
+fn stream_over_link(conditions: LinkConditions) -> (MediaSession, MediaSession, u64, u64) {
+    let master = SrtpMasterKey::generate();
+    // the whole stream is buffered before the playout
+    let config = JitterConfig {
+        max_depth: FRAMES as usize,
+        ..JitterConfig::default()
+    };
+    let mut sender = MediaSession::new(&master, AUDIO_PAYLOAD_TYPE, AUDIO_CLOCK_RATE, config);
+    let mut receiver = MediaSession::new(&master, AUDIO_PAYLOAD_TYPE, AUDIO_CLOCK_RATE, config);
+
+    let receiver_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    receiver_socket
+        .set_read_timeout(Some(Duration::from_millis(300)))
+        .unwrap();
+    let link = LossyLink::start(receiver_socket.local_addr().unwrap(), conditions);
+
+    // the receiver reads while the frames are sent, so the socket buffer never overflows
+    let receiving = thread::spawn(move || {
+        let started = Instant::now();
+        let mut buf = [0u8; 1500];
+        let mut replays = 0;
+        while let Ok(len) = receiver_socket.recv(&mut buf) {
+            let arrival_ms = started.elapsed().as_millis() as u64;
+            match receiver.receive(&buf[..len], arrival_ms) {
+                Ok(Incoming::Media(_, arrival)) => assert_eq!(arrival, Arrival::Buffered),
+                Err(MediaError::Replay) => replays += 1,
+                other => panic!("unexpected datagram: {other:?}"),
+            }
+        }
+        (receiver, replays)
+    });
+
+    let sender_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for frame in 0..FRAMES {
+        let datagram = sender.send(frame.to_be_bytes().to_vec());
+        sender_socket.send_to(&datagram, link.addr).unwrap();
+        thread::sleep(Duration::from_micros(200));
+    }
+
+    let (receiver, replays) = receiving.join().unwrap();
+    let report = link.finish();
+    assert_eq!(report.duplicated, replays);
+    (sender, receiver, report.dropped, report.reordered)
+}
+
+#[test]
+fn reorders_and_detects_losses_over_lossy_link() {
+    let conditions = LinkConditions {
+        loss: 0.05,
+        reorder: 0.1,
+        duplicate: 0.02,
+        seed: 0x5eed,
+    };
+    let (_, mut receiver, dropped, reordered) = stream_over_link(conditions);
+    assert!(dropped > 0 && reordered > 0);
+
+    let mut playout = Vec::new();
+    loop {
+        let frames = receiver.playout();
+        if frames.is_empty() {
+            break;
+        }
+        playout.extend(frames.into_iter().map(|(_, frame)| frame));
+    }
+
+    // every frame between the first and the last one is either played or lost, in order
+    let mut played = 0;
+    let mut previous: Option<u32> = None;
+    for frame in &playout {
+        let counter = match frame {
+            Playout::Frame(packet) => {
+                played += 1;
+                u32::from_be_bytes(packet.payload[..4].try_into().unwrap())
+            }
+            Playout::Lost(_) => previous.unwrap() + 1,
+        };
+        if let Some(previous) = previous {
+            assert_eq!(counter, previous + 1);
+        }
+        previous = Some(counter);
+    }
+
+    let (_, source) = receiver.sources().next().unwrap();
+    let stats = source.jitter.stats();
+    assert_eq!(played, FRAMES as u64 - dropped);
+    assert_eq!(stats.played, played);
+    assert!(stats.lost > 0 && stats.lost <= dropped);
+    assert_eq!(stats.late, 0);
+}
+
+#[test]
+fn reports_losses_to_the_sender() {
+    let conditions = LinkConditions {
+        loss: 0.1,
+        reorder: 0.0,
+        duplicate: 0.0,
+        seed: 42,
+    };
+    let (mut sender, mut receiver, dropped, _) = stream_over_link(conditions);
+
+    let report = receiver.report(0);
+    let reports = match sender.receive(&report, 0).unwrap() {
+        Incoming::Reports(reports) => reports,
+        other => panic!("expected reports, got {other:?}"),
+    };
+
+    assert_eq!(reports.len(), 1);
+    assert_eq!(reports[0].ssrc, sender.ssrc());
+    assert!(reports[0].cumulative_lost > 0);
+    assert!(reports[0].cumulative_lost as u64 <= dropped);
+}
+
+/// Passes the RTCP of the participant through the metrics like the relay does
+fn relay_reports(metrics: &mut CallMetrics, reporter: Uuid, datagram: &[u8], now_ms: u64) {
+    let rtcp = srtcp_plaintext(datagram).unwrap();
+    for packet in RtcpPacket::decode_compound(rtcp).unwrap() {
+        if let RtcpPacket::SenderReport { ssrc, info, .. } = &packet {
+            metrics.record_sender_report(*ssrc, info.ntp_timestamp, now_ms);
+        }
+        metrics.record_reports(reporter, packet.reports(), now_ms);
+    }
+}
+
+#[test]
+fn measures_call_quality_on_the_relay() {
+    let master = SrtpMasterKey::generate();
+    let config = JitterConfig::default();
+    let mut caller = MediaSession::new(&master, AUDIO_PAYLOAD_TYPE, AUDIO_CLOCK_RATE, config);
+    let mut callee = MediaSession::new(&master, AUDIO_PAYLOAD_TYPE, AUDIO_CLOCK_RATE, config);
+    let (caller_uuid, callee_uuid) = (Uuid::new_v4(), Uuid::new_v4());
+    let mut metrics = CallMetrics::new();
+
+    // one second of audio, every tenth packet is lost after the relay
+    for frame in 0..50u64 {
+        let now_ms = frame * 20;
+        let datagram = caller.send(vec![0u8; 80]);
+        metrics.record_in(caller_uuid, datagram.len(), now_ms);
+        if frame % 10 != 5 {
+            metrics.record_out(callee_uuid, datagram.len());
+            callee.receive(&datagram, now_ms + 5).unwrap();
+        }
+    }
+
+    // the SR takes 10 ms from the relay to the callee, which holds it
+    // for 40 ms, and its report takes another 10 ms back to the relay
+    let sender_report = caller.report(1000);
+    relay_reports(&mut metrics, caller_uuid, &sender_report, 1000);
+    callee.receive(&sender_report, 1010).unwrap();
+    let receiver_report = callee.report(1050);
+    relay_reports(&mut metrics, callee_uuid, &receiver_report, 1060);
+
+    let stats = metrics.participants();
+    let caller_stats = stats.iter().find(|s| s.user == caller_uuid).unwrap();
+    let callee_stats = stats.iter().find(|s| s.user == callee_uuid).unwrap();
+
+    assert_eq!(caller_stats.packets_in, 50);
+    assert_eq!(callee_stats.packets_out, 45);
+    assert_eq!(caller_stats.bitrate, caller_stats.bytes_in * 8 * 1000 / 980);
+    assert_eq!(callee_stats.rtt_ms, Some(20));
+    assert_eq!(caller_stats.rtt_ms, None);
+    assert!((callee_stats.loss_percent - 10.0).abs() < 1.0);
+}
+
+#[test]
+fn adapts_depth_to_jitter() {
+    let mut buffer = JitterBuffer::new(JitterConfig::default(), AUDIO_CLOCK_RATE);
+
+    // arrivals alternating 40 ms early and late
+    for frame in 0..100u32 {
+        let offset = if frame % 2 == 0 { 0 } else { 80 };
+        buffer.push(packet(frame as u16, frame), (frame * 20 + offset) as u64);
+    }
+    let jittery = buffer.target_depth();
+    assert!(jittery > JitterConfig::default().min_depth);
+
+    // steady arrivals lower the depth again
+    for frame in 100..400u32 {
+        buffer.push(packet(frame as u16, frame), (frame * 20) as u64);
+        while buffer.depth() > 10 {
+            buffer.pop();
+        }
+    }
+    assert!(buffer.target_depth() < jittery);
+}
+
+#[test]
+fn late_packets_deepen_the_buffer() {
+    let mut buffer = JitterBuffer::new(JitterConfig::default(), AUDIO_CLOCK_RATE);
+
+    for frame in [0u32, 1, 3, 4, 5] {
+        buffer.push(packet(frame as u16, frame), (frame * 20) as u64);
+    }
+    let mut lost = Vec::new();
+    while let Some(playout) = buffer.pop() {
+        if let Playout::Lost(seq) = playout {
+            lost.push(seq);
+        }
+    }
+    assert_eq!(lost, vec![2]);
+
+    let depth = buffer.target_depth();
+    assert_eq!(buffer.push(packet(2, 2), 200), Arrival::Late);
+    assert!(buffer.target_depth() > depth);
+}
+
+#[test]
+fn plays_across_sequence_wraparound() {
+    let mut buffer = JitterBuffer::new(JitterConfig::default(), AUDIO_CLOCK_RATE);
+
+    // 65534, 65535, 0 and 1 arriving reordered
+    for frame in [1u32, 0, 3, 2] {
+        buffer.push(packet(65534u16.wrapping_add(frame as u16), frame), 0);
+    }
+    let mut played = Vec::new();
+    while let Some(Playout::Frame(packet)) = buffer.pop() {
+        played.push(packet.header.seq);
+    }
+    assert_eq!(played, vec![65534, 65535, 0, 1]);
+}
+
+#[test]
+fn conceals_with_fading_then_silence() {
+    let mut concealer = Concealer::new();
+    concealer.received(&[1000; 4]);
+
+    assert_eq!(concealer.conceal(4), vec![500; 4]);
+    assert_eq!(concealer.conceal(4), vec![250; 4]);
+    for _ in 0..3 {
+        concealer.conceal(4);
+    }
+    assert_eq!(concealer.conceal(4), vec![0; 4]);
+
+    concealer.received(&[1000; 4]);
+    assert_eq!(concealer.consecutive(), 0);
+}
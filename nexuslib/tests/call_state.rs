@@ -0,0 +1,129 @@
+use nexuslib::{
+    models::call::state::{CallSide, CallState},
+    request::index_token::IndexToken,
+};
+
+const STATES: [CallState; 7] = [
+    CallState::Ringing,
+    CallState::Accepted,
+    CallState::Rejected,
+    CallState::Busy,
+    CallState::Missed,
+    CallState::Ended,
+    CallState::TimedOut,
+];
+
+const TOKENS: [IndexToken; 8] = [
+    IndexToken::Start,
+    IndexToken::Accept,
+    IndexToken::Accepted,
+    IndexToken::Reject,
+    IndexToken::Busy,
+    IndexToken::End,
+    IndexToken::Timeout,
+    IndexToken::Missed,
+];
+
+const SIDES: [CallSide; 2] = [CallSide::Caller, CallSide::Callee];
+
+/// Every transition the state machine allows
+const ALLOWED: [(CallState, IndexToken, CallSide, CallState); 9] = [
+    (
+        CallState::Ringing,
+        IndexToken::Accept,
+        CallSide::Callee,
+        CallState::Accepted,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::Reject,
+        CallSide::Callee,
+        CallState::Rejected,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::Busy,
+        CallSide::Callee,
+        CallState::Busy,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::End,
+        CallSide::Caller,
+        CallState::Missed,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::End,
+        CallSide::Callee,
+        CallState::Rejected,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::Timeout,
+        CallSide::Caller,
+        CallState::TimedOut,
+    ),
+    (
+        CallState::Ringing,
+        IndexToken::Timeout,
+        CallSide::Callee,
+        CallState::TimedOut,
+    ),
+    (
+        CallState::Accepted,
+        IndexToken::End,
+        CallSide::Caller,
+        CallState::Ended,
+    ),
+    (
+        CallState::Accepted,
+        IndexToken::End,
+        CallSide::Callee,
+        CallState::Ended,
+    ),
+];
+
+#[test]
+fn allows_the_listed_transitions() {
+    for (state, token, side, next) in ALLOWED {
+        assert_eq!(
+            state.next(token, side),
+            Some(next),
+            "{state} {token:?} {side:?}"
+        );
+    }
+}
+
+#[test]
+fn refuses_every_other_transition() {
+    for state in STATES {
+        for token in TOKENS {
+            for side in SIDES {
+                let allowed = ALLOWED
+                    .iter()
+                    .any(|(s, t, c, _)| (*s, *t, *c) == (state, token, side));
+                if !allowed {
+                    assert_eq!(state.next(token, side), None, "{state} {token:?} {side:?}");
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn terminal_states_have_no_transitions() {
+    for state in STATES.into_iter().filter(CallState::is_terminal) {
+        assert!(TOKENS
+            .iter()
+            .all(|token| SIDES.iter().all(|side| state.next(*token, *side).is_none())));
+    }
+}
+
+#[test]
+fn round_trips_the_index() {
+    for state in STATES {
+        assert_eq!(CallState::from_index(state.get_index()), Some(state));
+    }
+    assert_eq!(CallState::from_index(STATES.len() as u8), None);
+}
@@ -0,0 +1,137 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+/// How the link disturbs the datagrams, the rates are in 0..1
+#[derive(Debug, Clone, Copy)]
+pub struct LinkConditions {
+    pub loss: f64,
+    /// The datagram is held back and sent after the next one
+    pub reorder: f64,
+    pub duplicate: f64,
+    /// Seed of the generator, the same seed disturbs the same datagrams
+    pub seed: u64,
+}
+
+/// What the link did to the datagrams
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinkReport {
+    pub forwarded: u64,
+    pub dropped: u64,
+    pub reordered: u64,
+    pub duplicated: u64,
+}
+
+/// Lossy network between two local UDP sockets
+///
+/// Datagrams sent to `addr` are forwarded to the target with losses,
+/// reordering and duplicates decided by a seeded generator
+pub struct LossyLink {
+    pub addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    report: Arc<Mutex<LinkReport>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl LossyLink {
+    pub fn start(target: SocketAddr, conditions: LinkConditions) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(Duration::from_millis(10)))
+            .unwrap();
+        let addr = socket.local_addr().unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let report = Arc::new(Mutex::new(LinkReport::default()));
+
+        let handle = {
+            let (stop, report) = (stop.clone(), report.clone());
+            thread::spawn(move || forward(socket, target, conditions, stop, report))
+        };
+
+        Self {
+            addr,
+            stop,
+            report,
+            handle: Some(handle),
+        }
+    }
+
+    /// Stops the link and returns what it did
+    pub fn finish(mut self) -> LinkReport {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            handle.join().unwrap();
+        }
+        let report = *self.report.lock().unwrap();
+        report
+    }
+}
+
+fn forward(
+    socket: UdpSocket,
+    target: SocketAddr,
+    conditions: LinkConditions,
+    stop: Arc<AtomicBool>,
+    report: Arc<Mutex<LinkReport>>,
+) {
+    let mut rng = XorShift(conditions.seed.max(1));
+    let mut held: Option<Vec<u8>> = None;
+    let mut buf = [0u8; 1500];
+
+    while !stop.load(Ordering::Relaxed) {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(_) => {
+                // the link went idle => release the held datagram
+                if let Some(datagram) = held.take() {
+                    socket.send_to(&datagram, target).unwrap();
+                    report.lock().unwrap().forwarded += 1;
+                }
+                continue;
+            }
+        };
+        let datagram = &buf[..len];
+        let mut report = report.lock().unwrap();
+
+        let roll = rng.next_f64();
+        if roll < conditions.loss {
+            report.dropped += 1;
+            continue;
+        }
+        if roll < conditions.loss + conditions.reorder && held.is_none() {
+            report.reordered += 1;
+            held = Some(datagram.to_vec());
+            continue;
+        }
+
+        socket.send_to(datagram, target).unwrap();
+        report.forwarded += 1;
+        if rng.next_f64() < conditions.duplicate {
+            socket.send_to(datagram, target).unwrap();
+            report.duplicated += 1;
+        }
+        if let Some(datagram) = held.take() {
+            socket.send_to(&datagram, target).unwrap();
+            report.forwarded += 1;
+        }
+    }
+}
+
+/// Deterministic generator of the link decisions
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
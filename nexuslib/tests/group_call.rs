@@ -0,0 +1,113 @@
+use nexuslib::media::{
+    audio::{AudioFormat, ToneSource},
+    codec::Codec,
+    jitter::JitterConfig,
+    pipeline::AudioPipeline,
+    rtp::{AudioLevel, RtpHeader},
+    session::Incoming,
+    speaker::SpeakerDetector,
+    srtp::SrtpMasterKey,
+};
+
+const FORMAT: AudioFormat = AudioFormat {
+    sample_rate: 16_000,
+    channels: 1,
+};
+
+fn pipeline(master: &SrtpMasterKey) -> AudioPipeline {
+    AudioPipeline::new(master, Codec::L16, FORMAT, JitterConfig::default()).unwrap()
+}
+
+#[test]
+fn forwards_every_participant_to_the_others() {
+    let master = SrtpMasterKey::generate();
+    let mut participants = [pipeline(&master), pipeline(&master), pipeline(&master)];
+    let mut sources = [
+        ToneSource::new(FORMAT, 440.0, 8000),
+        ToneSource::new(FORMAT, 660.0, 50),
+        ToneSource::new(FORMAT, 880.0, 0),
+    ];
+
+    let mut levels = Vec::new();
+    for _ in 0..10 {
+        for sender in 0..participants.len() {
+            let datagram = participants[sender]
+                .capture(&mut sources[sender])
+                .unwrap()
+                .unwrap();
+            // the relay reads the level from the header that is not encrypted
+            let (header, _) = RtpHeader::decode(&datagram).unwrap();
+            levels.push((sender, header.audio_level().unwrap()));
+
+            // as the relay does: the datagram goes to all the others as it is
+            for (receiver, participant) in participants.iter_mut().enumerate() {
+                if receiver != sender {
+                    let incoming = participant.session.receive(&datagram, 0).unwrap();
+                    assert!(matches!(incoming, Incoming::Media(..)));
+                }
+            }
+        }
+    }
+
+    for participant in &participants {
+        assert_eq!(participant.session.sources().count(), 2);
+    }
+    for (sender, level) in levels {
+        match sender {
+            0 => assert!(level.voice && level.level < 20),
+            1 => assert!(!level.voice && level.level > 50),
+            _ => assert_eq!(level, AudioLevel::measure(&[0; 16])),
+        }
+    }
+}
+
+#[test]
+fn switches_to_the_louder_speaker() {
+    let loud = AudioLevel {
+        voice: true,
+        level: 10,
+    };
+    let silent = AudioLevel {
+        voice: false,
+        level: 127,
+    };
+    let mut detector = SpeakerDetector::new();
+
+    // single loud frames are not speech
+    assert_eq!(detector.update(1, loud, 0), None);
+
+    let mut now_ms = 0;
+    let mut speakers = Vec::new();
+    for _ in 0..50 {
+        now_ms += 20;
+        speakers.extend(detector.update(1, loud, now_ms));
+        speakers.extend(detector.update(2, silent, now_ms));
+    }
+    assert_eq!(speakers, vec![1]);
+
+    // the other participant takes over only after it stays the loudest for a while
+    let mut switched_at = None;
+    for _ in 0..50 {
+        now_ms += 20;
+        let switched = [
+            detector.update(1, silent, now_ms),
+            detector.update(2, loud, now_ms),
+        ];
+        if switched.contains(&Some(2)) {
+            switched_at.get_or_insert(now_ms);
+        }
+    }
+    assert!(switched_at.unwrap() >= 1000 + 300);
+    assert_eq!(detector.speaker(), Some(2));
+
+    // everybody is silent => the last speaker is kept
+    for _ in 0..50 {
+        now_ms += 20;
+        assert_eq!(detector.update(1, silent, now_ms), None);
+        assert_eq!(detector.update(2, silent, now_ms), None);
+    }
+    assert_eq!(detector.speaker(), Some(2));
+
+    detector.remove(&2);
+    assert_eq!(detector.speaker(), None);
+}
@@ -0,0 +1,162 @@
+use std::{
+    net::{SocketAddr, UdpSocket},
+    thread,
+    time::Duration,
+};
+
+use nexuslib::{
+    media::{
+        ice::{IceAgent, IceState, CHECK_INTERVAL_MS},
+        stun::{is_stun, StunAttribute, StunMessage, StunType},
+    },
+    models::call::candidate::{CandidateKind, IceCandidate},
+};
+
+/// Sample request of RFC 5769 with the password `VOkJxbRl1RmTxUk/WvJxBt`
+const SAMPLE_REQUEST: [u8; 108] = [
+    0x00, 0x01, 0x00, 0x58, 0x21, 0x12, 0xa4, 0x42, 0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86,
+    0xfa, 0x87, 0xdf, 0xae, 0x80, 0x22, 0x00, 0x10, 0x53, 0x54, 0x55, 0x4e, 0x20, 0x74, 0x65, 0x73,
+    0x74, 0x20, 0x63, 0x6c, 0x69, 0x65, 0x6e, 0x74, 0x00, 0x24, 0x00, 0x04, 0x6e, 0x00, 0x01, 0xff,
+    0x80, 0x29, 0x00, 0x08, 0x93, 0x2f, 0xf9, 0xb1, 0x51, 0x26, 0x3b, 0x36, 0x00, 0x06, 0x00, 0x09,
+    0x65, 0x76, 0x74, 0x6a, 0x3a, 0x68, 0x36, 0x76, 0x59, 0x20, 0x20, 0x20, 0x00, 0x08, 0x00, 0x14,
+    0x9a, 0xea, 0xa7, 0x0c, 0xbf, 0xd8, 0xcb, 0x56, 0x78, 0x1e, 0xf2, 0xb5, 0xb2, 0xd3, 0xf2, 0x49,
+    0xc1, 0xb5, 0x71, 0xa2, 0x80, 0x28, 0x00, 0x04, 0xe5, 0x7a, 0x3b, 0xcf,
+];
+
+/// Answers the binding requests like the server does
+fn stun_server() -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let addr = socket.local_addr().unwrap();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1500];
+        while let Ok((len, from)) = socket.recv_from(&mut buf) {
+            if let Ok(request) = StunMessage::decode(&buf[..len]) {
+                let response = StunMessage::binding_success(&request, from);
+                socket.send_to(&response.encode(None), from).unwrap();
+            }
+        }
+    });
+    addr
+}
+
+/// Gathers the host and the server reflexive candidates of the socket
+fn gather(socket: &UdpSocket, server: SocketAddr) -> Vec<IceCandidate> {
+    let request = StunMessage::binding_request();
+    socket.send_to(&request.encode(None), server).unwrap();
+
+    let mut buf = [0u8; 1500];
+    let (len, _) = socket.recv_from(&mut buf).unwrap();
+    let response = StunMessage::decode(&buf[..len]).unwrap();
+    assert_eq!(response.transaction, request.transaction);
+
+    vec![
+        IceCandidate::new(CandidateKind::Host, socket.local_addr().unwrap()),
+        IceCandidate::new(
+            CandidateKind::ServerReflexive,
+            response.mapped_address().unwrap(),
+        ),
+        IceCandidate::new(CandidateKind::Relay, server),
+    ]
+}
+
+/// Sends the checks and handles the received datagrams of both agents
+fn run_checks(agents: &mut [(IceAgent, UdpSocket); 2], rounds: u64) {
+    let mut buf = [0u8; 1500];
+    for round in 0..rounds {
+        let now_ms = round * CHECK_INTERVAL_MS;
+        for (agent, socket) in agents.iter_mut() {
+            for (target, check) in agent.poll(now_ms) {
+                socket.send_to(&check, target).unwrap();
+            }
+        }
+        for (agent, socket) in agents.iter_mut() {
+            while let Ok((len, from)) = socket.recv_from(&mut buf) {
+                assert!(is_stun(&buf[..len]));
+                if let Some(response) = agent.handle(&buf[..len], from) {
+                    socket.send_to(&response, from).unwrap();
+                }
+            }
+        }
+    }
+}
+
+fn socket() -> UdpSocket {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_millis(20)))
+        .unwrap();
+    socket
+}
+
+#[test]
+fn decodes_the_rfc_5769_request() {
+    let message = StunMessage::decode(&SAMPLE_REQUEST).unwrap();
+
+    assert_eq!(message.kind, StunType::BindingRequest);
+    assert_eq!(message.username(), Some("evtj:h6vY"));
+    assert!(message
+        .attributes
+        .contains(&StunAttribute::Priority(0x6e0001ff)));
+    assert!(StunMessage::verify_integrity(
+        &SAMPLE_REQUEST,
+        b"VOkJxbRl1RmTxUk/WvJxBt"
+    ));
+    assert!(!StunMessage::verify_integrity(&SAMPLE_REQUEST, b"wrong"));
+
+    // the re-encoded message is the same except for the unknown SOFTWARE attribute
+    let encoded = message.encode(Some(b"VOkJxbRl1RmTxUk/WvJxBt"));
+    let decoded = StunMessage::decode(&encoded).unwrap();
+    assert_eq!(decoded, message);
+    assert!(StunMessage::verify_integrity(
+        &encoded,
+        b"VOkJxbRl1RmTxUk/WvJxBt"
+    ));
+}
+
+#[test]
+fn connects_directly_through_the_candidates() {
+    let server = stun_server();
+    let (caller_socket, callee_socket) = (socket(), socket());
+
+    let mut caller = IceAgent::new(true, gather(&caller_socket, server));
+    let mut callee = IceAgent::new(false, gather(&callee_socket, server));
+    assert_eq!(caller.state(), IceState::New);
+
+    // exchanged over the signaling with `Start` and `Accept`
+    caller.set_remote(callee.local().clone());
+    callee.set_remote(caller.local().clone());
+
+    let callee_addr = callee_socket.local_addr().unwrap();
+    let caller_addr = caller_socket.local_addr().unwrap();
+    let mut agents = [(caller, caller_socket), (callee, callee_socket)];
+    run_checks(&mut agents, 5);
+
+    assert_eq!(agents[0].0.state(), IceState::Connected(callee_addr));
+    assert_eq!(agents[1].0.state(), IceState::Connected(caller_addr));
+}
+
+#[test]
+fn fails_over_to_the_relay_when_unreachable() {
+    let server = stun_server();
+    let (caller_socket, callee_socket) = (socket(), socket());
+
+    // the callee is behind a firewall: its candidates are not reachable
+    let blackhole = socket();
+    let callee_candidates = vec![
+        IceCandidate::new(CandidateKind::Host, blackhole.local_addr().unwrap()),
+        IceCandidate::new(CandidateKind::Relay, server),
+    ];
+
+    let mut caller = IceAgent::new(true, gather(&caller_socket, server));
+    let mut callee = IceAgent::new(false, callee_candidates);
+    caller.set_remote(callee.local().clone());
+    callee.set_remote(caller.local().clone());
+
+    let mut agents = [(caller, caller_socket), (callee, callee_socket)];
+    run_checks(&mut agents, 25);
+
+    // the checks of the caller never arrive => it falls back to the relay,
+    // while the callee still reaches the caller directly
+    assert_eq!(agents[0].0.state(), IceState::Failed);
+    assert!(matches!(agents[1].0.state(), IceState::Connected(_)));
+}
@@ -0,0 +1,35 @@
+use nexuslib::models::message::media::{MediaAttachment, MediaPreview, MediaType, Thumbnail};
+use uuid::Uuid;
+
+fn thumbnail(size: u32, width: u32, height: u32) -> Thumbnail {
+    Thumbnail {
+        size,
+        width,
+        height,
+        path: format!("thumb_{size}.jpg"),
+    }
+}
+
+#[test]
+fn picks_the_smallest_covering_thumbnail() {
+    let preview = MediaPreview {
+        thumbnails: vec![thumbnail(160, 160, 90), thumbnail(320, 320, 180)],
+        blurhash: None,
+    };
+
+    assert_eq!(preview.thumbnail_for(100).unwrap().size, 160);
+    assert_eq!(preview.thumbnail_for(200).unwrap().size, 320);
+    // none is large enough
+    assert_eq!(preview.thumbnail_for(1000).unwrap().size, 320);
+    assert!(MediaPreview::default().thumbnail_for(100).is_none());
+}
+
+#[test]
+fn attachments_without_previews_are_parsed() {
+    let attachment = MediaAttachment::new(Uuid::new_v4(), "cat.png", "", MediaType::Image);
+    let json = serde_json::to_string(&attachment).unwrap();
+    assert!(!json.contains("preview"));
+
+    let parsed = serde_json::from_str::<MediaAttachment>(&json).unwrap();
+    assert!(parsed.preview.is_none());
+}
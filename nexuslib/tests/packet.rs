@@ -0,0 +1,69 @@
+use uuid::Uuid;
+
+use nexuslib::models::call::packet::{
+    MediaHeader, MediaPacket, PacketKind, MAX_DATAGRAM_LEN, MEDIA_HEADER_LEN,
+};
+
+#[test]
+fn round_trips_packets() {
+    let call = Uuid::new_v4();
+    let packets = [
+        MediaPacket::media(call, 7, 960, vec![1, 2, 3]),
+        MediaPacket::media(call, u32::MAX, u32::MAX, vec![]),
+        MediaPacket::bind(call, vec![0xab; 16]),
+    ];
+
+    for packet in packets {
+        let bytes = packet.encode();
+        assert_eq!(bytes.len(), MEDIA_HEADER_LEN + packet.payload.len());
+        assert_eq!(MediaHeader::decode(&bytes), Some(packet.header));
+        assert_eq!(MediaPacket::decode(&bytes), Some(packet));
+    }
+}
+
+#[test]
+fn encodes_the_header_layout() {
+    let call = Uuid::new_v4();
+    let bytes = MediaPacket::media(call, 0x01020304, 0x0a0b0c0d, vec![0xff]).encode();
+
+    assert_eq!(bytes[0], PacketKind::Media as u8);
+    assert_eq!(&bytes[1..17], call.as_bytes());
+    assert_eq!(&bytes[17..21], &[1, 2, 3, 4]);
+    assert_eq!(&bytes[21..25], &[0x0a, 0x0b, 0x0c, 0x0d]);
+    assert_eq!(&bytes[25..], &[0xff]);
+}
+
+#[test]
+fn rejects_truncated_datagrams() {
+    let bytes = MediaPacket::media(Uuid::new_v4(), 1, 2, vec![3; 10]).encode();
+
+    for len in 0..MEDIA_HEADER_LEN {
+        assert_eq!(MediaHeader::decode(&bytes[..len]), None, "{len}");
+        assert_eq!(MediaPacket::decode(&bytes[..len]), None, "{len}");
+    }
+    // the header alone is a packet without payload
+    let packet = MediaPacket::decode(&bytes[..MEDIA_HEADER_LEN]).unwrap();
+    assert!(packet.payload.is_empty());
+}
+
+#[test]
+fn rejects_oversized_datagrams() {
+    let call = Uuid::new_v4();
+    let largest = MediaPacket::media(call, 1, 2, vec![0; MAX_DATAGRAM_LEN - MEDIA_HEADER_LEN]);
+    assert!(MediaPacket::decode(&largest.encode()).is_some());
+
+    let oversized = MediaPacket::media(call, 1, 2, vec![0; MAX_DATAGRAM_LEN]);
+    assert_eq!(MediaPacket::decode(&oversized.encode()), None);
+}
+
+#[test]
+fn rejects_unknown_kinds() {
+    let mut bytes = MediaPacket::bind(Uuid::new_v4(), vec![0; 16]).encode();
+
+    // STUN, DTLS and RTP share the socket
+    for kind in [0x00, 0x01, 0x16, 0x42, 0x80, 0xff] {
+        bytes[0] = kind;
+        assert_eq!(MediaHeader::decode(&bytes), None, "{kind}");
+        assert_eq!(MediaPacket::decode(&bytes), None, "{kind}");
+    }
+}
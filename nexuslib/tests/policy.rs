@@ -0,0 +1,73 @@
+use nexuslib::{
+    crypto::hasher::get_bytes_hash,
+    errors::file::UploadError,
+    models::{
+        file::{format::sniff, media_file::MediaFile, policy::UploadPolicy},
+        message::media::MediaType,
+    },
+};
+use uuid::Uuid;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+const MP4: &[u8] = b"\0\0\0\x18ftypisom\0\0\x02\0isomiso2";
+
+fn file_of(name: &str, media_type: MediaType, bytes: &[u8]) -> MediaFile {
+    MediaFile::new(
+        Uuid::new_v4(),
+        bytes.len(),
+        name.to_owned(),
+        media_type,
+        false,
+        Uuid::new_v4(),
+        get_bytes_hash(bytes),
+    )
+}
+
+#[test]
+fn recognizes_formats_by_content() {
+    assert_eq!(sniff(PNG).unwrap().mime, "image/png");
+    assert_eq!(sniff(MP4).unwrap().mime, "video/mp4");
+    assert_eq!(sniff(b"ID3\x04\0\0").unwrap().media_type, MediaType::Audio);
+    assert_eq!(
+        sniff("plain text, ünicode\n".as_bytes()).unwrap().mime,
+        "text/plain"
+    );
+    assert!(sniff(&[0x00, 0x01, 0x02, 0x03]).is_none());
+}
+
+#[test]
+fn checks_content_against_the_description() {
+    let images = UploadPolicy::default_for(MediaType::Image);
+
+    let png = file_of("cat.png", MediaType::Image, PNG);
+    assert!(images.check_content(&png, PNG).is_ok());
+
+    // the client claims an image, but sends a video
+    let video = file_of("cat.png", MediaType::Image, MP4);
+    assert_eq!(
+        images.check_content(&video, MP4),
+        Err(UploadError::TypeMismatch)
+    );
+
+    let renamed = file_of("cat.jpg", MediaType::Image, PNG);
+    assert_eq!(
+        images.check_content(&renamed, PNG),
+        Err(UploadError::Extension)
+    );
+
+    let only_jpeg = UploadPolicy::new(1024).with_formats(&["image/jpeg"]);
+    assert_eq!(
+        only_jpeg.check_content(&png, PNG),
+        Err(UploadError::UnsupportedFormat)
+    );
+    assert_eq!(
+        UploadPolicy::new(4).check_size(&png),
+        Err(UploadError::TooLarge)
+    );
+
+    // any content is a file
+    let unknown = [0x00, 0x01, 0x02, 0x03];
+    let blob = file_of("data.bin", MediaType::File, &unknown);
+    let files = UploadPolicy::default_for(MediaType::File);
+    assert!(files.check_content(&blob, &unknown).is_ok());
+}
@@ -0,0 +1,196 @@
+use nexuslib::{
+    errors::media::MediaError,
+    media::{
+        rtp::{RtpHeader, RtpPacket, AUDIO_PAYLOAD_TYPE},
+        srtp::{srtcp_plaintext, SrtpContext, SrtpMasterKey},
+    },
+};
+
+/// Session key and salt of the AEAD_AES_256_GCM test vectors (RFC 7714 16.2, 17.2)
+const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+const SALT: &str = "517569642070726f2071756f";
+
+const RTP: &str = "8040f17b8041f8d35501a0b2\
+                   47616c6c696120657374206f6d6e69732064697669736120696e207061727465732074726573";
+const SRTP: &str = "8040f17b8041f8d35501a0b2\
+                    32b1de78a822fe12ef9f78fa332e33aab18012389a58e2f3b50b2a0276ffae0f\
+                    1ba63799b87b7aa3db36dfffd6b0f9bb7878d7a76c13";
+
+const RTCP: &str = "81c8000d4d6172734e5450314e545032525450200000042a0000e9304c756e61\
+                    deadbeefdeadbeefdeadbeefdeadbeefdeadbeef";
+/// Encrypted with the SRTCP index 0x5d4
+const SRTCP: &str = "81c8000d4d617273d50ae4d1f5ce5d304ba297e47d470c282c3ece5dbffe0a50\
+                     a2eaa5c1110555be8415f658c61de0476f1b6fad1d1eb30c4446839f57ff6f6c\
+                     b26ac3be800005d4";
+/// Only authenticated with the SRTCP index 0x5d4
+const SRTCP_AUTH: &str = "81c8000d4d6172734e5450314e545032525450200000042a0000e9304c756e61\
+                          deadbeefdeadbeefdeadbeefdeadbeefdeadbeef\
+                          91db4afbfeee5a978fab4393ed2615fe000005d4";
+const SRTCP_INDEX: u32 = 0x5d4;
+
+fn bytes(hex: &str) -> Vec<u8> {
+    hex::decode(hex).unwrap()
+}
+
+fn vector_context() -> SrtpContext {
+    let (key, salt) = (bytes(KEY), bytes(SALT));
+    SrtpContext::from_session_keys(&key, &salt, &key, &salt).unwrap()
+}
+
+fn pair() -> (SrtpContext, SrtpContext) {
+    let master = SrtpMasterKey::generate();
+    (SrtpContext::new(&master), SrtpContext::new(&master))
+}
+
+fn packet(seq: u16, ssrc: u32) -> RtpPacket {
+    let header = RtpHeader::new(AUDIO_PAYLOAD_TYPE, seq, seq as u32 * 960, ssrc);
+    RtpPacket::new(header, vec![seq as u8; 40])
+}
+
+#[test]
+fn matches_rfc7714_srtp_vector() {
+    let plain = RtpPacket::decode(&bytes(RTP)).unwrap();
+    assert_eq!(vector_context().protect_rtp(&plain), bytes(SRTP));
+
+    let decrypted = vector_context().unprotect_rtp(&bytes(SRTP)).unwrap();
+    assert_eq!(decrypted.encode(), bytes(RTP));
+}
+
+#[test]
+fn matches_rfc7714_srtcp_vectors() {
+    let mut sender = vector_context();
+    for _ in 1..SRTCP_INDEX {
+        sender.protect_rtcp(&bytes(RTCP), true).unwrap();
+    }
+    assert_eq!(
+        sender.protect_rtcp(&bytes(RTCP), true).unwrap(),
+        bytes(SRTCP)
+    );
+
+    let mut receiver = vector_context();
+    assert_eq!(receiver.unprotect_rtcp(&bytes(SRTCP)).unwrap(), bytes(RTCP));
+    assert_eq!(
+        vector_context().unprotect_rtcp(&bytes(SRTCP_AUTH)).unwrap(),
+        bytes(RTCP)
+    );
+    assert_eq!(srtcp_plaintext(&bytes(SRTCP_AUTH)), Some(&bytes(RTCP)[..]));
+    assert_eq!(srtcp_plaintext(&bytes(SRTCP)), None);
+}
+
+#[test]
+fn round_trips_with_derived_keys() {
+    let (mut sender, mut receiver) = pair();
+
+    for seq in 0..100 {
+        let packet = packet(seq, 7);
+        let protected = sender.protect_rtp(&packet);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+    }
+
+    let rtcp = bytes(RTCP);
+    for encrypt in [true, false] {
+        let protected = sender.protect_rtcp(&rtcp, encrypt).unwrap();
+        assert_eq!(receiver.unprotect_rtcp(&protected).unwrap(), rtcp);
+    }
+}
+
+#[test]
+fn rejects_tampered_packets() {
+    let (mut sender, mut receiver) = pair();
+    let protected = sender.protect_rtp(&packet(1, 7));
+
+    // the header is authenticated, the payload and the tag are sealed
+    for i in [1, 20, protected.len() - 1] {
+        let mut tampered = protected.clone();
+        tampered[i] ^= 0x01;
+        assert_eq!(
+            receiver.unprotect_rtp(&tampered),
+            Err(MediaError::Authentication)
+        );
+    }
+    assert!(receiver.unprotect_rtp(&protected).is_ok());
+
+    let mut tampered = sender.protect_rtcp(&bytes(RTCP), false).unwrap();
+    tampered[12] ^= 0x01;
+    assert_eq!(
+        receiver.unprotect_rtcp(&tampered),
+        Err(MediaError::Authentication)
+    );
+}
+
+#[test]
+fn rejects_replays() {
+    let (mut sender, mut receiver) = pair();
+    let protected = (0..80)
+        .map(|seq| sender.protect_rtp(&packet(seq, 7)))
+        .collect::<Vec<_>>();
+
+    assert!(receiver.unprotect_rtp(&protected[0]).is_ok());
+    assert_eq!(
+        receiver.unprotect_rtp(&protected[0]),
+        Err(MediaError::Replay)
+    );
+
+    // the packets within the window are accepted out of order
+    assert!(receiver.unprotect_rtp(&protected[70]).is_ok());
+    assert!(receiver.unprotect_rtp(&protected[40]).is_ok());
+    assert_eq!(
+        receiver.unprotect_rtp(&protected[40]),
+        Err(MediaError::Replay)
+    );
+    // and the older ones are not
+    assert_eq!(
+        receiver.unprotect_rtp(&protected[5]),
+        Err(MediaError::Replay)
+    );
+
+    let rtcp = sender.protect_rtcp(&bytes(RTCP), true).unwrap();
+    assert!(receiver.unprotect_rtcp(&rtcp).is_ok());
+    assert_eq!(receiver.unprotect_rtcp(&rtcp), Err(MediaError::Replay));
+}
+
+#[test]
+fn follows_the_rollover_of_the_sequence() {
+    let (mut sender, mut receiver) = pair();
+
+    for seq in [0xfffd, 0xfffe, 0xffff, 0, 1, 2] {
+        let packet = packet(seq, 7);
+        let protected = sender.protect_rtp(&packet);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+    }
+}
+
+#[test]
+fn forged_packets_do_not_change_the_state() {
+    let (mut sender, mut receiver) = pair();
+
+    // a forged packet of a new source, far ahead of the genuine stream
+    let mut forged = packet(0x9000, 7).encode();
+    forged.extend_from_slice(&[0; 16]);
+    assert_eq!(
+        receiver.unprotect_rtp(&forged),
+        Err(MediaError::Authentication)
+    );
+
+    for seq in 1..10 {
+        let packet = packet(seq, 7);
+        let protected = sender.protect_rtp(&packet);
+        assert_eq!(receiver.unprotect_rtp(&protected).unwrap(), packet);
+    }
+}
+
+#[test]
+fn rejects_short_rtcp() {
+    let (mut sender, mut receiver) = pair();
+
+    for len in 0..8 {
+        assert_eq!(
+            sender.protect_rtcp(&vec![0x81; len], true),
+            Err(MediaError::Malformed)
+        );
+    }
+    assert_eq!(
+        receiver.unprotect_rtcp(&[0x81; 27]),
+        Err(MediaError::Malformed)
+    );
+}
@@ -0,0 +1,64 @@
+use nexuslib::crypto::totp::{code_at, decode_secret, encode_secret, step_at, verify, TOTP_STEP};
+
+/// Seed of the HMAC-SHA1 test vectors (RFC 6238 Appendix B)
+const SEED: &[u8] = b"12345678901234567890";
+
+#[test]
+fn matches_rfc6238_vectors() {
+    // the last six digits of the eight-digit codes of the RFC
+    let vectors = [
+        (59, "287082"),
+        (1111111109, "081804"),
+        (1111111111, "050471"),
+        (1234567890, "005924"),
+        (2000000000, "279037"),
+        (20000000000, "353130"),
+    ];
+
+    for (timestamp, code) in vectors {
+        assert_eq!(code_at(SEED, step_at(timestamp)), code, "at {timestamp}");
+        assert_eq!(verify(SEED, code, timestamp, 0), Some(step_at(timestamp)));
+    }
+}
+
+#[test]
+fn accepts_codes_within_the_window() {
+    let timestamp = 1234567890;
+    let step = step_at(timestamp);
+
+    for drift in [-1, 0, 1] {
+        let code = code_at(SEED, step + drift);
+        assert_eq!(verify(SEED, &code, timestamp, 1), Some(step + drift));
+    }
+    for drift in [-2, 2] {
+        let code = code_at(SEED, step + drift);
+        assert_eq!(verify(SEED, &code, timestamp, 1), None);
+    }
+
+    // the previous code is not accepted without the window
+    assert_eq!(verify(SEED, &code_at(SEED, step - 1), timestamp, 0), None);
+    // a step later the code is still accepted within the window
+    let code = code_at(SEED, step);
+    assert_eq!(verify(SEED, &code, timestamp + TOTP_STEP, 1), Some(step));
+}
+
+#[test]
+fn rejects_malformed_codes() {
+    let timestamp = 1234567890;
+    let code = code_at(SEED, step_at(timestamp));
+
+    assert_eq!(
+        verify(SEED, &format!(" {code}\n"), timestamp, 0),
+        Some(step_at(timestamp))
+    );
+    assert_eq!(verify(SEED, &code[..5], timestamp, 0), None);
+    assert_eq!(verify(SEED, &format!("{code}0"), timestamp, 0), None);
+    assert_eq!(verify(SEED, "", timestamp, 0), None);
+}
+
+#[test]
+fn round_trips_the_secret() {
+    let encoded = encode_secret(SEED);
+    assert_eq!(encoded, "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+    assert_eq!(decode_secret(&encoded).unwrap(), SEED);
+}
@@ -0,0 +1,102 @@
+use nexuslib::{
+    crypto::hasher::get_bytes_hash,
+    errors::file::UploadError,
+    models::{
+        file::{
+            chunk::{FileChunk, CHUNK_SIZE},
+            media_file::MediaFile,
+            upload::{UploadProgress, MAX_FILE_SIZE},
+        },
+        message::media::MediaType,
+    },
+    request::{file::FileRequest, index_token::FileToken},
+};
+use uuid::Uuid;
+
+fn file_of(bytes: &[u8]) -> MediaFile {
+    MediaFile::new(
+        Uuid::new_v4(),
+        bytes.len(),
+        "file.bin".to_owned(),
+        MediaType::File,
+        false,
+        Uuid::new_v4(),
+        get_bytes_hash(bytes),
+    )
+}
+
+fn chunks_of(bytes: &[u8]) -> Vec<FileChunk> {
+    bytes
+        .chunks(CHUNK_SIZE)
+        .enumerate()
+        .map(|(index, data)| FileChunk::new(index, data.to_vec()))
+        .collect()
+}
+
+/// Sends the chunk as a line of JSON like the client does
+fn send(chunk: &FileChunk) -> FileChunk {
+    let request =
+        FileRequest::new(FileToken::Chunk, Some(Uuid::new_v4())).with_chunk(chunk.clone());
+    let line = serde_json::to_string(&request).unwrap();
+    serde_json::from_str::<FileRequest>(&line)
+        .unwrap()
+        .chunk
+        .unwrap()
+}
+
+#[test]
+fn resumes_from_the_last_acknowledged_chunk() {
+    let bytes = (0..CHUNK_SIZE * 2 + 1000)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    let file = file_of(&bytes);
+    assert_eq!(file.len_chunks, 3);
+
+    let chunks = chunks_of(&bytes);
+    let mut progress = UploadProgress::new(file).unwrap();
+    progress.accept(&send(&chunks[0])).unwrap();
+    assert_eq!(progress.finish(), Err(UploadError::Incomplete));
+
+    // the ack of the second chunk was lost => it is sent again after resuming
+    progress.accept(&send(&chunks[1])).unwrap();
+    assert_eq!(
+        progress.accept(&chunks[1]),
+        Err(UploadError::OutOfOrder { expected: 2 })
+    );
+    assert_eq!(progress.next_chunk(), 2);
+
+    progress.accept(&send(&chunks[2])).unwrap();
+    assert_eq!(progress.finish(), Ok(()));
+}
+
+#[test]
+fn rejects_corrupted_and_oversized_uploads() {
+    let bytes = vec![7u8; CHUNK_SIZE + 10];
+    let chunks = chunks_of(&bytes);
+
+    // the data changed on the way
+    let mut progress = UploadProgress::new(file_of(&bytes)).unwrap();
+    let mut corrupted = chunks[0].clone();
+    corrupted.data[0] ^= 1;
+    assert_eq!(progress.accept(&corrupted), Err(UploadError::Corrupted));
+
+    // a short chunk that is not the last one
+    let short = FileChunk::new(0, bytes[..10].to_vec());
+    assert_eq!(progress.accept(&short), Err(UploadError::ChunkSize));
+
+    // every chunk is intact but the file is not the described one
+    let mut file = file_of(&bytes);
+    file.hash = get_bytes_hash(b"other");
+    let mut progress = UploadProgress::new(file).unwrap();
+    for chunk in &chunks {
+        progress.accept(chunk).unwrap();
+    }
+    assert_eq!(progress.finish(), Err(UploadError::Corrupted));
+
+    let mut file = file_of(&bytes);
+    file.len_bytes = MAX_FILE_SIZE + 1;
+    assert_eq!(
+        UploadProgress::new(file).unwrap_err(),
+        UploadError::TooLarge
+    );
+}
@@ -0,0 +1,76 @@
+use std::{f32::consts::PI, path::PathBuf};
+
+use nexuslib::{
+    media::{
+        audio::{AudioFormat, PcmSource, WavSource},
+        voice::record_voice,
+    },
+    models::message::{
+        voice::{VoiceNote, WAVEFORM_LEN},
+        MessageBody, MessageContent,
+    },
+};
+use uuid::Uuid;
+
+const FORMAT: AudioFormat = AudioFormat {
+    sample_rate: 16_000,
+    channels: 1,
+};
+
+fn temp_path(name: &str, ext: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("nexus-{name}-{}.{ext}", Uuid::new_v4()))
+}
+
+#[test]
+fn records_the_duration_and_the_waveform() {
+    // a second of silence, then a second of a tone
+    let samples = (0..2 * FORMAT.sample_rate)
+        .map(|index| match index < FORMAT.sample_rate {
+            true => 0,
+            false => ((2.0 * PI * 440.0 * index as f32 / 16_000.0).sin() * 8000.0) as i16,
+        })
+        .collect::<Vec<i16>>();
+    let pcm = temp_path("voice", "pcm");
+    let bytes = samples
+        .iter()
+        .flat_map(|sample| sample.to_le_bytes())
+        .collect::<Vec<u8>>();
+    std::fs::write(&pcm, bytes).unwrap();
+
+    let wav = temp_path("voice", "wav");
+    let mut source = PcmSource::open(&pcm, FORMAT).unwrap();
+    let recording = record_voice(&mut source, &wav).unwrap();
+
+    assert_eq!(recording.duration_ms, 2000);
+    assert_eq!(recording.waveform.len(), WAVEFORM_LEN);
+    assert_eq!(recording.waveform[0], 0);
+    assert_eq!(*recording.waveform.last().unwrap(), 255);
+
+    // the recorded file is the same audio
+    let mut recorded = WavSource::open(&wav).unwrap();
+    let copy = temp_path("voice-copy", "wav");
+    let again = record_voice(&mut recorded, &copy).unwrap();
+    assert_eq!(again, recording);
+    for path in [pcm, wav, copy] {
+        let _ = std::fs::remove_file(path);
+    }
+
+    let note = VoiceNote::new(Uuid::new_v4(), recording.duration_ms, recording.waveform);
+    assert!(note.is_valid());
+    assert!(!VoiceNote::new(note.media, 0, Vec::new()).is_valid());
+    assert!(!VoiceNote::new(note.media, 1000, vec![0; WAVEFORM_LEN + 1]).is_valid());
+}
+
+#[test]
+fn tells_voice_notes_from_texts() {
+    let voice = r#"{"text":"sealed","voice":{"media":"6f2c1b1e-0d7a-4b8e-9a51-3c2f6d3e8b10","duration_ms":1500,"waveform":[0,128,255]}}"#;
+    let body: MessageBody = serde_json::from_str(voice).unwrap();
+    assert!(matches!(body, MessageBody::Voice(_)));
+    assert_eq!(body.get_voice().unwrap().duration_ms, 1500);
+    assert_eq!(serde_json::to_string(&body).unwrap(), voice);
+
+    let body: MessageBody = serde_json::from_str(r#"{"text":"sealed"}"#).unwrap();
+    assert!(matches!(body, MessageBody::Text(_)));
+    assert!(body.get_voice().is_none());
+    assert_eq!(body.get_text().as_deref(), Some("sealed"));
+}